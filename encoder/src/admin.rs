@@ -0,0 +1,280 @@
+#[derive(serde::Deserialize)]
+pub struct AdminConfig {
+    /// Address of the status and admin API, e.g. "127.0.0.1:9101"
+    pub listen: std::net::SocketAddr,
+    /// Require "Authorization: Bearer {token}" when set
+    pub token: Option<String>,
+}
+
+/// State of sqs-encode shared with the admin API
+pub struct WorkerState {
+    started: std::time::Instant,
+    /// Stop receiving messages while true
+    pub paused: std::sync::atomic::AtomicBool,
+    /// Whether control.pause_path exists
+    pause_file: std::sync::atomic::AtomicBool,
+    /// Exit after the current job
+    draining: std::sync::atomic::AtomicBool,
+    pub current: std::sync::Mutex<Option<CurrentJob>>,
+    /// Notified to cancel the current job
+    pub cancel: tokio::sync::Notify,
+    interrupted: std::sync::atomic::AtomicBool,
+    /// Notified to return the current job to the queue before exiting
+    pub interrupt: tokio::sync::Notify,
+}
+
+#[derive(Clone, serde::Serialize)]
+pub struct CurrentJob {
+    pub message_id: String,
+    pub file: String,
+    pub state: &'static str,
+    pub started_at: String,
+    /// Estimated finish time from the speed of the profile
+    pub eta: Option<String>,
+}
+
+impl Default for WorkerState {
+    fn default() -> Self {
+        Self {
+            started: std::time::Instant::now(),
+            paused: std::sync::atomic::AtomicBool::new(false),
+            pause_file: std::sync::atomic::AtomicBool::new(false),
+            draining: std::sync::atomic::AtomicBool::new(false),
+            current: std::sync::Mutex::new(None),
+            cancel: tokio::sync::Notify::new(),
+            interrupted: std::sync::atomic::AtomicBool::new(false),
+            interrupt: tokio::sync::Notify::new(),
+        }
+    }
+}
+
+impl WorkerState {
+    /// Paused by the admin API or control.pause_path
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(std::sync::atomic::Ordering::SeqCst)
+            || self.pause_file.load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    pub fn set_pause_file(&self, exists: bool) {
+        self.pause_file
+            .store(exists, std::sync::atomic::Ordering::SeqCst);
+    }
+
+    /// Stop receiving messages and exit after the current job
+    pub fn drain(&self) {
+        self.draining
+            .store(true, std::sync::atomic::Ordering::SeqCst);
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    /// Stop receiving messages and abort the current job, e.g. on spot interruption
+    pub fn interrupt(&self) {
+        self.interrupted
+            .store(true, std::sync::atomic::Ordering::SeqCst);
+        self.interrupt.notify();
+    }
+
+    pub fn is_interrupted(&self) -> bool {
+        self.interrupted.load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    pub fn start_job(&self, message_id: &str, file: &str) {
+        *self.current.lock().unwrap() = Some(CurrentJob {
+            message_id: message_id.to_owned(),
+            file: file.to_owned(),
+            state: crate::jobs::State::Running.as_str(),
+            started_at: chrono::Utc::now().to_rfc3339(),
+            eta: None,
+        });
+    }
+
+    pub fn set_eta(&self, eta: chrono::DateTime<chrono::Utc>) {
+        if let Some(ref mut job) = *self.current.lock().unwrap() {
+            job.eta = Some(eta.to_rfc3339());
+        }
+    }
+
+    pub fn set_job_state(&self, state: crate::jobs::State) {
+        if let Some(ref mut job) = *self.current.lock().unwrap() {
+            job.state = state.as_str();
+        }
+    }
+
+    pub fn finish_job(&self) {
+        *self.current.lock().unwrap() = None;
+    }
+
+    pub fn status(&self) -> serde_json::Value {
+        serde_json::json!({
+            "status": "ok",
+            "paused": self.is_paused(),
+            "draining": self.is_draining(),
+            "interrupted": self.is_interrupted(),
+            "uptime": self.started.elapsed().as_secs(),
+            "current": *self.current.lock().unwrap(),
+        })
+    }
+}
+
+/// Serves the following endpoints.
+///
+/// - GET /health
+/// - GET /jobs/current
+/// - GET /jobs?limit=N (requires [jobs])
+/// - POST /pause and POST /resume: stop and resume receiving messages
+/// - POST /drain: exit after the current job
+/// - POST /abort: return the current job to the queue and exit
+/// - POST /jobs/current/cancel
+/// - POST /jobs/{file}/requeue
+pub struct Admin {
+    pub state: std::sync::Arc<WorkerState>,
+    pub token: Option<String>,
+    pub sqs_client: rusoto_sqs::SqsClient,
+    pub queue_url: String,
+    pub job_store: Option<crate::jobs::JobStore>,
+}
+
+type Response = hyper::Response<hyper::Body>;
+
+fn json_response<T: serde::Serialize>(status: u16, value: &T) -> Response {
+    hyper::Response::builder()
+        .status(status)
+        .header("content-type", "application/json")
+        .body(hyper::Body::from(
+            serde_json::to_vec(value).unwrap_or_default(),
+        ))
+        .unwrap()
+}
+
+fn error_response(status: u16, message: &str) -> Response {
+    json_response(status, &serde_json::json!({ "error": message }))
+}
+
+impl Admin {
+    /// Serve the API until the process exits
+    pub async fn serve(
+        self: std::sync::Arc<Self>,
+        addr: std::net::SocketAddr,
+    ) -> Result<(), anyhow::Error> {
+        let make_service = hyper::service::make_service_fn(move |_| {
+            let admin = self.clone();
+            async move {
+                Ok::<_, std::convert::Infallible>(hyper::service::service_fn(move |req| {
+                    let admin = admin.clone();
+                    async move { Ok::<_, std::convert::Infallible>(admin.handle(req).await) }
+                }))
+            }
+        });
+        hyper::Server::bind(&addr).serve(make_service).await?;
+        Ok(())
+    }
+
+    async fn handle(&self, req: hyper::Request<hyper::Body>) -> Response {
+        if let Some(ref token) = self.token {
+            let authorized = req
+                .headers()
+                .get("authorization")
+                .and_then(|value| value.to_str().ok())
+                == Some(&format!("Bearer {}", token));
+            if !authorized {
+                return error_response(401, "unauthorized");
+            }
+        }
+
+        let path = req.uri().path().trim_end_matches('/');
+        let segments = path.split('/').skip(1).collect::<Vec<_>>();
+        let method = req.method().clone();
+        match (method.as_str(), segments.as_slice()) {
+            ("GET", ["health"]) => json_response(200, &self.state.status()),
+            ("GET", ["jobs", "current"]) => {
+                json_response(200, &*self.state.current.lock().unwrap())
+            }
+            ("GET", ["jobs"]) => {
+                let limit = req
+                    .uri()
+                    .query()
+                    .and_then(|query| {
+                        query
+                            .split('&')
+                            .find_map(|pair| pair.strip_prefix("limit="))
+                            .and_then(|limit| limit.parse().ok())
+                    })
+                    .unwrap_or(50);
+                match self.job_store {
+                    Some(ref job_store) => match job_store.recent(limit).await {
+                        Ok(jobs) => json_response(
+                            200,
+                            &jobs
+                                .into_iter()
+                                .map(|(file, record)| serde_json::json!({ "file": file, "record": record }))
+                                .collect::<Vec<_>>(),
+                        ),
+                        Err(e) => error_response(500, &e.to_string()),
+                    },
+                    None => error_response(404, "[jobs] is not configured"),
+                }
+            }
+            ("POST", ["pause"]) | ("POST", ["resume"]) => {
+                let paused = segments[0] == "pause";
+                self.state
+                    .paused
+                    .store(paused, std::sync::atomic::Ordering::SeqCst);
+                tracing::info!("Intake is {}", if paused { "paused" } else { "resumed" });
+                json_response(200, &serde_json::json!({ "paused": paused }))
+            }
+            ("POST", ["drain"]) => {
+                tracing::info!("Drain requested");
+                self.state.drain();
+                json_response(200, &serde_json::json!({ "draining": true }))
+            }
+            ("POST", ["abort"]) => {
+                tracing::warn!("Abort requested");
+                self.state.interrupt();
+                json_response(200, &serde_json::json!({ "interrupted": true }))
+            }
+            ("POST", ["jobs", "current", "cancel"]) => {
+                if self.state.current.lock().unwrap().is_none() {
+                    return error_response(404, "no job is running");
+                }
+                self.state.cancel.notify();
+                json_response(200, &serde_json::json!({ "cancelled": true }))
+            }
+            ("POST", ["jobs", file, "requeue"]) => match self.requeue(file).await {
+                Ok(()) => json_response(200, &serde_json::json!({ "requeued": file })),
+                Err(e) => error_response(500, &e.to_string()),
+            },
+            _ => error_response(404, "not found"),
+        }
+    }
+
+    async fn requeue(&self, file: &str) -> Result<(), anyhow::Error> {
+        use rusoto_sqs::Sqs as _;
+
+        if let Some(ref job_store) = self.job_store {
+            if let Some(record) = job_store.get(file).await? {
+                if record.get("state").map(String::as_str)
+                    != Some(crate::jobs::State::Failed.as_str())
+                {
+                    return Err(anyhow::anyhow!("{} has not failed", file));
+                }
+            }
+        }
+        self.sqs_client
+            .send_message(rusoto_sqs::SendMessageRequest {
+                queue_url: self.queue_url.clone(),
+                message_body: file.to_owned(),
+                ..Default::default()
+            })
+            .await?;
+        tracing::info!("Requeue {}", file);
+        if let Some(ref job_store) = self.job_store {
+            job_store
+                .transition(file, crate::jobs::State::Queued, &[])
+                .await?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,619 @@
+#[derive(Debug)]
+pub struct EventInfo {
+    pub event_id: u16,
+    /// Unix time
+    pub start_time: i64,
+    /// Unix time
+    pub end_time: i64,
+    pub name: Option<String>,
+    pub text: Option<String>,
+    pub genre: Option<&'static str>,
+    pub dual_mono: Option<crate::dual_mono::DualMono>,
+    pub series: Option<Series>,
+    /// Events on other services which continue the event (event relay)
+    pub relays: Vec<EventRelay>,
+}
+
+/// Destination of an event relay found in the event group descriptor
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct EventRelay {
+    /// Only for the relay to another network
+    pub original_network_id: Option<u16>,
+    /// Only for the relay to another network
+    pub transport_stream_id: Option<u16>,
+    pub service_id: u16,
+    pub event_id: u16,
+}
+
+/// Series descriptor of the event
+#[derive(Debug, Clone)]
+pub struct Series {
+    pub series_id: u16,
+    /// 0 when unknown
+    pub episode_number: u16,
+    pub name: String,
+}
+
+/// Event in EPG of any service broadcast in the TS
+#[derive(Debug)]
+pub struct EpgEvent {
+    pub network_id: u16,
+    pub service_id: u16,
+    pub service_name: Option<String>,
+    /// table_id and version_number of the EIT section which carried the event
+    pub table_id: u8,
+    pub version: u8,
+    pub event: EventInfo,
+}
+
+/// Broadcast information collected from SI in the source TS
+#[derive(Debug, Default)]
+pub struct SourceInfo {
+    pub service_id: Option<u16>,
+    pub service_name: Option<String>,
+    /// Unix time at the first PCR, measured with TOT
+    pub clock_start: Option<f64>,
+    /// In seconds, measured with PCR continuing across discontinuities
+    pub duration: f64,
+    /// Events in EIT[p/f actual] of the service, ordered by start_time
+    pub events: Vec<EventInfo>,
+    /// Positions in seconds where PMT of the service was updated
+    pub pmt_changes: Vec<f64>,
+    /// Events of the service starting or ending during the recording, found in EIT[p/f] and RST
+    pub transitions: Vec<Transition>,
+}
+
+/// Change of the event on air, which is later than start_time or end_time in EIT when the
+/// event is delayed
+#[derive(Debug, Clone)]
+pub struct Transition {
+    /// Position in seconds from the beginning of the TS
+    pub position: f64,
+    pub event_id: u16,
+    pub kind: tsutils::running::TransitionKind,
+}
+
+impl SourceInfo {
+    /// The event overlapping the recording the most
+    pub fn main_event(&self) -> Option<&EventInfo> {
+        let clock_start = self.clock_start?;
+        let clock_end = clock_start + self.duration;
+        self.events
+            .iter()
+            .map(|event| {
+                let overlap = (event.end_time as f64).min(clock_end)
+                    - (event.start_time as f64).max(clock_start);
+                (event, overlap)
+            })
+            .filter(|&(_, overlap)| overlap > 0.0)
+            .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+            .map(|(event, _)| event)
+    }
+
+    /// Convert Unix time into the position in seconds from the beginning of the TS
+    pub fn position_of(&self, time: i64) -> Option<f64> {
+        self.clock_start
+            .map(|clock_start| time as f64 - clock_start)
+    }
+
+    /// Positions in seconds where the event actually started and ended, when they are found in
+    /// the TS
+    pub fn on_air(&self, event_id: u16) -> (Option<f64>, Option<f64>) {
+        let find = |kind| {
+            self.transitions
+                .iter()
+                .find(|transition| transition.event_id == event_id && transition.kind == kind)
+                .map(|transition| transition.position)
+        };
+        (
+            find(tsutils::running::TransitionKind::Started),
+            find(tsutils::running::TransitionKind::Ended),
+        )
+    }
+
+    /// Destination of the main event when it is relayed to another service after the recording
+    pub fn relay(&self) -> Option<&EventRelay> {
+        self.main_event()?.relays.first()
+    }
+
+    /// The event on air at the position
+    pub fn event_at(&self, position: f64) -> Option<&EventInfo> {
+        let clock_start = self.clock_start?;
+        let time = clock_start + position;
+        self.events
+            .iter()
+            .find(|event| event.start_time as f64 <= time && time < event.end_time as f64)
+    }
+}
+
+/// Scan the source TS and collect SI of the service. When service_id is None, the program with
+/// the smallest program_number is used. Archives written by seekable::compress ("*.zst") are
+/// read as well.
+pub fn analyze<P>(ts_path: P, service_id: Option<u16>) -> Result<SourceInfo, anyhow::Error>
+where
+    P: AsRef<std::path::Path>,
+{
+    let reader = std::io::BufReader::new(crate::seekable::open(ts_path.as_ref())?);
+    let mut tracker = tsutils::filter::ProgramTracker::new();
+    let mut sdt_assembler = tsutils::psi::SectionAssembler::new();
+    let mut eit_assembler = tsutils::psi::SectionAssembler::new();
+    let mut tot_assembler = tsutils::psi::SectionAssembler::new();
+    let mut ldt = LinkedDescriptions::default();
+    let mut event_tracker = tsutils::running::EventTracker::new();
+    let mut events = std::collections::HashMap::new();
+    let mut pmt_version = None;
+    let mut timeline = tsutils::timeline::Timeline::new();
+    let mut info = SourceInfo::default();
+
+    for (index, buf) in tsutils::packet::ts_packets(reader).enumerate() {
+        let buf = buf?;
+        if buf[0] != 0x47 || (buf[1] & 0b10000000) != 0 {
+            continue;
+        }
+        let packet = tsutils::TsPacket::new(&buf);
+        if info.service_id.is_none() {
+            info.service_id = service_id.or_else(|| {
+                tracker
+                    .pat()
+                    .and_then(|pat| pat.program_map.values().min().cloned())
+            });
+        }
+
+        for transition in event_tracker.push(index as u64, &packet) {
+            if Some(transition.service_id) == info.service_id {
+                info.transitions.push(Transition {
+                    position: info.duration,
+                    event_id: transition.event_id,
+                    kind: transition.kind,
+                });
+            }
+        }
+
+        match packet.pid {
+            0x0011 => {
+                for section in sdt_assembler.push(&packet) {
+                    let sdt = match tsutils::ServiceDescriptionTable::parse(&section) {
+                        Ok(sdt) => sdt,
+                        Err(_) => continue,
+                    };
+                    if !sdt.is_actual() {
+                        continue;
+                    }
+                    if let Some(service) = sdt
+                        .services
+                        .iter()
+                        .find(|s| Some(s.service_id) == info.service_id)
+                    {
+                        if let Some(descriptor) = service.service_descriptor() {
+                            info.service_name =
+                                Some(tsutils::arib_string::decode(descriptor.service_name));
+                        }
+                    }
+                }
+            }
+            0x0012 => {
+                for section in eit_assembler.push(&packet) {
+                    let eit = match tsutils::EventInformationTable::parse(&section) {
+                        Ok(eit) => eit,
+                        Err(_) => continue,
+                    };
+                    if !eit.is_present_following_actual() || Some(eit.service_id) != info.service_id
+                    {
+                        continue;
+                    }
+                    for event in eit.events {
+                        if let (Some(start_time), Some(end_time)) =
+                            (event.start_time, event.end_time())
+                        {
+                            events.insert(
+                                event.event_id,
+                                (event_info(&event, start_time, end_time), ldt_links(&event)),
+                            );
+                        }
+                    }
+                }
+            }
+            0x0025 => ldt.push(&packet),
+            0x0014 => {
+                for section in tot_assembler.push(&packet) {
+                    if info.clock_start.is_some() || timeline.last_pcr().is_none() {
+                        continue;
+                    }
+                    if let Ok(tot) = tsutils::TimeOffsetTable::parse(&section) {
+                        if let Some(jst_time) = tot.jst_time {
+                            info.clock_start = Some(jst_time as f64 - info.duration);
+                        }
+                    }
+                }
+            }
+            _ => match tracker.push(&packet) {
+                Ok(true) => {
+                    if let Some(program) = info
+                        .service_id
+                        .and_then(|service_id| tracker.programs().get(&service_id))
+                    {
+                        if let Some(version) = pmt_version {
+                            if version != program.version_number {
+                                info.pmt_changes.push(info.duration);
+                            }
+                        }
+                        pmt_version = Some(program.version_number);
+                    }
+                }
+                Ok(false) => {}
+                // Sections broken by drops are skipped like the other SI
+                Err(e) => tracing::warn!("Skipped a broken PAT or PMT at packet {}: {}", index, e),
+            },
+        }
+
+        if timeline.push(index as u64, &packet).is_some() {
+            info.duration = timeline.duration_secs();
+        }
+    }
+
+    info.events = events
+        .into_values()
+        .map(|(mut event, links)| {
+            ldt.resolve(&mut event, &links);
+            event
+        })
+        .collect();
+    info.events.sort_by_key(|event| event.start_time);
+    Ok(info)
+}
+
+/// Scan the TS and collect events in EIT[p/f] and EIT[schedule] of all services, ordered by
+/// start_time. The latest version of each event is kept. The TS may be a "*.zst" archive.
+pub fn collect_epg<P>(ts_path: P) -> Result<Vec<EpgEvent>, anyhow::Error>
+where
+    P: AsRef<std::path::Path>,
+{
+    let reader = std::io::BufReader::new(crate::seekable::open(ts_path.as_ref())?);
+    let mut sdt_assembler = tsutils::psi::SectionAssembler::new();
+    // EIT is carried in 0x0012, and also in 0x0026 and 0x0027 for terrestrial broadcasting
+    let mut eit_assemblers = std::collections::HashMap::new();
+    let mut service_names = std::collections::HashMap::new();
+    let mut ldt = LinkedDescriptions::default();
+    let mut events = std::collections::HashMap::new();
+
+    for buf in tsutils::packet::ts_packets(reader) {
+        let buf = buf?;
+        if buf[0] != 0x47 || (buf[1] & 0b10000000) != 0 {
+            continue;
+        }
+        let packet = tsutils::TsPacket::new(&buf);
+        match packet.pid {
+            0x0011 => {
+                for section in sdt_assembler.push(&packet) {
+                    if let Ok(sdt) = tsutils::ServiceDescriptionTable::parse(&section) {
+                        for service in &sdt.services {
+                            if let Some(descriptor) = service.service_descriptor() {
+                                service_names.insert(
+                                    (sdt.original_network_id, service.service_id),
+                                    tsutils::arib_string::decode(descriptor.service_name),
+                                );
+                            }
+                        }
+                    }
+                }
+            }
+            0x0012 | 0x0026 | 0x0027 => {
+                let assembler = eit_assemblers
+                    .entry(packet.pid)
+                    .or_insert_with(tsutils::psi::SectionAssembler::new);
+                for section in assembler.push(&packet) {
+                    let eit = match tsutils::EventInformationTable::parse(&section) {
+                        Ok(eit) => eit,
+                        Err(_) => continue,
+                    };
+                    for event in eit.events {
+                        if let (Some(start_time), Some(end_time)) =
+                            (event.start_time, event.end_time())
+                        {
+                            events.insert(
+                                (eit.original_network_id, eit.service_id, event.event_id),
+                                (
+                                    eit.table_id,
+                                    eit.version_number,
+                                    event_info(&event, start_time, end_time),
+                                    ldt_links(&event),
+                                ),
+                            );
+                        }
+                    }
+                }
+            }
+            0x0025 => ldt.push(&packet),
+            _ => {}
+        }
+    }
+
+    let mut events = events
+        .into_iter()
+        .map(
+            |((network_id, service_id, _), (table_id, version, mut event, links))| {
+                ldt.resolve(&mut event, &links);
+                EpgEvent {
+                    network_id,
+                    service_id,
+                    service_name: service_names.get(&(network_id, service_id)).cloned(),
+                    table_id,
+                    version,
+                    event,
+                }
+            },
+        )
+        .collect::<Vec<_>>();
+    events.sort_by_key(|epg| (epg.event.start_time, epg.service_id));
+    Ok(events)
+}
+
+/// service_id and the service name in SDT of the source. It reads only until SDT is found.
+pub fn identify_service<P>(
+    ts_path: P,
+    service_id: Option<u16>,
+) -> Result<(Option<u16>, Option<String>), anyhow::Error>
+where
+    P: AsRef<std::path::Path>,
+{
+    let reader = std::io::BufReader::new(crate::seekable::open(ts_path.as_ref())?);
+    let mut tracker = tsutils::filter::ProgramTracker::new();
+    let mut sdt_assembler = tsutils::psi::SectionAssembler::new();
+    let mut sdt_services = None;
+
+    for buf in tsutils::packet::ts_packets(reader) {
+        let buf = buf?;
+        if buf[0] != 0x47 || (buf[1] & 0b10000000) != 0 {
+            continue;
+        }
+        let packet = tsutils::TsPacket::new(&buf);
+        if packet.pid == 0x0011 {
+            for section in sdt_assembler.push(&packet) {
+                if let Ok(sdt) = tsutils::ServiceDescriptionTable::parse(&section) {
+                    if sdt.is_actual() {
+                        sdt_services = Some(
+                            sdt.services
+                                .iter()
+                                .map(|service| {
+                                    (
+                                        service.service_id,
+                                        service.service_descriptor().map(|descriptor| {
+                                            tsutils::arib_string::decode(descriptor.service_name)
+                                        }),
+                                    )
+                                })
+                                .collect::<Vec<_>>(),
+                        );
+                    }
+                }
+            }
+        } else if let Err(e) = tracker.push(&packet) {
+            tracing::warn!("Skipped a broken PAT or PMT: {}", e);
+        }
+
+        let service_id = service_id.or_else(|| {
+            tracker
+                .pat()
+                .and_then(|pat| pat.program_map.values().min().cloned())
+        });
+        if let (Some(service_id), Some(services)) = (service_id, &sdt_services) {
+            let name = services
+                .iter()
+                .find(|(id, _)| *id == service_id)
+                .and_then(|(_, name)| name.clone());
+            return Ok((Some(service_id), name));
+        }
+    }
+    Ok((service_id, None))
+}
+
+fn event_info(event: &tsutils::eit::Event, start_time: i64, end_time: i64) -> EventInfo {
+    describe(event.event_id, event.descriptors, start_time, end_time)
+}
+
+fn describe(event_id: u16, descriptors: &[u8], start_time: i64, end_time: i64) -> EventInfo {
+    use tsutils::descriptor::{
+        AudioComponentDescriptor, ContentNibble, EventGroupDescriptor, SeriesDescriptor,
+        ShortEventDescriptor,
+    };
+
+    let mut info = EventInfo {
+        event_id,
+        start_time,
+        end_time,
+        name: None,
+        text: None,
+        genre: None,
+        dual_mono: None,
+        series: None,
+        relays: vec![],
+    };
+    for (tag, body) in tsutils::descriptor::descriptors(descriptors) {
+        match tag {
+            ShortEventDescriptor::TAG => {
+                if let Some(descriptor) = ShortEventDescriptor::parse(body) {
+                    info.name = Some(tsutils::arib_string::decode(descriptor.event_name));
+                    info.text = Some(tsutils::arib_string::decode(descriptor.text));
+                }
+            }
+            AudioComponentDescriptor::TAG => {
+                if let Some(descriptor) = AudioComponentDescriptor::parse(body) {
+                    if descriptor.is_dual_mono() && info.dual_mono.is_none() {
+                        let main_language =
+                            String::from_utf8_lossy(descriptor.iso_639_language_code).into_owned();
+                        let sub_language = descriptor
+                            .iso_639_language_code_2
+                            .map(|code| String::from_utf8_lossy(code).into_owned())
+                            .unwrap_or_else(|| main_language.clone());
+                        info.dual_mono = Some(crate::dual_mono::DualMono {
+                            main_language,
+                            sub_language,
+                        });
+                    }
+                }
+            }
+            ContentNibble::TAG => {
+                info.genre = ContentNibble::parse_descriptor(body)
+                    .first()
+                    .and_then(|nibble| nibble.genre_name());
+            }
+            SeriesDescriptor::TAG => {
+                if let Some(descriptor) = SeriesDescriptor::parse(body) {
+                    info.series = Some(Series {
+                        series_id: descriptor.series_id,
+                        episode_number: descriptor.episode_number,
+                        name: tsutils::arib_string::decode(descriptor.series_name),
+                    });
+                }
+            }
+            EventGroupDescriptor::TAG => {
+                if let Some(descriptor) = EventGroupDescriptor::parse(body) {
+                    if descriptor.is_relay() {
+                        info.relays
+                            .extend(descriptor.events.into_iter().map(|event| EventRelay {
+                                original_network_id: event.original_network_id,
+                                transport_stream_id: event.transport_stream_id,
+                                service_id: event.service_id,
+                                event_id: event.event_id,
+                            }));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    info
+}
+
+/// Descriptions in LDT (PID 0x0025), which BS broadcasters refer from EIT instead of
+/// repeating them in every EIT section
+#[derive(Default)]
+struct LinkedDescriptions {
+    assembler: tsutils::psi::SectionAssembler,
+    /// Descriptors keyed by (original_network_id, original_service_id, description_id)
+    descriptions: std::collections::HashMap<(u16, u16, u16), Vec<u8>>,
+}
+
+impl LinkedDescriptions {
+    fn push(&mut self, packet: &tsutils::TsPacket) {
+        for section in self.assembler.push(packet) {
+            if let Ok(ldt) = tsutils::LinkedDescriptionTable::parse(&section) {
+                for description in &ldt.descriptions {
+                    self.descriptions.insert(
+                        (
+                            ldt.original_network_id,
+                            ldt.original_service_id,
+                            description.description_id,
+                        ),
+                        description.descriptors.to_vec(),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Fill what EIT lacks with the linked descriptions
+    fn resolve(&self, info: &mut EventInfo, links: &[(u16, u16, u16)]) {
+        for link in links {
+            if let Some(descriptors) = self.descriptions.get(link) {
+                let linked = describe(info.event_id, descriptors, info.start_time, info.end_time);
+                info.name = info.name.take().or(linked.name);
+                info.text = info
+                    .text
+                    .take()
+                    .filter(|text| !text.is_empty())
+                    .or(linked.text);
+                info.genre = info.genre.or(linked.genre);
+                info.dual_mono = info.dual_mono.take().or(linked.dual_mono);
+                info.series = info.series.take().or(linked.series);
+            }
+        }
+    }
+}
+
+/// (original_network_id, original_service_id, description_id) of LDT referred by the event
+fn ldt_links(event: &tsutils::eit::Event) -> Vec<(u16, u16, u16)> {
+    use tsutils::descriptor::LdtLinkageDescriptor;
+
+    tsutils::descriptor::descriptors(event.descriptors)
+        .filter(|&(tag, _)| tag == LdtLinkageDescriptor::TAG)
+        .filter_map(|(_, body)| LdtLinkageDescriptor::parse(body))
+        .flat_map(|descriptor| {
+            descriptor
+                .descriptions
+                .iter()
+                .map(|description| {
+                    (
+                        descriptor.original_network_id,
+                        descriptor.original_service_id,
+                        description.description_id,
+                    )
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PMT_PID: u16 = 0x01f0;
+
+    fn packet(pid: u16, counter: u8, section: &[u8]) -> Vec<u8> {
+        // With payload_unit_start_indicator and pointer_field of 0
+        let mut buf = vec![
+            0x47,
+            0x40 | (pid >> 8) as u8,
+            pid as u8,
+            0x10 | (counter & 0x0f),
+            0x00,
+        ];
+        buf.extend_from_slice(section);
+        buf.resize(188, 0xff);
+        buf
+    }
+
+    fn pat() -> Vec<u8> {
+        // Program 0x0400 with PMT in PMT_PID
+        vec![
+            0x00, 0xb0, 13, 0x00, 0x01, 0xc1, 0x00, 0x00, 0x04, 0x00, 0xe1, 0xf0, 0, 0, 0, 0,
+        ]
+    }
+
+    fn pmt(version: u8) -> Vec<u8> {
+        // H.262 video in PID 0x0100, which also carries PCR
+        let mut section = vec![
+            0x02, 0xb0, 18, 0x04, 0x00, 0xc1, 0x00, 0x00, 0xe1, 0x00, 0xf0, 0x00, 0x02, 0xe1, 0x00,
+            0xf0, 0x00, 0, 0, 0, 0,
+        ];
+        section[5] |= version << 1;
+        section
+    }
+
+    #[test]
+    fn broken_pmt() {
+        // section_length beyond the packet, as the rest of the section was dropped
+        let mut truncated = pmt(0);
+        truncated[1] = 0xb3;
+        truncated[2] = 0xff;
+        // Each section is parsed when the next one starts
+        let packets = [
+            packet(0x0000, 0, &pat()),
+            packet(0x0000, 1, &pat()),
+            packet(PMT_PID, 0, &truncated),
+            packet(PMT_PID, 1, &pmt(0)),
+            packet(PMT_PID, 2, &pmt(1)),
+            packet(PMT_PID, 3, &pmt(1)),
+        ];
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        std::io::Write::write_all(&mut file, &packets.concat()).unwrap();
+
+        let info = analyze(file.path(), None).unwrap();
+        assert_eq!(info.service_id, Some(0x0400));
+        // The PMTs after the broken one are still tracked
+        assert_eq!(info.pmt_changes.len(), 1);
+
+        assert!(identify_service(file.path(), None).is_ok());
+    }
+}
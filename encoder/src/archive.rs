@@ -0,0 +1,81 @@
+/// Built-in video arguments for archival encodes with x265 or SVT-AV1. They are prepended to
+/// ffmpeg_args of the profile by load_config, so audio options and overrides go to ffmpeg_args.
+/// Neither encoder handles interlaced pictures well, so combine with [deinterlace] for 1080i
+/// sources.
+#[derive(serde::Deserialize)]
+pub struct ArchiveConfig {
+    #[serde(default)]
+    pub codec: ArchiveCodec,
+    /// Defaults to 22 for x265 and 30 for SVT-AV1
+    pub crf: Option<u32>,
+    /// Defaults to "slow" for x265 and "6" for SVT-AV1
+    pub preset: Option<String>,
+    /// Encode in yuv420p10le, which avoids banding of 8-bit broadcasts at the same bitrate
+    #[serde(default = "default_ten_bit")]
+    pub ten_bit: bool,
+}
+
+fn default_ten_bit() -> bool {
+    true
+}
+
+#[derive(Clone, Copy, Debug, Default, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArchiveCodec {
+    #[default]
+    X265,
+    Svtav1,
+}
+
+impl ArchiveConfig {
+    pub fn ffmpeg_args(&self) -> Vec<String> {
+        let (encoder, crf, preset, params_key, params) = match self.codec {
+            ArchiveCodec::X265 => (
+                "libx265",
+                22,
+                "slow",
+                "-x265-params",
+                "colorprim=bt709:transfer=bt709:colormatrix=bt709:range=limited",
+            ),
+            ArchiveCodec::Svtav1 => (
+                "libsvtav1",
+                30,
+                "6",
+                "-svtav1-params",
+                "color-primaries=1:transfer-characteristics=1:matrix-coefficients=1:color-range=0",
+            ),
+        };
+        let mut args = vec![
+            "-c:v".to_owned(),
+            encoder.to_owned(),
+            "-crf".to_owned(),
+            self.crf.unwrap_or(crf).to_string(),
+            "-preset".to_owned(),
+            self.preset.as_deref().unwrap_or(preset).to_owned(),
+            "-pix_fmt".to_owned(),
+            if self.ten_bit {
+                "yuv420p10le"
+            } else {
+                "yuv420p"
+            }
+            .to_owned(),
+            // ISDB broadcasts are BT.709 in limited range but don't always signal it
+            "-color_primaries".to_owned(),
+            "bt709".to_owned(),
+            "-color_trc".to_owned(),
+            "bt709".to_owned(),
+            "-colorspace".to_owned(),
+            "bt709".to_owned(),
+            "-color_range".to_owned(),
+            "tv".to_owned(),
+            params_key.to_owned(),
+            params.to_owned(),
+        ];
+        if let ArchiveCodec::X265 = self.codec {
+            // Playable on Apple devices in MP4
+            args.push("-tag:v".to_owned());
+            args.push("hvc1".to_owned());
+        }
+        args
+    }
+}
@@ -0,0 +1,49 @@
+/// Archive only the audio, e.g. of radio services broadcast as TS. The profile writes a single
+/// "{source stem}.m4a" or "{source stem}.opus" instead of the MP4 when no outputs are declared,
+/// and the video verification is skipped. Leave video options out of ffmpeg_args.
+#[derive(serde::Deserialize)]
+pub struct AudioConfig {
+    #[serde(default)]
+    pub codec: AudioCodec,
+    /// e.g. "128k". Defaults to the encoder's default.
+    pub bitrate: Option<String>,
+}
+
+#[derive(Clone, Copy, Default, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioCodec {
+    #[default]
+    Aac,
+    Opus,
+}
+
+impl AudioCodec {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Aac => "m4a",
+            Self::Opus => "opus",
+        }
+    }
+}
+
+impl AudioConfig {
+    /// Output arguments appended to ffmpeg_args of the profile
+    pub fn ffmpeg_args(&self) -> Vec<String> {
+        let codec = match self.codec {
+            AudioCodec::Aac => "aac",
+            AudioCodec::Opus => "libopus",
+        };
+        let mut args = vec![
+            "-vn".to_owned(),
+            "-sn".to_owned(),
+            "-dn".to_owned(),
+            "-c:a".to_owned(),
+            codec.to_owned(),
+        ];
+        if let Some(ref bitrate) = self.bitrate {
+            args.push("-b:a".to_owned());
+            args.push(bitrate.clone());
+        }
+        args
+    }
+}
@@ -0,0 +1,190 @@
+/// Measure the backlog of the queues so that a fleet of workers, e.g. in an Auto Scaling group,
+/// can scale on it
+#[derive(Clone, serde::Deserialize)]
+pub struct AutoscalingConfig {
+    /// Seconds between measurements
+    #[serde(default = "default_interval")]
+    pub interval: u64,
+    /// Wall seconds of a job assumed until the worker finishes one
+    #[serde(default = "default_job_seconds")]
+    pub job_seconds: f64,
+    /// Put the backlog into CloudWatch as custom metrics
+    pub cloudwatch: Option<CloudWatchConfig>,
+}
+
+fn default_interval() -> u64 {
+    60
+}
+
+fn default_job_seconds() -> f64 {
+    3600.0
+}
+
+/// Puts "QueueDepth" (Count) and "BacklogHours" (None) with the dimensions
+#[derive(Clone, serde::Deserialize)]
+pub struct CloudWatchConfig {
+    #[serde(default = "default_namespace")]
+    pub namespace: String,
+    /// e.g. { AutoScalingGroupName = "encoder" }
+    #[serde(default)]
+    pub dimensions: std::collections::BTreeMap<String, String>,
+    /// Defaults to the region from the environment
+    pub region: Option<String>,
+    /// Named profile in the shared credentials file
+    pub profile: Option<String>,
+}
+
+fn default_namespace() -> String {
+    "Encoder".to_owned()
+}
+
+#[derive(Debug)]
+pub struct Backlog {
+    /// Visible messages in all queues
+    pub depth: u64,
+    /// Wall hours for a worker to encode all of them
+    pub hours: f64,
+}
+
+impl AutoscalingConfig {
+    /// Measure the backlog with the mean wall time of the jobs finished by the worker
+    pub async fn backlog<Sqs>(
+        &self,
+        sqs_client: &Sqs,
+        queue_urls: &[String],
+        metrics: &crate::metrics::Metrics,
+    ) -> Result<Backlog, anyhow::Error>
+    where
+        Sqs: rusoto_sqs::Sqs,
+    {
+        let mut depth = 0;
+        for queue_url in queue_urls {
+            let attributes = sqs_client
+                .get_queue_attributes(rusoto_sqs::GetQueueAttributesRequest {
+                    queue_url: queue_url.clone(),
+                    attribute_names: Some(vec!["ApproximateNumberOfMessages".to_owned()]),
+                })
+                .await
+                .map_err(|e| anyhow::anyhow!("failed to call sqs:GetQueueAttributes: {}", e))?
+                .attributes
+                .unwrap_or_default();
+            if let Some(n) = attributes.get("ApproximateNumberOfMessages") {
+                depth += n.parse::<u64>()?;
+            }
+        }
+        let count = metrics.encode_duration.get_sample_count();
+        let job_seconds = if count > 0 {
+            metrics.encode_duration.get_sample_sum() / count as f64
+        } else {
+            self.job_seconds
+        };
+        Ok(Backlog {
+            depth,
+            hours: depth as f64 * job_seconds / 3600.0,
+        })
+    }
+
+    /// Update the metrics and CloudWatch every interval until the process exits. Failures are
+    /// logged and retried at the next interval.
+    pub async fn run<Sqs>(
+        self,
+        sqs_client: Sqs,
+        queue_urls: Vec<String>,
+        metrics: std::sync::Arc<crate::metrics::Metrics>,
+    ) where
+        Sqs: rusoto_sqs::Sqs,
+    {
+        use futures::StreamExt as _;
+
+        let cloudwatch = match self.cloudwatch {
+            Some(ref cloudwatch) => match cloudwatch.client() {
+                Ok(client) => Some((cloudwatch, client)),
+                Err(e) => {
+                    tracing::error!("Failed to create the CloudWatch client: {}", e);
+                    None
+                }
+            },
+            None => None,
+        };
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(self.interval));
+        while interval.next().await.is_some() {
+            let backlog = match self.backlog(&sqs_client, &queue_urls, &metrics).await {
+                Ok(backlog) => backlog,
+                Err(e) => {
+                    tracing::warn!("Failed to measure the backlog: {}", e);
+                    continue;
+                }
+            };
+            tracing::debug!("{:?}", backlog);
+            metrics.queue_depth.set(backlog.depth as i64);
+            metrics.backlog_hours.set(backlog.hours);
+            if let Some((cloudwatch, ref client)) = cloudwatch {
+                if let Err(e) = cloudwatch.put(client, &backlog).await {
+                    tracing::warn!("{}", e);
+                }
+            }
+        }
+    }
+}
+
+impl CloudWatchConfig {
+    pub fn client(&self) -> Result<rusoto_core::Client, anyhow::Error> {
+        let dispatcher = rusoto_core::HttpClient::new()?;
+        Ok(match self.profile {
+            Some(ref profile) => {
+                let mut provider = rusoto_core::credential::ProfileProvider::new()?;
+                provider.set_profile(profile.as_str());
+                rusoto_core::Client::new_with(provider, dispatcher)
+            }
+            None => rusoto_core::Client::new_with(
+                rusoto_core::credential::DefaultCredentialsProvider::new()?,
+                dispatcher,
+            ),
+        })
+    }
+
+    /// Call cloudwatch:PutMetricData of the query API, which rusoto_core signs
+    pub async fn put(
+        &self,
+        client: &rusoto_core::Client,
+        backlog: &Backlog,
+    ) -> Result<(), anyhow::Error> {
+        let region = match self.region {
+            Some(ref region) => region.parse()?,
+            None => rusoto_core::Region::default(),
+        };
+        let mut request =
+            rusoto_core::signature::SignedRequest::new("POST", "monitoring", &region, "/");
+        request.add_param("Action", "PutMetricData");
+        request.add_param("Version", "2010-08-01");
+        request.add_param("Namespace", &self.namespace);
+        let data = [
+            ("QueueDepth", backlog.depth as f64, "Count"),
+            ("BacklogHours", backlog.hours, "None"),
+        ];
+        for (i, (name, value, unit)) in data.iter().enumerate() {
+            let member = format!("MetricData.member.{}", i + 1);
+            request.add_param(format!("{}.MetricName", member), name.to_string());
+            request.add_param(format!("{}.Value", member), value.to_string());
+            request.add_param(format!("{}.Unit", member), unit.to_string());
+            for (j, (dimension, value)) in self.dimensions.iter().enumerate() {
+                let key = format!("{}.Dimensions.member.{}", member, j + 1);
+                request.add_param(format!("{}.Name", key), dimension.clone());
+                request.add_param(format!("{}.Value", key), value.clone());
+            }
+        }
+        let mut response = client
+            .sign_and_dispatch(request)
+            .await
+            .map_err(|e| anyhow::anyhow!("failed to call cloudwatch:PutMetricData: {:?}", e))?;
+        if !response.status.is_success() {
+            let response = response.buffer().await?;
+            return Err(anyhow::anyhow!(
+                "cloudwatch:PutMetricData returned {}: {}",
+                response.status,
+                response.body_as_str()
+            ));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,104 @@
+/// Keep archive transfers from congesting the link, e.g. while clients are streaming in the
+/// evening. A file started within a window is transferred to the end even if the window closes.
+#[derive(Clone, serde::Deserialize)]
+pub struct BandwidthConfig {
+    /// Bytes per second
+    pub limit: Option<u64>,
+    /// Local time ranges like "02:00-07:00" when transfers are allowed. Hours beyond 24 continue
+    /// into the next day. Transfers are allowed at any time when empty.
+    #[serde(default)]
+    pub windows: Vec<String>,
+    /// Seconds between checks while waiting for a window
+    #[serde(default = "default_poll_interval")]
+    pub poll_interval: u64,
+}
+
+fn default_poll_interval() -> u64 {
+    60
+}
+
+impl Default for BandwidthConfig {
+    fn default() -> Self {
+        Self {
+            limit: None,
+            windows: vec![],
+            poll_interval: default_poll_interval(),
+        }
+    }
+}
+
+impl BandwidthConfig {
+    /// Whether transfers are allowed now
+    pub fn is_allowed(&self) -> Result<bool, anyhow::Error> {
+        use chrono::Timelike as _;
+
+        if self.windows.is_empty() {
+            return Ok(true);
+        }
+        let now = chrono::Local::now();
+        let minutes = now.hour() * 60 + now.minute();
+        for window in &self.windows {
+            let range = crate::throttle::parse_window(window)
+                .ok_or_else(|| anyhow::anyhow!("Invalid transfer window {}", window))?;
+            if crate::throttle::in_window(range, minutes) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Wait until a window opens and return the time spent waiting
+    pub async fn wait_for_window(&self) -> Result<std::time::Duration, anyhow::Error> {
+        let started = std::time::Instant::now();
+        if !self.is_allowed()? {
+            tracing::info!("Waiting for the transfer windows {:?}", self.windows);
+            while !self.is_allowed()? {
+                tokio::time::delay_for(std::time::Duration::from_secs(self.poll_interval)).await;
+            }
+        }
+        Ok(started.elapsed())
+    }
+
+    /// Suffix of the plan notes, e.g. " at 1000000 bytes/s within 02:00-07:00"
+    pub fn describe(&self) -> String {
+        let mut s = String::new();
+        if let Some(limit) = self.limit {
+            s.push_str(&format!(" at {} bytes/s", limit));
+        }
+        if !self.windows.is_empty() {
+            s.push_str(&format!(" within {}", self.windows.join(", ")));
+        }
+        s
+    }
+
+    pub fn limiter(&self) -> Limiter {
+        Limiter {
+            limit: self.limit,
+            started: std::time::Instant::now(),
+            bytes: 0,
+        }
+    }
+}
+
+/// Pace the transfer of chunks so that the average rate stays under the limit
+pub struct Limiter {
+    limit: Option<u64>,
+    started: std::time::Instant,
+    bytes: u64,
+}
+
+impl Limiter {
+    /// Wait until len more bytes can be sent
+    pub async fn consume(&mut self, len: u64) {
+        let limit = match self.limit {
+            Some(limit) if limit > 0 => limit,
+            _ => return,
+        };
+        let due = std::time::Duration::from_secs_f64(self.bytes as f64 / limit as f64);
+        let elapsed = self.started.elapsed();
+        if due > elapsed {
+            tokio::time::delay_for(due - elapsed).await;
+        }
+        self.bytes += len;
+    }
+}
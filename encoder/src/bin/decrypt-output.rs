@@ -0,0 +1,53 @@
+/// Decrypt an output encrypted with [encryption] of the profile, e.g. after downloading it from
+/// the storage.
+///
+///     decrypt-output [--profile NAME] INPUT [OUTPUT]
+///
+/// OUTPUT defaults to INPUT without ".enc". It is removed when the decryption fails.
+#[tokio::main]
+async fn main() -> encoder::exit::Exit {
+    run().await.into()
+}
+
+async fn run() -> Result<(), anyhow::Error> {
+    let config = encoder::load_config()?;
+    encoder::logging::init(&config.log)?;
+    let mut profile_name = None;
+    let mut paths = vec![];
+    let mut args = encoder::exit::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--profile" => {
+                profile_name = Some(
+                    args.next()
+                        .ok_or_else(|| encoder::exit::usage("missing profile name"))?,
+                )
+            }
+            _ => paths.push(std::path::PathBuf::from(arg)),
+        }
+    }
+    let input = paths
+        .first()
+        .ok_or_else(|| encoder::exit::usage("INPUT is required"))?;
+    let output = match paths.get(1) {
+        Some(output) => output.clone(),
+        None if input.extension().is_some_and(|ext| ext == "enc") => input.with_extension(""),
+        None => {
+            return Err(encoder::exit::usage(
+                "OUTPUT is required unless INPUT ends with .enc",
+            ))
+        }
+    };
+    let encryption = config
+        .profile(profile_name.as_deref())?
+        .encryption
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("[encryption] is not configured in the profile"))?;
+
+    if let Err(e) = encryption.decrypt_file(input, &output).await {
+        let _ = std::fs::remove_file(&output);
+        return Err(e);
+    }
+    tracing::info!("Decrypted {} into {}", input.display(), output.display());
+    Ok(())
+}
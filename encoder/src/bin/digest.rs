@@ -0,0 +1,53 @@
+/// Summarize the jobs recorded in [jobs] within digest.hours and send the summary to the emails
+/// and webhooks whose `on` includes "digest". Run it daily from cron or a systemd timer.
+///
+///     digest [--print]
+///
+/// --print writes the summary to stdout instead of sending it.
+#[tokio::main]
+async fn main() -> encoder::exit::Exit {
+    run().await.into()
+}
+
+async fn run() -> Result<(), anyhow::Error> {
+    let config = encoder::load_config()?;
+    encoder::logging::init(&config.log)?;
+    let print = encoder::exit::args().skip(1).any(|arg| arg == "--print");
+    let job_store = encoder::jobs::JobStore::new(
+        config
+            .jobs
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("[jobs] is not configured"))?,
+        &config.redis,
+    )?;
+    let dirs = config
+        .encoder
+        .base_dirs
+        .iter()
+        .map(|base_dir| base_dir.path.clone())
+        .collect::<Vec<_>>();
+    let digest = config.digest.collect(&job_store, &dirs).await?;
+    let subject = config.digest.subject();
+
+    if print {
+        println!("{}\n\n{}", subject, digest);
+        return Ok(());
+    }
+    let mut failed = false;
+    for email in &config.emails {
+        if let Err(e) = email.send_digest(&subject, &digest).await {
+            tracing::error!("{}", e);
+            failed = true;
+        }
+    }
+    for webhook in &config.webhooks {
+        if let Err(e) = webhook.send_digest(&digest).await {
+            tracing::error!("{}", e);
+            failed = true;
+        }
+    }
+    if failed {
+        return Err(anyhow::anyhow!("failed to send the digest"));
+    }
+    Ok(())
+}
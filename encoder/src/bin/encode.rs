@@ -1,8 +1,152 @@
+/// Encode TS files with the profile resolved for each of them.
+///
+///     encode [--profile NAME] [--jobs N] [--dry-run] PATH...
+///     encode TS PROFILE
+///
+/// PATH is a TS file, a directory whose "*.ts" files are encoded, or a quoted pattern with "*"
+/// and "?" in the file name. Files are encoded in order, or N at a time with --jobs, and a summary
+/// is printed at the end. --dry-run prints the plan of each file instead.
 #[tokio::main]
-async fn main() -> Result<(), anyhow::Error> {
+async fn main() -> encoder::exit::Exit {
+    run().await.into()
+}
+
+async fn run() -> Result<encoder::exit::Status, anyhow::Error> {
+    use futures::StreamExt as _;
+
     ffmpeg::init()?;
 
-    let config = encoder::load_config()?;
-    let ts_path = std::path::PathBuf::from(std::env::args().nth(1).expect("missing file"));
-    encoder::encode(&config, ts_path).await
+    let config = std::sync::Arc::new(encoder::load_config()?);
+    encoder::logging::init(&config.log)?;
+    let mut profile = None;
+    let mut jobs = 1;
+    let mut dry_run = false;
+    let mut patterns = vec![];
+    let mut args = encoder::exit::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--profile" => {
+                profile = Some(
+                    args.next()
+                        .ok_or_else(|| encoder::exit::usage("missing profile"))?,
+                )
+            }
+            "--jobs" => {
+                jobs = args
+                    .next()
+                    .ok_or_else(|| encoder::exit::usage("missing jobs"))?
+                    .parse()?
+            }
+            "--dry-run" => dry_run = true,
+            _ => patterns.push(arg),
+        }
+    }
+    // The former usage: encode TS PROFILE
+    if profile.is_none()
+        && patterns.len() == 2
+        && config.profiles.contains_key(&patterns[1])
+        && !std::path::Path::new(&patterns[1]).exists()
+    {
+        profile = patterns.pop();
+    }
+    if patterns.is_empty() {
+        return Err(encoder::exit::usage("missing file"));
+    }
+    let mut ts_paths = vec![];
+    for pattern in &patterns {
+        ts_paths.extend(expand(pattern)?);
+    }
+    if dry_run {
+        for ts_path in ts_paths {
+            let (profile, channel) = config.resolve(profile.as_deref(), &ts_path)?;
+            encoder::plan::plan(&config, profile, channel, &ts_path)?.print();
+        }
+        return Ok(encoder::exit::Status::Ok);
+    }
+
+    let results = futures::stream::iter(ts_paths)
+        .map(|ts_path| {
+            let config = config.clone();
+            let profile = profile.clone();
+            tokio::spawn(async move {
+                let started = std::time::Instant::now();
+                let result = encode(&config, profile.as_deref(), &ts_path).await;
+                (ts_path, started.elapsed(), result)
+            })
+        })
+        .buffered(jobs.max(1))
+        .collect::<Vec<_>>()
+        .await;
+
+    let mut failed = 0;
+    println!();
+    for result in &results {
+        let (ts_path, elapsed, result) = result.as_ref().map_err(|e| anyhow::anyhow!("{}", e))?;
+        match result {
+            Ok(()) => println!("ok\t{:.0}s\t{}", elapsed.as_secs_f64(), ts_path.display()),
+            Err(e) => {
+                failed += 1;
+                println!(
+                    "failed\t{:.0}s\t{}\t{}",
+                    elapsed.as_secs_f64(),
+                    ts_path.display(),
+                    e
+                );
+            }
+        }
+    }
+    println!("{} encoded, {} failed", results.len() - failed, failed);
+    if failed > 0 {
+        Ok(encoder::exit::Status::Failure)
+    } else {
+        Ok(encoder::exit::Status::Ok)
+    }
+}
+
+async fn encode(
+    config: &encoder::Config,
+    profile: Option<&str>,
+    ts_path: &std::path::Path,
+) -> Result<(), anyhow::Error> {
+    let (profile, channel) = config.resolve(profile, ts_path)?;
+    let report = encoder::encode_with_state(profile, channel, ts_path, None).await?;
+    for output in report.outputs {
+        println!("{}: {:?}", output.path.display(), output.scores);
+        if let Some(sha256) = output.sha256 {
+            println!("{}: sha256={}", output.path.display(), sha256);
+        }
+    }
+    Ok(())
+}
+
+/// TS files designated by the argument in the order of their names
+fn expand(pattern: &str) -> Result<Vec<std::path::PathBuf>, anyhow::Error> {
+    let path = std::path::Path::new(pattern);
+    let (dir, regex) = if path.is_dir() {
+        (path, regex::Regex::new(r"\A.*\.ts\z")?)
+    } else if pattern.contains(['*', '?']) {
+        let fname = path
+            .file_name()
+            .ok_or_else(|| anyhow::anyhow!("invalid pattern {}", pattern))?
+            .to_string_lossy();
+        let regex = regex::escape(&fname)
+            .replace(r"\*", ".*")
+            .replace(r"\?", ".");
+        let dir = match path.parent() {
+            Some(dir) if dir != std::path::Path::new("") => dir,
+            _ => std::path::Path::new("."),
+        };
+        (dir, regex::Regex::new(&format!(r"\A{}\z", regex))?)
+    } else {
+        return Ok(vec![path.to_owned()]);
+    };
+    let mut paths = vec![];
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() && regex.is_match(&entry.file_name().to_string_lossy()) {
+            paths.push(entry.path());
+        }
+    }
+    paths.sort();
+    Ok(paths)
 }
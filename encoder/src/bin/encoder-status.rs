@@ -0,0 +1,243 @@
+/// Print recent jobs recorded in [jobs].
+///
+///     encoder-status [--json] [--state STATE] [--since HOURS] [--longest] [--limit N]
+///     encoder-status [--json] --queue
+///     encoder-status [--json] --workers
+#[tokio::main]
+async fn main() -> encoder::exit::Exit {
+    run().await.into()
+}
+
+async fn run() -> Result<(), anyhow::Error> {
+    let config = encoder::load_config()?;
+    let mut json = false;
+    let mut queue = false;
+    let mut workers = false;
+    let mut longest = false;
+    let mut state = None;
+    let mut since_hours = 24;
+    let mut limit = 20;
+    let mut args = encoder::exit::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => json = true,
+            "--queue" => queue = true,
+            "--workers" => workers = true,
+            "--longest" => longest = true,
+            "--failed" => state = Some(encoder::jobs::State::Failed),
+            "--state" => {
+                state = Some(
+                    args.next()
+                        .ok_or_else(|| encoder::exit::usage("missing state"))?
+                        .parse()?,
+                );
+            }
+            "--since" => {
+                since_hours = args
+                    .next()
+                    .ok_or_else(|| encoder::exit::usage("missing hours"))?
+                    .parse()?
+            }
+            "--limit" => {
+                limit = args
+                    .next()
+                    .ok_or_else(|| encoder::exit::usage("missing limit"))?
+                    .parse()?
+            }
+            _ => return Err(anyhow::anyhow!("Unknown argument {}", arg)),
+        }
+    }
+    if workers {
+        let registry = encoder::workers::Registry::new(
+            config
+                .workers
+                .as_ref()
+                .ok_or_else(|| anyhow::anyhow!("[workers] is not configured"))?,
+            &config.redis,
+        )?;
+        let workers = registry.list().await?;
+        if json {
+            println!("{}", serde_json::to_string(&workers)?);
+        } else {
+            for worker in workers {
+                println!(
+                    "{}\t{}\t{}\t{}",
+                    worker["worker"].as_str().unwrap_or("-"),
+                    if worker["draining"].as_bool().unwrap_or(false) {
+                        "draining"
+                    } else if worker["paused"].as_bool().unwrap_or(false) {
+                        "paused"
+                    } else {
+                        "active"
+                    },
+                    worker["current"]["file"].as_str().unwrap_or("-"),
+                    worker["current"]["eta"].as_str().unwrap_or("-"),
+                );
+            }
+        }
+        return Ok(());
+    }
+
+    let job_store = encoder::jobs::JobStore::new(
+        config
+            .jobs
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("[jobs] is not configured"))?,
+        &config.redis,
+    )?;
+
+    if queue {
+        let depth = queue_depth(&config, &job_store).await?;
+        if json {
+            println!("{}", serde_json::to_string(&depth)?);
+        } else {
+            for (name, count) in depth.as_object().unwrap() {
+                println!("{}: {}", name, count);
+            }
+        }
+        return Ok(());
+    }
+
+    let since = chrono::Utc::now() - chrono::Duration::hours(since_hours);
+    let mut jobs = job_store
+        .updated_since(since)
+        .await?
+        .into_iter()
+        .map(|(file, record)| Job::new(file, record))
+        .filter(|job| state.is_none_or(|state| job.state == Some(state)))
+        .collect::<Vec<_>>();
+    if longest {
+        jobs.sort_by(|a, b| b.encode_secs.partial_cmp(&a.encode_secs).unwrap());
+    }
+    jobs.truncate(limit);
+
+    if json {
+        println!("{}", serde_json::to_string(&jobs)?);
+    } else {
+        for job in jobs {
+            println!(
+                "{}\t{}\t{}\t{}\t{}{}",
+                job.updated_at
+                    .map(|at| at.with_timezone(&chrono::Local).format("%F %T").to_string())
+                    .unwrap_or_else(|| "-".to_owned()),
+                job.state.map_or("-", |state| state.as_str()),
+                job.encode_secs
+                    .map(|secs| format!("{:.0}s", secs))
+                    .unwrap_or_else(|| "-".to_owned()),
+                job.record.get("worker").map_or("-", String::as_str),
+                job.file,
+                job.record
+                    .get("error")
+                    .map(|error| format!("\t{}", error))
+                    .unwrap_or_default(),
+            );
+        }
+    }
+    Ok(())
+}
+
+#[derive(serde::Serialize)]
+struct Job {
+    file: String,
+    #[serde(serialize_with = "serialize_state")]
+    state: Option<encoder::jobs::State>,
+    updated_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// From running to done or failed
+    encode_secs: Option<f64>,
+    record: encoder::jobs::Record,
+}
+
+fn serialize_state<S>(
+    state: &Option<encoder::jobs::State>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serializer.serialize_str(state.map_or("unknown", |state| state.as_str()))
+}
+
+impl Job {
+    fn new(file: String, record: encoder::jobs::Record) -> Self {
+        use encoder::jobs::{transitioned_at, State};
+
+        let state = record
+            .get("state")
+            .and_then(|state| state.parse::<State>().ok());
+        let updated_at = state.and_then(|state| transitioned_at(&record, state));
+        let encode_secs = match (
+            transitioned_at(&record, State::Running),
+            transitioned_at(&record, State::Done)
+                .or_else(|| transitioned_at(&record, State::Failed)),
+        ) {
+            (Some(running_at), Some(finished_at)) if running_at <= finished_at => {
+                Some((finished_at - running_at).num_milliseconds() as f64 / 1000.0)
+            }
+            _ => None,
+        };
+        Self {
+            file,
+            state,
+            updated_at,
+            encode_secs,
+            record,
+        }
+    }
+}
+
+/// Files waiting in the Redis list, messages in SQS and jobs recorded as queued in the last week
+async fn queue_depth(
+    config: &encoder::Config,
+    job_store: &encoder::jobs::JobStore,
+) -> Result<serde_json::Value, anyhow::Error> {
+    use rusoto_sqs::Sqs as _;
+
+    let redis_client = redis::Client::open(config.redis.url.as_str())?;
+    let mut conn = redis_client.get_async_connection().await?;
+    let redis: u64 = redis::cmd("LLEN")
+        .arg("jobs")
+        .query_async(&mut conn)
+        .await?;
+
+    // Summed over the priority queues too
+    let sqs_client = config.sqs.client()?;
+    let mut sqs_visible = 0;
+    let mut sqs_in_flight = 0;
+    for queue_url in config.sqs.all_queue_urls() {
+        let attributes = sqs_client
+            .get_queue_attributes(rusoto_sqs::GetQueueAttributesRequest {
+                queue_url: queue_url.to_owned(),
+                attribute_names: Some(vec![
+                    "ApproximateNumberOfMessages".to_owned(),
+                    "ApproximateNumberOfMessagesNotVisible".to_owned(),
+                ]),
+            })
+            .await?
+            .attributes
+            .unwrap_or_default();
+        let attribute = |name: &str| -> u64 {
+            attributes
+                .get(name)
+                .and_then(|count| count.parse().ok())
+                .unwrap_or(0)
+        };
+        sqs_visible += attribute("ApproximateNumberOfMessages");
+        sqs_in_flight += attribute("ApproximateNumberOfMessagesNotVisible");
+    }
+
+    let queued = job_store
+        .updated_since(chrono::Utc::now() - chrono::Duration::days(7))
+        .await?
+        .into_iter()
+        .filter(|(_, record)| {
+            record.get("state").map(String::as_str) == Some(encoder::jobs::State::Queued.as_str())
+        })
+        .count();
+
+    Ok(serde_json::json!({
+        "redis": redis,
+        "sqs_visible": sqs_visible,
+        "sqs_in_flight": sqs_in_flight,
+        "queued": queued,
+    }))
+}
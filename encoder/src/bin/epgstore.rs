@@ -0,0 +1,91 @@
+/// Ingest EPG of TS files into the store of [epg] and print the events matching the query.
+///
+///     epgstore [--service SERVICE_ID] [--from RFC3339] [--to RFC3339] [--keyword WORD] [TS...]
+///
+/// Only ingests the TS files when they are given without any condition.
+fn main() -> encoder::exit::Exit {
+    run().into()
+}
+
+fn run() -> Result<(), anyhow::Error> {
+    use chrono::TimeZone as _;
+
+    let config = encoder::load_config()?;
+    encoder::logging::init(&config.log)?;
+    let epg = config
+        .epg
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("[epg] is not configured"))?;
+    let mut query = encoder::epgstore::Query::default();
+    let mut ts_paths = vec![];
+    let mut args = encoder::exit::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--service" => {
+                query.service_id = Some(
+                    args.next()
+                        .ok_or_else(|| encoder::exit::usage("missing service"))?
+                        .parse()?,
+                )
+            }
+            "--from" => {
+                query.from = Some(parse_time(
+                    &args
+                        .next()
+                        .ok_or_else(|| encoder::exit::usage("missing from"))?,
+                )?)
+            }
+            "--to" => {
+                query.to = Some(parse_time(
+                    &args
+                        .next()
+                        .ok_or_else(|| encoder::exit::usage("missing to"))?,
+                )?)
+            }
+            "--keyword" => {
+                query.keyword = Some(
+                    args.next()
+                        .ok_or_else(|| encoder::exit::usage("missing keyword"))?,
+                )
+            }
+            _ => ts_paths.push(arg),
+        }
+    }
+
+    let mut store = encoder::epgstore::EpgStore::open(epg)?;
+    for ts_path in &ts_paths {
+        let changed = store.ingest(&encoder::analysis::collect_epg(ts_path)?)?;
+        println!("{}: {} events ingested", ts_path, changed);
+    }
+    if !ts_paths.is_empty()
+        && query.service_id.is_none()
+        && query.from.is_none()
+        && query.to.is_none()
+        && query.keyword.is_none()
+    {
+        return Ok(());
+    }
+
+    for epg in store.events(&query)? {
+        println!(
+            "{}\t{}\t{}\t{}\t{}",
+            chrono::Local
+                .timestamp(epg.event.start_time, 0)
+                .format("%Y-%m-%d %H:%M"),
+            chrono::Local
+                .timestamp(epg.event.end_time, 0)
+                .format("%H:%M"),
+            epg.service_name
+                .as_deref()
+                .unwrap_or(&epg.service_id.to_string()),
+            epg.event.event_id,
+            epg.event.name.as_deref().unwrap_or("-"),
+        );
+    }
+    Ok(())
+}
+
+/// Unix time of RFC 3339
+fn parse_time(s: &str) -> Result<i64, anyhow::Error> {
+    Ok(chrono::DateTime::parse_from_rfc3339(s)?.timestamp())
+}
@@ -0,0 +1,36 @@
+fn main() -> encoder::exit::Exit {
+    run().into()
+}
+
+/// Exits with Status::VerificationFailed when a base directory is short of space
+fn run() -> Result<encoder::exit::Status, anyhow::Error> {
+    let config = encoder::load_config()?;
+    encoder::logging::init(&config.log)?;
+    let dry_run = encoder::exit::args()
+        .skip(1)
+        .any(|arg| arg == "-n" || arg == "--dry-run");
+    let janitor = config
+        .janitor
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("[janitor] is not configured"))?;
+
+    let mut alerted = false;
+    for base_dir in &config.encoder.base_dirs {
+        for path in janitor.clean(&base_dir.path, dry_run)? {
+            if dry_run {
+                println!("Would delete {}", path.display());
+            } else {
+                println!("Deleted {}", path.display());
+            }
+        }
+        if let Some(alert) = janitor.space_alert(&base_dir.path)? {
+            eprintln!("{}", alert);
+            alerted = true;
+        }
+    }
+    if alerted {
+        Ok(encoder::exit::Status::VerificationFailed)
+    } else {
+        Ok(encoder::exit::Status::Ok)
+    }
+}
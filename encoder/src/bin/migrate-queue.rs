@@ -0,0 +1,81 @@
+/// Move pending jobs between queue backends, e.g. when sqs-encode is moved to another
+/// infrastructure.
+///
+///     migrate-queue [--dry-run] [--no-dedup] FROM TO
+///
+/// FROM and TO are "redis-list[:KEY]", "redis-stream[:KEY]" or "sqs[:QUEUE_URL]". KEY defaults
+/// to "jobs" and QUEUE_URL defaults to sqs.queue_url. Duplicate jobs are dropped unless
+/// --no-dedup is given. Stop the consumers of FROM before running it.
+#[tokio::main]
+async fn main() -> encoder::exit::Exit {
+    run().await.into()
+}
+
+async fn run() -> Result<(), anyhow::Error> {
+    use rusoto_sqs::Sqs as _;
+
+    let config = encoder::load_config()?;
+    encoder::logging::init(&config.log)?;
+    let mut dry_run = false;
+    let mut dedup = true;
+    let mut specs = vec![];
+    for arg in encoder::exit::args().skip(1) {
+        match arg.as_str() {
+            "--dry-run" => dry_run = true,
+            "--no-dedup" => dedup = false,
+            _ => specs.push(arg),
+        }
+    }
+    if specs.len() != 2 {
+        return Err(encoder::exit::usage("FROM and TO are required"));
+    }
+    let mut from = encoder::queue::Queue::open(&specs[0], &config).await?;
+    let mut to = encoder::queue::Queue::open(&specs[1], &config).await?;
+
+    if dry_run {
+        let mut seen = std::collections::HashSet::new();
+        if dedup {
+            seen.extend(to.list().await?.unwrap_or_default());
+        }
+        match from.list().await? {
+            Some(jobs) => {
+                for body in jobs {
+                    if dedup && !seen.insert(body.clone()) {
+                        println!("Would drop duplicate {}", body);
+                    } else {
+                        println!("Would move {} to {}", body, specs[1]);
+                    }
+                }
+            }
+            None => {
+                if let encoder::queue::Queue::Sqs { client, queue_url } = &from {
+                    let output = client
+                        .get_queue_attributes(rusoto_sqs::GetQueueAttributesRequest {
+                            queue_url: queue_url.clone(),
+                            attribute_names: Some(vec!["ApproximateNumberOfMessages".to_owned()]),
+                        })
+                        .await?;
+                    let count = output
+                        .attributes
+                        .and_then(|mut attributes| attributes.remove("ApproximateNumberOfMessages"))
+                        .unwrap_or_default();
+                    println!(
+                        "Would move about {} messages to {} (they are not listed without being received)",
+                        count, specs[1]
+                    );
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    let report = encoder::queue::migrate(&mut from, &mut to, dedup).await?;
+    tracing::info!(
+        "Moved {} jobs from {} to {} ({} duplicates dropped)",
+        report.moved,
+        specs[0],
+        specs[1],
+        report.duplicates
+    );
+    Ok(())
+}
@@ -0,0 +1,60 @@
+/// Watch recordings being written with [monitor] and send alerts to the webhooks, emails and the
+/// publish destinations.
+#[tokio::main]
+async fn main() -> encoder::exit::Exit {
+    run().await.into()
+}
+
+async fn run() -> Result<(), anyhow::Error> {
+    use futures::StreamExt as _;
+
+    let config = encoder::load_config()?;
+    encoder::logging::init(&config.log)?;
+    let monitor = config
+        .monitor
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("[monitor] is not configured"))?;
+    let dirs = match monitor.dir {
+        Some(ref dir) => vec![dir.clone()],
+        None => config
+            .encoder
+            .base_dirs
+            .iter()
+            .map(|base_dir| base_dir.path.clone())
+            .collect(),
+    };
+    let sqs_client = config.sqs.client()?;
+    let mut watcher = encoder::monitor::Watcher::new(monitor);
+
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(monitor.interval));
+    while interval.next().await.is_some() {
+        for alert in watcher.poll(&dirs)? {
+            tracing::warn!("{}: {}", alert.path.display(), alert.message);
+            let outcome = encoder::outcome::JobOutcome {
+                status: encoder::outcome::Status::Alert,
+                message_id: "",
+                file: &alert.path,
+                profile: None,
+                report: None,
+                error: Some(alert.message),
+                eta: None,
+            };
+            for webhook in &config.webhooks {
+                if let Err(e) = webhook.notify(&outcome).await {
+                    tracing::warn!("{}", e);
+                }
+            }
+            for email in &config.emails {
+                if let Err(e) = email.notify(&outcome).await {
+                    tracing::warn!("{}", e);
+                }
+            }
+            if let Some(ref publish) = config.publish {
+                if let Err(e) = publish.publish(&sqs_client, &config.redis, &outcome).await {
+                    tracing::warn!("Failed to publish the alert: {}", e);
+                }
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,129 @@
+/// Record a service into base_dir and enqueue the encode job of it.
+///
+///     record --service SERVICE_ID --start RFC3339 --end RFC3339 [--name NAME] [--no-enqueue]
+///     record --service SERVICE_ID --event EVENT_ID [--name NAME] [--no-enqueue]
+///
+/// The time window of --event is looked up in the EPG of Mirakurun. The window is widened by the
+/// margins in [record], and captured with the tuner, tuner_command or Mirakurun.
+#[tokio::main]
+async fn main() -> encoder::exit::Exit {
+    run().await.into()
+}
+
+async fn run() -> Result<(), anyhow::Error> {
+    use rusoto_sqs::Sqs as _;
+
+    let config = encoder::load_config()?;
+    encoder::logging::init(&config.log)?;
+    let mut service_id = None;
+    let mut start = None;
+    let mut end = None;
+    let mut event_id = None;
+    let mut name = None;
+    let mut no_enqueue = config.record.no_enqueue;
+    let mut args = encoder::exit::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--service" => {
+                service_id = Some(
+                    args.next()
+                        .ok_or_else(|| encoder::exit::usage("missing service"))?
+                        .parse()?,
+                )
+            }
+            "--start" => {
+                start = Some(
+                    args.next()
+                        .ok_or_else(|| encoder::exit::usage("missing start"))?,
+                )
+            }
+            "--end" => {
+                end = Some(
+                    args.next()
+                        .ok_or_else(|| encoder::exit::usage("missing end"))?,
+                )
+            }
+            "--event" => {
+                event_id = Some(
+                    args.next()
+                        .ok_or_else(|| encoder::exit::usage("missing event"))?
+                        .parse()?,
+                )
+            }
+            "--name" => {
+                name = Some(
+                    args.next()
+                        .ok_or_else(|| encoder::exit::usage("missing name"))?,
+                )
+            }
+            "--no-enqueue" => no_enqueue = true,
+            _ => return Err(encoder::exit::usage(format!("unknown argument {}", arg))),
+        }
+    }
+    let service_id: u16 = service_id.ok_or_else(|| encoder::exit::usage("missing --service"))?;
+    let mirakurun = config
+        .mirakurun
+        .as_ref()
+        .map(encoder::mirakurun::Client::new);
+
+    let request = match (event_id, start, end) {
+        (Some(event_id), None, None) => {
+            let client = mirakurun
+                .as_ref()
+                .ok_or_else(|| anyhow::anyhow!("--event requires [mirakurun]"))?;
+            let service = client.service(service_id).await?;
+            let program = client.program(&service, event_id).await?;
+            tracing::info!(
+                "{} on {}: {} - {}",
+                program.name.as_deref().unwrap_or("-"),
+                service.name,
+                program.start(),
+                program.end()
+            );
+            encoder::mirakurun::SourceRequest {
+                service_id,
+                start: program.start().to_rfc3339(),
+                end: program.end().to_rfc3339(),
+                name,
+            }
+        }
+        (None, Some(start), Some(end)) => encoder::mirakurun::SourceRequest {
+            service_id,
+            start,
+            end,
+            name,
+        },
+        _ => {
+            return Err(encoder::exit::usage(
+                "either --event or --start and --end is required",
+            ))
+        }
+    };
+    let fname = request.file_name()?;
+    let ts_path = config.encoder.source_path(&fname)?;
+    if ts_path.exists() {
+        return Err(anyhow::anyhow!("{} already exists", ts_path.display()));
+    }
+    encoder::record::record(&config, &request, &ts_path).await?;
+    tracing::info!("Recorded {}", ts_path.display());
+    if no_enqueue {
+        return Ok(());
+    }
+
+    config
+        .sqs
+        .client()?
+        .send_message(rusoto_sqs::SendMessageRequest {
+            queue_url: config.sqs.queue_url.clone(),
+            message_body: fname.clone(),
+            ..Default::default()
+        })
+        .await?;
+    tracing::info!("Enqueued {}", fname);
+    if let Some(ref jobs) = config.jobs {
+        encoder::jobs::JobStore::new(jobs, &config.redis)?
+            .transition(&fname, encoder::jobs::State::Queued, &[])
+            .await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,99 @@
+/// Run the stages in [pipeline] for a recording.
+///
+///     recutils-pipeline [--profile NAME] TS
+///     recutils-pipeline [--profile NAME] --service SERVICE_ID --start RFC3339 --end RFC3339 [--name NAME]
+///
+/// The progress is saved next to the source TS, and running the same command again
+/// resumes from the unfinished stage.
+#[tokio::main]
+async fn main() -> encoder::exit::Exit {
+    run().await.into()
+}
+
+async fn run() -> Result<(), anyhow::Error> {
+    ffmpeg::init()?;
+
+    let config = encoder::load_config()?;
+    encoder::logging::init(&config.log)?;
+    let pipeline = config
+        .pipeline
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("[pipeline] is not configured"))?;
+    let mut profile = None;
+    let mut service_id = None;
+    let mut start = None;
+    let mut end = None;
+    let mut name = None;
+    let mut ts_path = None;
+    let mut args = encoder::exit::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--profile" => {
+                profile = Some(
+                    args.next()
+                        .ok_or_else(|| encoder::exit::usage("missing profile"))?,
+                )
+            }
+            "--service" => {
+                service_id = Some(
+                    args.next()
+                        .ok_or_else(|| encoder::exit::usage("missing service"))?
+                        .parse()?,
+                )
+            }
+            "--start" => {
+                start = Some(
+                    args.next()
+                        .ok_or_else(|| encoder::exit::usage("missing start"))?,
+                )
+            }
+            "--end" => {
+                end = Some(
+                    args.next()
+                        .ok_or_else(|| encoder::exit::usage("missing end"))?,
+                )
+            }
+            "--name" => {
+                name = Some(
+                    args.next()
+                        .ok_or_else(|| encoder::exit::usage("missing name"))?,
+                )
+            }
+            _ => ts_path = Some(std::path::PathBuf::from(arg)),
+        }
+    }
+
+    let (request, ts_path) = match (service_id, start, end, ts_path) {
+        (Some(service_id), Some(start), Some(end), None) => {
+            let request = encoder::mirakurun::SourceRequest {
+                service_id,
+                start,
+                end,
+                name,
+            };
+            let ts_path = config.encoder.source_path(&request.file_name()?)?;
+            (Some(request), ts_path)
+        }
+        (None, None, None, Some(ts_path)) => (None, ts_path),
+        _ => {
+            return Err(encoder::exit::usage(
+                "either TS or --service, --start and --end is required",
+            ))
+        }
+    };
+    let state_path = encoder::pipeline::PipelineState::path(&ts_path);
+    let mut state = match encoder::pipeline::PipelineState::load(&state_path)? {
+        Some(state) => {
+            tracing::info!("Resume {} after {:?}", ts_path.display(), state.completed);
+            state
+        }
+        None => encoder::pipeline::PipelineState {
+            request,
+            profile,
+            ..Default::default()
+        },
+    };
+    encoder::pipeline::run(&config, pipeline, &ts_path, &mut state, &state_path).await?;
+    tracing::info!("Finished {}", ts_path.display());
+    Ok(())
+}
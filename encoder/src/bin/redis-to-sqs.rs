@@ -1,12 +1,32 @@
 #[tokio::main]
-async fn main() -> Result<(), anyhow::Error> {
+async fn main() -> encoder::exit::Exit {
+    run().await.into()
+}
+
+async fn run() -> Result<(), anyhow::Error> {
     use redis::Commands as _;
     use rusoto_sqs::Sqs as _;
 
     let config = encoder::load_config()?;
-    let redis_client = redis::Client::open(config.redis.url)?;
+    encoder::logging::init(&config.log)?;
+    let redis_client = redis::Client::open(config.redis.url.as_str())?;
     let mut conn = redis_client.get_connection()?;
-    let sqs_client = rusoto_sqs::SqsClient::new(Default::default());
+    let sqs_client = config.sqs.client()?;
+    if encoder::exit::args().skip(1).any(|arg| arg == "--dry-run") {
+        let jobs: Vec<String> = conn.lrange("jobs", 0, -1)?;
+        for fname in jobs {
+            if config.redis.dedup_window.is_some() && conn.exists(dedup_key(&fname))? {
+                println!("Would skip {} enqueued recently", fname);
+            } else {
+                println!("Would enqueue {} to {}", fname, config.sqs.queue_url);
+            }
+        }
+        return Ok(());
+    }
+    let job_store = match config.jobs {
+        Some(ref jobs) => Some(encoder::jobs::JobStore::new(jobs, &config.redis)?),
+        None => None,
+    };
 
     loop {
         let job: Vec<String> = conn.blpop(&["jobs", "0"], 5)?;
@@ -14,15 +34,44 @@ async fn main() -> Result<(), anyhow::Error> {
             break;
         }
         let fname = job.into_iter().nth(1).unwrap();
-        println!("Enqueue {}", fname);
+        if let Some(window) = config.redis.dedup_window {
+            let marked: Option<String> = redis::cmd("SET")
+                .arg(dedup_key(&fname))
+                .arg(1)
+                .arg("NX")
+                .arg("EX")
+                .arg(window)
+                .query(&mut conn)?;
+            if marked.is_none() {
+                tracing::info!("Skip {} enqueued within {}s", fname, window);
+                continue;
+            }
+        }
+        tracing::info!("Enqueue {}", fname);
 
-        sqs_client
+        let result = sqs_client
             .send_message(rusoto_sqs::SendMessageRequest {
                 queue_url: config.sqs.queue_url.clone(),
-                message_body: fname,
+                message_body: fname.clone(),
                 ..Default::default()
             })
-            .await?;
+            .await;
+        if let Err(e) = result {
+            if config.redis.dedup_window.is_some() {
+                // Let the job pushed again be enqueued
+                conn.del::<_, i64>(dedup_key(&fname))?;
+            }
+            return Err(e.into());
+        }
+        if let Some(ref job_store) = job_store {
+            job_store
+                .transition(&fname, encoder::jobs::State::Queued, &[])
+                .await?;
+        }
     }
     Ok(())
 }
+
+fn dedup_key(fname: &str) -> String {
+    format!("enqueued:{}", fname)
+}
@@ -0,0 +1,100 @@
+/// Reserve recordings of events in EPG with the rules in [reserve].
+///
+///     reserve [--within SECS --enqueue] TS...
+///
+/// EPG is collected from the TS files, or read from the store after the TS files are ingested
+/// into it when [epg] is configured. Reservations are printed with their tuners. With
+/// --enqueue, reservations starting within SECS are sent to sqs.queue_url as job messages which
+/// sqs-encode records and encodes. Run it every SECS so that each event is enqueued once.
+#[tokio::main]
+async fn main() -> encoder::exit::Exit {
+    run().await.into()
+}
+
+async fn run() -> Result<(), anyhow::Error> {
+    use chrono::TimeZone as _;
+    use rusoto_sqs::Sqs as _;
+
+    let config = encoder::load_config()?;
+    encoder::logging::init(&config.log)?;
+    let reserve = config
+        .reserve
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("[reserve] is not configured"))?;
+    let mut within = None;
+    let mut enqueue = false;
+    let mut ts_paths = vec![];
+    let mut args = encoder::exit::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--within" => {
+                within = Some(
+                    args.next()
+                        .ok_or_else(|| encoder::exit::usage("missing within"))?
+                        .parse::<i64>()?,
+                )
+            }
+            "--enqueue" => enqueue = true,
+            _ => ts_paths.push(arg),
+        }
+    }
+    if enqueue && within.is_none() {
+        return Err(anyhow::anyhow!("--enqueue requires --within"));
+    }
+
+    let now = chrono::Utc::now().timestamp();
+    let mut events = vec![];
+    for ts_path in &ts_paths {
+        events.extend(encoder::analysis::collect_epg(ts_path)?);
+    }
+    if let Some(ref epg) = config.epg {
+        let mut store = encoder::epgstore::EpgStore::open(epg)?;
+        store.ingest(&events)?;
+        events = store.events(&encoder::epgstore::Query {
+            from: Some(now),
+            ..Default::default()
+        })?;
+    }
+    let reservations = reserve.reserve(&events)?;
+
+    let sqs_client = config.sqs.client()?;
+    for reservation in reservations {
+        if reservation.end_time <= now {
+            continue;
+        }
+        println!(
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            chrono::Local
+                .timestamp(reservation.start_time, 0)
+                .format("%Y-%m-%d %H:%M"),
+            reservation
+                .service_name
+                .as_deref()
+                .unwrap_or(&reservation.service_id.to_string()),
+            reservation.name.as_deref().unwrap_or("-"),
+            reservation.rule,
+            reservation.event_id,
+            match reservation.tuner {
+                Some(tuner) => format!("tuner {}", tuner),
+                None => "conflict".to_owned(),
+            }
+        );
+        if !enqueue || reservation.tuner.is_none() {
+            continue;
+        }
+        if let Some(within) = within {
+            if now <= reservation.start_time && reservation.start_time < now + within {
+                let body = serde_json::to_string(&reservation.source_request())?;
+                sqs_client
+                    .send_message(rusoto_sqs::SendMessageRequest {
+                        queue_url: config.sqs.queue_url.clone(),
+                        message_body: body.clone(),
+                        ..Default::default()
+                    })
+                    .await?;
+                tracing::info!("Enqueued {}", body);
+            }
+        }
+    }
+    Ok(())
+}
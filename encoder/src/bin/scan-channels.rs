@@ -0,0 +1,69 @@
+/// Capture each channel in [scan] and write the services found in them to scan.path.
+///
+///     scan-channels [CHANNEL...]
+///
+/// CHANNEL defaults to scan.channels. Services of channels which are not scanned this time are
+/// kept in the map.
+#[tokio::main]
+async fn main() -> encoder::exit::Exit {
+    run().await.into()
+}
+
+async fn run() -> Result<(), anyhow::Error> {
+    let config = encoder::load_config()?;
+    encoder::logging::init(&config.log)?;
+    let scan = config
+        .scan
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("[scan] is not configured"))?;
+    let mut channels = encoder::exit::args().skip(1).collect::<Vec<_>>();
+    if channels.is_empty() {
+        channels = scan.channels.clone();
+    }
+    let mirakurun = config
+        .mirakurun
+        .as_ref()
+        .map(encoder::mirakurun::Client::new);
+
+    let mut map = if scan.path.exists() {
+        encoder::scan::ServiceMap::load(&scan.path)?
+    } else {
+        encoder::scan::ServiceMap::default()
+    };
+    let dir = tempfile::Builder::new()
+        .prefix("scan-channels.")
+        .tempdir()?;
+    for channel in &channels {
+        let ts_path = dir.path().join("channel.ts");
+        tracing::info!("Scan {}", channel);
+        let services = match scan.capture(mirakurun.as_ref(), channel, &ts_path).await {
+            Ok(()) => encoder::scan::scan_ts(channel, &ts_path),
+            Err(e) => Err(e),
+        };
+        std::fs::remove_file(&ts_path).ok();
+        let services = match services {
+            Ok(services) => services,
+            Err(e) => {
+                tracing::warn!("Failed to scan {}: {}", channel, e);
+                continue;
+            }
+        };
+        for service in &services {
+            println!(
+                "{}\t{:03}\t{}\t{}\t{}",
+                channel,
+                service.channel_number,
+                service.service_id,
+                service.name.as_deref().unwrap_or("-"),
+                service.ts_name.as_deref().unwrap_or("-")
+            );
+        }
+        map.services.retain(|service| service.channel != *channel);
+        map.services.extend(services);
+    }
+    map.services
+        .sort_by_key(|service| (service.channel_number, service.service_id));
+    map.save(&scan.path)?;
+    println!("{} services in {}", map.services.len(), scan.path.display());
+    Ok(())
+}
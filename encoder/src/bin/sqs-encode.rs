@@ -1,99 +1,831 @@
 #[tokio::main]
-async fn main() -> Result<(), anyhow::Error> {
+async fn main() -> encoder::exit::Exit {
+    run().await.into()
+}
+
+async fn run() -> Result<(), anyhow::Error> {
     use anyhow::Context as _;
-    use futures::StreamExt as _;
     use rusoto_sqs::Sqs as _;
+    use tracing::Instrument as _;
 
-    let config = encoder::load_config()?;
-    let sqs_client = rusoto_sqs::SqsClient::new(Default::default());
-    let stop_path = std::path::Path::new("/tmp/stop-encode.txt");
-    let base_dir = std::path::Path::new(&config.encoder.base_dir);
+    let mut config = encoder::load_config()?;
+    encoder::logging::init(&config.log)?;
+    let sqs_client = config.sqs.client()?;
+    if encoder::exit::args().skip(1).any(|arg| arg == "--dry-run") {
+        return dry_run(&config, &sqs_client).await;
+    }
+    let metrics = std::sync::Arc::new(encoder::metrics::Metrics::new()?);
+    if let Some(ref metrics_config) = config.metrics {
+        let listen = metrics_config.listen;
+        tokio::spawn(metrics.clone().serve(listen));
+    }
+    if let Some(ref autoscaling) = config.autoscaling {
+        tokio::spawn(autoscaling.clone().run(
+            sqs_client.clone(),
+            config.sqs.all_queue_urls().map(str::to_owned).collect(),
+            metrics.clone(),
+        ));
+    }
+    if let Some(ref hwaccel) = config.encoder.profile.hwaccel {
+        hwaccel.select().await?;
+    }
+    let job_store = match config.jobs {
+        Some(ref jobs) => Some(encoder::jobs::JobStore::new(jobs, &config.redis)?),
+        None => None,
+    };
+    if let Some(ref recovery) = config.recovery {
+        let extensions = encoder::recovery::output_extensions(&config);
+        let mut paths = vec![];
+        for base_dir in &config.encoder.base_dirs {
+            paths.extend(recovery.clean(&base_dir.path, &extensions)?);
+        }
+        tracing::info!("Cleaned up {} partial files", paths.len());
+        if let Some(ref job_store) = job_store {
+            let files = encoder::recovery::reconcile(job_store).await?;
+            tracing::info!("Requeued {} interrupted jobs", files.len());
+        }
+    }
+    let state = std::sync::Arc::new(encoder::admin::WorkerState::default());
+    config.control.apply(&state);
+    tokio::spawn(config.control.clone().watch(state.clone()));
+    if let Some(ref admin_config) = config.admin {
+        let admin = std::sync::Arc::new(encoder::admin::Admin {
+            state: state.clone(),
+            token: admin_config.token.clone(),
+            sqs_client: sqs_client.clone(),
+            queue_url: config.sqs.queue_url.clone(),
+            job_store: job_store.clone(),
+        });
+        tokio::spawn(admin.serve(admin_config.listen));
+    }
+    let registry = match config.workers {
+        Some(ref workers) => {
+            if config.lock.is_none() {
+                return Err(anyhow::anyhow!("[workers] requires [lock]"));
+            }
+            let registry = encoder::workers::Registry::new(workers, &config.redis)?;
+            registry.heartbeat(&state).await?;
+            registry.clone().spawn(state.clone())?;
+            Some(registry)
+        }
+        None => None,
+    };
+    if let Some(ref spot) = config.spot {
+        let spot = spot.clone();
+        let state = state.clone();
+        tokio::spawn(async move {
+            let notice = spot.wait_for_interruption().await;
+            tracing::warn!("Spot interruption notice: {}", notice);
+            state.interrupt();
+        });
+    }
+    // Listen addresses, [jobs], [admin], [control], [autoscaling], [workers], [spot] and [log]
+    // are applied only at startup
+    let reload = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    {
+        let reload = reload.clone();
+        let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())?;
+        tokio::spawn(async move {
+            while hangup.recv().await.is_some() {
+                reload.store(true, std::sync::atomic::Ordering::SeqCst);
+            }
+        });
+    }
 
+    let mut consecutive_priority = 0;
+    let mut throttled = false;
+    // (queue_url, message) received but not handled yet
+    let mut prefetched = std::collections::VecDeque::new();
     loop {
-        if stop_path.exists() {
+        if state.is_draining() || state.is_interrupted() {
             break;
         }
-        let resp = sqs_client
-            .receive_message(rusoto_sqs::ReceiveMessageRequest {
-                queue_url: config.sqs.queue_url.clone(),
-                wait_time_seconds: Some(5),
-                visibility_timeout: Some(60),
-                ..Default::default()
-            })
-            .await
-            .context("failed to call sqs:ReceiveMessage")?;
-        if let Some(messages) = resp.messages {
-            let message = messages.into_iter().next().unwrap();
-            let fname = message.body.expect("SQS message body is missing");
+        // Applied to the jobs received after the reload
+        if reload.swap(false, std::sync::atomic::Ordering::SeqCst) {
+            match encoder::load_config() {
+                Ok(new_config) => {
+                    tracing::info!("Reloaded config.toml");
+                    config = new_config;
+                }
+                Err(e) => tracing::error!("Failed to reload config.toml: {:#}", e),
+            }
+        }
+        if state.is_paused() {
+            release_messages(&sqs_client, &mut prefetched).await;
+            tokio::time::delay_for(tokio::time::Duration::from_secs(5)).await;
+            continue;
+        }
+        if let Some(ref throttle) = config.throttle {
+            match throttle.reason()? {
+                Some(reason) => {
+                    release_messages(&sqs_client, &mut prefetched).await;
+                    if !throttled {
+                        tracing::info!("Throttled: {}", reason);
+                        throttled = true;
+                    }
+                    tokio::time::delay_for(tokio::time::Duration::from_secs(
+                        throttle.poll_interval,
+                    ))
+                    .await;
+                    continue;
+                }
+                None => {
+                    if throttled {
+                        tracing::info!("Resumed");
+                        throttled = false;
+                    }
+                }
+            }
+        }
+        metrics
+            .disk_free
+            .set(encoder::disk::available_space(config.encoder.base_dir())? as i64);
+        if prefetched.is_empty() {
+            // Only the last queue is long-polled
+            let poll_order = config.sqs.poll_order(consecutive_priority);
+            for (i, queue_url) in poll_order.iter().enumerate() {
+                let resp = sqs_client
+                    .receive_message(rusoto_sqs::ReceiveMessageRequest {
+                        queue_url: (*queue_url).to_owned(),
+                        max_number_of_messages: Some(config.sqs.prefetch.clamp(1, 10) as i64),
+                        wait_time_seconds: Some(if i + 1 == poll_order.len() { 5 } else { 0 }),
+                        visibility_timeout: Some(60),
+                        attribute_names: Some(vec!["SentTimestamp".to_owned()]),
+                        ..Default::default()
+                    })
+                    .await
+                    .context("failed to call sqs:ReceiveMessage")?;
+                let messages = resp.messages.unwrap_or_default();
+                if !messages.is_empty() {
+                    prefetched.extend(
+                        messages
+                            .into_iter()
+                            .map(|message| ((*queue_url).to_owned(), message)),
+                    );
+                    break;
+                }
+            }
+        }
+        if let Some((queue_url, message)) = prefetched.pop_front() {
+            if queue_url == config.sqs.queue_url {
+                consecutive_priority = 0;
+            } else {
+                consecutive_priority += 1;
+            }
+            let body = message.body.expect("SQS message body is missing");
             let message_id = message.message_id.expect("SQS message_id is missing");
             let receipt_handle = message
                 .receipt_handle
                 .expect("SQS receipt_handle is missing");
-            println!("[message_id={}] {}", message_id, fname);
-
-            let ts_path = base_dir.join(format!("{}.ts", fname));
-            if ts_path.exists() {
-                let interval = tokio::time::interval(tokio::time::Duration::from_secs(60))
-                    .map(|_| futures::future::Either::Left(()));
-                let encode = futures::stream::once(encoder::encode(&config, ts_path))
-                    .map(futures::future::Either::Right);
-                tokio::pin!(encode);
-                let mut stream = futures::stream::select(interval, encode);
-
-                while let Some(item) = stream.next().await {
-                    match item {
-                        futures::future::Either::Left(_) => {
-                            let result = sqs_client
-                                .change_message_visibility(
-                                    rusoto_sqs::ChangeMessageVisibilityRequest {
-                                        queue_url: config.sqs.queue_url.clone(),
-                                        receipt_handle: receipt_handle.clone(),
-                                        visibility_timeout: 70,
-                                    },
-                                )
-                                .await;
-                            if let Err(e) = result {
-                                eprintln!("Failed to change message visibility: {:?}", e);
-                            }
+            // The message is left to be retried or moved to the dead-letter queue
+            let request = match encoder::mirakurun::SourceRequest::parse(&body) {
+                Ok(request) => request,
+                Err(e) => {
+                    tracing::error!(%message_id, "Invalid message {}: {}", body, e);
+                    continue;
+                }
+            };
+            let fname = match request {
+                Some(ref request) => match request.file_name() {
+                    Ok(fname) => fname,
+                    Err(e) => {
+                        tracing::error!(%message_id, "Invalid message {}: {}", body, e);
+                        continue;
+                    }
+                },
+                None => body,
+            };
+            tracing::info!(%message_id, "Received {}", fname);
+            metrics.jobs_received.inc();
+            if let Some(sent_timestamp) = message
+                .attributes
+                .as_ref()
+                .and_then(|attributes| attributes.get("SentTimestamp"))
+                .and_then(|timestamp| timestamp.parse::<i64>().ok())
+            {
+                let wait = chrono::Utc::now().timestamp_millis() - sent_timestamp;
+                metrics.queue_wait.observe(wait as f64 / 1000.0);
+            }
+
+            let span = tracing::info_span!(
+                "job",
+                message_id = %message_id,
+                file = %fname,
+                profile = tracing::field::Empty,
+            );
+            let worker = Worker {
+                config: &config,
+                sqs_client: &sqs_client,
+                metrics: &metrics,
+                job_store: job_store.as_ref(),
+                state: &state,
+                registry: registry.as_ref(),
+            };
+            let handle = async {
+                if let Some(ref request) = request {
+                    if !worker
+                        .fetch_source(&queue_url, &receipt_handle, request, &fname)
+                        .await
+                    {
+                        return Ok(());
+                    }
+                }
+                worker
+                    .handle_message(&queue_url, &message_id, &fname, &receipt_handle)
+                    .await
+            }
+            .instrument(span);
+            let keep_visible = keep_visible(&sqs_client, &prefetched);
+            futures::pin_mut!(handle, keep_visible);
+            match futures::future::select(handle, keep_visible).await {
+                futures::future::Either::Left((result, _)) => result?,
+                futures::future::Either::Right(_) => unreachable!(),
+            }
+        } else {
+            break;
+        }
+    }
+    release_messages(&sqs_client, &mut prefetched).await;
+
+    if let Some(ref registry) = registry {
+        registry.unregister().await?;
+    }
+    Ok(())
+}
+
+/// Print the plan of the messages at the head of the queues. The messages are received with zero
+/// visibility timeout so that workers can receive them right away, but the receive counts are
+/// incremented.
+async fn dry_run<Sqs>(config: &encoder::Config, sqs_client: &Sqs) -> Result<(), anyhow::Error>
+where
+    Sqs: rusoto_sqs::Sqs,
+{
+    use anyhow::Context as _;
+
+    for queue_url in config.sqs.all_queue_urls() {
+        let messages = sqs_client
+            .receive_message(rusoto_sqs::ReceiveMessageRequest {
+                queue_url: queue_url.to_owned(),
+                max_number_of_messages: Some(10),
+                visibility_timeout: Some(0),
+                ..Default::default()
+            })
+            .await
+            .context("failed to call sqs:ReceiveMessage")?
+            .messages
+            .unwrap_or_default();
+        println!("{}: {} messages", queue_url, messages.len());
+        for message in messages {
+            let body = message.body.expect("SQS message body is missing");
+            let fname = match encoder::mirakurun::SourceRequest::parse(&body)? {
+                Some(request) => {
+                    println!(
+                        "record service {} from {} to {}",
+                        request.service_id, request.start, request.end
+                    );
+                    request.file_name()?
+                }
+                None => body,
+            };
+            let ts_path = match config.encoder.source_path(&fname) {
+                Ok(ts_path) => ts_path,
+                Err(e) => {
+                    println!("{}", e);
+                    continue;
+                }
+            };
+            if !ts_path.exists() {
+                println!("{} does not exist", ts_path.display());
+                continue;
+            }
+            let (profile, channel) = config.resolve(None, &ts_path)?;
+            encoder::plan::plan(config, profile, channel, &ts_path)?.print();
+        }
+    }
+    Ok(())
+}
+
+/// Shared by the jobs of the worker
+struct Worker<'a, Sqs> {
+    config: &'a encoder::Config,
+    sqs_client: &'a Sqs,
+    metrics: &'a encoder::metrics::Metrics,
+    job_store: Option<&'a encoder::jobs::JobStore>,
+    state: &'a encoder::admin::WorkerState,
+    registry: Option<&'a encoder::workers::Registry>,
+}
+
+enum Event {
+    Heartbeat,
+    RefreshLock,
+    State(encoder::jobs::State),
+    Cancel,
+    Interrupt,
+    Finished(Result<encoder::Report, anyhow::Error>),
+}
+
+impl<'a, Sqs> Worker<'a, Sqs>
+where
+    Sqs: rusoto_sqs::Sqs,
+{
+    /// Record the requested source into the first base_dir unless it exists. The message is kept invisible
+    /// while recording, and left to be retried when the recording fails.
+    async fn fetch_source(
+        &self,
+        queue_url: &str,
+        receipt_handle: &str,
+        request: &encoder::mirakurun::SourceRequest,
+        fname: &str,
+    ) -> bool {
+        use futures::StreamExt as _;
+
+        let ts_path = match self.config.encoder.source_path(fname) {
+            Ok(ts_path) => ts_path,
+            Err(e) => {
+                tracing::error!("{}", e);
+                return false;
+            }
+        };
+        if ts_path.exists() {
+            return true;
+        }
+        let client = match self.config.mirakurun {
+            Some(ref mirakurun) => encoder::mirakurun::Client::new(mirakurun),
+            None => {
+                tracing::error!("[mirakurun] is not configured for {:?}", request);
+                return false;
+            }
+        };
+        let record = client.record(request, &ts_path);
+        let heartbeat = async {
+            let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(60));
+            while interval.next().await.is_some() {
+                let result = self
+                    .sqs_client
+                    .change_message_visibility(rusoto_sqs::ChangeMessageVisibilityRequest {
+                        queue_url: queue_url.to_owned(),
+                        receipt_handle: receipt_handle.to_owned(),
+                        visibility_timeout: 70,
+                    })
+                    .await;
+                if let Err(e) = result {
+                    tracing::warn!("Failed to change message visibility: {:?}", e);
+                }
+            }
+        };
+        futures::pin_mut!(record, heartbeat);
+        match futures::future::select(record, heartbeat).await {
+            futures::future::Either::Left((Ok(()), _)) => true,
+            futures::future::Either::Left((Err(e), _)) => {
+                tracing::error!("Failed to record {}: {}", ts_path.display(), e);
+                false
+            }
+            futures::future::Either::Right(_) => unreachable!(),
+        }
+    }
+
+    async fn handle_message(
+        &self,
+        queue_url: &str,
+        message_id: &str,
+        fname: &str,
+        receipt_handle: &str,
+    ) -> Result<(), anyhow::Error> {
+        use anyhow::Context as _;
+        use futures::StreamExt as _;
+
+        let ts_path = match self.config.encoder.source_path(fname) {
+            Ok(ts_path) => ts_path,
+            Err(e) => {
+                // Left in the queue for the dead-letter queue
+                tracing::error!("{}", e);
+                return Ok(());
+            }
+        };
+        let base_dir = ts_path.parent().unwrap();
+        if let (true, Some(space)) = (ts_path.exists(), &self.config.encoder.profile.space) {
+            if let Some((required, available)) = space.shortage(&ts_path, base_dir)? {
+                // Leave the message in the queue and pause until enough space is available
+                tracing::warn!(
+                    "{} requires {} bytes but {} has only {} bytes",
+                    ts_path.display(),
+                    required,
+                    base_dir.display(),
+                    available
+                );
+                while !self.state.is_draining()
+                    && !self.state.is_interrupted()
+                    && encoder::disk::available_space(base_dir)? < required
+                {
+                    tokio::time::delay_for(tokio::time::Duration::from_secs(space.poll_interval))
+                        .await;
+                }
+                return Ok(());
+            }
+        }
+        if ts_path.exists() {
+            let lock = match self.config.lock {
+                Some(ref lock) => match lock
+                    .acquire(&self.config.redis, fname, self.registry)
+                    .await?
+                {
+                    Some(lock) => Some(lock),
+                    // Left in the queue until the other worker finishes
+                    None => return Ok(()),
+                },
+                None => None,
+            };
+            let (profile, channel) = self.config.resolve(None, &ts_path)?;
+            tracing::Span::current()
+                .record("profile", &profile.name.as_deref().unwrap_or("default"));
+            if encoder::is_encoded(profile, channel, &ts_path)? {
+                tracing::info!("{} is already encoded and verified", ts_path.display());
+                delete_message_with_retry(self.sqs_client, queue_url, receipt_handle).await?;
+                if let Some(lock) = lock {
+                    if let Err(e) = lock.release().await {
+                        tracing::warn!("Failed to release the lock: {}", e);
+                    }
+                }
+                return Ok(());
+            }
+            let ts_size = std::fs::metadata(&ts_path)?.len();
+            self.metrics.in_flight_jobs.inc();
+            self.state.start_job(message_id, fname);
+            let estimate = self.estimate(profile, &ts_path).await;
+            let eta = estimate.map(|seconds| {
+                chrono::Utc::now() + chrono::Duration::milliseconds((seconds * 1000.0) as i64)
+            });
+            let eta_str = eta.map(|eta| eta.to_rfc3339());
+            let mut fields = vec![("message_id", message_id)];
+            if let Some(ref eta_str) = eta_str {
+                tracing::info!("{} will finish at {}", ts_path.display(), eta_str);
+                fields.push(("eta", eta_str));
+            }
+            if let Some(eta) = eta {
+                self.state.set_eta(eta);
+            }
+            self.transition(fname, encoder::jobs::State::Running, &fields)
+                .await;
+            self.notify(&encoder::outcome::JobOutcome::started(
+                message_id, &ts_path, profile, eta,
+            ))
+            .await;
+            let heartbeat_interval = heartbeat_interval(estimate);
+            let (state_tx, state_rx) = tokio::sync::watch::channel(encoder::jobs::State::Running);
+            let interval =
+                tokio::time::interval(tokio::time::Duration::from_secs(heartbeat_interval))
+                    .map(|_| Event::Heartbeat);
+            // Long jobs beat less often than the lock expires
+            let refresh_lock = match lock {
+                Some(ref lock) => {
+                    tokio::time::interval(tokio::time::Duration::from_secs(lock.refresh_interval()))
+                        .map(|_| Event::RefreshLock)
+                        .left_stream()
+                }
+                None => futures::stream::pending().right_stream(),
+            };
+            let states = state_rx.map(Event::State);
+            let cancel = futures::stream::once(self.state.cancel.notified()).map(|_| Event::Cancel);
+            tokio::pin!(cancel);
+            let interrupt =
+                futures::stream::once(self.state.interrupt.notified()).map(|_| Event::Interrupt);
+            tokio::pin!(interrupt);
+            let encode = futures::stream::once(encoder::encode_with_state(
+                profile,
+                channel,
+                &ts_path,
+                Some(&state_tx),
+            ))
+            .map(Event::Finished);
+            tokio::pin!(encode);
+            let mut stream = futures::stream::select(
+                futures::stream::select(futures::stream::select(interval, refresh_lock), states),
+                futures::stream::select(futures::stream::select(cancel, interrupt), encode),
+            );
+
+            while let Some(event) = stream.next().await {
+                match event {
+                    Event::State(state) => {
+                        self.state.set_job_state(state);
+                        if state != encoder::jobs::State::Running {
+                            self.transition(fname, state, &[]).await;
                         }
-                        futures::future::Either::Right(result) => {
-                            match result {
-                                Ok(_) => {
-                                    delete_message_with_retry(
-                                        &sqs_client,
-                                        &config.sqs.queue_url,
-                                        &receipt_handle,
-                                    )
-                                    .await?;
-                                }
-                                Err(e) => {
-                                    eprintln!("encode failed: {:?}", e);
+                    }
+                    Event::Heartbeat => {
+                        let result = self
+                            .sqs_client
+                            .change_message_visibility(rusoto_sqs::ChangeMessageVisibilityRequest {
+                                queue_url: queue_url.to_owned(),
+                                receipt_handle: receipt_handle.to_owned(),
+                                visibility_timeout: heartbeat_interval as i64 + 10,
+                            })
+                            .await;
+                        if let Err(e) = result {
+                            tracing::warn!("Failed to change message visibility: {:?}", e);
+                        }
+                    }
+                    Event::RefreshLock => {
+                        if let Some(ref lock) = lock {
+                            match lock.refresh().await {
+                                Ok(true) => {}
+                                Ok(false) => {
+                                    // Returning drops the encode and kills ffmpeg. The outputs,
+                                    // the message and the job state belong to the worker holding
+                                    // the lock now.
+                                    tracing::error!(
+                                        "Lost the lock of {} and gave up encoding it",
+                                        ts_path.display()
+                                    );
+                                    self.metrics.in_flight_jobs.dec();
+                                    self.state.finish_job();
+                                    return Ok(());
                                 }
+                                // Retried on the next refresh before the lock expires
+                                Err(e) => tracing::warn!("Failed to refresh the lock: {}", e),
                             }
-                            break;
                         }
                     }
+                    Event::Cancel => {
+                        // Dropping the encode kills ffmpeg. The message is deleted so that the job
+                        // is not redelivered; it can be requeued from the admin API.
+                        tracing::warn!("Cancelled {}", ts_path.display());
+                        self.remove_outputs(profile, &ts_path);
+                        delete_message_with_retry(self.sqs_client, queue_url, receipt_handle)
+                            .await?;
+                        self.finish(
+                            profile,
+                            message_id,
+                            fname,
+                            &ts_path,
+                            ts_size,
+                            Err(anyhow::anyhow!("cancelled")),
+                        )
+                        .await;
+                        break;
+                    }
+                    Event::Interrupt => {
+                        // Make the message visible to other workers right away
+                        tracing::warn!("Interrupted {}", ts_path.display());
+                        self.remove_outputs(profile, &ts_path);
+                        self.sqs_client
+                            .change_message_visibility(rusoto_sqs::ChangeMessageVisibilityRequest {
+                                queue_url: queue_url.to_owned(),
+                                receipt_handle: receipt_handle.to_owned(),
+                                visibility_timeout: 0,
+                            })
+                            .await
+                            .context("failed to call sqs:ChangeMessageVisibility")?;
+                        self.transition(fname, encoder::jobs::State::Queued, &[])
+                            .await;
+                        self.metrics.in_flight_jobs.dec();
+                        self.state.finish_job();
+                        break;
+                    }
+                    Event::Finished(result) => {
+                        if result.is_ok() {
+                            delete_message_with_retry(self.sqs_client, queue_url, receipt_handle)
+                                .await?;
+                        }
+                        self.finish(profile, message_id, fname, &ts_path, ts_size, result)
+                            .await;
+                        break;
+                    }
                 }
+            }
+            if let Some(lock) = lock {
+                if let Err(e) = lock.release().await {
+                    tracing::warn!("Failed to release the lock: {}", e);
+                }
+            }
+        } else {
+            // Outputs named with the naming template cannot be found without the TS
+            let profile = &self.config.encoder.profile;
+            if encoder::is_encoded(profile, None, &ts_path)? {
+                tracing::info!(
+                    "{} is already encoded to {}",
+                    ts_path.display(),
+                    encoder::output::outputs(profile, &ts_path, None)[0]
+                        .path
+                        .display()
+                );
+                delete_message_with_retry(self.sqs_client, queue_url, receipt_handle).await?;
             } else {
-                let mp4_path = base_dir.join(format!("{}.mp4", fname));
-                if mp4_path.exists() {
-                    println!(
-                        "{} is already encoded to {}",
-                        ts_path.display(),
-                        mp4_path.display()
-                    );
-                    delete_message_with_retry(&sqs_client, &config.sqs.queue_url, &receipt_handle)
-                        .await?;
-                } else {
-                    println!("{} does not exist", ts_path.display());
+                tracing::info!("{} does not exist", ts_path.display());
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove partial outputs of an aborted encode
+    fn remove_outputs(&self, profile: &encoder::ProfileConfig, ts_path: &std::path::Path) {
+        // Outputs named with the naming template cannot be found without the analysis
+        if profile.naming.is_some() {
+            return;
+        }
+        for output in encoder::output::outputs(profile, ts_path, None) {
+            if let Err(e) = output.remove() {
+                tracing::warn!("Failed to remove {}: {}", output.path.display(), e);
+            }
+        }
+    }
+
+    /// Record the result of the job and notify it
+    async fn finish(
+        &self,
+        profile: &encoder::ProfileConfig,
+        message_id: &str,
+        fname: &str,
+        ts_path: &std::path::Path,
+        ts_size: u64,
+        result: Result<encoder::Report, anyhow::Error>,
+    ) {
+        match result {
+            Ok(ref report) => {
+                self.metrics.jobs_succeeded.inc();
+                // Waiting for the transfer windows doesn't tell the throughput
+                let elapsed = report.elapsed - report.waited;
+                self.metrics.encode_duration.observe(elapsed);
+                if elapsed > 0.0 {
+                    let speed = report.duration / elapsed;
+                    self.metrics.speed.observe(speed);
+                    if let Some(job_store) = self.job_store {
+                        let profile_name = profile.name.as_deref().unwrap_or("default");
+                        if let Err(e) = job_store.record_speed(profile_name, speed).await {
+                            tracing::warn!("Failed to record the speed: {}", e);
+                        }
+                    }
                 }
+                self.metrics.bytes_in.inc_by(ts_size);
+                for output in &report.outputs {
+                    self.metrics.bytes_out.inc_by(output.size);
+                    tracing::info!("{}: {:?}", output.path.display(), output.scores);
+                    if let Some(ref sha256) = output.sha256 {
+                        tracing::info!("{}: sha256={}", output.path.display(), sha256);
+                    }
+                }
+                let duration = report.duration.to_string();
+                let mut fields = vec![("duration", duration.as_str())];
+                let output = report
+                    .outputs
+                    .first()
+                    .map(|output| output.path.to_string_lossy().into_owned());
+                if let Some(ref output) = output {
+                    fields.push(("output", output));
+                }
+                let precheck = report.precheck.as_ref().map(|precheck| precheck.summary());
+                if let Some(ref precheck) = precheck {
+                    fields.push(("precheck", precheck));
+                }
+                self.transition(fname, encoder::jobs::State::Done, &fields)
+                    .await;
+            }
+            Err(ref e) => {
+                self.metrics.jobs_failed.inc();
+                tracing::error!("encode failed: {:?}", e);
+                self.transition(
+                    fname,
+                    encoder::jobs::State::Failed,
+                    &[("error", &format!("{:#}", e))],
+                )
+                .await;
+            }
+        }
+        self.metrics.in_flight_jobs.dec();
+        self.state.finish_job();
+
+        self.notify(&encoder::outcome::JobOutcome::new(
+            message_id, ts_path, profile, &result,
+        ))
+        .await;
+    }
+
+    /// Publish the outcome and send it to webhooks and emails
+    async fn notify(&self, outcome: &encoder::outcome::JobOutcome<'_>) {
+        if let Some(ref publish) = self.config.publish {
+            if let Err(e) = publish
+                .publish(self.sqs_client, &self.config.redis, outcome)
+                .await
+            {
+                tracing::warn!("Failed to publish the outcome: {}", e);
+            }
+        }
+        for webhook in &self.config.webhooks {
+            if let Err(e) = webhook.notify(outcome).await {
+                tracing::warn!("{}", e);
             }
+        }
+        for email in &self.config.emails {
+            if let Err(e) = email.notify(outcome).await {
+                tracing::warn!("{}", e);
+            }
+        }
+    }
+
+    /// Wall seconds to encode the source with the speed factor recorded for the profile
+    async fn estimate(
+        &self,
+        profile: &encoder::ProfileConfig,
+        ts_path: &std::path::Path,
+    ) -> Option<f64> {
+        let job_store = self.job_store?;
+        let profile_name = profile.name.as_deref().unwrap_or("default");
+        let speed = match job_store.speed(profile_name).await {
+            Ok(Some(speed)) if speed > 0.0 => speed,
+            Ok(_) => return None,
+            Err(e) => {
+                tracing::warn!("Failed to get the speed of {}: {}", profile_name, e);
+                return None;
+            }
+        };
+        let duration = match ffmpeg::format::input(&ts_path) {
+            Ok(ictx) => ictx.duration() as f64 * f64::from(ffmpeg::rescale::TIME_BASE),
+            Err(e) => {
+                tracing::warn!("Failed to get the duration of {}: {}", ts_path.display(), e);
+                return None;
+            }
+        };
+        if duration > 0.0 {
+            Some(duration / speed)
         } else {
-            break;
+            None
         }
     }
 
-    Ok(())
+    /// Record the state of the job. Failures are logged since they don't affect the encode.
+    async fn transition(&self, fname: &str, state: encoder::jobs::State, fields: &[(&str, &str)]) {
+        if let Some(job_store) = self.job_store {
+            if let Err(e) = job_store.transition(fname, state, fields).await {
+                tracing::warn!("Failed to record the job state {}: {}", state.as_str(), e);
+            }
+        }
+    }
+}
+
+/// Seconds between heartbeats of a job estimated to take the given wall seconds. Long jobs beat
+/// less often, with the visibility timeout extended a little beyond the next heartbeat.
+fn heartbeat_interval(estimate: Option<f64>) -> u64 {
+    estimate.map_or(60, |seconds| ((seconds / 20.0) as u64).clamp(60, 600))
+}
+
+/// Extend the visibility timeout of the prefetched messages like the heartbeat of the job
+async fn keep_visible<Sqs>(
+    sqs_client: &Sqs,
+    prefetched: &std::collections::VecDeque<(String, rusoto_sqs::Message)>,
+) where
+    Sqs: rusoto_sqs::Sqs,
+{
+    use futures::StreamExt as _;
+
+    let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(60));
+    while interval.next().await.is_some() {
+        change_visibility(sqs_client, prefetched, 70).await;
+    }
+}
+
+/// Make the prefetched messages visible to the other workers
+async fn release_messages<Sqs>(
+    sqs_client: &Sqs,
+    prefetched: &mut std::collections::VecDeque<(String, rusoto_sqs::Message)>,
+) where
+    Sqs: rusoto_sqs::Sqs,
+{
+    change_visibility(sqs_client, prefetched, 0).await;
+    prefetched.clear();
+}
+
+async fn change_visibility<Sqs>(
+    sqs_client: &Sqs,
+    messages: &std::collections::VecDeque<(String, rusoto_sqs::Message)>,
+    visibility_timeout: i64,
+) where
+    Sqs: rusoto_sqs::Sqs,
+{
+    let mut entries = std::collections::BTreeMap::<&str, Vec<_>>::new();
+    for (i, (queue_url, message)) in messages.iter().enumerate() {
+        if let Some(ref receipt_handle) = message.receipt_handle {
+            entries.entry(queue_url.as_str()).or_default().push(
+                rusoto_sqs::ChangeMessageVisibilityBatchRequestEntry {
+                    id: i.to_string(),
+                    receipt_handle: receipt_handle.clone(),
+                    visibility_timeout: Some(visibility_timeout),
+                },
+            );
+        }
+    }
+    for (queue_url, entries) in entries {
+        let result = sqs_client
+            .change_message_visibility_batch(rusoto_sqs::ChangeMessageVisibilityBatchRequest {
+                queue_url: queue_url.to_owned(),
+                entries,
+            })
+            .await;
+        if let Err(e) = result {
+            tracing::warn!(
+                "Failed to change visibility of prefetched messages: {:?}",
+                e
+            );
+        }
+    }
 }
 
 async fn delete_message_with_retry<Sqs>(
@@ -116,7 +848,7 @@ where
                 return Ok(());
             }
             Err(e) => {
-                eprintln!("[{}] failed to call sqs:DeleteMessage: {}", i, e);
+                tracing::warn!("[{}] failed to call sqs:DeleteMessage: {}", i, e);
             }
         }
     }
@@ -0,0 +1,133 @@
+/// Detect outputs which are mostly black or silent. Broken or scrambled sources sometimes encode
+/// into such outputs with the right duration.
+#[derive(Clone, serde::Deserialize)]
+pub struct BlankConfig {
+    /// Maximum ratio of black frames to the duration
+    #[serde(default = "default_max_ratio")]
+    pub max_black_ratio: f64,
+    /// Maximum ratio of silence to the duration
+    #[serde(default = "default_max_ratio")]
+    pub max_silence_ratio: f64,
+    /// pix_th of blackdetect
+    #[serde(default = "default_pixel_threshold")]
+    pub pixel_threshold: f64,
+    /// Noise tolerance of silencedetect in dB
+    #[serde(default = "default_noise")]
+    pub noise: f64,
+}
+
+fn default_max_ratio() -> f64 {
+    0.9
+}
+
+fn default_pixel_threshold() -> f64 {
+    0.10
+}
+
+fn default_noise() -> f64 {
+    -60.0
+}
+
+/// Total durations in seconds reported by blackdetect and silencedetect
+#[derive(Debug, Default)]
+struct Detection {
+    black: f64,
+    silence: f64,
+}
+
+impl Detection {
+    fn parse(stderr: &str, duration: f64) -> Self {
+        let mut detection = Self::default();
+        let mut silence_start = None;
+        for line in stderr.lines() {
+            if line.contains("[blackdetect") {
+                if let Some(value) = field(line, "black_duration:") {
+                    detection.black += value;
+                }
+            } else if line.contains("[silencedetect") {
+                if let Some(value) = field(line, "silence_start:") {
+                    silence_start = Some(value);
+                } else if let Some(value) = field(line, "silence_duration:") {
+                    detection.silence += value;
+                    silence_start = None;
+                }
+            }
+        }
+        // silencedetect doesn't report silence lasting until the end
+        if let Some(start) = silence_start {
+            detection.silence += (duration - start).max(0.0);
+        }
+        detection
+    }
+}
+
+fn field(line: &str, key: &str) -> Option<f64> {
+    let value = &line[line.find(key)? + key.len()..];
+    value.split_whitespace().next()?.parse().ok()
+}
+
+impl crate::verify::Verifier for BlankConfig {
+    fn name(&self) -> &'static str {
+        "blank"
+    }
+
+    fn verify(
+        &self,
+        target: &crate::verify::Target,
+        scores: &mut crate::verify::Scores,
+    ) -> Result<(), anyhow::Error> {
+        let mut command = std::process::Command::new("ffmpeg");
+        if let Some(work_dir) = target.work_dir {
+            command.current_dir(work_dir);
+        }
+        command.arg("-nostats").arg("-i").arg(target.output_path);
+        if target.audio_only {
+            command.arg("-vn");
+        } else {
+            command
+                .arg("-filter:v")
+                .arg(format!("blackdetect=d=1:pix_th={}", self.pixel_threshold));
+        }
+        let output = command
+            .arg("-filter:a")
+            .arg(format!("silencedetect=n={}dB:d=1", self.noise))
+            .args(["-f", "null", "-"])
+            .output()?;
+        if !output.status.success() {
+            return Err(anyhow::anyhow!(
+                "blackdetect/silencedetect failed with {}",
+                output.status
+            ));
+        }
+
+        let duration = target.expected_duration as f64 / 1_000_000.0;
+        if duration <= 0.0 {
+            return Ok(());
+        }
+        let detection = Detection::parse(&String::from_utf8_lossy(&output.stderr), duration);
+        let black_ratio = detection.black / duration;
+        let silence_ratio = detection.silence / duration;
+        if !target.audio_only {
+            scores.insert("black_ratio".to_owned(), black_ratio);
+        }
+        scores.insert("silence_ratio".to_owned(), silence_ratio);
+
+        if !target.audio_only && black_ratio > self.max_black_ratio {
+            return Err(anyhow::anyhow!(
+                "{:.1}% of the output is black ({:.1}s of {:.1}s)",
+                black_ratio * 100.0,
+                detection.black,
+                duration
+            ));
+        }
+        if silence_ratio > self.max_silence_ratio {
+            return Err(anyhow::anyhow!(
+                "{:.1}% of the output is silent ({:.1}s of {:.1}s)",
+                silence_ratio * 100.0,
+                detection.silence,
+                duration
+            ));
+        }
+        Ok(())
+    }
+}
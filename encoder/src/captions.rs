@@ -0,0 +1,167 @@
+/// Export ARIB captions of the source as SRT and mux them into the outputs as a subtitle stream
+#[derive(serde::Deserialize)]
+pub struct CaptionsConfig {
+    /// Write "{output stem}.srt" next to each output
+    #[serde(default)]
+    pub srt: bool,
+    /// Add a mov_text (MP4) or ASS (MKV) stream to each video output
+    #[serde(default = "default_mux")]
+    pub mux: bool,
+}
+
+fn default_mux() -> bool {
+    true
+}
+
+// 33 bits of PTS
+const PTS_MODULO: u64 = 1 << 33;
+// Displayed until the next statement, but no longer than this
+const MAX_CUE_SECS: f64 = 10.0;
+
+fn format_time(secs: f64) -> String {
+    let millis = (secs * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02},{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+/// Captions of the source TS in SRT. The filtered TS drops the caption stream, so captions are
+/// read from source_path while positions are relative to the start of ts_path, or to the start of
+/// the range when trimmed. None when there are no captions.
+pub fn extract_srt(
+    source_path: &std::path::Path,
+    ts_path: &std::path::Path,
+    service_id: Option<u16>,
+    range: Option<(f64, f64)>,
+) -> Result<Option<String>, anyhow::Error> {
+    let captions = tsutils::caption::extract(
+        std::io::BufReader::new(std::fs::File::open(source_path)?),
+        service_id,
+    )?;
+    if captions.is_empty() {
+        return Ok(None);
+    }
+    // Output timestamps start from the earliest stream as ffmpeg shifts them to zero
+    let start_pts = match ffmpeg::format::input(&ts_path)?
+        .streams()
+        .map(|stream| stream.start_time())
+        // AV_NOPTS_VALUE
+        .filter(|&start_time| start_time >= 0)
+        .min()
+    {
+        // The time base of MPEG-TS streams is 1/90000
+        Some(start_time) => start_time as u64 % PTS_MODULO,
+        None => return Ok(None),
+    };
+    let position = |pts: u64| {
+        let secs = ((pts + PTS_MODULO - start_pts) % PTS_MODULO) as f64 / 90000.0;
+        match range {
+            Some((start, _)) => secs - start,
+            None => secs,
+        }
+    };
+    let limit = range.map(|(start, end)| end - start);
+
+    let mut srt = String::new();
+    let mut index = 0;
+    for (i, caption) in captions.iter().enumerate() {
+        if caption.text.is_empty() {
+            continue;
+        }
+        let start = position(caption.pts);
+        let mut end = match captions.get(i + 1) {
+            Some(next) => position(next.pts).min(start + MAX_CUE_SECS),
+            None => start + MAX_CUE_SECS,
+        };
+        if let Some(limit) = limit {
+            end = end.min(limit);
+        }
+        if end <= 0.0 || start >= end {
+            continue;
+        }
+        index += 1;
+        srt.push_str(&format!(
+            "{}\n{} --> {}\n{}\n\n",
+            index,
+            format_time(start.max(0.0)),
+            format_time(end),
+            caption.text
+        ));
+    }
+    Ok(if index == 0 { None } else { Some(srt) })
+}
+
+impl CaptionsConfig {
+    /// Write the SRT next to the output and/or mux it into the output
+    pub async fn write(
+        &self,
+        work_dir: &std::path::Path,
+        output_path: &std::path::Path,
+        srt: &str,
+    ) -> Result<(), anyhow::Error> {
+        use std::io::Write as _;
+
+        if self.srt {
+            std::fs::write(output_path.with_extension("srt"), srt)?;
+        }
+        if self.mux {
+            let mut srt_file = tempfile::NamedTempFile::new_in(work_dir)?;
+            srt_file.write_all(srt.as_bytes())?;
+            let srt_path = srt_file.into_temp_path();
+            mux(work_dir, output_path, &srt_path).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Remux the output with the SRT as a subtitle stream without re-encoding
+pub async fn mux(
+    work_dir: &std::path::Path,
+    output_path: &std::path::Path,
+    srt_path: &std::path::Path,
+) -> Result<(), anyhow::Error> {
+    let extension = output_path
+        .extension()
+        .map(|extension| extension.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let codec = if extension == "mkv" {
+        "ass"
+    } else {
+        "mov_text"
+    };
+    let tmp_path = output_path.with_extension(format!("sub.{}", extension));
+
+    let status = tokio::process::Command::new("ffmpeg")
+        .current_dir(work_dir)
+        .args(["-y", "-i"])
+        .arg(output_path)
+        .args(["-f", "srt", "-i"])
+        .arg(srt_path)
+        .args([
+            "-map",
+            "0",
+            "-map",
+            "1",
+            "-c",
+            "copy",
+            "-c:s",
+            codec,
+            "-metadata:s:s:0",
+            "language=jpn",
+        ])
+        .arg(&tmp_path)
+        .status()
+        .await?;
+    if !status.success() {
+        if tmp_path.exists() {
+            std::fs::remove_file(&tmp_path)?;
+        }
+        return Err(anyhow::anyhow!("ffmpeg failed to mux captions"));
+    }
+    std::fs::rename(&tmp_path, output_path)?;
+    Ok(())
+}
@@ -0,0 +1,39 @@
+/// Overrides for the recordings of a channel. Keys of [channels] are service_id of SDT, e.g.
+/// "1024", the service name, or the 3-digit channel number in the service map, e.g. "011".
+#[derive(serde::Deserialize)]
+pub struct ChannelConfig {
+    /// Name in [profiles] used instead of the default profile
+    pub profile: Option<String>,
+    pub trim: Option<crate::trim::TrimConfig>,
+    pub dual_mono: Option<crate::dual_mono::DualMonoConfig>,
+    /// Write the outputs into this directory
+    pub output_dir: Option<std::path::PathBuf>,
+}
+
+/// Find the channel of the source by its service in SDT
+pub fn find<'a>(
+    channels: &'a std::collections::HashMap<String, ChannelConfig>,
+    services: &crate::scan::ServiceMap,
+    source_path: &std::path::Path,
+    service_id: Option<u16>,
+) -> Result<Option<(&'a str, &'a ChannelConfig)>, anyhow::Error> {
+    // The service is identified by SDT of MPEG-TS
+    if channels.is_empty() || !crate::is_transport_stream(source_path)? {
+        return Ok(None);
+    }
+    let (service_id, service_name) = crate::analysis::identify_service(source_path, service_id)?;
+    let channel_number = service_id
+        .and_then(|service_id| services.find(service_id))
+        .map(|service| format!("{:03}", service.channel_number));
+    let keys = service_id
+        .map(|service_id| service_id.to_string())
+        .into_iter()
+        .chain(service_name)
+        .chain(channel_number);
+    for key in keys {
+        if let Some((key, channel)) = channels.get_key_value(&key) {
+            return Ok(Some((key, channel)));
+        }
+    }
+    Ok(None)
+}
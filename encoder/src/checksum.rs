@@ -0,0 +1,115 @@
+/// File listing the checksums of the outputs in its directory in the format of sha256sum, so
+/// that `sha256sum -c SHA256SUMS` detects bit rot or incomplete copies.
+pub const MANIFEST_NAME: &str = "SHA256SUMS";
+
+/// Compute SHA-256 of the outputs. The muxer seeks back to write the header, so the output is
+/// hashed once after the last write (including embedded metadata) and the checksum is shared by
+/// the report, the sidecar and the manifest.
+#[derive(Clone, serde::Deserialize)]
+pub struct ChecksumConfig {
+    /// Record the checksums in SHA256SUMS next to the outputs
+    #[serde(default = "default_manifest")]
+    pub manifest: bool,
+}
+
+fn default_manifest() -> bool {
+    true
+}
+
+/// Hex-encoded SHA-256 of the file
+pub fn sha256(path: &std::path::Path) -> Result<String, anyhow::Error> {
+    use sha2::Digest as _;
+
+    let mut hasher = sha2::Sha256::new();
+    std::io::copy(&mut std::fs::File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+impl ChecksumConfig {
+    /// Checksum of the output at the path. Every file of streaming outputs is recorded in the
+    /// manifest.
+    pub fn record(&self, path: &std::path::Path, streaming: bool) -> Result<String, anyhow::Error> {
+        let dir = path.parent().unwrap();
+        let mut entries = vec![];
+        if streaming {
+            for entry in std::fs::read_dir(dir)? {
+                let entry = entry?;
+                if entry.file_type()?.is_file() && entry.file_name() != MANIFEST_NAME {
+                    entries.push((entry.path(), sha256(&entry.path())?));
+                }
+            }
+        } else {
+            entries.push((path.to_owned(), sha256(path)?));
+        }
+        let checksum = entries
+            .iter()
+            .find(|(entry_path, _)| entry_path == path)
+            .map(|(_, checksum)| checksum.clone())
+            .ok_or_else(|| anyhow::anyhow!("{} is not written", path.display()))?;
+        if self.manifest {
+            update_manifest(dir, &entries)?;
+        }
+        Ok(checksum)
+    }
+
+    /// Compare the output with the checksum recorded in the manifest. Outputs without a recorded
+    /// checksum pass.
+    pub fn verify(&self, output: &crate::output::Output) -> Result<(), anyhow::Error> {
+        let dir = output.path.parent().unwrap();
+        let manifest_path = dir.join(MANIFEST_NAME);
+        if !self.manifest || !manifest_path.exists() {
+            return Ok(());
+        }
+        let name = output.path.file_name().unwrap().to_string_lossy();
+        let recorded = std::fs::read_to_string(&manifest_path)?
+            .lines()
+            .filter_map(|line| line.split_once("  "))
+            .find(|(_, n)| *n == name)
+            .map(|(checksum, _)| checksum.to_owned());
+        match recorded {
+            Some(recorded) => {
+                let actual = sha256(&output.path)?;
+                if actual == recorded {
+                    Ok(())
+                } else {
+                    Err(anyhow::anyhow!(
+                        "Checksum mismatch: recorded {}, actual {}",
+                        recorded,
+                        actual
+                    ))
+                }
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// Add the checksums to the manifest in dir, replacing existing entries of the same files
+fn update_manifest(
+    dir: &std::path::Path,
+    entries: &[(std::path::PathBuf, String)],
+) -> Result<(), anyhow::Error> {
+    let manifest_path = dir.join(MANIFEST_NAME);
+    let names = entries
+        .iter()
+        .map(|(path, _)| path.file_name().unwrap().to_string_lossy().into_owned())
+        .collect::<Vec<_>>();
+    let mut lines = vec![];
+    if manifest_path.exists() {
+        for line in std::fs::read_to_string(&manifest_path)?.lines() {
+            let name = line.split_once("  ").map_or("", |(_, name)| name);
+            if !names.iter().any(|n| n == name) {
+                lines.push(line.to_owned());
+            }
+        }
+    }
+    for ((_, checksum), name) in entries.iter().zip(&names) {
+        lines.push(format!("{}  {}", checksum, name));
+    }
+    lines.sort_by_key(|line| line.split_once("  ").map(|(_, name)| name.to_owned()));
+
+    let tmp_path = dir.join(format!("{}.tmp", MANIFEST_NAME));
+    std::fs::write(&tmp_path, lines.join("\n") + "\n")?;
+    std::fs::rename(&tmp_path, &manifest_path)?;
+    Ok(())
+}
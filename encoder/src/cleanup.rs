@@ -0,0 +1,222 @@
+/// What to do with the source TS after encoding. Deleted sources cannot be recovered, so the
+/// deletion can be disabled or delayed, or the sources can be archived instead.
+#[derive(serde::Deserialize)]
+pub struct CleanupConfig {
+    /// Delete the filtered and original TS after all outputs are verified. Ignored when
+    /// source_after_encode is set.
+    #[serde(default = "default_delete_sources")]
+    pub delete_sources: bool,
+    /// e.g. "keep", { move = "/mnt/archive" } or { compress = { path = "/mnt/cold" } }
+    pub source_after_encode: Option<SourceAction>,
+    /// Also handle the original TS which the encoded TS was produced from
+    #[serde(default = "default_delete_original")]
+    pub delete_original: bool,
+    /// Regex matched against the file name of the encoded TS to find the original TS
+    #[serde(default = "default_original_pattern")]
+    pub original_pattern: String,
+    /// File name of the original TS expanded with the captures of original_pattern, e.g. "$1.ts"
+    /// or "${name}.ts"
+    #[serde(default = "default_original_template")]
+    pub original_template: String,
+    /// Seconds to wait before deleting or archiving the sources
+    #[serde(default)]
+    pub grace_period: u64,
+    /// Move the sources into this directory when verification fails
+    pub quarantine_dir: Option<std::path::PathBuf>,
+}
+
+impl Default for CleanupConfig {
+    fn default() -> Self {
+        Self {
+            delete_sources: default_delete_sources(),
+            source_after_encode: None,
+            delete_original: default_delete_original(),
+            original_pattern: default_original_pattern(),
+            original_template: default_original_template(),
+            grace_period: 0,
+            quarantine_dir: None,
+        }
+    }
+}
+
+/// What happens to the sources after all outputs are verified
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SourceAction {
+    Delete,
+    Keep,
+    /// Move into the directory
+    Move(std::path::PathBuf),
+    /// Compress into "{file name}.zst" in the directory and delete the source. The archive is in
+    /// the seekable format of zstd, which analysis reads without decompressing it first.
+    Compress {
+        path: std::path::PathBuf,
+        #[serde(default = "default_zstd_level")]
+        level: i32,
+    },
+}
+
+fn default_zstd_level() -> i32 {
+    19
+}
+
+fn default_delete_sources() -> bool {
+    true
+}
+
+fn default_delete_original() -> bool {
+    true
+}
+
+fn default_original_pattern() -> String {
+    r#"\A(\d+_\d+)"#.to_owned()
+}
+
+fn default_original_template() -> String {
+    "$1.ts".to_owned()
+}
+
+impl CleanupConfig {
+    /// Path of the original TS in the same directory as ts_path. It is None when the file name
+    /// doesn't match original_pattern.
+    pub fn original_path(
+        &self,
+        ts_path: &std::path::Path,
+    ) -> Result<Option<std::path::PathBuf>, anyhow::Error> {
+        let fname = match ts_path.file_name().and_then(|fname| fname.to_str()) {
+            Some(fname) => fname,
+            None => return Ok(None),
+        };
+        let captures = match regex::Regex::new(&self.original_pattern)?.captures(fname) {
+            Some(captures) => captures,
+            None => return Ok(None),
+        };
+        let mut orig_fname = String::new();
+        captures.expand(&self.original_template, &mut orig_fname);
+        Ok(Some(ts_path.with_file_name(orig_fname)))
+    }
+
+    pub fn source_action(&self) -> SourceAction {
+        match self.source_after_encode {
+            Some(ref action) => action.clone(),
+            None if self.delete_sources => SourceAction::Delete,
+            None => SourceAction::Keep,
+        }
+    }
+
+    /// The encoded TS followed by the original TS if it is to be handled as well
+    pub fn sources(
+        &self,
+        ts_path: &std::path::Path,
+    ) -> Result<Vec<std::path::PathBuf>, anyhow::Error> {
+        let mut sources = vec![ts_path.to_owned()];
+        if self.delete_original {
+            match self.original_path(ts_path)? {
+                Some(orig_path) => {
+                    if orig_path != ts_path {
+                        sources.push(orig_path);
+                    }
+                }
+                None => tracing::warn!(
+                    "{} doesn't match {}, keeping the original TS",
+                    ts_path.display(),
+                    self.original_pattern
+                ),
+            }
+        }
+        Ok(sources)
+    }
+
+    /// Apply source_action to the sources after grace_period
+    pub async fn dispose(&self, paths: &[std::path::PathBuf]) -> Result<(), anyhow::Error> {
+        let action = self.source_action();
+        if let SourceAction::Keep = action {
+            return Ok(());
+        }
+        if self.grace_period > 0 {
+            tokio::time::delay_for(std::time::Duration::from_secs(self.grace_period)).await;
+        }
+        for path in paths {
+            // Another worker may have removed it
+            if !path.exists() {
+                continue;
+            }
+            match action {
+                SourceAction::Delete => {
+                    for path in &[path.clone(), crate::tuner::SignalReport::path(path)] {
+                        match std::fs::remove_file(path) {
+                            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                                return Err(e.into())
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                SourceAction::Keep => {}
+                SourceAction::Move(ref dir) => {
+                    std::fs::create_dir_all(dir)?;
+                    let dest = dir.join(path.file_name().unwrap());
+                    tracing::info!("Move {} to {}", path.display(), dest.display());
+                    move_file(path, &dest)?;
+                    move_signal(path, dir)?;
+                }
+                SourceAction::Compress {
+                    path: ref dir,
+                    level,
+                } => {
+                    std::fs::create_dir_all(dir)?;
+                    let dest = dir.join(format!(
+                        "{}.zst",
+                        path.file_name().unwrap().to_string_lossy()
+                    ));
+                    tracing::info!("Compress {} into {}", path.display(), dest.display());
+                    let source = path.clone();
+                    tokio::task::spawn_blocking(move || {
+                        crate::seekable::compress(&source, &dest, level)
+                    })
+                    .await??;
+                    std::fs::remove_file(path)?;
+                    move_signal(path, dir)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Move the sources into quarantine_dir if configured
+    pub fn quarantine(&self, paths: &[std::path::PathBuf]) -> Result<(), anyhow::Error> {
+        let dir = match self.quarantine_dir {
+            Some(ref dir) => dir,
+            None => return Ok(()),
+        };
+        std::fs::create_dir_all(dir)?;
+        for path in paths {
+            if !path.exists() {
+                continue;
+            }
+            let dest = dir.join(path.file_name().unwrap());
+            tracing::warn!("Quarantine {} to {}", path.display(), dest.display());
+            move_file(path, &dest)?;
+            move_signal(path, dir)?;
+        }
+        Ok(())
+    }
+}
+
+/// Keep the signal report recorded by the tuner next to the moved source
+fn move_signal(path: &std::path::Path, dir: &std::path::Path) -> Result<(), anyhow::Error> {
+    let signal_path = crate::tuner::SignalReport::path(path);
+    if signal_path.exists() {
+        move_file(&signal_path, &dir.join(signal_path.file_name().unwrap()))?;
+    }
+    Ok(())
+}
+
+fn move_file(path: &std::path::Path, dest: &std::path::Path) -> Result<(), anyhow::Error> {
+    if std::fs::rename(path, dest).is_err() {
+        // rename fails across filesystems
+        std::fs::copy(path, dest)?;
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,605 @@
+#[derive(serde::Deserialize)]
+pub struct Config {
+    pub encoder: EncoderConfig,
+    #[serde(default)]
+    pub profiles: std::collections::HashMap<String, ProfileConfig>,
+    /// Overrides keyed by service_id or service name
+    #[serde(default)]
+    pub channels: std::collections::HashMap<String, crate::channels::ChannelConfig>,
+    pub redis: RedisConfig,
+    pub sqs: SqsConfig,
+    pub janitor: Option<crate::janitor::JanitorConfig>,
+    pub metrics: Option<crate::metrics::MetricsConfig>,
+    /// Measure the backlog of the queues for scaling workers
+    pub autoscaling: Option<crate::autoscaling::AutoscalingConfig>,
+    /// Notified when sqs-encode finishes a job
+    #[serde(default)]
+    pub webhooks: Vec<crate::webhook::WebhookConfig>,
+    /// Mailed the same outcomes as webhooks
+    #[serde(default)]
+    pub emails: Vec<crate::email::EmailConfig>,
+    pub publish: Option<crate::publish::PublishConfig>,
+    /// Track state transitions of jobs in Redis
+    pub jobs: Option<crate::jobs::JobStoreConfig>,
+    pub admin: Option<crate::admin::AdminConfig>,
+    /// Files pausing, draining and aborting sqs-encode
+    #[serde(default)]
+    pub control: crate::control::ControlConfig,
+    /// Skip files being encoded by another worker
+    pub lock: Option<crate::lock::LockConfig>,
+    /// Register workers sharing storage. Requires [lock].
+    pub workers: Option<crate::workers::WorkersConfig>,
+    /// Hold off encodes while recording
+    pub throttle: Option<crate::throttle::ThrottleConfig>,
+    /// Return the job to the queue and exit on EC2 spot interruption
+    pub spot: Option<crate::spot::SpotConfig>,
+    /// Record sources requested by job messages
+    pub mirakurun: Option<crate::mirakurun::MirakurunConfig>,
+    /// Capture by the record binary
+    #[serde(default)]
+    pub record: crate::record::RecordConfig,
+    /// Ingest EPG of sources into SQLite, which fills events missing from the sources and feeds
+    /// the reserve binary
+    pub epg: Option<crate::epgstore::EpgStoreConfig>,
+    /// Stages of the recutils-pipeline binary
+    pub pipeline: Option<crate::pipeline::PipelineConfig>,
+    /// Alert on recordings going bad
+    pub monitor: Option<crate::monitor::MonitorConfig>,
+    /// Channels scanned by the scan-channels binary
+    pub scan: Option<crate::scan::ScanConfig>,
+    /// Read from scan.path by load_config
+    #[serde(skip)]
+    pub services: std::sync::Arc<crate::scan::ServiceMap>,
+    /// Rules of the reserve binary
+    pub reserve: Option<crate::reserve::ReserveConfig>,
+    /// Summary sent by the digest binary
+    #[serde(default)]
+    pub digest: crate::digest::DigestConfig,
+    /// Clean up after crashed workers when sqs-encode starts
+    pub recovery: Option<crate::recovery::RecoveryConfig>,
+    #[serde(default)]
+    pub log: crate::logging::LogConfig,
+}
+
+impl Config {
+    /// Return the named profile, or the default profile written in [encoder] section
+    pub fn profile(&self, name: Option<&str>) -> Result<&ProfileConfig, anyhow::Error> {
+        match name {
+            Some(name) => self
+                .profiles
+                .get(name)
+                .ok_or_else(|| anyhow::anyhow!("Unknown profile {}", name)),
+            None => Ok(&self.encoder.profile),
+        }
+    }
+
+    /// Profile and channel overrides for the source. The profile named on the command line takes
+    /// precedence over the profile of the channel.
+    pub fn resolve(
+        &self,
+        name: Option<&str>,
+        source_path: &std::path::Path,
+    ) -> Result<(&ProfileConfig, Option<&crate::channels::ChannelConfig>), anyhow::Error> {
+        let service_id = self
+            .encoder
+            .profile
+            .filter
+            .as_ref()
+            .and_then(|filter| filter.service_id);
+        let channel =
+            crate::channels::find(&self.channels, &self.services, source_path, service_id)?;
+        if let Some((key, _)) = channel {
+            tracing::info!("{}: channel {}", source_path.display(), key);
+        }
+        let channel = channel.map(|(_, channel)| channel);
+        let name = name.or_else(|| channel.and_then(|channel| channel.profile.as_deref()));
+        Ok((self.profile(name)?, channel))
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct EncoderConfig {
+    /// A directory or a list of directories of the sources, e.g. one per tuner host mount.
+    /// Sources are probed in order and recordings are written into the first one.
+    #[serde(rename = "base_dir", deserialize_with = "deserialize_base_dirs")]
+    pub base_dirs: Vec<BaseDir>,
+    #[serde(flatten)]
+    pub profile: ProfileConfig,
+}
+
+impl EncoderConfig {
+    /// The first directory, where recordings and files not tied to a source are written
+    pub fn base_dir(&self) -> &std::path::Path {
+        &self.base_dirs[0].path
+    }
+
+    /// Path of the source named by a job, searched in the directories in order. The name is a
+    /// file stem completed with SOURCE_EXTENSIONS, or a relative path with any extension, e.g.
+    /// "host1/capture.mkv". Defaults to "{name}.ts" in the first directory. Names escaping the
+    /// directories, e.g. absolute paths or ones with "..", are rejected.
+    pub fn source_path(&self, name: &str) -> Result<std::path::PathBuf, anyhow::Error> {
+        use std::path::Component;
+
+        let relative = std::path::Path::new(name);
+        if relative.components().any(|component| {
+            matches!(
+                component,
+                Component::ParentDir | Component::RootDir | Component::Prefix(_)
+            )
+        }) {
+            return Err(anyhow::anyhow!(
+                "source name {:?} is outside of base_dir",
+                name
+            ));
+        }
+        let mut file_names = vec![];
+        if relative.extension().is_some() {
+            file_names.push(name.to_owned());
+        }
+        file_names.extend(
+            SOURCE_EXTENSIONS
+                .iter()
+                .map(|extension| format!("{}.{}", name, extension)),
+        );
+        Ok(self
+            .base_dirs
+            .iter()
+            .flat_map(|base_dir| file_names.iter().map(move |f| base_dir.path.join(f)))
+            .find(|path| path.is_file())
+            .unwrap_or_else(|| self.base_dir().join(format!("{}.ts", name))))
+    }
+}
+
+/// Extensions tried in order for jobs naming the source without one
+pub const SOURCE_EXTENSIONS: &[&str] = &["ts", "m2ts", "mts"];
+
+/// Written as a path or a table with output_dir
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(from = "BaseDirEntry")]
+pub struct BaseDir {
+    pub path: std::path::PathBuf,
+    /// Write the outputs of the sources in this directory here instead of next to the sources.
+    /// output_dir of the channel takes precedence.
+    pub output_dir: Option<std::path::PathBuf>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum BaseDirEntry {
+    Path(std::path::PathBuf),
+    Table {
+        path: std::path::PathBuf,
+        output_dir: Option<std::path::PathBuf>,
+    },
+}
+
+impl From<BaseDirEntry> for BaseDir {
+    fn from(entry: BaseDirEntry) -> Self {
+        match entry {
+            BaseDirEntry::Path(path) => Self {
+                path,
+                output_dir: None,
+            },
+            BaseDirEntry::Table { path, output_dir } => Self { path, output_dir },
+        }
+    }
+}
+
+fn deserialize_base_dirs<'de, D>(deserializer: D) -> Result<Vec<BaseDir>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(BaseDir),
+        Many(Vec<BaseDir>),
+    }
+
+    let base_dirs = match serde::Deserialize::deserialize(deserializer)? {
+        OneOrMany::One(base_dir) => vec![base_dir],
+        OneOrMany::Many(base_dirs) => base_dirs,
+    };
+    if base_dirs.is_empty() {
+        return Err(serde::de::Error::custom("base_dir is empty"));
+    }
+    Ok(base_dirs)
+}
+
+#[derive(serde::Deserialize)]
+pub struct ProfileConfig {
+    /// Key in [profiles] section, filled by load_config
+    #[serde(skip)]
+    pub name: Option<String>,
+    /// [epg] section, filled by load_config
+    #[serde(skip)]
+    pub epg: Option<crate::epgstore::EpgStoreConfig>,
+    /// Services found by scan-channels, filled by load_config
+    #[serde(skip)]
+    pub services: std::sync::Arc<crate::scan::ServiceMap>,
+    /// encoder.base_dir routing the outputs, filled by load_config
+    #[serde(skip)]
+    pub base_dirs: std::sync::Arc<Vec<BaseDir>>,
+    #[serde(default)]
+    pub input_args: Vec<String>,
+    /// Common to all outputs
+    #[serde(default)]
+    pub ffmpeg_args: Vec<String>,
+    /// Encoded in one ffmpeg invocation. A single file in container is written when empty.
+    #[serde(default)]
+    pub outputs: Vec<crate::output::OutputConfig>,
+    #[serde(default)]
+    pub container: crate::output::Container,
+    /// Write a single audio file instead of the video when outputs are empty
+    pub audio: Option<crate::audio::AudioConfig>,
+    /// Video arguments of an x265 or SVT-AV1 archival encode
+    pub archive: Option<crate::archive::ArchiveConfig>,
+    /// Name outputs after the broadcast metadata. Outputs are named after the source TS when
+    /// this is not set.
+    pub naming: Option<crate::naming::NamingConfig>,
+    /// Joined into -filter:v after the deinterlace filter. Do not put -filter:v in ffmpeg_args
+    /// when this or deinterlace is set.
+    #[serde(default)]
+    pub video_filters: Vec<String>,
+    pub deinterlace: Option<crate::deinterlace::DeinterlaceConfig>,
+    pub hwaccel: Option<crate::hwaccel::HwaccelConfig>,
+    /// Transcode in process instead of running ffmpeg. hwaccel and two_pass are not used, and
+    /// fallback still runs ffmpeg.
+    pub library: Option<crate::transcode::LibraryConfig>,
+    /// Applied to every attempt including hwaccel and fallback
+    pub two_pass: Option<crate::two_pass::TwoPassConfig>,
+    pub fallback: Option<FallbackConfig>,
+    pub precheck: Option<PrecheckConfig>,
+    pub filter: Option<FilterConfig>,
+    pub trim: Option<crate::trim::TrimConfig>,
+    pub metadata: Option<crate::metadata::MetadataConfig>,
+    pub captions: Option<crate::captions::CaptionsConfig>,
+    /// Note that loudnorm upsamples to 192kHz unless the sample rate is given in ffmpeg_args
+    pub loudnorm: Option<crate::loudnorm::LoudnormConfig>,
+    /// Defaults to the ffmpeg check only
+    pub verify: Option<crate::verify::VerifyConfig>,
+    /// Applied when the main event is broadcast in dual-mono. Don't put -filter:a in ffmpeg_args
+    /// when this is set.
+    pub dual_mono: Option<crate::dual_mono::DualMonoConfig>,
+    /// Deletion of the source TS after encoding
+    #[serde(default)]
+    pub cleanup: crate::cleanup::CleanupConfig,
+    /// Free space checked by sqs-encode before encoding
+    pub space: Option<crate::disk::SpaceConfig>,
+    /// SHA-256 of the outputs recorded in the report, the sidecar and the manifest
+    pub checksum: Option<crate::checksum::ChecksumConfig>,
+    /// Write "{output}.json" describing the job next to each output
+    #[serde(default)]
+    pub sidecar: bool,
+    /// Upload the outputs and sidecars before the sources are deleted
+    pub upload: Option<crate::upload::UploadConfig>,
+    /// Transfer the outputs and sidecars to a NAS before the sources are deleted
+    pub transfer: Option<crate::transfer::TransferConfig>,
+    /// Encrypt the uploaded and transferred copies of the outputs
+    pub encryption: Option<crate::encryption::EncryptionConfig>,
+    /// Niceness, ionice and cgroup limits of ffmpeg
+    pub resources: Option<crate::resources::ResourceConfig>,
+    /// Parent of the temporary working directory of each job, which keeps passlogs and other
+    /// files written by ffmpeg. Defaults to the system temporary directory.
+    pub work_dir: Option<std::path::PathBuf>,
+    /// Keep stderr of each ffmpeg attempt in "{dir}/{source stem}.{attempt}.log", and that of its
+    /// first pass with two_pass in "{dir}/{source stem}.{attempt}.pass1.log"
+    pub ffmpeg_log_dir: Option<std::path::PathBuf>,
+}
+
+/// Filter the source TS with tsutils before encoding
+#[derive(serde::Deserialize)]
+pub struct FilterConfig {
+    /// Keep audio and video of this service. Defaults to the first program in PAT.
+    pub service_id: Option<u16>,
+    /// The filtered TS is written to "{source stem}{suffix}.ts"
+    #[serde(default = "default_filter_suffix")]
+    pub suffix: String,
+    /// Some players need EIT[p/f] or SDT to show the program
+    #[serde(default)]
+    pub si: SiRetention,
+    /// Drop scrambled packets which encoders cannot decode
+    #[serde(default)]
+    pub scrambled: ScrambledPackets,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScrambledPackets {
+    #[default]
+    Keep,
+    Drop,
+    /// Only after the first clear packet of each PID, keeping entirely scrambled ones. Scrambled
+    /// packets before it are kept too, so use Drop when descrambling may start late.
+    DropDescrambled,
+}
+
+/// SI kept in the filtered TS besides PAT and PMT
+#[derive(Clone, Copy, Default, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SiRetention {
+    #[default]
+    Psi,
+    /// EIT[p/f actual] of the service
+    EitPf,
+    /// EIT[p/f actual], SDT and TOT
+    Si,
+    /// Everything but other services
+    All,
+}
+
+impl From<SiRetention> for tsutils::filter::SiPolicy {
+    fn from(retention: SiRetention) -> Self {
+        match retention {
+            SiRetention::Psi => Self::Psi,
+            SiRetention::EitPf => Self::EitPresentFollowing,
+            SiRetention::Si => Self::ServiceInformation,
+            SiRetention::All => Self::All,
+        }
+    }
+}
+
+fn default_filter_suffix() -> String {
+    "_av".to_owned()
+}
+
+/// Arguments used to retry once when ffmpeg fails with the primary arguments
+#[derive(serde::Deserialize)]
+pub struct FallbackConfig {
+    #[serde(default)]
+    pub input_args: Vec<String>,
+    /// Defaults to ffmpeg_args of the profile
+    pub ffmpeg_args: Option<Vec<String>>,
+}
+
+/// Thresholds for the integrity check of the source TS before encoding
+#[derive(serde::Deserialize)]
+pub struct PrecheckConfig {
+    pub max_sync_errors: Option<u64>,
+    pub max_drops: Option<u64>,
+    pub max_scrambled_ratio: Option<f64>,
+    /// In seconds
+    pub min_duration: Option<f64>,
+    #[serde(default)]
+    pub on_failure: PrecheckAction,
+}
+
+#[derive(Clone, Copy, Default, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PrecheckAction {
+    /// Fail the job without encoding
+    #[default]
+    Refuse,
+    /// Encode with fallback arguments from the beginning
+    Fallback,
+}
+
+impl PrecheckConfig {
+    pub(crate) fn violation(&self, report: &tsutils::integrity::IntegrityReport) -> Option<String> {
+        if let Some(max) = self.max_sync_errors {
+            if report.sync_errors > max {
+                return Some(format!("{} sync errors", report.sync_errors));
+            }
+        }
+        if let Some(max) = self.max_drops {
+            if report.drops > max {
+                return Some(format!("{} drops", report.drops));
+            }
+        }
+        if let Some(max) = self.max_scrambled_ratio {
+            if report.scrambled_ratio() > max {
+                return Some(format!(
+                    "{:.2}% scrambled",
+                    report.scrambled_ratio() * 100.0
+                ));
+            }
+        }
+        if let Some(min) = self.min_duration {
+            if report.duration_secs() < min {
+                return Some(format!("duration {:.1}s", report.duration_secs()));
+            }
+        }
+        None
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct RedisConfig {
+    pub url: String,
+    /// redis-to-sqs skips a file name pushed again within this many seconds after it's enqueued
+    pub dedup_window: Option<u64>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct SqsConfig {
+    pub queue_url: String,
+    /// Queues polled before queue_url, the most urgent first
+    #[serde(default)]
+    pub priority_queue_urls: Vec<String>,
+    /// queue_url is polled first after this many consecutive jobs from priority queues so that
+    /// the backlog is not starved
+    #[serde(default = "default_max_consecutive_priority")]
+    pub max_consecutive_priority: u32,
+    /// Receive up to this many messages (at most 10) per call and keep them visible only to this
+    /// worker until their turn. Messages of priority queues may wait behind the prefetched ones.
+    #[serde(default = "default_prefetch")]
+    pub prefetch: u32,
+    /// Defaults to the region from the environment
+    pub region: Option<String>,
+    /// Named profile in the shared credentials file. Defaults to the credentials from the
+    /// environment.
+    pub profile: Option<String>,
+    /// e.g. "http://localhost:9324" for ElasticMQ
+    pub endpoint: Option<String>,
+}
+
+fn default_max_consecutive_priority() -> u32 {
+    10
+}
+
+fn default_prefetch() -> u32 {
+    1
+}
+
+impl SqsConfig {
+    pub fn client(&self) -> Result<rusoto_sqs::SqsClient, anyhow::Error> {
+        let region = match (&self.region, &self.endpoint) {
+            (region, Some(endpoint)) => rusoto_core::Region::Custom {
+                name: region.clone().unwrap_or_else(|| "us-east-1".to_owned()),
+                endpoint: endpoint.clone(),
+            },
+            (Some(region), None) => region.parse()?,
+            (None, None) => rusoto_core::Region::default(),
+        };
+        Ok(match self.profile {
+            Some(ref profile) => {
+                let mut provider = rusoto_core::credential::ProfileProvider::new()?;
+                provider.set_profile(profile.as_str());
+                rusoto_sqs::SqsClient::new_with(rusoto_core::HttpClient::new()?, provider, region)
+            }
+            None => rusoto_sqs::SqsClient::new(region),
+        })
+    }
+
+    /// Queues in the order to be polled after the number of consecutive jobs from priority queues
+    pub fn poll_order(&self, consecutive_priority: u32) -> Vec<&str> {
+        let mut queue_urls = self
+            .priority_queue_urls
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        if consecutive_priority >= self.max_consecutive_priority {
+            queue_urls.insert(0, &self.queue_url);
+        } else {
+            queue_urls.push(&self.queue_url);
+        }
+        queue_urls
+    }
+
+    pub fn all_queue_urls(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.queue_url.as_str())
+            .chain(self.priority_queue_urls.iter().map(String::as_str))
+    }
+}
+
+pub fn load_config() -> Result<Config, anyhow::Error> {
+    let body = std::fs::read("config.toml")?;
+    parse_config(&body)
+}
+
+fn parse_config(body: &[u8]) -> Result<Config, anyhow::Error> {
+    let mut config: Config = toml::from_slice(body)?;
+    if let Some(ref lock) = config.lock {
+        lock.validate()?;
+    }
+    if let Some(ref scan) = config.scan {
+        if scan.path.exists() {
+            config.services = std::sync::Arc::new(crate::scan::ServiceMap::load(&scan.path)?);
+        }
+    }
+    config.encoder.profile.epg = config.epg.clone();
+    config.encoder.profile.services = config.services.clone();
+    let base_dirs = std::sync::Arc::new(config.encoder.base_dirs.clone());
+    config.encoder.profile.base_dirs = base_dirs.clone();
+    expand_archive(&mut config.encoder.profile);
+    for (name, profile) in &mut config.profiles {
+        profile.name = Some(name.clone());
+        profile.epg = config.epg.clone();
+        profile.services = config.services.clone();
+        profile.base_dirs = base_dirs.clone();
+        expand_archive(profile);
+    }
+    Ok(config)
+}
+
+/// Prepend the built-in arguments of [archive] to ffmpeg_args
+fn expand_archive(profile: &mut ProfileConfig) {
+    if let Some(ref archive) = profile.archive {
+        profile.ffmpeg_args = [archive.ffmpeg_args(), profile.ffmpeg_args.clone()].concat();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn archive_args() {
+        let config = parse_config(
+            br#"
+[encoder]
+base_dir = "/tmp"
+ffmpeg_args = ["-c:a", "aac"]
+
+[encoder.archive]
+crf = 20
+
+[profiles.av1]
+ffmpeg_args = ["-c:a", "libopus"]
+
+[profiles.av1.archive]
+codec = "svtav1"
+ten_bit = false
+
+[profiles.plain]
+ffmpeg_args = ["-c:v", "libx264"]
+
+[redis]
+url = "redis://localhost"
+
+[sqs]
+queue_url = "https://sqs.ap-northeast-1.amazonaws.com/0/encode-jobs"
+"#,
+        )
+        .unwrap();
+
+        let args = &config.encoder.profile.ffmpeg_args;
+        assert_eq!(args[..4], ["-c:v", "libx265", "-crf", "20"]);
+        assert!(args.windows(2).any(|w| w == ["-colorspace", "bt709"]));
+        assert!(args.windows(2).any(|w| w == ["-pix_fmt", "yuv420p10le"]));
+        assert_eq!(args[args.len() - 2..], ["-c:a", "aac"]);
+
+        let args = &config.profiles["av1"].ffmpeg_args;
+        assert_eq!(args[..4], ["-c:v", "libsvtav1", "-crf", "30"]);
+        assert!(args.windows(2).any(|w| w == ["-pix_fmt", "yuv420p"]));
+        assert_eq!(args[args.len() - 2..], ["-c:a", "libopus"]);
+
+        assert_eq!(config.profiles["plain"].ffmpeg_args, ["-c:v", "libx264"]);
+    }
+
+    #[test]
+    fn source_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = EncoderConfig {
+            base_dirs: vec![BaseDir {
+                path: dir.path().to_owned(),
+                output_dir: None,
+            }],
+            profile: toml::from_str("").unwrap(),
+        };
+        std::fs::create_dir(dir.path().join("host1")).unwrap();
+        std::fs::write(dir.path().join("host1/capture.mkv"), b"").unwrap();
+        std::fs::write(dir.path().join("foo.m2ts"), b"").unwrap();
+
+        assert_eq!(
+            config.source_path("host1/capture.mkv").unwrap(),
+            dir.path().join("host1/capture.mkv")
+        );
+        assert_eq!(
+            config.source_path("foo").unwrap(),
+            dir.path().join("foo.m2ts")
+        );
+        assert_eq!(
+            config.source_path("bar").unwrap(),
+            dir.path().join("bar.ts")
+        );
+        for name in &[
+            "../foo",
+            "host1/../../foo",
+            "/etc/passwd",
+            "../../other/dir/x",
+        ] {
+            assert!(config.source_path(name).is_err(), "{}", name);
+        }
+    }
+}
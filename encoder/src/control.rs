@@ -0,0 +1,71 @@
+/// Files controlling a running sqs-encode, which is active while the file exists. The same
+/// controls are available from the admin API.
+#[derive(Clone, serde::Deserialize)]
+pub struct ControlConfig {
+    /// Stop receiving messages and return the prefetched ones to the queue. The current job
+    /// continues and receiving resumes when the file is removed.
+    #[serde(default = "default_pause_path")]
+    pub pause_path: std::path::PathBuf,
+    /// Finish the current job and exit
+    #[serde(default = "default_drain_path")]
+    pub drain_path: std::path::PathBuf,
+    /// Abort the current job, return its message to the queue and exit
+    #[serde(default = "default_abort_path")]
+    pub abort_path: std::path::PathBuf,
+    /// Seconds between checks of the files
+    #[serde(default = "default_poll_interval")]
+    pub poll_interval: u64,
+}
+
+fn default_pause_path() -> std::path::PathBuf {
+    std::path::PathBuf::from("/tmp/pause-encode.txt")
+}
+
+fn default_drain_path() -> std::path::PathBuf {
+    std::path::PathBuf::from("/tmp/stop-encode.txt")
+}
+
+fn default_abort_path() -> std::path::PathBuf {
+    std::path::PathBuf::from("/tmp/abort-encode.txt")
+}
+
+fn default_poll_interval() -> u64 {
+    5
+}
+
+impl Default for ControlConfig {
+    fn default() -> Self {
+        Self {
+            pause_path: default_pause_path(),
+            drain_path: default_drain_path(),
+            abort_path: default_abort_path(),
+            poll_interval: default_poll_interval(),
+        }
+    }
+}
+
+impl ControlConfig {
+    /// Reflect the files in the state
+    pub fn apply(&self, state: &crate::admin::WorkerState) {
+        state.set_pause_file(self.pause_path.exists());
+        if self.drain_path.exists() && !state.is_draining() {
+            tracing::info!("Drain requested by {}", self.drain_path.display());
+            state.drain();
+        }
+        if self.abort_path.exists() && !state.is_interrupted() {
+            tracing::warn!("Abort requested by {}", self.abort_path.display());
+            state.interrupt();
+        }
+    }
+
+    /// Check the files until the process exits
+    pub async fn watch(self, state: std::sync::Arc<crate::admin::WorkerState>) {
+        use futures::StreamExt as _;
+
+        let mut interval =
+            tokio::time::interval(std::time::Duration::from_secs(self.poll_interval));
+        while interval.next().await.is_some() {
+            self.apply(&state);
+        }
+    }
+}
@@ -0,0 +1,124 @@
+/// Probe the source with idet and deinterlace only interlaced content
+#[derive(serde::Deserialize)]
+pub struct DeinterlaceConfig {
+    #[serde(default)]
+    pub filter: DeinterlaceFilter,
+    /// Number of frames analyzed by idet
+    #[serde(default = "default_probe_frames")]
+    pub probe_frames: u32,
+    /// Minimum ratio of interlaced frames to deinterlace
+    #[serde(default = "default_threshold")]
+    pub threshold: f64,
+}
+
+fn default_probe_frames() -> u32 {
+    1000
+}
+
+fn default_threshold() -> f64 {
+    0.5
+}
+
+#[derive(Clone, Copy, Default, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeinterlaceFilter {
+    #[default]
+    Yadif,
+    Bwdif,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum FieldOrder {
+    TopFieldFirst,
+    BottomFieldFirst,
+}
+
+/// Frame counts of "Multi frame detection" reported by idet
+#[derive(Debug, Default)]
+pub struct IdetResult {
+    pub tff: u64,
+    pub bff: u64,
+    pub progressive: u64,
+    pub undetermined: u64,
+}
+
+impl IdetResult {
+    fn parse(stderr: &str) -> Option<Self> {
+        let line = stderr
+            .lines()
+            .rev()
+            .find(|line| line.contains("Multi frame detection:"))?;
+        let mut result = Self::default();
+        let mut words = line.split_whitespace();
+        while let Some(word) = words.next() {
+            let count = match word {
+                "TFF:" => &mut result.tff,
+                "BFF:" => &mut result.bff,
+                "Progressive:" => &mut result.progressive,
+                "Undetermined:" => &mut result.undetermined,
+                _ => continue,
+            };
+            *count = words.next()?.parse().ok()?;
+        }
+        Some(result)
+    }
+}
+
+impl DeinterlaceConfig {
+    /// Return the field order when the source is detected as interlaced
+    pub async fn detect(
+        &self,
+        work_dir: &std::path::Path,
+        ts_path: &std::path::Path,
+        input_args: &[String],
+    ) -> Result<Option<FieldOrder>, anyhow::Error> {
+        let output = tokio::process::Command::new("ffmpeg")
+            .current_dir(work_dir)
+            .arg("-nostats")
+            .args(input_args)
+            .arg("-i")
+            .arg(ts_path)
+            .args([
+                "-map",
+                "0:v:0",
+                "-an",
+                "-sn",
+                "-dn",
+                "-vf",
+                "idet",
+                "-frames:v",
+            ])
+            .arg(self.probe_frames.to_string())
+            .args(["-f", "null", "-"])
+            .output()
+            .await?;
+        if !output.status.success() {
+            return Err(anyhow::anyhow!("ffmpeg idet failed"));
+        }
+        let result = IdetResult::parse(&String::from_utf8_lossy(&output.stderr))
+            .ok_or_else(|| anyhow::anyhow!("idet result is missing in ffmpeg output"))?;
+        tracing::info!("{}: {:?}", ts_path.display(), result);
+
+        let interlaced = result.tff + result.bff;
+        let total = interlaced + result.progressive;
+        if total == 0 || (interlaced as f64) < (total as f64) * self.threshold {
+            Ok(None)
+        } else if result.tff >= result.bff {
+            Ok(Some(FieldOrder::TopFieldFirst))
+        } else {
+            Ok(Some(FieldOrder::BottomFieldFirst))
+        }
+    }
+
+    pub fn filter(&self, field_order: FieldOrder) -> String {
+        let name = match self.filter {
+            DeinterlaceFilter::Yadif => "yadif",
+            DeinterlaceFilter::Bwdif => "bwdif",
+        };
+        let parity = match field_order {
+            FieldOrder::TopFieldFirst => "tff",
+            FieldOrder::BottomFieldFirst => "bff",
+        };
+        format!("{}=parity={}", name, parity)
+    }
+}
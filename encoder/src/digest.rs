@@ -0,0 +1,158 @@
+/// Summary of the recent jobs sent by the digest binary, which is run daily from cron or a
+/// systemd timer
+#[derive(serde::Deserialize)]
+pub struct DigestConfig {
+    /// Jobs finished within this many hours are summarized
+    #[serde(default = "default_hours")]
+    pub hours: i64,
+    /// {date} is replaced with the local date
+    #[serde(default = "default_subject")]
+    pub subject: String,
+}
+
+impl Default for DigestConfig {
+    fn default() -> Self {
+        Self {
+            hours: default_hours(),
+            subject: default_subject(),
+        }
+    }
+}
+
+fn default_hours() -> i64 {
+    24
+}
+
+fn default_subject() -> String {
+    "[encoder] Digest {date}".to_owned()
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct Digest {
+    pub since: chrono::DateTime<chrono::Utc>,
+    /// File names of the first outputs, or of the sources when unknown
+    pub encoded: Vec<String>,
+    /// Encoded duration of the sources
+    pub encoded_hours: f64,
+    /// (source file, error)
+    pub failures: Vec<(String, String)>,
+    pub disks: Vec<DiskUsage>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct DiskUsage {
+    pub path: std::path::PathBuf,
+    pub available: u64,
+    /// Change of the available space since the previous digest and the time of it
+    pub change: Option<(i64, chrono::DateTime<chrono::Utc>)>,
+}
+
+impl DigestConfig {
+    pub fn subject(&self) -> String {
+        self.subject.replace(
+            "{date}",
+            &chrono::Local::now().format("%Y-%m-%d").to_string(),
+        )
+    }
+
+    /// Aggregate the jobs finished within hours and the available space of the directories. The
+    /// available space is stored so that the next digest shows the trend.
+    pub async fn collect(
+        &self,
+        job_store: &crate::jobs::JobStore,
+        dirs: &[std::path::PathBuf],
+    ) -> Result<Digest, anyhow::Error> {
+        let since = chrono::Utc::now() - chrono::Duration::hours(self.hours);
+        let mut digest = Digest {
+            since,
+            encoded: vec![],
+            encoded_hours: 0.0,
+            failures: vec![],
+            disks: vec![],
+        };
+        let mut jobs = job_store.updated_since(since).await?;
+        // Oldest first
+        jobs.reverse();
+        for (file, record) in jobs {
+            match record.get("state").map(String::as_str) {
+                Some("done") => {
+                    let name = record
+                        .get("output")
+                        .and_then(|output| {
+                            std::path::Path::new(output)
+                                .file_name()
+                                .map(|fname| fname.to_string_lossy().into_owned())
+                        })
+                        .unwrap_or_else(|| file.clone());
+                    digest.encoded.push(name);
+                    if let Some(duration) = record
+                        .get("duration")
+                        .and_then(|duration| duration.parse::<f64>().ok())
+                    {
+                        digest.encoded_hours += duration / 3600.0;
+                    }
+                }
+                Some("failed") => {
+                    let error = record
+                        .get("error")
+                        .cloned()
+                        .unwrap_or_else(|| "unknown".to_owned());
+                    digest.failures.push((file, error));
+                }
+                _ => {}
+            }
+        }
+        for dir in dirs {
+            let available = crate::disk::available_space(dir)?;
+            let previous = job_store.swap_disk_usage(dir, available).await?;
+            digest.disks.push(DiskUsage {
+                path: dir.clone(),
+                available,
+                change: previous.map(|(at, bytes)| (available as i64 - bytes as i64, at)),
+            });
+        }
+        Ok(digest)
+    }
+}
+
+impl std::fmt::Display for Digest {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Since {}",
+            self.since.with_timezone(&chrono::Local).format("%F %T")
+        )?;
+        writeln!(
+            f,
+            "\nEncoded {} files ({:.1} hours)",
+            self.encoded.len(),
+            self.encoded_hours
+        )?;
+        for name in &self.encoded {
+            writeln!(f, "  {}", name)?;
+        }
+        writeln!(f, "\nFailed {} files", self.failures.len())?;
+        for (file, error) in &self.failures {
+            writeln!(f, "  {}: {}", file, error)?;
+        }
+        writeln!(f, "\nAvailable space")?;
+        for disk in &self.disks {
+            write!(
+                f,
+                "  {}: {:.1} GB",
+                disk.path.display(),
+                disk.available as f64 / 1e9
+            )?;
+            if let Some((change, at)) = disk.change {
+                write!(
+                    f,
+                    " ({:+.1} GB since {})",
+                    change as f64 / 1e9,
+                    at.with_timezone(&chrono::Local).format("%F %T")
+                )?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
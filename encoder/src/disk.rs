@@ -0,0 +1,66 @@
+/// Available space in bytes of the filesystem containing path, like statvfs(1) in this
+/// repository
+pub fn available_space(path: &std::path::Path) -> Result<u64, std::io::Error> {
+    use std::os::unix::ffi::OsStrExt as _;
+
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes())?;
+    let mut buf = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    if unsafe { libc::statvfs(path.as_ptr(), buf.as_mut_ptr()) } == 0 {
+        let buf = unsafe { buf.assume_init() };
+        // The types of the fields differ between platforms
+        #[allow(clippy::unnecessary_cast)]
+        Ok(buf.f_bsize as u64 * buf.f_bavail as u64)
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+/// Free space required before starting an encode. Running out of space in the middle of an
+/// encode produces broken outputs.
+#[derive(serde::Deserialize)]
+pub struct SpaceConfig {
+    /// Multiplied by the size of the source TS, covering the filtered TS and the outputs
+    #[serde(default = "default_factor")]
+    pub factor: f64,
+    /// Added to the estimation (GB)
+    #[serde(default = "default_margin_gb")]
+    pub margin_gb: f64,
+    /// Seconds between checks while waiting for space
+    #[serde(default = "default_poll_interval")]
+    pub poll_interval: u64,
+}
+
+fn default_factor() -> f64 {
+    1.5
+}
+
+fn default_margin_gb() -> f64 {
+    1.0
+}
+
+fn default_poll_interval() -> u64 {
+    60
+}
+
+impl SpaceConfig {
+    /// Estimated bytes required to encode ts_path
+    pub fn required(&self, ts_path: &std::path::Path) -> Result<u64, std::io::Error> {
+        let size = std::fs::metadata(ts_path)?.len() as f64;
+        Ok((size * self.factor + self.margin_gb * 1e9) as u64)
+    }
+
+    /// (required, available) bytes when dir doesn't have enough space for ts_path
+    pub fn shortage(
+        &self,
+        ts_path: &std::path::Path,
+        dir: &std::path::Path,
+    ) -> Result<Option<(u64, u64)>, std::io::Error> {
+        let required = self.required(ts_path)?;
+        let available = available_space(dir)?;
+        Ok(if available < required {
+            Some((required, available))
+        } else {
+            None
+        })
+    }
+}
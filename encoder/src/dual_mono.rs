@@ -0,0 +1,105 @@
+/// How to encode dual-mono audio detected by the audio component descriptor of the event
+#[derive(serde::Deserialize)]
+pub struct DualMonoConfig {
+    #[serde(default)]
+    pub mode: DualMonoMode,
+}
+
+#[derive(Clone, Copy, Default, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DualMonoMode {
+    /// Two mono audio tracks. -map options in ffmpeg_args are replaced.
+    #[default]
+    Split,
+    /// Only the main (left) channel
+    Main,
+    /// Only the sub (right) channel
+    Sub,
+}
+
+/// Languages of the dual-mono audio in ISO 639-2
+#[derive(Debug)]
+pub struct DualMono {
+    pub main_language: String,
+    pub sub_language: String,
+}
+
+impl DualMonoConfig {
+    /// Output arguments replacing -filter:a. audio_filters are applied to each channel.
+    pub fn output_args(&self, dual_mono: &DualMono, audio_filters: &[String]) -> Vec<String> {
+        let chain = |filter: &str| {
+            std::iter::once(filter.to_owned())
+                .chain(audio_filters.iter().cloned())
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        match self.mode {
+            DualMonoMode::Split => {
+                let filter = if audio_filters.is_empty() {
+                    "[0:a:0]channelsplit=channel_layout=stereo[main][sub]".to_owned()
+                } else {
+                    let filters = audio_filters.join(",");
+                    format!(
+                        "[0:a:0]channelsplit=channel_layout=stereo[left][right];[left]{}[main];[right]{}[sub]",
+                        filters, filters
+                    )
+                };
+                vec![
+                    "-filter_complex".to_owned(),
+                    filter,
+                    "-map".to_owned(),
+                    "0:v:0".to_owned(),
+                    "-map".to_owned(),
+                    "[main]".to_owned(),
+                    "-map".to_owned(),
+                    "[sub]".to_owned(),
+                    "-ac".to_owned(),
+                    "1".to_owned(),
+                    "-metadata:s:a:0".to_owned(),
+                    format!("language={}", dual_mono.main_language),
+                    "-metadata:s:a:1".to_owned(),
+                    format!("language={}", dual_mono.sub_language),
+                ]
+            }
+            DualMonoMode::Main => vec![
+                "-filter:a".to_owned(),
+                chain("pan=mono|c0=FL"),
+                "-ac".to_owned(),
+                "1".to_owned(),
+                "-metadata:s:a:0".to_owned(),
+                format!("language={}", dual_mono.main_language),
+            ],
+            DualMonoMode::Sub => vec![
+                "-filter:a".to_owned(),
+                chain("pan=mono|c0=FR"),
+                "-ac".to_owned(),
+                "1".to_owned(),
+                "-metadata:s:a:0".to_owned(),
+                format!("language={}", dual_mono.sub_language),
+            ],
+        }
+    }
+
+    pub fn is_split(&self) -> bool {
+        matches!(self.mode, DualMonoMode::Split)
+    }
+
+    /// Remove -map options from ffmpeg_args when they are replaced by output_args
+    pub fn ffmpeg_args(&self, ffmpeg_args: &[String]) -> Vec<String> {
+        match self.mode {
+            DualMonoMode::Split => {
+                let mut args = vec![];
+                let mut iter = ffmpeg_args.iter();
+                while let Some(arg) = iter.next() {
+                    if arg == "-map" {
+                        iter.next();
+                    } else {
+                        args.push(arg.clone());
+                    }
+                }
+                args
+            }
+            DualMonoMode::Main | DualMonoMode::Sub => ffmpeg_args.to_vec(),
+        }
+    }
+}
@@ -0,0 +1,300 @@
+/// Send outcomes by email over SMTP, e.g. for users without a chat service receiving webhooks.
+/// Subject and body are templates where {status}, {file} (file name of the source TS), {path},
+/// {profile}, {message_id}, {error} and {eta} are replaced.
+#[derive(serde::Deserialize)]
+pub struct EmailConfig {
+    pub host: String,
+    /// Defaults to 587 for starttls, 465 for tls and 25 for none
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub tls: Tls,
+    /// Authenticate with AUTH PLAIN when set
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// e.g. "Encoder <encoder@example.com>"
+    pub from: String,
+    pub to: Vec<String>,
+    /// Defaults to failures, alerts and digests
+    #[serde(default = "default_on")]
+    pub on: Vec<crate::outcome::Status>,
+    #[serde(default = "default_subject")]
+    pub subject: String,
+    #[serde(default = "default_body")]
+    pub body: String,
+    /// Name sent with EHLO
+    #[serde(default = "default_helo")]
+    pub helo: String,
+    /// Seconds to wait for the whole session
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Tls {
+    /// Upgrade the plain connection with STARTTLS, which the server must support
+    #[default]
+    Starttls,
+    /// Connect with TLS from the start (SMTPS)
+    Tls,
+    /// Plain text, only for a relay on localhost
+    None,
+}
+
+fn default_on() -> Vec<crate::outcome::Status> {
+    vec![
+        crate::outcome::Status::Failed,
+        crate::outcome::Status::Alert,
+        crate::outcome::Status::Digest,
+    ]
+}
+
+fn default_subject() -> String {
+    "[encoder] {status}: {file}".to_owned()
+}
+
+fn default_body() -> String {
+    "Status: {status}\nFile: {path}\nProfile: {profile}\nMessage ID: {message_id}\nETA: {eta}\n\n{error}\n"
+        .to_owned()
+}
+
+fn default_helo() -> String {
+    "localhost".to_owned()
+}
+
+fn default_timeout() -> u64 {
+    60
+}
+
+impl EmailConfig {
+    fn expand(&self, template: &str, outcome: &crate::outcome::JobOutcome) -> String {
+        let file = outcome
+            .file
+            .file_name()
+            .map(|fname| fname.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        template
+            .replace("{status}", outcome.status.as_str())
+            .replace("{file}", &file)
+            .replace("{path}", &outcome.file.to_string_lossy())
+            .replace("{profile}", outcome.profile.unwrap_or("default"))
+            .replace("{message_id}", outcome.message_id)
+            .replace("{error}", outcome.error.as_deref().unwrap_or(""))
+            .replace("{eta}", outcome.eta.as_deref().unwrap_or("-"))
+    }
+
+    /// Send the outcome if its status is in on
+    pub async fn notify(
+        &self,
+        outcome: &crate::outcome::JobOutcome<'_>,
+    ) -> Result<(), anyhow::Error> {
+        if !self.on.contains(&outcome.status) {
+            return Ok(());
+        }
+        self.send(
+            &self.expand(&self.subject, outcome),
+            &self.expand(&self.body, outcome),
+        )
+        .await
+    }
+
+    /// Send the digest if "digest" is in on
+    pub async fn send_digest(
+        &self,
+        subject: &str,
+        digest: &crate::digest::Digest,
+    ) -> Result<(), anyhow::Error> {
+        if !self.on.contains(&crate::outcome::Status::Digest) {
+            return Ok(());
+        }
+        self.send(subject, &digest.to_string()).await
+    }
+
+    /// Send a plain text mail to all recipients
+    pub async fn send(&self, subject: &str, body: &str) -> Result<(), anyhow::Error> {
+        let message = self.message(subject, body);
+        tokio::time::timeout(
+            std::time::Duration::from_secs(self.timeout),
+            self.session(&message),
+        )
+        .await
+        .map_err(|_| anyhow::anyhow!("SMTP session with {} timed out", self.host))?
+        .map_err(|e| anyhow::anyhow!("failed to send mail via {}: {}", self.host, e))
+    }
+
+    fn port(&self) -> u16 {
+        self.port.unwrap_or(match self.tls {
+            Tls::Starttls => 587,
+            Tls::Tls => 465,
+            Tls::None => 25,
+        })
+    }
+
+    async fn session(&self, message: &str) -> Result<(), anyhow::Error> {
+        let stream = tokio::net::TcpStream::connect((self.host.as_str(), self.port())).await?;
+        match self.tls {
+            Tls::Tls => {
+                let stream = self.connect_tls(stream).await?;
+                self.transaction(tokio::io::BufReader::new(stream), message)
+                    .await
+            }
+            Tls::Starttls => {
+                let mut stream = tokio::io::BufReader::new(stream);
+                expect(&mut stream, 220).await?;
+                command(&mut stream, &format!("EHLO {}", self.helo), 250).await?;
+                command(&mut stream, "STARTTLS", 220).await?;
+                let stream = self.connect_tls(stream.into_inner()).await?;
+                let mut stream = tokio::io::BufReader::new(stream);
+                command(&mut stream, &format!("EHLO {}", self.helo), 250).await?;
+                self.deliver(&mut stream, message).await
+            }
+            Tls::None => {
+                self.transaction(tokio::io::BufReader::new(stream), message)
+                    .await
+            }
+        }
+    }
+
+    async fn connect_tls(
+        &self,
+        stream: tokio::net::TcpStream,
+    ) -> Result<tokio_rustls::client::TlsStream<tokio::net::TcpStream>, anyhow::Error> {
+        let mut config = tokio_rustls::rustls::ClientConfig::new();
+        config.root_store =
+            rustls_native_certs::load_native_certs().or_else(|(partial, e)| partial.ok_or(e))?;
+        let domain = tokio_rustls::webpki::DNSNameRef::try_from_ascii_str(&self.host)
+            .map_err(|_| anyhow::anyhow!("{} is not a valid DNS name", self.host))?;
+        Ok(
+            tokio_rustls::TlsConnector::from(std::sync::Arc::new(config))
+                .connect(domain, stream)
+                .await?,
+        )
+    }
+
+    /// Greeting, EHLO and delivery on a connection without STARTTLS
+    async fn transaction<S>(
+        &self,
+        mut stream: tokio::io::BufReader<S>,
+        message: &str,
+    ) -> Result<(), anyhow::Error>
+    where
+        S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+    {
+        expect(&mut stream, 220).await?;
+        command(&mut stream, &format!("EHLO {}", self.helo), 250).await?;
+        self.deliver(&mut stream, message).await
+    }
+
+    async fn deliver<S>(
+        &self,
+        stream: &mut tokio::io::BufReader<S>,
+        message: &str,
+    ) -> Result<(), anyhow::Error>
+    where
+        S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+    {
+        if let (Some(username), Some(password)) = (&self.username, &self.password) {
+            let credentials = base64::encode(format!("\0{}\0{}", username, password));
+            command(stream, &format!("AUTH PLAIN {}", credentials), 235).await?;
+        }
+        command(stream, &format!("MAIL FROM:<{}>", address(&self.from)), 250).await?;
+        for to in &self.to {
+            command(stream, &format!("RCPT TO:<{}>", address(to)), 250).await?;
+        }
+        command(stream, "DATA", 354).await?;
+        // The body is base64-encoded, so no line starts with a dot
+        command(stream, &format!("{}\r\n.", message), 250).await?;
+        command(stream, "QUIT", 221).await?;
+        Ok(())
+    }
+
+    /// Headers and the base64-encoded body in CRLF
+    fn message(&self, subject: &str, body: &str) -> String {
+        let domain = address(&self.from)
+            .rsplit('@')
+            .next()
+            .unwrap_or("localhost")
+            .to_owned();
+        let now = chrono::Utc::now();
+        let mut message = format!(
+            "From: {}\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\nMessage-ID: <{}.{}@{}>\r\n",
+            self.from,
+            self.to.join(", "),
+            encode_header(subject),
+            now.to_rfc2822(),
+            now.timestamp_millis(),
+            std::process::id(),
+            domain
+        );
+        message.push_str("MIME-Version: 1.0\r\nContent-Type: text/plain; charset=UTF-8\r\nContent-Transfer-Encoding: base64\r\n\r\n");
+        let encoded = base64::encode(body.replace("\r\n", "\n").replace('\n', "\r\n"));
+        for line in encoded.as_bytes().chunks(76) {
+            message.push_str(std::str::from_utf8(line).unwrap());
+            message.push_str("\r\n");
+        }
+        message.truncate(message.len() - 2);
+        message
+    }
+}
+
+/// Address part of "Name <user@example.com>"
+fn address(mailbox: &str) -> &str {
+    match (mailbox.rfind('<'), mailbox.rfind('>')) {
+        (Some(start), Some(end)) if start < end => &mailbox[start + 1..end],
+        _ => mailbox.trim(),
+    }
+}
+
+/// Encode non-ASCII header values as RFC 2047 encoded words
+fn encode_header(value: &str) -> String {
+    if value.is_ascii() {
+        value.to_owned()
+    } else {
+        format!("=?UTF-8?B?{}?=", base64::encode(value))
+    }
+}
+
+/// Read a reply, which may continue over lines like "250-...", and check its code
+async fn expect<S>(stream: &mut tokio::io::BufReader<S>, code: u16) -> Result<(), anyhow::Error>
+where
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+{
+    use tokio::io::AsyncBufReadExt as _;
+
+    let mut reply = String::new();
+    loop {
+        let mut line = String::new();
+        if stream.read_line(&mut line).await? == 0 {
+            return Err(anyhow::anyhow!("connection closed"));
+        }
+        reply.push_str(&line);
+        if line.as_bytes().get(3) != Some(&b'-') {
+            break;
+        }
+    }
+    if reply.get(..3).and_then(|c| c.parse::<u16>().ok()) == Some(code) {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!(
+            "expected {} but got {}",
+            code,
+            reply.trim_end()
+        ))
+    }
+}
+
+async fn command<S>(
+    stream: &mut tokio::io::BufReader<S>,
+    line: &str,
+    code: u16,
+) -> Result<(), anyhow::Error>
+where
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+{
+    use tokio::io::AsyncWriteExt as _;
+
+    stream.write_all(line.as_bytes()).await?;
+    stream.write_all(b"\r\n").await?;
+    stream.flush().await?;
+    expect(stream, code).await
+}
@@ -0,0 +1,453 @@
+const MAGIC: &[u8; 8] = b"RCUENC1\n";
+const CHUNK_SIZE: usize = 64 * 1024;
+const TAG_SIZE: usize = 16;
+const NONCE_PREFIX_SIZE: usize = 7;
+
+/// Encrypt the outputs with AES-256-GCM before they are uploaded or transferred, e.g. to
+/// third-party object storage. Each file is sent as "{name}.enc" while sidecars are sent in plain
+/// text with the key_id so that the key can be found later. Local outputs are kept unencrypted.
+/// decrypt-output restores the files.
+///
+/// The file starts with the magic "RCUENC1\n", the key_id and the data key wrapped by KMS (both
+/// prefixed with u16 lengths in big endian) and a random 7-byte nonce prefix. The plaintext
+/// follows in 64 KiB chunks sealed with the nonce of the prefix, the chunk counter (u32 in big
+/// endian) and 1 for the last chunk or 0 otherwise, with the header as associated data.
+#[derive(serde::Deserialize)]
+pub struct EncryptionConfig {
+    /// Recorded in the header and the sidecars, e.g. "archive-2024"
+    pub key_id: String,
+    /// Base64 of the 32-byte key
+    pub key: Option<String>,
+    /// File containing the 32-byte key in raw bytes or base64
+    pub key_file: Option<std::path::PathBuf>,
+    /// Generate a data key with kms:GenerateDataKey for each job instead of key or key_file.
+    /// The wrapped data key is stored in the header and unwrapped with kms:Decrypt.
+    pub kms_key_id: Option<String>,
+    /// Region of KMS. Defaults to the region from the environment.
+    pub region: Option<String>,
+}
+
+/// Data key and its wrapped form stored in the header
+struct DataKey {
+    plaintext: Vec<u8>,
+    wrapped: Vec<u8>,
+}
+
+impl EncryptionConfig {
+    async fn data_key(&self) -> Result<DataKey, anyhow::Error> {
+        if let Some(ref kms_key_id) = self.kms_key_id {
+            let response = self
+                .kms(
+                    "GenerateDataKey",
+                    serde_json::json!({"KeyId": kms_key_id, "KeySpec": "AES_256"}),
+                )
+                .await?;
+            Ok(DataKey {
+                plaintext: decode_field(&response, "Plaintext")?,
+                wrapped: decode_field(&response, "CiphertextBlob")?,
+            })
+        } else {
+            Ok(DataKey {
+                plaintext: self.local_key()?,
+                wrapped: vec![],
+            })
+        }
+    }
+
+    fn local_key(&self) -> Result<Vec<u8>, anyhow::Error> {
+        let key = match (&self.key, &self.key_file) {
+            (Some(key), _) => base64::decode(key.trim())?,
+            (None, Some(key_file)) => {
+                let bytes = std::fs::read(key_file)?;
+                if bytes.len() == 32 {
+                    bytes
+                } else {
+                    base64::decode(String::from_utf8(bytes)?.trim())?
+                }
+            }
+            (None, None) => {
+                return Err(anyhow::anyhow!(
+                    "encryption requires key, key_file or kms_key_id"
+                ))
+            }
+        };
+        if key.len() != 32 {
+            return Err(anyhow::anyhow!(
+                "encryption key must be 32 bytes but {} bytes",
+                key.len()
+            ));
+        }
+        Ok(key)
+    }
+
+    /// Call the JSON API of KMS, which rusoto_core signs
+    async fn kms(
+        &self,
+        action: &str,
+        body: serde_json::Value,
+    ) -> Result<serde_json::Value, anyhow::Error> {
+        let region = match self.region {
+            Some(ref region) => region.parse()?,
+            None => rusoto_core::Region::default(),
+        };
+        let client = rusoto_core::Client::shared();
+        let mut request = rusoto_core::signature::SignedRequest::new("POST", "kms", &region, "/");
+        request.add_header("x-amz-target", &format!("TrentService.{}", action));
+        request.set_content_type("application/x-amz-json-1.1".to_owned());
+        request.set_payload(Some(serde_json::to_vec(&body)?));
+        let response = client
+            .sign_and_dispatch(request)
+            .await
+            .map_err(|e| anyhow::anyhow!("failed to call kms:{}: {:?}", action, e))?
+            .buffer()
+            .await?;
+        if !response.status.is_success() {
+            return Err(anyhow::anyhow!(
+                "kms:{} returned {}: {}",
+                action,
+                response.status,
+                response.body_as_str()
+            ));
+        }
+        Ok(serde_json::from_slice(&response.body)?)
+    }
+
+    /// Encrypt the files except sidecars into "{path}.enc" with a data key shared in the job,
+    /// and return them with the names suffixed with ".enc". Sidecars are returned as they are.
+    /// Each file gets its own header with a fresh nonce prefix so that no nonce is reused under
+    /// the shared key.
+    pub async fn encrypt_files(
+        &self,
+        files: &[(std::path::PathBuf, String)],
+    ) -> Result<Vec<(std::path::PathBuf, String)>, anyhow::Error> {
+        let data_key = self.data_key().await?;
+        let mut encrypted = vec![];
+        for (path, name) in files {
+            if path.extension().is_some_and(|ext| ext == "json") {
+                encrypted.push((path.clone(), name.clone()));
+                continue;
+            }
+            let mut enc_path = path.as_os_str().to_owned();
+            enc_path.push(".enc");
+            let enc_path = std::path::PathBuf::from(enc_path);
+            tracing::info!("Encrypt {} into {}", path.display(), enc_path.display());
+            let source = path.clone();
+            let dest = enc_path.clone();
+            let key = data_key.plaintext.clone();
+            let header = header(&self.key_id, &data_key.wrapped)?;
+            tokio::task::spawn_blocking(move || encrypt(&source, &dest, &key, &header)).await??;
+            encrypted.push((enc_path, format!("{}.enc", name)));
+        }
+        Ok(encrypted)
+    }
+
+    /// Decrypt the file written by encrypt_files into dest
+    pub async fn decrypt_file(
+        &self,
+        path: &std::path::Path,
+        dest: &std::path::Path,
+    ) -> Result<(), anyhow::Error> {
+        let mut reader = std::io::BufReader::new(std::fs::File::open(path)?);
+        let (key_id, wrapped, header) = read_header(&mut reader)?;
+        let key = if wrapped.is_empty() {
+            if key_id != self.key_id {
+                return Err(anyhow::anyhow!(
+                    "{} is encrypted with {} but the configured key is {}",
+                    path.display(),
+                    key_id,
+                    self.key_id
+                ));
+            }
+            self.local_key()?
+        } else {
+            let response = self
+                .kms(
+                    "Decrypt",
+                    serde_json::json!({"CiphertextBlob": base64::encode(&wrapped)}),
+                )
+                .await?;
+            decode_field(&response, "Plaintext")?
+        };
+        let dest = dest.to_owned();
+        tokio::task::spawn_blocking(move || decrypt(reader, &dest, &key, &header)).await?
+    }
+}
+
+fn decode_field(response: &serde_json::Value, name: &str) -> Result<Vec<u8>, anyhow::Error> {
+    let value = response[name]
+        .as_str()
+        .ok_or_else(|| anyhow::anyhow!("KMS response has no {}", name))?;
+    Ok(base64::decode(value)?)
+}
+
+/// Magic, key_id, wrapped key and a fresh nonce prefix
+fn header(key_id: &str, wrapped: &[u8]) -> Result<Vec<u8>, anyhow::Error> {
+    let mut header = MAGIC.to_vec();
+    header.extend_from_slice(&(key_id.len() as u16).to_be_bytes());
+    header.extend_from_slice(key_id.as_bytes());
+    header.extend_from_slice(&(wrapped.len() as u16).to_be_bytes());
+    header.extend_from_slice(wrapped);
+    let mut prefix = [0; NONCE_PREFIX_SIZE];
+    getrandom::getrandom(&mut prefix).map_err(|e| anyhow::anyhow!("getrandom failed: {}", e))?;
+    header.extend_from_slice(&prefix);
+    Ok(header)
+}
+
+/// (key_id, wrapped key, whole header)
+fn read_header<R>(reader: &mut R) -> Result<(String, Vec<u8>, Vec<u8>), anyhow::Error>
+where
+    R: std::io::Read,
+{
+    let mut header = vec![0; MAGIC.len()];
+    reader.read_exact(&mut header)?;
+    if header != MAGIC {
+        return Err(anyhow::anyhow!("not an encrypted output"));
+    }
+    let mut fields = vec![];
+    for _ in 0..2 {
+        let mut len = [0; 2];
+        reader.read_exact(&mut len)?;
+        let mut field = vec![0; u16::from_be_bytes(len) as usize];
+        reader.read_exact(&mut field)?;
+        header.extend_from_slice(&len);
+        header.extend_from_slice(&field);
+        fields.push(field);
+    }
+    let mut prefix = [0; NONCE_PREFIX_SIZE];
+    reader.read_exact(&mut prefix)?;
+    header.extend_from_slice(&prefix);
+    let wrapped = fields.pop().unwrap();
+    let key_id = String::from_utf8(fields.pop().unwrap())?;
+    Ok((key_id, wrapped, header))
+}
+
+fn cipher(key: &[u8]) -> aes_gcm::Aes256Gcm {
+    use aes_gcm::aead::NewAead as _;
+
+    aes_gcm::Aes256Gcm::new(aes_gcm::aead::generic_array::GenericArray::from_slice(key))
+}
+
+fn nonce(header: &[u8], counter: u32, last: bool) -> [u8; 12] {
+    let mut nonce = [0; 12];
+    nonce[..NONCE_PREFIX_SIZE].copy_from_slice(&header[header.len() - NONCE_PREFIX_SIZE..]);
+    nonce[NONCE_PREFIX_SIZE..11].copy_from_slice(&counter.to_be_bytes());
+    nonce[11] = last as u8;
+    nonce
+}
+
+/// Read up to buf.len() bytes, stopping early only at the end of the file
+fn read_full<R>(reader: &mut R, buf: &mut [u8]) -> std::io::Result<usize>
+where
+    R: std::io::Read,
+{
+    let mut n = 0;
+    while n < buf.len() {
+        match reader.read(&mut buf[n..])? {
+            0 => break,
+            m => n += m,
+        }
+    }
+    Ok(n)
+}
+
+fn encrypt(
+    path: &std::path::Path,
+    dest: &std::path::Path,
+    key: &[u8],
+    header: &[u8],
+) -> Result<(), anyhow::Error> {
+    use aes_gcm::aead::Aead as _;
+    use std::io::Write as _;
+
+    let cipher = cipher(key);
+    let mut reader = std::io::BufReader::new(std::fs::File::open(path)?);
+    let mut writer = std::io::BufWriter::new(std::fs::File::create(dest)?);
+    writer.write_all(header)?;
+    let mut chunk = vec![0; CHUNK_SIZE];
+    let mut next = vec![0; CHUNK_SIZE];
+    let mut len = read_full(&mut reader, &mut chunk)?;
+    let mut counter = 0u32;
+    loop {
+        // Look ahead to mark the last chunk
+        let next_len = if len == CHUNK_SIZE {
+            read_full(&mut reader, &mut next)?
+        } else {
+            0
+        };
+        let last = next_len == 0;
+        let sealed = cipher
+            .encrypt(
+                aes_gcm::aead::generic_array::GenericArray::from_slice(&nonce(
+                    header, counter, last,
+                )),
+                aes_gcm::aead::Payload {
+                    msg: &chunk[..len],
+                    aad: header,
+                },
+            )
+            .map_err(|_| anyhow::anyhow!("failed to encrypt {}", path.display()))?;
+        writer.write_all(&sealed)?;
+        if last {
+            break;
+        }
+        std::mem::swap(&mut chunk, &mut next);
+        len = next_len;
+        counter = counter
+            .checked_add(1)
+            .ok_or_else(|| anyhow::anyhow!("{} is too large to encrypt", path.display()))?;
+    }
+    writer
+        .into_inner()
+        .map_err(|e| e.into_error())?
+        .sync_all()?;
+    Ok(())
+}
+
+fn decrypt<R>(
+    mut reader: R,
+    dest: &std::path::Path,
+    key: &[u8],
+    header: &[u8],
+) -> Result<(), anyhow::Error>
+where
+    R: std::io::Read,
+{
+    use aes_gcm::aead::Aead as _;
+    use std::io::Write as _;
+
+    let cipher = cipher(key);
+    let mut writer = std::io::BufWriter::new(std::fs::File::create(dest)?);
+    let mut chunk = vec![0; CHUNK_SIZE + TAG_SIZE];
+    let mut next = vec![0; CHUNK_SIZE + TAG_SIZE];
+    let mut len = read_full(&mut reader, &mut chunk)?;
+    let mut counter = 0u32;
+    loop {
+        let next_len = if len == chunk.len() {
+            read_full(&mut reader, &mut next)?
+        } else {
+            0
+        };
+        let last = next_len == 0;
+        let opened = cipher
+            .decrypt(
+                aes_gcm::aead::generic_array::GenericArray::from_slice(&nonce(
+                    header, counter, last,
+                )),
+                aes_gcm::aead::Payload {
+                    msg: &chunk[..len],
+                    aad: header,
+                },
+            )
+            .map_err(|_| anyhow::anyhow!("chunk {} is corrupted or truncated", counter))?;
+        writer.write_all(&opened)?;
+        if last {
+            break;
+        }
+        std::mem::swap(&mut chunk, &mut next);
+        len = next_len;
+        counter += 1;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> EncryptionConfig {
+        EncryptionConfig {
+            key_id: "test".to_owned(),
+            key: Some(base64::encode([7; 32])),
+            key_file: None,
+            kms_key_id: None,
+            region: None,
+        }
+    }
+
+    fn round_trip(size: usize) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("plain");
+        let enc_path = dir.path().join("plain.enc");
+        let dec_path = dir.path().join("decrypted");
+        let plaintext: Vec<u8> = (0..size).map(|i| (i % 251) as u8).collect();
+        std::fs::write(&path, &plaintext).unwrap();
+
+        let key = config().local_key().unwrap();
+        let header = header("test", &[]).unwrap();
+        encrypt(&path, &enc_path, &key, &header).unwrap();
+        let mut reader = std::io::BufReader::new(std::fs::File::open(&enc_path).unwrap());
+        let (key_id, wrapped, read) = read_header(&mut reader).unwrap();
+        assert_eq!(key_id, "test");
+        assert!(wrapped.is_empty());
+        assert_eq!(read, header);
+        decrypt(reader, &dec_path, &key, &read).unwrap();
+        assert_eq!(std::fs::read(&dec_path).unwrap(), plaintext);
+    }
+
+    #[test]
+    fn round_trip_sizes() {
+        for &size in &[
+            0,
+            1,
+            CHUNK_SIZE - 1,
+            CHUNK_SIZE,
+            CHUNK_SIZE + 1,
+            3 * CHUNK_SIZE,
+        ] {
+            round_trip(size);
+        }
+    }
+
+    #[test]
+    fn truncated_file_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("plain");
+        let enc_path = dir.path().join("plain.enc");
+        std::fs::write(&path, vec![1; 2 * CHUNK_SIZE]).unwrap();
+        let key = config().local_key().unwrap();
+        let header = header("test", &[]).unwrap();
+        encrypt(&path, &enc_path, &key, &header).unwrap();
+
+        // Dropping the last chunk must not look like a complete file
+        let encrypted = std::fs::read(&enc_path).unwrap();
+        let truncated = &encrypted[..header.len() + CHUNK_SIZE + TAG_SIZE];
+        let mut reader = truncated;
+        let (_, _, read) = read_header(&mut reader).unwrap();
+        assert!(decrypt(reader, &dir.path().join("decrypted"), &key, &read).is_err());
+    }
+
+    #[tokio::test]
+    async fn files_have_distinct_nonces() {
+        let dir = tempfile::tempdir().unwrap();
+        let files: Vec<_> = ["a.mp4", "b.m4a"]
+            .iter()
+            .map(|name| {
+                let path = dir.path().join(name);
+                std::fs::write(&path, vec![0; CHUNK_SIZE + 1]).unwrap();
+                (path, name.to_string())
+            })
+            .collect();
+        let encrypted = config().encrypt_files(&files).await.unwrap();
+        assert_eq!(encrypted.len(), 2);
+
+        let headers: Vec<_> = encrypted
+            .iter()
+            .map(|(path, _)| {
+                let mut reader = std::io::BufReader::new(std::fs::File::open(path).unwrap());
+                read_header(&mut reader).unwrap().2
+            })
+            .collect();
+        assert_ne!(headers[0], headers[1]);
+        assert_ne!(nonce(&headers[0], 0, false), nonce(&headers[1], 0, false));
+
+        for ((path, _), (source, _)) in encrypted.iter().zip(&files) {
+            let dest = dir.path().join("decrypted");
+            config().decrypt_file(path, &dest).await.unwrap();
+            assert_eq!(
+                std::fs::read(&dest).unwrap(),
+                std::fs::read(source).unwrap()
+            );
+        }
+    }
+}
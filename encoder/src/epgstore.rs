@@ -0,0 +1,239 @@
+/// EPG collected from EIT, kept in SQLite
+#[derive(Clone, serde::Deserialize)]
+pub struct EpgStoreConfig {
+    pub path: std::path::PathBuf,
+    /// Events which ended this many days ago are removed on ingestion
+    #[serde(default = "default_retention_days")]
+    pub retention_days: i64,
+}
+
+fn default_retention_days() -> i64 {
+    30
+}
+
+/// Conditions of EpgStore::events. Unset conditions match any event.
+#[derive(Debug, Default)]
+pub struct Query {
+    pub service_id: Option<u16>,
+    /// Unix time. Events overlapping [from, to) match.
+    pub from: Option<i64>,
+    /// Unix time
+    pub to: Option<i64>,
+    /// Substring of the event name or text
+    pub keyword: Option<String>,
+}
+
+pub struct EpgStore {
+    conn: rusqlite::Connection,
+    retention_days: i64,
+}
+
+const COLUMNS: &str = "network_id, service_id, service_name, table_id, version, event_id, \
+                       start_time, end_time, name, text, genre, dual_mono_main, dual_mono_sub, \
+                       series_id, episode_number, series_name";
+
+impl EpgStore {
+    pub fn open(config: &EpgStoreConfig) -> Result<Self, anyhow::Error> {
+        let conn = rusqlite::Connection::open(&config.path)?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS events (
+                network_id INTEGER NOT NULL,
+                service_id INTEGER NOT NULL,
+                service_name TEXT,
+                table_id INTEGER NOT NULL,
+                version INTEGER NOT NULL,
+                event_id INTEGER NOT NULL,
+                start_time INTEGER NOT NULL,
+                end_time INTEGER NOT NULL,
+                name TEXT,
+                text TEXT,
+                genre TEXT,
+                dual_mono_main TEXT,
+                dual_mono_sub TEXT,
+                series_id INTEGER,
+                episode_number INTEGER,
+                series_name TEXT,
+                PRIMARY KEY (network_id, service_id, event_id)
+            );
+            CREATE INDEX IF NOT EXISTS events_start_time ON events (start_time);",
+        )?;
+        Ok(Self {
+            conn,
+            retention_days: config.retention_days,
+        })
+    }
+
+    /// Insert the events, or update stored events when they come from another version or sub
+    /// table of EIT. Returns the number of inserted or updated events.
+    pub fn ingest(&mut self, events: &[crate::analysis::EpgEvent]) -> Result<usize, anyhow::Error> {
+        let tx = self.conn.transaction()?;
+        let mut changed = 0;
+        {
+            let mut stmt = tx.prepare(&format!(
+                "INSERT INTO events ({}) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                ON CONFLICT (network_id, service_id, event_id) DO UPDATE SET
+                    service_name = coalesce(excluded.service_name, service_name),
+                    table_id = excluded.table_id,
+                    version = excluded.version,
+                    start_time = excluded.start_time,
+                    end_time = excluded.end_time,
+                    name = excluded.name,
+                    text = excluded.text,
+                    genre = excluded.genre,
+                    dual_mono_main = excluded.dual_mono_main,
+                    dual_mono_sub = excluded.dual_mono_sub,
+                    series_id = excluded.series_id,
+                    episode_number = excluded.episode_number,
+                    series_name = excluded.series_name
+                WHERE table_id != excluded.table_id OR version != excluded.version",
+                COLUMNS
+            ))?;
+            for epg in events {
+                let event = &epg.event;
+                changed += stmt.execute(rusqlite::params![
+                    epg.network_id,
+                    epg.service_id,
+                    epg.service_name,
+                    epg.table_id,
+                    epg.version,
+                    event.event_id,
+                    event.start_time,
+                    event.end_time,
+                    event.name,
+                    event.text,
+                    event.genre,
+                    event.dual_mono.as_ref().map(|d| &d.main_language),
+                    event.dual_mono.as_ref().map(|d| &d.sub_language),
+                    event.series.as_ref().map(|s| s.series_id),
+                    event.series.as_ref().map(|s| s.episode_number),
+                    event.series.as_ref().map(|s| &s.name),
+                ])?;
+            }
+        }
+        let expired = chrono::Utc::now().timestamp() - self.retention_days * 24 * 60 * 60;
+        tx.execute(
+            "DELETE FROM events WHERE end_time < ?",
+            rusqlite::params![expired],
+        )?;
+        tx.commit()?;
+        Ok(changed)
+    }
+
+    /// Events matching the query, ordered by start_time
+    pub fn events(&self, query: &Query) -> Result<Vec<crate::analysis::EpgEvent>, anyhow::Error> {
+        let mut conditions = vec![];
+        let mut params: Vec<Box<dyn rusqlite::ToSql>> = vec![];
+        if let Some(service_id) = query.service_id {
+            conditions.push("service_id = ?");
+            params.push(Box::new(service_id));
+        }
+        if let Some(from) = query.from {
+            conditions.push("end_time > ?");
+            params.push(Box::new(from));
+        }
+        if let Some(to) = query.to {
+            conditions.push("start_time < ?");
+            params.push(Box::new(to));
+        }
+        if let Some(ref keyword) = query.keyword {
+            conditions.push("(instr(name, ?) > 0 OR instr(text, ?) > 0)");
+            params.push(Box::new(keyword.clone()));
+            params.push(Box::new(keyword.clone()));
+        }
+        let mut sql = format!("SELECT {} FROM events", COLUMNS);
+        if !conditions.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&conditions.join(" AND "));
+        }
+        sql.push_str(" ORDER BY start_time, service_id");
+
+        let mut stmt = self.conn.prepare(&sql)?;
+        let rows = stmt.query_map(params, |row| {
+            let dual_mono = match (row.get(11)?, row.get(12)?) {
+                (Some(main_language), Some(sub_language)) => Some(crate::dual_mono::DualMono {
+                    main_language,
+                    sub_language,
+                }),
+                _ => None,
+            };
+            let series = match row.get(13)? {
+                Some(series_id) => Some(crate::analysis::Series {
+                    series_id,
+                    episode_number: row.get::<_, Option<u16>>(14)?.unwrap_or(0),
+                    name: row.get::<_, Option<String>>(15)?.unwrap_or_default(),
+                }),
+                None => None,
+            };
+            Ok(crate::analysis::EpgEvent {
+                network_id: row.get(0)?,
+                service_id: row.get(1)?,
+                service_name: row.get(2)?,
+                table_id: row.get(3)?,
+                version: row.get(4)?,
+                event: crate::analysis::EventInfo {
+                    event_id: row.get(5)?,
+                    start_time: row.get(6)?,
+                    end_time: row.get(7)?,
+                    name: row.get(8)?,
+                    text: row.get(9)?,
+                    genre: row
+                        .get::<_, Option<String>>(10)?
+                        .and_then(|genre| genre_name(&genre)),
+                    dual_mono,
+                    series,
+                    relays: vec![],
+                },
+            })
+        })?;
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+}
+
+/// The static name of content_nibble_level_1 equal to the stored genre
+fn genre_name(genre: &str) -> Option<&'static str> {
+    (0..=0xf)
+        .filter_map(|level_1| {
+            tsutils::descriptor::ContentNibble {
+                content_nibble_level_1: level_1,
+                content_nibble_level_2: 0,
+                user_nibble_1: 0,
+                user_nibble_2: 0,
+            }
+            .genre_name()
+        })
+        .find(|name| *name == genre)
+}
+
+/// Ingest EPG of the source into the store, and add events of the service during the recording
+/// which are missing from EIT[p/f] of the source
+pub fn supplement(
+    config: &EpgStoreConfig,
+    source_path: &std::path::Path,
+    info: &mut crate::analysis::SourceInfo,
+) -> Result<(), anyhow::Error> {
+    let mut store = EpgStore::open(config)?;
+    let changed = store.ingest(&crate::analysis::collect_epg(source_path)?)?;
+    tracing::info!("Ingested {} events of {}", changed, source_path.display());
+
+    let (service_id, clock_start) = match (info.service_id, info.clock_start) {
+        (Some(service_id), Some(clock_start)) => (service_id, clock_start),
+        _ => return Ok(()),
+    };
+    let events = store.events(&Query {
+        service_id: Some(service_id),
+        from: Some(clock_start as i64),
+        to: Some((clock_start + info.duration).ceil() as i64),
+        keyword: None,
+    })?;
+    for epg in events {
+        if !info
+            .events
+            .iter()
+            .any(|event| event.event_id == epg.event.event_id)
+        {
+            info.events.push(epg.event);
+        }
+    }
+    info.events.sort_by_key(|event| event.start_time);
+    Ok(())
+}
@@ -0,0 +1,147 @@
+/// Exit statuses shared with the tsutils binaries
+pub use tsutils::cli::Status;
+
+/// Error carrying the status the binary exits with, e.g. wrong arguments or a failed check
+#[derive(Debug)]
+pub struct StatusError {
+    pub status: Status,
+    pub message: String,
+}
+
+impl StatusError {
+    pub fn new(status: Status, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for StatusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for StatusError {}
+
+/// Wrong arguments of a binary
+pub fn usage(message: impl Into<String>) -> anyhow::Error {
+    StatusError::new(Status::Usage, message).into()
+}
+
+/// Status for the error, decided by the first cause which has one
+pub fn classify(e: &anyhow::Error) -> Status {
+    for cause in e.chain() {
+        if let Some(e) = cause.downcast_ref::<StatusError>() {
+            return e.status;
+        }
+        if let Some(e) = cause.downcast_ref::<std::io::Error>() {
+            if tsutils::progress::is_cancelled(e) || e.kind() == std::io::ErrorKind::Interrupted {
+                return Status::Interrupted;
+            }
+            match e.kind() {
+                std::io::ErrorKind::NotFound => return Status::InputNotFound,
+                std::io::ErrorKind::InvalidData => return Status::ParseError,
+                _ => {}
+            }
+        }
+        if cause.is::<tsutils::progress::Cancelled>() {
+            return Status::Interrupted;
+        }
+        if cause.is::<serde_json::Error>()
+            || cause.is::<toml::de::Error>()
+            || cause.is::<std::num::ParseIntError>()
+            || cause.is::<chrono::ParseError>()
+        {
+            return Status::ParseError;
+        }
+        if let Some(e) = cause.downcast_ref::<tsutils::filter::Error>() {
+            match e {
+                tsutils::filter::Error::Io(_) => {}
+                _ => return Status::ParseError,
+            }
+        }
+    }
+    Status::Failure
+}
+
+/// Whether another attempt can succeed, i.e. the failure is not caused by the arguments or the
+/// input
+pub fn is_retryable(status: Status) -> bool {
+    matches!(status, Status::Failure | Status::Interrupted)
+}
+
+/// Returned by main of the binaries to exit with the status of the result. The error is written
+/// to stderr and, when `--error-json PATH` is given, to PATH in JSON like
+/// {"program": "encode", "status": "input_not_found", "code": 66, "message": "..."}.
+pub struct Exit(Result<Status, anyhow::Error>);
+
+impl From<Result<(), anyhow::Error>> for Exit {
+    fn from(result: Result<(), anyhow::Error>) -> Self {
+        Self(result.map(|()| Status::Ok))
+    }
+}
+
+impl From<Result<Status, anyhow::Error>> for Exit {
+    fn from(result: Result<Status, anyhow::Error>) -> Self {
+        Self(result)
+    }
+}
+
+impl std::process::Termination for Exit {
+    fn report(self) -> std::process::ExitCode {
+        let (status, message) = match self.0 {
+            Ok(status) => (status, None),
+            Err(e) => {
+                eprintln!("Error: {:?}", e);
+                (classify(&e), Some(format!("{:#}", e)))
+            }
+        };
+        if status != Status::Ok {
+            if let Some(path) = error_json_path() {
+                let program = std::env::args()
+                    .next()
+                    .map(|arg0| {
+                        std::path::Path::new(&arg0)
+                            .file_name()
+                            .map_or(arg0.clone(), |name| name.to_string_lossy().into_owned())
+                    })
+                    .unwrap_or_default();
+                let report = serde_json::json!({
+                    "program": program,
+                    "status": status.as_str(),
+                    "code": status.code(),
+                    "message": message,
+                });
+                if let Err(e) = std::fs::write(&path, format!("{}\n", report)) {
+                    eprintln!("{}: {}", path, e);
+                }
+            }
+        }
+        std::process::ExitCode::from(status.code() as u8)
+    }
+}
+
+/// `std::env::args()` without `--error-json PATH`, which is handled by Exit
+pub fn args() -> impl Iterator<Item = String> {
+    let mut args = std::env::args();
+    std::iter::from_fn(move || loop {
+        match args.next() {
+            Some(ref arg) if arg == "--error-json" => {
+                args.next();
+            }
+            arg => return arg,
+        }
+    })
+}
+
+fn error_json_path() -> Option<String> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--error-json" {
+            return args.next();
+        }
+    }
+    None
+}
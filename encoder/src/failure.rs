@@ -0,0 +1,142 @@
+/// Common causes of ffmpeg failures recognized in its stderr
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Failure {
+    CorruptInput,
+    MissingCodec,
+    DiskFull,
+    HwEncoderBusy,
+    Other,
+}
+
+// Checked in order since a failure often causes others, e.g. a full disk breaks the muxer
+const SIGNATURES: &[(Failure, &[&str])] = &[
+    (Failure::DiskFull, &["No space left on device"]),
+    (
+        Failure::HwEncoderBusy,
+        &[
+            "OpenEncodeSessionEx failed",
+            "No capable devices found",
+            "Cannot load libcuda",
+            "Failed to create a VAAPI device",
+            "Error creating a MFX session",
+            "Device or resource busy",
+        ],
+    ),
+    (
+        Failure::MissingCodec,
+        &[
+            "Unknown encoder",
+            "Encoder not found",
+            "Decoder not found",
+            "Unsupported codec",
+        ],
+    ),
+    (
+        Failure::CorruptInput,
+        &[
+            "Invalid data found when processing input",
+            "Error while decoding stream",
+            "could not find codec parameters",
+        ],
+    ),
+];
+
+impl Failure {
+    pub fn classify(stderr: &str) -> Self {
+        SIGNATURES
+            .iter()
+            .find(|(_, patterns)| patterns.iter().any(|pattern| stderr.contains(pattern)))
+            .map_or(Self::Other, |(failure, _)| *failure)
+    }
+
+    /// Whether another attempt, e.g. with fallback arguments, can succeed
+    pub fn is_retryable(self) -> bool {
+        self != Self::DiskFull
+    }
+}
+
+/// ffmpeg exited with failure
+#[derive(Debug)]
+pub struct FfmpegError {
+    pub failure: Failure,
+    pub status: std::process::ExitStatus,
+    /// Last line of stderr
+    pub message: String,
+    /// Where the whole stderr is kept
+    pub log_path: Option<std::path::PathBuf>,
+}
+
+impl FfmpegError {
+    pub fn new(
+        status: std::process::ExitStatus,
+        stderr: &str,
+        log_path: Option<std::path::PathBuf>,
+    ) -> Self {
+        Self {
+            failure: Failure::classify(stderr),
+            status,
+            message: stderr
+                .lines()
+                .flat_map(|line| line.rsplit('\r'))
+                .map(str::trim)
+                .rfind(|line| !line.is_empty())
+                .unwrap_or_default()
+                .to_owned(),
+            log_path,
+        }
+    }
+}
+
+impl std::fmt::Display for FfmpegError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "ffmpeg failed with {} ({:?}): {}",
+            self.status, self.failure, self.message
+        )?;
+        if let Some(ref log_path) = self.log_path {
+            write!(f, " (see {})", log_path.display())?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for FfmpegError {}
+
+/// Bytes of stderr kept for the classification
+const TAIL_BYTES: usize = 64 * 1024;
+
+/// Forward stderr of ffmpeg to ours while writing it to log_path and keeping its tail
+pub async fn capture<R>(
+    mut stderr: R,
+    log_path: Option<&std::path::Path>,
+) -> Result<String, anyhow::Error>
+where
+    R: tokio::io::AsyncRead + Unpin,
+{
+    use std::io::Write as _;
+    use tokio::io::AsyncReadExt as _;
+
+    let mut log = match log_path {
+        Some(log_path) => Some(std::fs::File::create(log_path)?),
+        None => None,
+    };
+    let mut tail = vec![];
+    let mut buf = [0; 8192];
+    loop {
+        let n = stderr.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        std::io::stderr().write_all(&buf[..n])?;
+        if let Some(ref mut log) = log {
+            log.write_all(&buf[..n])?;
+        }
+        tail.extend_from_slice(&buf[..n]);
+        if tail.len() > TAIL_BYTES {
+            tail.drain(..tail.len() - TAIL_BYTES);
+        }
+    }
+    Ok(String::from_utf8_lossy(&tail).into_owned())
+}
@@ -0,0 +1,116 @@
+/// Encode with a hardware encoder available on the host, falling back to ffmpeg_args of the
+/// profile when none is available or the hardware encode fails
+#[derive(serde::Deserialize)]
+pub struct HwaccelConfig {
+    /// Tried in order
+    pub prefer: Vec<Hwaccel>,
+    pub nvenc: Option<HwaccelArgs>,
+    pub qsv: Option<HwaccelArgs>,
+    pub vaapi: Option<HwaccelArgs>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Hwaccel {
+    Nvenc,
+    Qsv,
+    Vaapi,
+}
+
+/// Arguments used instead of the profile's ones with the hwaccel
+#[derive(serde::Deserialize)]
+pub struct HwaccelArgs {
+    /// e.g. ["-vaapi_device", "/dev/dri/renderD128"]
+    #[serde(default)]
+    pub input_args: Vec<String>,
+    /// Replaces ffmpeg_args of the profile. The video codec is given with -c:v or -vcodec.
+    pub ffmpeg_args: Vec<String>,
+    /// Appended to the video filters, e.g. ["format=nv12", "hwupload"]
+    #[serde(default)]
+    pub video_filters: Vec<String>,
+}
+
+impl HwaccelArgs {
+    fn video_codec(&self) -> Option<&str> {
+        self.ffmpeg_args
+            .iter()
+            .position(|arg| arg == "-c:v" || arg == "-vcodec")
+            .and_then(|i| self.ffmpeg_args.get(i + 1))
+            .map(|codec| codec.as_str())
+    }
+
+    /// Encode a short test pattern to check the encoder really works on this host
+    async fn probe(&self) -> Result<bool, anyhow::Error> {
+        let codec = match self.video_codec() {
+            Some(codec) => codec,
+            None => return Ok(false),
+        };
+        let mut command = tokio::process::Command::new("ffmpeg");
+        command
+            .args(["-hide_banner", "-loglevel", "error"])
+            .args(&self.input_args)
+            .args(["-f", "lavfi", "-i", "testsrc=size=320x240:duration=0.5"]);
+        if !self.video_filters.is_empty() {
+            command.arg("-filter:v").arg(self.video_filters.join(","));
+        }
+        let status = command
+            .args(["-c:v", codec, "-f", "null", "-"])
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .status()
+            .await?;
+        Ok(status.success())
+    }
+}
+
+// Probe results are cached for the lifetime of the process
+static PROBED: std::sync::Mutex<Vec<(Hwaccel, String, bool)>> = std::sync::Mutex::new(Vec::new());
+
+impl HwaccelConfig {
+    pub(crate) fn args(&self, hwaccel: Hwaccel) -> Option<&HwaccelArgs> {
+        match hwaccel {
+            Hwaccel::Nvenc => self.nvenc.as_ref(),
+            Hwaccel::Qsv => self.qsv.as_ref(),
+            Hwaccel::Vaapi => self.vaapi.as_ref(),
+        }
+    }
+
+    /// Return the most preferred hwaccel which works on this host
+    pub async fn select(&self) -> Result<Option<(Hwaccel, &HwaccelArgs)>, anyhow::Error> {
+        for &hwaccel in &self.prefer {
+            let args = match self.args(hwaccel) {
+                Some(args) => args,
+                None => continue,
+            };
+            let codec = args.video_codec().unwrap_or_default().to_owned();
+            let cached = PROBED
+                .lock()
+                .unwrap()
+                .iter()
+                .find(|(h, c, _)| *h == hwaccel && *c == codec)
+                .map(|&(_, _, available)| available);
+            let available = match cached {
+                Some(available) => available,
+                None => {
+                    let available = args.probe().await?;
+                    tracing::info!(
+                        "hwaccel {:?} ({}): {}",
+                        hwaccel,
+                        codec,
+                        if available {
+                            "available"
+                        } else {
+                            "unavailable"
+                        }
+                    );
+                    PROBED.lock().unwrap().push((hwaccel, codec, available));
+                    available
+                }
+            };
+            if available {
+                return Ok(Some((hwaccel, args)));
+            }
+        }
+        Ok(None)
+    }
+}
@@ -0,0 +1,109 @@
+/// Retention policies of base_dir enforced by the janitor binary
+#[derive(serde::Deserialize)]
+pub struct JanitorConfig {
+    #[serde(default)]
+    pub rules: Vec<RetentionRule>,
+    /// Alert when the available space of base_dir is less than this (GB)
+    pub min_free_gb: Option<f64>,
+}
+
+/// Delete files in base_dir matching the rule, e.g. filtered TS older than 7 days whose MP4
+/// exists and is verified
+#[derive(serde::Deserialize)]
+pub struct RetentionRule {
+    /// Regex matched against file names
+    pub pattern: String,
+    /// In days, compared with the modification time
+    pub older_than_days: f64,
+    /// Keep the file unless "{file stem}.{require_output}" exists, e.g. "mp4"
+    pub require_output: Option<String>,
+    /// Also require the sidecar of the output, which is written only after verification
+    #[serde(default)]
+    pub require_sidecar: bool,
+}
+
+impl RetentionRule {
+    /// Whether the file is to be deleted at now
+    fn expired(
+        &self,
+        pattern: &regex::Regex,
+        path: &std::path::Path,
+        now: std::time::SystemTime,
+    ) -> Result<bool, anyhow::Error> {
+        let fname = match path.file_name().and_then(|fname| fname.to_str()) {
+            Some(fname) => fname,
+            None => return Ok(false),
+        };
+        if !pattern.is_match(fname) {
+            return Ok(false);
+        }
+        let age = now
+            .duration_since(std::fs::metadata(path)?.modified()?)
+            .unwrap_or_default();
+        if age.as_secs_f64() < self.older_than_days * 24.0 * 60.0 * 60.0 {
+            return Ok(false);
+        }
+        if let Some(ref extension) = self.require_output {
+            let stem = path.file_stem().unwrap().to_string_lossy();
+            let output_path = path.with_file_name(format!("{}.{}", stem, extension));
+            if !output_path.exists() {
+                return Ok(false);
+            }
+            if self.require_sidecar && !crate::sidecar::Sidecar::path(&output_path).exists() {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+impl JanitorConfig {
+    /// Delete expired files and return their paths. Nothing is deleted when dry_run is true.
+    pub fn clean(
+        &self,
+        base_dir: &std::path::Path,
+        dry_run: bool,
+    ) -> Result<Vec<std::path::PathBuf>, anyhow::Error> {
+        let rules = self
+            .rules
+            .iter()
+            .map(|rule| Ok((rule, regex::Regex::new(&rule.pattern)?)))
+            .collect::<Result<Vec<_>, anyhow::Error>>()?;
+        let now = std::time::SystemTime::now();
+        let mut deleted = vec![];
+        for entry in std::fs::read_dir(base_dir)? {
+            let entry = entry?;
+            if !entry.file_type()?.is_file() {
+                continue;
+            }
+            let path = entry.path();
+            for (rule, pattern) in &rules {
+                if rule.expired(pattern, &path, now)? {
+                    if !dry_run {
+                        std::fs::remove_file(&path)?;
+                    }
+                    deleted.push(path);
+                    break;
+                }
+            }
+        }
+        deleted.sort();
+        Ok(deleted)
+    }
+
+    /// Describe the shortage of the available space of base_dir if any
+    pub fn space_alert(&self, base_dir: &std::path::Path) -> Result<Option<String>, anyhow::Error> {
+        if let Some(min_free_gb) = self.min_free_gb {
+            let available_gb = crate::disk::available_space(base_dir)? as f64 / 1e9;
+            if available_gb < min_free_gb {
+                return Ok(Some(format!(
+                    "{} has only {:.1}GB available (min_free_gb={})",
+                    base_dir.display(),
+                    available_gb,
+                    min_free_gb
+                )));
+            }
+        }
+        Ok(None)
+    }
+}
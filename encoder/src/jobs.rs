@@ -0,0 +1,252 @@
+/// Record state transitions of each job in a Redis hash "{prefix}{file}" so that they survive
+/// worker restarts. Files are indexed by the last update in the sorted set "{prefix}index".
+#[derive(serde::Deserialize)]
+pub struct JobStoreConfig {
+    #[serde(default = "default_prefix")]
+    pub prefix: String,
+    /// Expire the records after this many days
+    pub ttl_days: Option<u64>,
+}
+
+fn default_prefix() -> String {
+    "encoder:job:".to_owned()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum State {
+    Queued,
+    Running,
+    Verifying,
+    Done,
+    Failed,
+}
+
+impl State {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Queued => "queued",
+            Self::Running => "running",
+            Self::Verifying => "verifying",
+            Self::Done => "done",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+impl std::str::FromStr for State {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "queued" => Ok(Self::Queued),
+            "running" => Ok(Self::Running),
+            "verifying" => Ok(Self::Verifying),
+            "done" => Ok(Self::Done),
+            "failed" => Ok(Self::Failed),
+            _ => Err(anyhow::anyhow!("Unknown job state {}", s)),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct JobStore {
+    client: redis::Client,
+    prefix: String,
+    ttl_secs: Option<u64>,
+}
+
+/// Fields of a job record
+pub type Record = std::collections::HashMap<String, String>;
+
+impl JobStore {
+    pub fn new(
+        config: &JobStoreConfig,
+        redis: &crate::config::RedisConfig,
+    ) -> Result<Self, anyhow::Error> {
+        Ok(Self {
+            client: redis::Client::open(redis.url.as_str())?,
+            prefix: config.prefix.clone(),
+            ttl_secs: config.ttl_days.map(|days| days * 24 * 60 * 60),
+        })
+    }
+
+    fn index_key(&self) -> String {
+        format!("{}index", self.prefix)
+    }
+
+    /// Hash of the average speed factor keyed by profile
+    fn speed_key(&self) -> String {
+        format!("{}speed", self.prefix)
+    }
+
+    /// Hash of "{timestamp}:{bytes}" keyed by directory, written by the digest
+    fn disk_key(&self) -> String {
+        format!("{}disk", self.prefix)
+    }
+
+    /// Store the available space of the directory and return the previously stored one
+    pub async fn swap_disk_usage(
+        &self,
+        dir: &std::path::Path,
+        available: u64,
+    ) -> Result<Option<(chrono::DateTime<chrono::Utc>, u64)>, anyhow::Error> {
+        let mut conn = self.client.get_async_connection().await?;
+        let field = dir.to_string_lossy();
+        let previous: Option<String> = redis::cmd("HGET")
+            .arg(self.disk_key())
+            .arg(field.as_ref())
+            .query_async(&mut conn)
+            .await?;
+        let _: i64 = redis::cmd("HSET")
+            .arg(self.disk_key())
+            .arg(field.as_ref())
+            .arg(format!("{}:{}", chrono::Utc::now().timestamp(), available))
+            .query_async(&mut conn)
+            .await?;
+        Ok(previous.and_then(|previous| {
+            use chrono::TimeZone as _;
+
+            let (at, bytes) = previous.split_once(':')?;
+            let at = chrono::Utc.timestamp_opt(at.parse().ok()?, 0).single()?;
+            Some((at, bytes.parse().ok()?))
+        }))
+    }
+
+    /// Encoded duration divided by the wall time, averaged over the recent jobs of the profile
+    pub async fn speed(&self, profile: &str) -> Result<Option<f64>, anyhow::Error> {
+        let mut conn = self.client.get_async_connection().await?;
+        let speed: Option<String> = redis::cmd("HGET")
+            .arg(self.speed_key())
+            .arg(profile)
+            .query_async(&mut conn)
+            .await?;
+        Ok(match speed {
+            Some(speed) => Some(speed.parse()?),
+            None => None,
+        })
+    }
+
+    /// Fold the speed factor of a finished job into the exponential moving average of the
+    /// profile
+    pub async fn record_speed(&self, profile: &str, speed: f64) -> Result<(), anyhow::Error> {
+        let average = match self.speed(profile).await? {
+            Some(average) => average * 0.7 + speed * 0.3,
+            None => speed,
+        };
+        let mut conn = self.client.get_async_connection().await?;
+        let _: i64 = redis::cmd("HSET")
+            .arg(self.speed_key())
+            .arg(profile)
+            .arg(average.to_string())
+            .query_async(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    /// Set the state with "{state}_at" timestamp, the worker id and the additional fields
+    pub async fn transition(
+        &self,
+        file: &str,
+        state: State,
+        fields: &[(&str, &str)],
+    ) -> Result<(), anyhow::Error> {
+        let now = chrono::Utc::now();
+        let key = format!("{}{}", self.prefix, file);
+        let mut conn = self.client.get_async_connection().await?;
+        let mut pipe = redis::pipe();
+        pipe.atomic()
+            .cmd("HSET")
+            .arg(&key)
+            .arg("state")
+            .arg(state.as_str())
+            .arg(format!("{}_at", state.as_str()))
+            .arg(now.to_rfc3339())
+            .arg("worker")
+            .arg(worker_id());
+        for (name, value) in fields {
+            pipe.arg(*name).arg(*value);
+        }
+        pipe.ignore()
+            .cmd("ZADD")
+            .arg(self.index_key())
+            .arg(now.timestamp())
+            .arg(file)
+            .ignore();
+        if let Some(ttl_secs) = self.ttl_secs {
+            pipe.cmd("EXPIRE").arg(&key).arg(ttl_secs).ignore();
+        }
+        let () = pipe.query_async(&mut conn).await?;
+        Ok(())
+    }
+
+    pub async fn get(&self, file: &str) -> Result<Option<Record>, anyhow::Error> {
+        let mut conn = self.client.get_async_connection().await?;
+        let record: Record = redis::cmd("HGETALL")
+            .arg(format!("{}{}", self.prefix, file))
+            .query_async(&mut conn)
+            .await?;
+        Ok(if record.is_empty() {
+            None
+        } else {
+            Some(record)
+        })
+    }
+
+    /// Recently updated jobs, the newest first. Expired records are skipped.
+    pub async fn recent(&self, limit: usize) -> Result<Vec<(String, Record)>, anyhow::Error> {
+        let mut conn = self.client.get_async_connection().await?;
+        let files: Vec<String> = redis::cmd("ZREVRANGE")
+            .arg(self.index_key())
+            .arg(0)
+            .arg(limit.saturating_sub(1))
+            .query_async(&mut conn)
+            .await?;
+        self.get_all(files).await
+    }
+
+    /// Jobs updated after the time, the newest first. Expired records are skipped.
+    pub async fn updated_since(
+        &self,
+        since: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<(String, Record)>, anyhow::Error> {
+        let mut conn = self.client.get_async_connection().await?;
+        let files: Vec<String> = redis::cmd("ZREVRANGEBYSCORE")
+            .arg(self.index_key())
+            .arg("+inf")
+            .arg(since.timestamp())
+            .query_async(&mut conn)
+            .await?;
+        self.get_all(files).await
+    }
+
+    async fn get_all(&self, files: Vec<String>) -> Result<Vec<(String, Record)>, anyhow::Error> {
+        let mut jobs = vec![];
+        for file in files {
+            if let Some(record) = self.get(&file).await? {
+                jobs.push((file, record));
+            }
+        }
+        Ok(jobs)
+    }
+}
+
+/// Time of the transition to the state
+pub fn transitioned_at(record: &Record, state: State) -> Option<chrono::DateTime<chrono::Utc>> {
+    record
+        .get(&format!("{}_at", state.as_str()))
+        .and_then(|at| chrono::DateTime::parse_from_rfc3339(at).ok())
+        .map(|at| at.with_timezone(&chrono::Utc))
+}
+
+/// "{hostname}:{pid}"
+pub fn worker_id() -> String {
+    let mut buf = [0u8; 256];
+    let hostname =
+        if unsafe { libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len()) } == 0 {
+            let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+            String::from_utf8_lossy(&buf[..len]).into_owned()
+        } else {
+            "unknown".to_owned()
+        };
+    format!("{}:{}", hostname, std::process::id())
+}
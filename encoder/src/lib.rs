@@ -1,112 +1,935 @@
-const EPS: i64 = 1000 * 1000; // 1 second
+pub mod admin;
+pub mod analysis;
+pub mod archive;
+pub mod audio;
+pub mod autoscaling;
+pub mod bandwidth;
+pub mod blank;
+pub mod captions;
+pub mod channels;
+pub mod checksum;
+pub mod cleanup;
+pub mod config;
+pub mod control;
+pub mod deinterlace;
+pub mod digest;
+pub mod disk;
+pub mod dual_mono;
+pub mod email;
+pub mod encryption;
+pub mod epgstore;
+pub mod exit;
+pub mod failure;
+pub mod hwaccel;
+pub mod janitor;
+pub mod jobs;
+pub mod lock;
+pub mod logging;
+pub mod loudnorm;
+pub mod metadata;
+pub mod metrics;
+pub mod mirakurun;
+pub mod monitor;
+pub mod naming;
+pub mod outcome;
+pub mod output;
+pub mod pipeline;
+pub mod plan;
+pub mod publish;
+pub mod quality;
+pub mod queue;
+pub mod record;
+pub mod recovery;
+pub mod reserve;
+pub mod resources;
+pub mod scan;
+pub mod seekable;
+pub mod sidecar;
+pub mod spot;
+pub mod streaming;
+pub mod throttle;
+pub mod transcode;
+pub mod transfer;
+pub mod trim;
+pub mod tuner;
+pub mod two_pass;
+pub mod upload;
+pub mod verify;
+pub mod webhook;
+pub mod workers;
 
-#[derive(serde::Deserialize)]
-pub struct Config {
-    pub encoder: EncoderConfig,
-    pub redis: RedisConfig,
-    pub sqs: SqsConfig,
+pub use config::{load_config, Config, ProfileConfig};
+
+/// Outcome of a successful encode
+#[derive(Debug, Default, serde::Serialize)]
+pub struct Report {
+    pub outputs: Vec<OutputReport>,
+    /// Encoded duration of the source in seconds
+    pub duration: f64,
+    /// Wall time of the whole job in seconds
+    pub elapsed: f64,
+    /// Seconds of elapsed spent waiting for the windows of upload and transfer
+    pub waited: f64,
+    /// None unless precheck is configured
+    pub precheck: Option<PrecheckReport>,
 }
 
-#[derive(serde::Deserialize)]
-pub struct EncoderConfig {
-    pub base_dir: String,
-    pub ffmpeg_args: Vec<String>,
+/// Result of the precheck of the source TS which passed or fell back
+#[derive(Debug, serde::Serialize)]
+pub struct PrecheckReport {
+    /// The threshold exceeded by the source, e.g. "12 drops"
+    pub violation: Option<String>,
+    /// Whether the fallback arguments were used because of the violation
+    pub fallback: bool,
 }
 
-#[derive(serde::Deserialize)]
-pub struct RedisConfig {
-    pub url: String,
+impl PrecheckReport {
+    /// "passed" or "fallback: {violation}"
+    pub fn summary(&self) -> String {
+        match self.violation {
+            Some(ref violation) => format!("fallback: {}", violation),
+            None => "passed".to_owned(),
+        }
+    }
 }
 
-#[derive(serde::Deserialize)]
-pub struct SqsConfig {
-    pub queue_url: String,
+#[derive(Debug, serde::Serialize)]
+pub struct OutputReport {
+    pub path: std::path::PathBuf,
+    pub scores: verify::Scores,
+    /// Hex-encoded SHA-256 of the output when checksum is configured
+    pub sha256: Option<String>,
+    /// In bytes. It is the size of the playlist for streaming outputs.
+    pub size: u64,
 }
 
-pub fn load_config() -> Result<Config, anyhow::Error> {
-    let body = std::fs::read("config.toml")?;
-    Ok(toml::from_slice(&body)?)
+pub async fn encode<P>(profile: &ProfileConfig, ts_path: P) -> Result<Report, anyhow::Error>
+where
+    P: AsRef<std::path::Path>,
+{
+    encode_with_state(profile, None, ts_path, None).await
 }
 
-pub async fn encode<P>(config: &Config, ts_path: P) -> Result<(), anyhow::Error>
+/// encode() with the overrides of the channel, which broadcasts the state when it moves on to
+/// verification
+pub async fn encode_with_state<P>(
+    profile: &ProfileConfig,
+    channel: Option<&channels::ChannelConfig>,
+    ts_path: P,
+    state: Option<&tokio::sync::watch::Sender<jobs::State>>,
+) -> Result<Report, anyhow::Error>
 where
     P: AsRef<std::path::Path>,
 {
-    let ts_path = ts_path.as_ref();
-    let mp4_path = ts_path.with_extension("mp4");
-    let ts_duration_micro = ffmpeg::format::input(&ts_path)?.duration();
-
-    let status = tokio::process::Command::new("ffmpeg")
-        .arg("-i")
-        .arg(&ts_path)
-        .args(&config.encoder.ffmpeg_args)
-        .arg(&mp4_path)
-        .status()
+    let started = std::time::Instant::now();
+    // ffmpeg runs in work_dir, so relative paths are resolved here
+    let source_path = &std::env::current_dir()?.join(ts_path.as_ref());
+    let ts_path = &filtered_path(profile, source_path);
+    // Removed on return, whether the encode succeeds or not
+    let job_dir = match profile.work_dir {
+        Some(ref dir) => {
+            std::fs::create_dir_all(dir)?;
+            tempfile::Builder::new().prefix("encode.").tempdir_in(dir)?
+        }
+        None => tempfile::Builder::new().prefix("encode.").tempdir()?,
+    };
+    let work_dir = job_dir.path();
+
+    let is_ts = is_transport_stream(source_path)?;
+    if !is_ts {
+        tracing::info!(
+            "{} is not MPEG-TS of 188-byte packets, so the integrity check, the filter and the analysis are skipped",
+            source_path.display()
+        );
+    }
+    let integrity = if is_ts && (profile.precheck.is_some() || profile.sidecar) {
+        let path = source_path.to_owned();
+        let report = blocking(move || {
+            let file = std::fs::File::open(path)?;
+            Ok(tsutils::integrity::check(std::io::BufReader::new(file))?)
+        })
+        .await?;
+        tracing::info!("{}: {:?}", source_path.display(), report);
+        Some(report)
+    } else {
+        None
+    };
+    let mut use_fallback = false;
+    let mut precheck_report = None;
+    if let (Some(precheck), Some(report)) = (&profile.precheck, &integrity) {
+        let violation = precheck.violation(report);
+        if let Some(ref violation) = violation {
+            match precheck.on_failure {
+                config::PrecheckAction::Refuse => {
+                    return Err(anyhow::anyhow!("Precheck failed: {}", violation));
+                }
+                config::PrecheckAction::Fallback => {
+                    if profile.fallback.is_none() {
+                        return Err(anyhow::anyhow!(
+                            "Precheck failed: {} (fallback is not configured)",
+                            violation
+                        ));
+                    }
+                    tracing::warn!("Precheck failed: {}, using fallback arguments", violation);
+                    use_fallback = true;
+                }
+            }
+        }
+        precheck_report = Some(PrecheckReport {
+            violation,
+            fallback: use_fallback,
+        });
+    }
+
+    if let (Some(filter), true) = (&profile.filter, is_ts) {
+        tracing::info!("Filter {} to {}", source_path.display(), ts_path.display());
+        filter_source(filter, source_path, ts_path).await?;
+    }
+    let mut ts_duration_micro = ffmpeg::format::input(&ts_path)?.duration();
+
+    let trim_config = channel
+        .and_then(|channel| channel.trim.as_ref())
+        .or(profile.trim.as_ref());
+    let dual_mono_config = channel
+        .and_then(|channel| channel.dual_mono.as_ref())
+        .or(profile.dual_mono.as_ref());
+    let source_info = if !is_ts {
+        None
+    } else if trim_config.is_some()
+        || profile.metadata.is_some()
+        || dual_mono_config.is_some()
+        || profile.naming.is_some()
+        || profile.sidecar
+        || profile
+            .upload
+            .as_ref()
+            .is_some_and(|upload| upload.needs_source_info())
+        || profile
+            .transfer
+            .as_ref()
+            .is_some_and(|transfer| transfer.needs_source_info())
+    {
+        let service_id = profile.filter.as_ref().and_then(|f| f.service_id);
+        let (path, epg) = (source_path.to_owned(), profile.epg.clone());
+        let info = blocking(move || {
+            let mut info = analysis::analyze(&path, service_id)?;
+            if let Some(ref epg) = epg {
+                epgstore::supplement(epg, &path, &mut info)?;
+            }
+            Ok(info)
+        })
         .await?;
-    if !status.success() {
-        return Err(anyhow::anyhow!("Encode failure!"));
+        Some(info)
+    } else {
+        None
+    };
+    let variables = match source_info {
+        Some(ref info)
+            if profile.naming.is_some()
+                || profile.upload.is_some()
+                || profile.transfer.is_some() =>
+        {
+            Some(naming::Variables::new(
+                source_path,
+                info,
+                naming::probe_height(ts_path)?,
+                &profile.services,
+            ))
+        }
+        _ => None,
+    };
+    let named = output_name(profile, channel, source_path, variables.as_ref());
+    let outputs = output::outputs(profile, source_path, named.as_deref());
+
+    let mut trim_args = vec![];
+    let mut trim_range = None;
+    if let (Some(trim), Some(info)) = (trim_config, &source_info) {
+        if let Some(range) = trim::find_range(trim, info) {
+            tracing::info!(
+                "Trim {} to {:.1}s-{:.1}s (event_id={})",
+                ts_path.display(),
+                range.start,
+                range.end,
+                range.event_id
+            );
+            trim_args.extend(vec![
+                "-ss".to_owned(),
+                format!("{:.3}", range.start),
+                "-to".to_owned(),
+                format!("{:.3}", range.end),
+            ]);
+            ts_duration_micro = ((range.end - range.start) * 1_000_000.0) as i64;
+            trim_range = Some(range);
+        }
+        if let Some(relay) = info.relay() {
+            tracing::info!(
+                "{}: the event continues on service_id={} (event_id={})",
+                ts_path.display(),
+                relay.service_id,
+                relay.event_id
+            );
+        }
     }
 
-    let mp4_duration_micro = ffmpeg::format::input(&ts_path)?.duration();
-    if (ts_duration_micro - mp4_duration_micro).abs() > EPS {
+    // Appended to ffmpeg_args of the profile
+    let mut output_args = vec![];
+    let mut video_filters = vec![];
+    // Video options are not given to ffmpeg when it writes only audio, e.g. for radio services
+    let has_video = !outputs.iter().all(|output| output.audio_only);
+    if let (Some(deinterlace), true) = (&profile.deinterlace, has_video) {
+        if let Some(field_order) = deinterlace.detect(work_dir, ts_path, &trim_args).await? {
+            video_filters.push(deinterlace.filter(field_order));
+        }
+    }
+    video_filters.extend(profile.video_filters.iter().cloned());
+    if profile.two_pass.is_some() && outputs.iter().any(|output| output.streaming.is_some()) {
         return Err(anyhow::anyhow!(
-            "Duration mismatch: TS {}, MP4 {} (microsecond)",
-            ts_duration_micro,
-            mp4_duration_micro
+            "two_pass cannot be used with streaming outputs"
+        ));
+    }
+    let mut audio_filters = vec![];
+    if let Some(ref loudnorm) = profile.loudnorm {
+        let measurement = loudnorm.measure(work_dir, ts_path, &trim_args).await?;
+        tracing::info!("{}: {:?}", ts_path.display(), measurement);
+        audio_filters.push(loudnorm.filter(&measurement));
+    }
+    let dual_mono = match (dual_mono_config, &source_info) {
+        (Some(config), Some(info)) => info
+            .main_event()
+            .and_then(|event| event.dual_mono.as_ref())
+            .map(|dual_mono| (config, dual_mono)),
+        _ => None,
+    };
+    if let Some((config, dual_mono)) = dual_mono {
+        if config.is_split()
+            && (outputs.len() > 1 || outputs.iter().any(|output| output.streaming.is_some()))
+        {
+            return Err(anyhow::anyhow!(
+                "dual_mono split mode cannot be used with multiple or streaming outputs"
+            ));
+        }
+        tracing::info!("{}: dual mono {:?}", ts_path.display(), dual_mono);
+        output_args.extend(config.output_args(dual_mono, &audio_filters));
+    } else if !audio_filters.is_empty() {
+        output_args.push("-filter:a".to_owned());
+        output_args.push(audio_filters.join(","));
+    }
+    let ffmpeg_args = |args: &[String]| match dual_mono {
+        Some((config, _)) => config.ffmpeg_args(args),
+        None => args.to_vec(),
+    };
+
+    // (description, input_args, ffmpeg_args, additional video filters) tried in order
+    let mut attempts = vec![];
+    if !use_fallback && profile.library.is_none() {
+        if let Some(ref hwaccel) = profile.hwaccel {
+            if let Some((name, args)) = hwaccel.select().await? {
+                attempts.push((
+                    format!("{:?}", name),
+                    &args.input_args,
+                    &args.ffmpeg_args,
+                    &args.video_filters[..],
+                ));
+            }
+        }
+        attempts.push((
+            "default arguments".to_owned(),
+            &profile.input_args,
+            &profile.ffmpeg_args,
+            &[],
+        ));
+    }
+    if let Some(ref fallback) = profile.fallback {
+        attempts.push((
+            "fallback arguments".to_owned(),
+            &fallback.input_args,
+            fallback
+                .ffmpeg_args
+                .as_ref()
+                .unwrap_or(&profile.ffmpeg_args),
+            &[],
         ));
     }
-    verify_audio_and_video(&mp4_path)?;
-
-    let ts_fname = ts_path.file_name().unwrap().to_str().unwrap();
-    let orig_fname = regex::Regex::new(r#"\A\d+_\d+"#)?
-        .find(ts_fname)
-        .expect("Unexpected filename")
-        .as_str();
-    let orig_path = ts_path
-        .parent()
-        .unwrap()
-        .join(orig_fname)
-        .with_extension("ts");
-
-    std::fs::remove_file(ts_path)?;
-    std::fs::remove_file(orig_path)?;
-    Ok(())
+
+    let mut succeeded = false;
+    let mut attempted = false;
+    // (method, input_args, output_args of each output) of the successful encode
+    let mut encoded_with = None;
+    if let (Some(library), false) = (&profile.library, use_fallback) {
+        if outputs.len() > 1
+            || outputs[0].streaming.is_some()
+            || outputs[0].audio_only
+            || dual_mono.is_some()
+        {
+            return Err(anyhow::anyhow!(
+                "library supports only a single video output without dual mono"
+            ));
+        }
+        outputs[0].prepare()?;
+        let job = transcode::Job {
+            ts_path: ts_path.to_owned(),
+            output_path: outputs[0].part_path(),
+            range: trim_range.as_ref().map(|range| (range.start, range.end)),
+            video_filters: video_filters.clone(),
+            audio_filters: audio_filters.clone(),
+        };
+        let name = ts_path.display().to_string();
+        let mut reported = 0;
+        let result = library
+            .run(job, move |progress| {
+                let percent = (progress.position / progress.duration * 100.0) as i64;
+                if percent >= reported + 10 {
+                    reported = percent - percent % 10;
+                    tracing::info!("{}: {}%", name, reported);
+                }
+            })
+            .await;
+        attempted = true;
+        match result {
+            Ok(()) => {
+                succeeded = true;
+                encoded_with = Some(("library".to_owned(), vec![], vec![vec![]]));
+            }
+            Err(e) => tracing::warn!("In-process encode failed: {}", e),
+        }
+    }
+    if succeeded {
+        attempts.clear();
+    }
+    let mut last_error = None;
+    for (i, (description, input_args, attempt_args, extra_video_filters)) in
+        attempts.into_iter().enumerate()
+    {
+        if attempted {
+            tracing::info!("Retrying with {}", description);
+            for output in &outputs {
+                output.remove()?;
+            }
+        }
+        attempted = true;
+        let filters = [&video_filters[..], extra_video_filters].concat();
+        let mut args = ffmpeg_args(attempt_args);
+        if has_video && !filters.is_empty() {
+            args.push("-filter:v".to_owned());
+            args.push(filters.join(","));
+        }
+        args.extend(output_args.iter().cloned());
+        for output in &outputs {
+            output.prepare()?;
+        }
+        let input_args = [&trim_args[..], &input_args[..]].concat();
+        let mut ffmpeg_outputs = outputs
+            .iter()
+            .map(|output| output.ffmpeg_output(&args))
+            .collect::<Vec<_>>();
+        let log_path = |suffix: &str| -> Result<_, anyhow::Error> {
+            match profile.ffmpeg_log_dir {
+                Some(ref dir) => {
+                    std::fs::create_dir_all(dir)?;
+                    Ok(Some(dir.join(format!(
+                        "{}.{}{}.log",
+                        source_path.file_stem().unwrap().to_string_lossy(),
+                        i + 1,
+                        suffix
+                    ))))
+                }
+                None => Ok(None),
+            }
+        };
+        // Kept until the second pass finishes
+        let mut _passlog = None;
+        if let Some(ref two_pass) = profile.two_pass {
+            match two_pass
+                .first_pass(
+                    work_dir,
+                    profile.resources.as_ref(),
+                    log_path(".pass1")?,
+                    ts_path,
+                    &input_args,
+                    &ffmpeg_outputs,
+                )
+                .await?
+            {
+                Ok(passlog) => {
+                    for (ffmpeg_args, _) in &mut ffmpeg_outputs {
+                        ffmpeg_args.extend(passlog.second_pass_args());
+                    }
+                    _passlog = Some(passlog);
+                }
+                Err(error) => {
+                    tracing::warn!("First pass with {} failed: {}", description, error);
+                    let retryable = error.failure.is_retryable();
+                    last_error = Some(error);
+                    if retryable {
+                        continue;
+                    } else {
+                        break;
+                    }
+                }
+            }
+        }
+        let log_path = log_path("")?;
+        let (status, stderr) = run_ffmpeg(
+            work_dir,
+            profile.resources.as_ref(),
+            log_path.as_deref(),
+            ts_path,
+            &input_args,
+            &ffmpeg_outputs,
+        )
+        .await?;
+        if status.success() {
+            succeeded = true;
+            encoded_with = Some((
+                description,
+                input_args,
+                ffmpeg_outputs.into_iter().map(|(args, _)| args).collect(),
+            ));
+            break;
+        }
+        let error = failure::FfmpegError::new(status, &stderr, log_path);
+        tracing::warn!("Encode with {} failed: {}", description, error);
+        let retryable = error.failure.is_retryable();
+        last_error = Some(error);
+        if !retryable {
+            break;
+        }
+    }
+    if !succeeded {
+        return Err(match last_error {
+            Some(error) => error.into(),
+            None => anyhow::anyhow!("Encode failure!"),
+        });
+    }
+    for output in &outputs {
+        output.commit()?;
+    }
+
+    let sources = profile.cleanup.sources(ts_path)?;
+    if let Some(state) = state {
+        let _ = state.broadcast(jobs::State::Verifying);
+    }
+
+    let (method, encoded_input_args, encoded_output_args) = encoded_with.unwrap();
+    let mut report = Report {
+        duration: ts_duration_micro as f64 / 1_000_000.0,
+        precheck: precheck_report,
+        ..Report::default()
+    };
+    let srt = match profile.captions {
+        Some(_) => {
+            let service_id = profile.filter.as_ref().and_then(|f| f.service_id);
+            let range = trim_range.as_ref().map(|range| (range.start, range.end));
+            let (source_path, ts_path) = (source_path.to_owned(), ts_path.to_owned());
+            blocking(move || captions::extract_srt(&source_path, &ts_path, service_id, range))
+                .await?
+        }
+        None => None,
+    };
+    for (output, output_args) in outputs.iter().zip(&encoded_output_args) {
+        if let (Some(metadata), Some(info), None) =
+            (&profile.metadata, &source_info, output.streaming)
+        {
+            let body = metadata::ffmetadata(metadata, info, trim_range.as_ref());
+            metadata::embed(work_dir, &output.path, &body).await?;
+        }
+        if let (Some(captions), Some(srt), None, false) =
+            (&profile.captions, &srt, output.streaming, output.audio_only)
+        {
+            captions.write(work_dir, &output.path, srt).await?;
+        }
+
+        let verify_config = profile.verify.clone().unwrap_or_default();
+        let (target_ts_path, output_path, target_work_dir) =
+            (ts_path.to_owned(), output.path.clone(), work_dir.to_owned());
+        let (audio_only, range, reference_filters) = (
+            output.audio_only,
+            trim_range.as_ref().map(|range| (range.start, range.end)),
+            video_filters.clone(),
+        );
+        let result = blocking(move || {
+            verify_config.verify(&verify::Target {
+                ts_path: &target_ts_path,
+                output_path: &output_path,
+                audio_only,
+                expected_duration: ts_duration_micro,
+                range,
+                reference_filters: &reference_filters,
+                work_dir: Some(&target_work_dir),
+            })
+        })
+        .await;
+        let scores = match result {
+            Ok(scores) => scores,
+            Err(e) => {
+                profile.cleanup.quarantine(&sources)?;
+                return Err(e);
+            }
+        };
+        let (path, streaming) = (output.path.clone(), output.streaming.is_some());
+        let sha256 = match profile.checksum {
+            Some(ref checksum) => {
+                let checksum = checksum.clone();
+                Some(blocking(move || checksum.record(&path, streaming)).await?)
+            }
+            None if profile.sidecar && !streaming => {
+                Some(blocking(move || checksum::sha256(&path)).await?)
+            }
+            None => None,
+        };
+        if profile.sidecar && output.streaming.is_none() {
+            sidecar::Sidecar {
+                source_path,
+                profile: profile.name.as_deref(),
+                method: &method,
+                input_args: &encoded_input_args,
+                output_args,
+                expected_duration: ts_duration_micro as f64 / 1_000_000.0,
+                durations: (&verify::StreamDurations::probe(&output.path)?).into(),
+                scores: &scores,
+                integrity: integrity.as_ref().map(Into::into),
+                precheck: report.precheck.as_ref(),
+                signal: tuner::SignalReport::read(source_path)?.map(|signal| signal.summary()),
+                encode_time: started.elapsed().as_secs_f64(),
+                sha256: sha256.as_deref(),
+                encryption_key_id: profile
+                    .encryption
+                    .as_ref()
+                    .map(|encryption| encryption.key_id.as_str()),
+                relay: source_info.as_ref().and_then(sidecar::Relay::new),
+            }
+            .write(&output.path)?;
+        }
+        report.outputs.push(OutputReport {
+            path: output.path.clone(),
+            scores,
+            sha256,
+            size: std::fs::metadata(&output.path)?.len(),
+        });
+    }
+
+    // Encrypted copies are sent instead of the outputs and removed afterwards
+    let encrypted = match profile.encryption {
+        Some(ref encryption) if profile.upload.is_some() || profile.transfer.is_some() => Some(
+            encryption
+                .encrypt_files(&output::files(&outputs, profile.sidecar)?)
+                .await?,
+        ),
+        _ => None,
+    };
+    let files = |outputs: &[output::Output]| match encrypted {
+        Some(ref files) => Ok(files.clone()),
+        None => output::files(outputs, profile.sidecar),
+    };
+
+    if let Some(ref upload) = profile.upload {
+        let prefix = match variables {
+            Some(ref variables) => variables.expand(&upload.prefix),
+            None => upload.prefix.clone(),
+        };
+        report.waited += upload
+            .upload(&prefix, &files(&outputs)?)
+            .await?
+            .as_secs_f64();
+    }
+
+    // After the upload since the local outputs may be removed
+    if let Some(ref transfer) = profile.transfer {
+        let dir = match variables {
+            Some(ref variables) => variables.expand(&transfer.dir),
+            None => transfer.dir.clone(),
+        };
+        report.waited += transfer
+            .transfer(&dir, &files(&outputs)?)
+            .await?
+            .as_secs_f64();
+    }
+
+    if encrypted.is_some() {
+        let remove_outputs = profile
+            .upload
+            .as_ref()
+            .is_some_and(|upload| upload.delete_outputs)
+            || profile
+                .transfer
+                .as_ref()
+                .is_some_and(|transfer| transfer.remove_local);
+        for (path, _) in output::files(&outputs, profile.sidecar)? {
+            let mut enc_path = path.as_os_str().to_owned();
+            enc_path.push(".enc");
+            let mut paths = vec![std::path::PathBuf::from(enc_path)];
+            if remove_outputs {
+                paths.push(path);
+            }
+            for path in paths {
+                match std::fs::remove_file(&path) {
+                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    report.elapsed = started.elapsed().as_secs_f64();
+    profile.cleanup.dispose(&sources).await?;
+    Ok(report)
+}
+
+/// Whether the outputs of the source already exist and pass the ffmpeg verification and the
+/// recorded checksum, e.g. when the message is delivered again after the encode. Outputs left by
+/// an interrupted encode fail the verification and are removed so that the job is redone. When
+/// the source is gone, only the streams are checked and nothing is removed.
+/// Profiles with naming or trim are not checked since their outputs and expected duration depend
+/// on the analysis of the source, nor are sources routed to output_dir.
+pub fn is_encoded(
+    profile: &ProfileConfig,
+    channel: Option<&channels::ChannelConfig>,
+    source_path: &std::path::Path,
+) -> Result<bool, anyhow::Error> {
+    use verify::Verifier as _;
+
+    if profile.naming.is_some()
+        || profile.trim.is_some()
+        || channel.is_some_and(|channel| channel.trim.is_some())
+        || output_dir(profile, channel, source_path).is_some()
+    {
+        return Ok(false);
+    }
+    let outputs = output::outputs(profile, source_path, None);
+    if !outputs.iter().any(|output| output.path.exists()) {
+        return Ok(false);
+    }
+    let default_verify = verify::VerifyConfig::default();
+    let verifier = verify::FfmpegVerifier {
+        tolerance: (profile
+            .verify
+            .as_ref()
+            .unwrap_or(&default_verify)
+            .duration_tolerance
+            * 1_000_000.0) as i64,
+    };
+    let source_exists = source_path.exists();
+    let expected_duration = if source_exists {
+        ffmpeg::format::input(&source_path)?.duration()
+    } else {
+        0
+    };
+    let verify = |output: &output::Output| -> Result<(), anyhow::Error> {
+        if !output.path.exists() {
+            return Err(anyhow::anyhow!("missing"));
+        }
+        if source_exists {
+            verifier.verify(
+                &verify::Target {
+                    ts_path: source_path,
+                    output_path: &output.path,
+                    audio_only: output.audio_only,
+                    expected_duration,
+                    range: None,
+                    reference_filters: &[],
+                    work_dir: None,
+                },
+                &mut verify::Scores::new(),
+            )?;
+        } else {
+            verify::check_streams(
+                &verify::StreamDurations::probe(&output.path)?,
+                output.audio_only,
+                verifier.tolerance,
+            )?;
+        }
+        if let Some(ref checksum) = profile.checksum {
+            checksum.verify(output)?;
+        }
+        Ok(())
+    };
+    for output in &outputs {
+        if let Err(e) = verify(output) {
+            tracing::info!("{} is not verified: {}", output.path.display(), e);
+            if !source_exists {
+                return Ok(false);
+            }
+            for output in &outputs {
+                tracing::info!("Remove stale {}", output.path.display());
+                output.remove()?;
+            }
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Directory of the outputs given by output_dir of the channel or of the base_dir of the source
+fn output_dir<'a>(
+    profile: &'a ProfileConfig,
+    channel: Option<&'a channels::ChannelConfig>,
+    source_path: &std::path::Path,
+) -> Option<&'a std::path::Path> {
+    channel
+        .and_then(|channel| channel.output_dir.as_deref())
+        .or_else(|| {
+            profile
+                .base_dirs
+                .iter()
+                .find(|base_dir| source_path.starts_with(&base_dir.path))
+                .and_then(|base_dir| base_dir.output_dir.as_deref())
+        })
+}
+
+/// Path of the outputs without extension relative to the source directory. It is rendered with
+/// naming of the profile and placed in output_dir of the channel or the base_dir.
+pub(crate) fn output_name(
+    profile: &ProfileConfig,
+    channel: Option<&channels::ChannelConfig>,
+    source_path: &std::path::Path,
+    variables: Option<&naming::Variables>,
+) -> Option<std::path::PathBuf> {
+    let named = match (&profile.naming, variables) {
+        (Some(naming), Some(variables)) => Some(naming.render(variables)),
+        _ => None,
+    };
+    match (output_dir(profile, channel, source_path), named) {
+        (Some(dir), Some(named)) => Some(dir.join(named)),
+        (Some(dir), None) => {
+            Some(dir.join(filtered_path(profile, source_path).file_stem().unwrap()))
+        }
+        (None, named) => named,
+    }
+}
+
+/// Whether tsutils can read the source. "*.ts" is assumed to be MPEG-TS of 188-byte packets and
+/// "*.m2ts" and "*.mts" to be BDAV of 192-byte packets, while the container of the other sources
+/// is probed by their sync bytes. Missing sources are assumed by their extension.
+pub fn is_transport_stream(source_path: &std::path::Path) -> Result<bool, anyhow::Error> {
+    use std::io::Read as _;
+
+    match source_path.extension().and_then(|ext| ext.to_str()) {
+        Some("ts") => return Ok(true),
+        Some("m2ts") | Some("mts") => return Ok(false),
+        _ => {}
+    }
+    let mut file = match std::fs::File::open(source_path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e.into()),
+    };
+    let mut buf = [0; 188 * 3];
+    let mut len = 0;
+    while len < buf.len() {
+        let n = file.read(&mut buf[len..])?;
+        if n == 0 {
+            return Ok(false);
+        }
+        len += n;
+    }
+    Ok(buf.iter().step_by(188).all(|&b| b == 0x47))
 }
 
-fn verify_audio_and_video<P>(mp4_path: P) -> Result<(), anyhow::Error>
+/// Path of the TS passed to ffmpeg. It differs from the source path when filter is configured
+/// and the source is MPEG-TS.
+pub fn filtered_path(profile: &ProfileConfig, source_path: &std::path::Path) -> std::path::PathBuf {
+    match profile.filter {
+        Some(ref filter) if is_transport_stream(source_path).unwrap_or(false) => {
+            let stem = source_path.file_stem().unwrap().to_str().unwrap();
+            source_path.with_file_name(format!("{}{}.ts", stem, filter.suffix))
+        }
+        _ => source_path.to_owned(),
+    }
+}
+
+/// Run synchronous work reading whole files, e.g. the source or the outputs, on a blocking thread
+/// so that the other tasks of the worker such as heartbeats keep running
+async fn blocking<F, T>(f: F) -> Result<T, anyhow::Error>
 where
-    P: AsRef<std::path::Path>,
+    F: FnOnce() -> Result<T, anyhow::Error> + Send + 'static,
+    T: Send + 'static,
 {
-    let audio_path = tempfile::NamedTempFile::new()?.into_temp_path();
-    let status = std::process::Command::new("ffmpeg")
-        .args(&["-y", "-i"])
-        .arg(mp4_path.as_ref())
-        .args(&["-vn", "-acodec", "copy", "-f", "mp4"])
-        .arg(&audio_path)
-        .status()?;
-    if !status.success() {
-        return Err(anyhow::anyhow!("ffmpeg -vn failed"));
-    }
-
-    let video_path = tempfile::NamedTempFile::new()?.into_temp_path();
-    let status = std::process::Command::new("ffmpeg")
-        .args(&["-y", "-i"])
-        .arg(mp4_path.as_ref())
-        .args(&["-an", "-vcodec", "copy", "-f", "mp4"])
-        .arg(&video_path)
-        .status()?;
-    if !status.success() {
-        return Err(anyhow::anyhow!("ffmpeg -an failed"));
-    }
-
-    let audio_duration_micro = ffmpeg::format::input(&audio_path)?.duration();
-    let video_duration_micro = ffmpeg::format::input(&video_path)?.duration();
-    if (audio_duration_micro - video_duration_micro).abs() > EPS {
-        return Err(anyhow::anyhow!(
-            "Duration mismatch! audio:{} video:{} (microsecond)",
-            audio_duration_micro,
-            video_duration_micro
-        ));
+    tokio::task::spawn_blocking(f).await?
+}
+
+/// Filter the source TS into ts_path on a blocking thread.  The filter stops reading the source
+/// when the future is dropped, e.g. when the job is cancelled.
+async fn filter_source(
+    filter: &config::FilterConfig,
+    source_path: &std::path::Path,
+    ts_path: &std::path::Path,
+) -> Result<(), anyhow::Error> {
+    struct CancelOnDrop(tsutils::progress::CancellationToken);
+    impl Drop for CancelOnDrop {
+        fn drop(&mut self) {
+            self.0.cancel();
+        }
+    }
+
+    let token = tsutils::progress::CancellationToken::new();
+    let _guard = CancelOnDrop(token.clone());
+    let file = std::fs::File::open(source_path)?;
+    let total = file.metadata()?.len();
+    let source_path = source_path.to_owned();
+    let name = source_path.display().to_string();
+    let reader = tsutils::progress::Watched::new(std::io::BufReader::new(file))
+        .total(total)
+        .on_progress(move |progress| {
+            tracing::debug!("Filter {}: {:.1}%", name, progress.percent().unwrap_or(0.0));
+        })
+        .cancellation(token);
+    let writer = std::io::BufWriter::new(std::fs::File::create(ts_path)?);
+    let (service_id, si, scrambled) = (filter.service_id, filter.si, filter.scrambled);
+    tokio::task::spawn_blocking(move || -> Result<(), anyhow::Error> {
+        if scrambled == config::ScrambledPackets::Keep {
+            tsutils::filter::keep_av_with_policy(reader, writer, service_id, si.into())?;
+            return Ok(());
+        }
+        let mut writer = tsutils::filter::DropScrambled::new(
+            writer,
+            scrambled == config::ScrambledPackets::DropDescrambled,
+        );
+        tsutils::filter::keep_av_with_policy(reader, &mut writer, service_id, si.into())?;
+        let (mut writer, report) = writer.into_inner();
+        std::io::Write::flush(&mut writer)?;
+        for (pid, dropped) in &report.dropped {
+            tracing::warn!(
+                "{}: dropped {} scrambled packets of PID 0x{:04x}",
+                source_path.display(),
+                dropped,
+                pid
+            );
+        }
+        Ok(())
+    })
+    .await?
+}
+
+pub fn mp4_path(profile: &ProfileConfig, source_path: &std::path::Path) -> std::path::PathBuf {
+    filtered_path(profile, source_path).with_extension(profile.container.extension())
+}
+
+/// Encode ts_path into the outputs, each of which is a pair of ffmpeg_args and the path. Return
+/// the exit status with the tail of stderr.
+async fn run_ffmpeg(
+    work_dir: &std::path::Path,
+    resources: Option<&resources::ResourceConfig>,
+    log_path: Option<&std::path::Path>,
+    ts_path: &std::path::Path,
+    input_args: &[String],
+    outputs: &[(Vec<String>, std::path::PathBuf)],
+) -> Result<(std::process::ExitStatus, String), anyhow::Error> {
+    let mut command = tokio::process::Command::new("ffmpeg");
+    // Killed when the job is cancelled
+    command.kill_on_drop(true).current_dir(work_dir);
+    if let Some(resources) = resources {
+        resources.apply(&mut command)?;
+    }
+    command.args(input_args).arg("-i").arg(ts_path);
+    for (ffmpeg_args, path) in outputs {
+        command.args(ffmpeg_args).arg(path);
     }
-    Ok(())
+    let mut child = command.stderr(std::process::Stdio::piped()).spawn()?;
+    let stderr = child.stderr.take().unwrap();
+    let (status, stderr) = futures::future::try_join(
+        async { Ok(child.await?) },
+        failure::capture(stderr, log_path),
+    )
+    .await?;
+    Ok((status, stderr))
 }
@@ -0,0 +1,157 @@
+/// Lock each file in Redis with SET NX so that duplicate deliveries of a message are not encoded
+/// concurrently. The lock expires unless it is refreshed, so a crashed worker doesn't hold it.
+/// With [workers], a lock held by a worker without heartbeats is taken over immediately.
+#[derive(serde::Deserialize)]
+pub struct LockConfig {
+    #[serde(default = "default_prefix")]
+    pub prefix: String,
+    /// Seconds until the lock expires. It is refreshed every third of it while encoding.
+    #[serde(default = "default_ttl")]
+    pub ttl: u64,
+}
+
+fn default_prefix() -> String {
+    "encoder:lock:".to_owned()
+}
+
+fn default_ttl() -> u64 {
+    300
+}
+
+/// The lock survives two failed refreshes, each of which has a few seconds to reach Redis
+const MIN_TTL: u64 = 30;
+
+pub struct Lock {
+    client: redis::Client,
+    key: String,
+    token: String,
+    ttl: u64,
+}
+
+const REFRESH_SCRIPT: &str = r#"
+if redis.call("GET", KEYS[1]) == ARGV[1] then
+  return redis.call("EXPIRE", KEYS[1], ARGV[2])
+else
+  return 0
+end
+"#;
+
+const TAKE_OVER_SCRIPT: &str = r#"
+if redis.call("GET", KEYS[1]) == ARGV[1] then
+  return redis.call("SET", KEYS[1], ARGV[2], "EX", ARGV[3])
+else
+  return false
+end
+"#;
+
+const RELEASE_SCRIPT: &str = r#"
+if redis.call("GET", KEYS[1]) == ARGV[1] then
+  return redis.call("DEL", KEYS[1])
+else
+  return 0
+end
+"#;
+
+impl LockConfig {
+    pub fn validate(&self) -> Result<(), anyhow::Error> {
+        if self.ttl < MIN_TTL {
+            return Err(anyhow::anyhow!(
+                "[lock] ttl must be at least {} seconds but got {}",
+                MIN_TTL,
+                self.ttl
+            ));
+        }
+        Ok(())
+    }
+
+    /// None when another live worker holds the lock
+    pub async fn acquire(
+        &self,
+        redis: &crate::config::RedisConfig,
+        file: &str,
+        registry: Option<&crate::workers::Registry>,
+    ) -> Result<Option<Lock>, anyhow::Error> {
+        let client = redis::Client::open(redis.url.as_str())?;
+        let mut conn = client.get_async_connection().await?;
+        let key = format!("{}{}", self.prefix, file);
+        // "{worker id}:{nanoseconds}"
+        let token = format!(
+            "{}:{}",
+            crate::jobs::worker_id(),
+            chrono::Utc::now().timestamp_nanos()
+        );
+        let acquired: Option<String> = redis::cmd("SET")
+            .arg(&key)
+            .arg(&token)
+            .arg("NX")
+            .arg("EX")
+            .arg(self.ttl)
+            .query_async(&mut conn)
+            .await?;
+        let lock = Lock {
+            client: client.clone(),
+            key: key.clone(),
+            token: token.clone(),
+            ttl: self.ttl,
+        };
+        if acquired.is_some() {
+            return Ok(Some(lock));
+        }
+
+        let holder: Option<String> = redis::cmd("GET").arg(&key).query_async(&mut conn).await?;
+        let holder = match holder {
+            Some(holder) => holder,
+            // Released in the meantime. The message is received again.
+            None => return Ok(None),
+        };
+        let worker = holder
+            .rsplit_once(':')
+            .map_or(holder.as_str(), |(worker, _)| worker);
+        if let Some(registry) = registry {
+            if !registry.is_alive(worker).await? {
+                let taken: Option<String> = redis::Script::new(TAKE_OVER_SCRIPT)
+                    .key(&key)
+                    .arg(&holder)
+                    .arg(&token)
+                    .arg(self.ttl)
+                    .invoke_async(&mut conn)
+                    .await?;
+                if taken.is_some() {
+                    tracing::warn!("Took over {} from the dead worker {}", file, worker);
+                    return Ok(Some(lock));
+                }
+            }
+        }
+        tracing::info!("{} is locked by {}", file, worker);
+        Ok(None)
+    }
+}
+
+impl Lock {
+    /// Seconds between refreshes, independent of the heartbeats of the message
+    pub fn refresh_interval(&self) -> u64 {
+        self.ttl / 3
+    }
+
+    /// Extend the expiration. false when the lock has expired and may be taken by another worker.
+    pub async fn refresh(&self) -> Result<bool, anyhow::Error> {
+        let mut conn = self.client.get_async_connection().await?;
+        let refreshed: i64 = redis::Script::new(REFRESH_SCRIPT)
+            .key(&self.key)
+            .arg(&self.token)
+            .arg(self.ttl)
+            .invoke_async(&mut conn)
+            .await?;
+        Ok(refreshed == 1)
+    }
+
+    pub async fn release(self) -> Result<(), anyhow::Error> {
+        let mut conn = self.client.get_async_connection().await?;
+        let _: i64 = redis::Script::new(RELEASE_SCRIPT)
+            .key(&self.key)
+            .arg(&self.token)
+            .invoke_async(&mut conn)
+            .await?;
+        Ok(())
+    }
+}
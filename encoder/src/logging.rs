@@ -0,0 +1,45 @@
+#[derive(serde::Deserialize)]
+pub struct LogConfig {
+    /// Directives of tracing_subscriber::EnvFilter, e.g. "info" or "encoder=debug". RUST_LOG
+    /// takes precedence.
+    #[serde(default = "default_level")]
+    pub level: String,
+    #[serde(default)]
+    pub format: LogFormat,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            level: default_level(),
+            format: LogFormat::default(),
+        }
+    }
+}
+
+fn default_level() -> String {
+    "info".to_owned()
+}
+
+#[derive(Clone, Copy, Default, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    /// One JSON object per line including the fields of the spans
+    Json,
+}
+
+/// Install the global subscriber
+pub fn init(config: &LogConfig) -> Result<(), anyhow::Error> {
+    let filter = match std::env::var("RUST_LOG") {
+        Ok(directives) => tracing_subscriber::EnvFilter::new(directives),
+        Err(_) => tracing_subscriber::EnvFilter::new(&config.level),
+    };
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+    match config.format {
+        LogFormat::Text => builder.try_init(),
+        LogFormat::Json => builder.json().try_init(),
+    }
+    .map_err(|e| anyhow::anyhow!("Failed to initialize logging: {}", e))
+}
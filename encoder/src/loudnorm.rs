@@ -0,0 +1,91 @@
+/// Two-pass EBU R128 loudness normalization with ffmpeg's loudnorm filter. Defaults follow
+/// ARIB TR-B32 (-24 LKFS, -1 dBTP).
+#[derive(serde::Deserialize)]
+pub struct LoudnormConfig {
+    /// Integrated loudness target in LUFS
+    #[serde(default = "default_integrated")]
+    pub integrated: f64,
+    /// Maximum true peak in dBTP
+    #[serde(default = "default_true_peak")]
+    pub true_peak: f64,
+    /// Loudness range target in LU
+    #[serde(default = "default_loudness_range")]
+    pub loudness_range: f64,
+}
+
+fn default_integrated() -> f64 {
+    -24.0
+}
+
+fn default_true_peak() -> f64 {
+    -1.0
+}
+
+fn default_loudness_range() -> f64 {
+    7.0
+}
+
+/// Values printed by the first pass of loudnorm
+#[derive(Debug, serde::Deserialize)]
+pub struct Measurement {
+    pub input_i: String,
+    pub input_tp: String,
+    pub input_lra: String,
+    pub input_thresh: String,
+    pub target_offset: String,
+}
+
+impl LoudnormConfig {
+    fn target(&self) -> String {
+        format!(
+            "loudnorm=I={}:TP={}:LRA={}",
+            self.integrated, self.true_peak, self.loudness_range
+        )
+    }
+
+    /// Run the first pass over the audio of the input
+    pub async fn measure(
+        &self,
+        work_dir: &std::path::Path,
+        ts_path: &std::path::Path,
+        input_args: &[String],
+    ) -> Result<Measurement, anyhow::Error> {
+        let output = tokio::process::Command::new("ffmpeg")
+            .current_dir(work_dir)
+            .arg("-nostats")
+            .args(input_args)
+            .arg("-i")
+            .arg(ts_path)
+            .args(["-vn", "-sn", "-dn", "-af"])
+            .arg(format!("{}:print_format=json", self.target()))
+            .args(["-f", "null", "-"])
+            .output()
+            .await?;
+        if !output.status.success() {
+            return Err(anyhow::anyhow!("ffmpeg loudnorm measurement failed"));
+        }
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let json = match (stderr.rfind('{'), stderr.rfind('}')) {
+            (Some(start), Some(end)) if start < end => &stderr[start..=end],
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "loudnorm measurement is missing in ffmpeg output"
+                ))
+            }
+        };
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Filter of the second pass
+    pub fn filter(&self, measurement: &Measurement) -> String {
+        format!(
+            "{}:measured_I={}:measured_TP={}:measured_LRA={}:measured_thresh={}:offset={}:linear=true",
+            self.target(),
+            measurement.input_i,
+            measurement.input_tp,
+            measurement.input_lra,
+            measurement.input_thresh,
+            measurement.target_offset
+        )
+    }
+}
@@ -0,0 +1,143 @@
+// Chapter boundaries closer than this are merged
+const MIN_CHAPTER_LENGTH: f64 = 5.0;
+
+/// Embed broadcast metadata from SDT/EIT into the output
+#[derive(serde::Deserialize)]
+pub struct MetadataConfig {
+    /// Add chapters at event boundaries and PMT updates
+    #[serde(default = "default_chapters")]
+    pub chapters: bool,
+}
+
+fn default_chapters() -> bool {
+    true
+}
+
+/// Build FFMETADATA for the output. range is the trimmed range of the source.
+pub fn ffmetadata(
+    config: &MetadataConfig,
+    info: &crate::analysis::SourceInfo,
+    range: Option<&crate::trim::TrimRange>,
+) -> String {
+    use chrono::TimeZone as _;
+
+    let mut body = ";FFMETADATA1\n".to_owned();
+    if let Some(event) = info.main_event() {
+        if let Some(ref name) = event.name {
+            push_tag(&mut body, "title", name);
+        }
+        let date = chrono::FixedOffset::east_opt(9 * 60 * 60)
+            .unwrap()
+            .timestamp_opt(event.start_time, 0)
+            .unwrap();
+        push_tag(&mut body, "date", &date.format("%Y-%m-%d").to_string());
+        if let Some(ref text) = event.text {
+            push_tag(&mut body, "description", text);
+            push_tag(&mut body, "synopsis", text);
+        }
+        if let Some(genre) = event.genre {
+            push_tag(&mut body, "genre", genre);
+        }
+    }
+    if let Some(ref service_name) = info.service_name {
+        push_tag(&mut body, "network", service_name);
+    }
+
+    if config.chapters {
+        let (start, end) = match range {
+            Some(range) => (range.start, range.end),
+            None => (0.0, info.duration),
+        };
+        let mut points: Vec<f64> = info
+            .events
+            .iter()
+            .filter_map(|event| info.position_of(event.start_time))
+            .chain(info.pmt_changes.iter().cloned())
+            .filter(|&p| start < p && p < end)
+            .collect();
+        points.push(start);
+        points.push(end);
+        points.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        points.dedup_by(|b, a| *b - *a < MIN_CHAPTER_LENGTH);
+        if let Some(last) = points.last_mut() {
+            *last = end;
+        }
+
+        for (i, window) in points.windows(2).enumerate() {
+            body.push_str("\n[CHAPTER]\nTIMEBASE=1/1000\n");
+            body.push_str(&format!(
+                "START={}\n",
+                ((window[0] - start) * 1000.0) as i64
+            ));
+            body.push_str(&format!("END={}\n", ((window[1] - start) * 1000.0) as i64));
+            let title = match info.event_at(window[0]).and_then(|e| e.name.as_ref()) {
+                Some(name) => format!("{} {}", name, i + 1),
+                None => format!("Chapter {}", i + 1),
+            };
+            push_tag(&mut body, "title", &title);
+        }
+    }
+    body
+}
+
+fn push_tag(body: &mut String, key: &str, value: &str) {
+    body.push_str(key);
+    body.push('=');
+    for c in value.chars() {
+        if c == '=' || c == ';' || c == '#' || c == '\\' || c == '\n' {
+            body.push('\\');
+        }
+        body.push(c);
+    }
+    body.push('\n');
+}
+
+/// Remux the output with the metadata without re-encoding.
+pub async fn embed<P>(
+    work_dir: &std::path::Path,
+    output_path: P,
+    metadata: &str,
+) -> Result<(), anyhow::Error>
+where
+    P: AsRef<std::path::Path>,
+{
+    use std::io::Write as _;
+
+    let output_path = output_path.as_ref();
+    let mut metadata_file = tempfile::NamedTempFile::new_in(work_dir)?;
+    metadata_file.write_all(metadata.as_bytes())?;
+    let metadata_path = metadata_file.into_temp_path();
+    let extension = output_path
+        .extension()
+        .map(|extension| extension.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let tmp_path = output_path.with_extension(format!("meta.{}", extension));
+
+    let status = tokio::process::Command::new("ffmpeg")
+        .current_dir(work_dir)
+        .args(["-y", "-i"])
+        .arg(output_path)
+        .args(["-f", "ffmetadata", "-i"])
+        .arg(&metadata_path)
+        .args([
+            "-map",
+            "0",
+            "-map_metadata",
+            "1",
+            "-map_chapters",
+            "1",
+            "-c",
+            "copy",
+        ])
+        .arg(&tmp_path)
+        .status()
+        .await?;
+    if !status.success() {
+        if tmp_path.exists() {
+            std::fs::remove_file(&tmp_path)?;
+        }
+        return Err(anyhow::anyhow!("ffmpeg failed to embed metadata"));
+    }
+    std::fs::rename(&tmp_path, output_path)?;
+    Ok(())
+}
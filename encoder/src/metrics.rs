@@ -0,0 +1,128 @@
+#[derive(serde::Deserialize)]
+pub struct MetricsConfig {
+    /// Address serving /metrics in the Prometheus text format, e.g. "0.0.0.0:9100"
+    pub listen: std::net::SocketAddr,
+}
+
+/// Metrics of the sqs-encode worker
+pub struct Metrics {
+    registry: prometheus::Registry,
+    pub jobs_received: prometheus::IntCounter,
+    pub jobs_succeeded: prometheus::IntCounter,
+    pub jobs_failed: prometheus::IntCounter,
+    pub in_flight_jobs: prometheus::IntGauge,
+    /// In seconds
+    pub encode_duration: prometheus::Histogram,
+    /// Seconds from sending the message to receiving it
+    pub queue_wait: prometheus::Histogram,
+    /// Duration of the encoded source divided by the wall time
+    pub speed: prometheus::Histogram,
+    pub bytes_in: prometheus::IntCounter,
+    pub bytes_out: prometheus::IntCounter,
+    pub disk_free: prometheus::IntGauge,
+    /// Updated with [autoscaling]
+    pub queue_depth: prometheus::IntGauge,
+    pub backlog_hours: prometheus::Gauge,
+}
+
+impl Metrics {
+    pub fn new() -> Result<Self, prometheus::Error> {
+        let registry = prometheus::Registry::new_custom(Some("encoder".to_owned()), None)?;
+        let counter = |name: &str, help: &str| -> Result<_, prometheus::Error> {
+            let counter = prometheus::IntCounter::new(name, help)?;
+            registry.register(Box::new(counter.clone()))?;
+            Ok(counter)
+        };
+        let gauge = |name: &str, help: &str| -> Result<_, prometheus::Error> {
+            let gauge = prometheus::IntGauge::new(name, help)?;
+            registry.register(Box::new(gauge.clone()))?;
+            Ok(gauge)
+        };
+        let float_gauge = |name: &str, help: &str| -> Result<_, prometheus::Error> {
+            let gauge = prometheus::Gauge::new(name, help)?;
+            registry.register(Box::new(gauge.clone()))?;
+            Ok(gauge)
+        };
+        let histogram =
+            |name: &str, help: &str, buckets: Vec<f64>| -> Result<_, prometheus::Error> {
+                let histogram = prometheus::Histogram::with_opts(
+                    prometheus::HistogramOpts::new(name, help).buckets(buckets),
+                )?;
+                registry.register(Box::new(histogram.clone()))?;
+                Ok(histogram)
+            };
+        let duration_buckets = prometheus::exponential_buckets(60.0, 2.0, 10)?;
+        Ok(Self {
+            jobs_received: counter("jobs_received_total", "Received SQS messages")?,
+            jobs_succeeded: counter("jobs_succeeded_total", "Succeeded encodes")?,
+            jobs_failed: counter("jobs_failed_total", "Failed encodes")?,
+            in_flight_jobs: gauge("in_flight_jobs", "Encodes in progress")?,
+            encode_duration: histogram(
+                "encode_duration_seconds",
+                "Wall time of encodes",
+                duration_buckets.clone(),
+            )?,
+            queue_wait: histogram(
+                "queue_wait_seconds",
+                "Time from enqueue to receipt",
+                duration_buckets,
+            )?,
+            speed: histogram(
+                "speed_factor",
+                "Encoded duration divided by the wall time",
+                prometheus::exponential_buckets(0.25, 2.0, 8)?,
+            )?,
+            bytes_in: counter("bytes_in_total", "Bytes of encoded source TS")?,
+            bytes_out: counter("bytes_out_total", "Bytes of written outputs")?,
+            disk_free: gauge("disk_free_bytes", "Available space of base_dir")?,
+            queue_depth: gauge("queue_depth", "Visible messages in all queues")?,
+            backlog_hours: float_gauge(
+                "backlog_hours",
+                "Wall hours for a worker to encode the visible messages",
+            )?,
+            registry,
+        })
+    }
+
+    /// Metrics in the Prometheus text format
+    pub fn render(&self) -> Result<String, prometheus::Error> {
+        use prometheus::Encoder as _;
+
+        let mut buf = vec![];
+        prometheus::TextEncoder::new().encode(&self.registry.gather(), &mut buf)?;
+        Ok(String::from_utf8_lossy(&buf).into_owned())
+    }
+
+    /// Serve /metrics until the process exits
+    pub async fn serve(
+        self: std::sync::Arc<Self>,
+        addr: std::net::SocketAddr,
+    ) -> Result<(), anyhow::Error> {
+        let make_service = hyper::service::make_service_fn(move |_| {
+            let metrics = self.clone();
+            async move {
+                Ok::<_, std::convert::Infallible>(hyper::service::service_fn(move |req| {
+                    let metrics = metrics.clone();
+                    async move {
+                        if req.uri().path() == "/metrics" {
+                            match metrics.render() {
+                                Ok(body) => hyper::Response::builder()
+                                    .header("content-type", "text/plain; version=0.0.4")
+                                    .body(hyper::Body::from(body)),
+                                Err(e) => hyper::Response::builder()
+                                    .status(500)
+                                    .body(hyper::Body::from(e.to_string())),
+                            }
+                        } else {
+                            hyper::Response::builder()
+                                .status(404)
+                                .body(hyper::Body::empty())
+                        }
+                    }
+                }))
+            }
+        });
+        hyper::Server::bind(&addr).serve(make_service).await?;
+        Ok(())
+    }
+}
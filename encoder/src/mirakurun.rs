@@ -0,0 +1,290 @@
+/// Pull sources from a Mirakurun tuner server instead of files recorded into base_dir
+#[derive(serde::Deserialize)]
+pub struct MirakurunConfig {
+    #[serde(default = "default_url")]
+    pub url: String,
+    /// X-Mirakurun-Priority of streams. Negative priorities yield tuners to other clients.
+    #[serde(default)]
+    pub priority: i32,
+    /// Let Mirakurun descramble the stream
+    #[serde(default = "default_decode")]
+    pub decode: bool,
+}
+
+fn default_url() -> String {
+    "http://localhost:40772".to_owned()
+}
+
+fn default_decode() -> bool {
+    true
+}
+
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Service {
+    /// ID in Mirakurun, which is network_id * 100000 + service_id
+    pub id: u64,
+    pub service_id: u16,
+    pub network_id: u16,
+    pub name: String,
+}
+
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Program {
+    pub id: u64,
+    pub event_id: u16,
+    pub service_id: u16,
+    pub network_id: u16,
+    /// Unix time in milliseconds
+    pub start_at: i64,
+    /// In milliseconds
+    pub duration: i64,
+    pub name: Option<String>,
+    pub description: Option<String>,
+}
+
+impl Program {
+    pub fn start(&self) -> chrono::DateTime<chrono::Utc> {
+        use chrono::TimeZone as _;
+
+        chrono::Utc.timestamp_millis_opt(self.start_at).unwrap()
+    }
+
+    pub fn end(&self) -> chrono::DateTime<chrono::Utc> {
+        self.start() + chrono::Duration::milliseconds(self.duration)
+    }
+}
+
+/// Job message asking to record the service before encoding instead of a file name, e.g.
+/// {"service_id": 1024, "start": "2020-10-01T21:00:00+09:00", "end": "2020-10-01T21:54:00+09:00"}
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+pub struct SourceRequest {
+    pub service_id: u16,
+    /// RFC 3339
+    pub start: String,
+    /// RFC 3339
+    pub end: String,
+    /// File stem in base_dir. Defaults to "{start in %Y%m%d%H%M}_{service_id}".
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+impl SourceRequest {
+    /// None when the message body is a file name
+    pub fn parse(body: &str) -> Result<Option<Self>, anyhow::Error> {
+        if body.trim_start().starts_with('{') {
+            Ok(Some(serde_json::from_str(body)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn range(
+        &self,
+    ) -> Result<(chrono::DateTime<chrono::Utc>, chrono::DateTime<chrono::Utc>), anyhow::Error> {
+        let start = chrono::DateTime::parse_from_rfc3339(&self.start)?;
+        let end = chrono::DateTime::parse_from_rfc3339(&self.end)?;
+        if end <= start {
+            return Err(anyhow::anyhow!("{} is not after {}", self.end, self.start));
+        }
+        Ok((
+            start.with_timezone(&chrono::Utc),
+            end.with_timezone(&chrono::Utc),
+        ))
+    }
+
+    pub fn file_name(&self) -> Result<String, anyhow::Error> {
+        Ok(match self.name {
+            Some(ref name) => name.clone(),
+            None => {
+                let start = chrono::DateTime::parse_from_rfc3339(&self.start)?;
+                format!("{}_{}", start.format("%Y%m%d%H%M"), self.service_id)
+            }
+        })
+    }
+}
+
+pub struct Client {
+    url: String,
+    priority: i32,
+    decode: bool,
+    http: hyper::Client<hyper::client::HttpConnector>,
+}
+
+impl Client {
+    pub fn new(config: &MirakurunConfig) -> Self {
+        Self {
+            url: config.url.trim_end_matches('/').to_owned(),
+            priority: config.priority,
+            decode: config.decode,
+            http: hyper::Client::new(),
+        }
+    }
+
+    async fn get<T>(&self, path: &str) -> Result<T, anyhow::Error>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let resp = self
+            .http
+            .get(format!("{}{}", self.url, path).parse()?)
+            .await?;
+        if !resp.status().is_success() {
+            return Err(anyhow::anyhow!(
+                "Mirakurun GET {} failed with {}",
+                path,
+                resp.status()
+            ));
+        }
+        let body = hyper::body::to_bytes(resp.into_body()).await?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    pub async fn services(&self) -> Result<Vec<Service>, anyhow::Error> {
+        self.get("/api/services").await
+    }
+
+    /// The service with the service_id. The first one is returned when several networks have it.
+    pub async fn service(&self, service_id: u16) -> Result<Service, anyhow::Error> {
+        self.services()
+            .await?
+            .into_iter()
+            .find(|service| service.service_id == service_id)
+            .ok_or_else(|| anyhow::anyhow!("Service {} is not found in Mirakurun", service_id))
+    }
+
+    pub async fn programs(&self, service: &Service) -> Result<Vec<Program>, anyhow::Error> {
+        self.get(&format!(
+            "/api/programs?networkId={}&serviceId={}",
+            service.network_id, service.service_id
+        ))
+        .await
+    }
+
+    /// The program of the event on the service
+    pub async fn program(
+        &self,
+        service: &Service,
+        event_id: u16,
+    ) -> Result<Program, anyhow::Error> {
+        self.programs(service)
+            .await?
+            .into_iter()
+            .find(|program| program.event_id == event_id)
+            .ok_or_else(|| anyhow::anyhow!("Event {} is not found on {}", event_id, service.name))
+    }
+
+    /// Write the stream of the service into ts_path until the time
+    pub async fn stream(
+        &self,
+        service: &Service,
+        ts_path: &std::path::Path,
+        until: chrono::DateTime<chrono::Utc>,
+    ) -> Result<u64, anyhow::Error> {
+        self.stream_to(
+            &format!("/api/services/{}/stream", service.id),
+            &service.name,
+            ts_path,
+            until,
+        )
+        .await
+    }
+
+    /// Write the whole TS of the channel like "GR/27" into ts_path until the time
+    pub async fn stream_channel(
+        &self,
+        channel: &str,
+        ts_path: &std::path::Path,
+        until: chrono::DateTime<chrono::Utc>,
+    ) -> Result<u64, anyhow::Error> {
+        self.stream_to(
+            &format!("/api/channels/{}/stream", channel),
+            channel,
+            ts_path,
+            until,
+        )
+        .await
+    }
+
+    async fn stream_to(
+        &self,
+        path: &str,
+        label: &str,
+        ts_path: &std::path::Path,
+        until: chrono::DateTime<chrono::Utc>,
+    ) -> Result<u64, anyhow::Error> {
+        use futures::StreamExt as _;
+        use std::io::Write as _;
+
+        let resp = self
+            .http
+            .request(
+                hyper::Request::get(format!(
+                    "{}{}?decode={}",
+                    self.url,
+                    path,
+                    if self.decode { 1 } else { 0 }
+                ))
+                .header("X-Mirakurun-Priority", self.priority.to_string())
+                .body(hyper::Body::empty())?,
+            )
+            .await?;
+        if !resp.status().is_success() {
+            return Err(anyhow::anyhow!(
+                "Mirakurun stream of {} failed with {}",
+                label,
+                resp.status()
+            ));
+        }
+        let mut body = resp.into_body();
+        let mut file = std::io::BufWriter::new(std::fs::File::create(ts_path)?);
+        let mut written = 0;
+        let remaining = (until - chrono::Utc::now()).to_std().unwrap_or_default();
+        let deadline = tokio::time::delay_for(remaining);
+        futures::pin_mut!(deadline);
+        loop {
+            match futures::future::select(body.next(), &mut deadline).await {
+                futures::future::Either::Left((Some(chunk), _)) => {
+                    let chunk = chunk?;
+                    file.write_all(&chunk)?;
+                    written += chunk.len() as u64;
+                }
+                futures::future::Either::Left((None, _)) => {
+                    return Err(anyhow::anyhow!(
+                        "Mirakurun closed the stream of {} before {}",
+                        label,
+                        until
+                    ));
+                }
+                futures::future::Either::Right(_) => break,
+            }
+        }
+        file.flush()?;
+        Ok(written)
+    }
+
+    /// Wait until the start of the request and record it into ts_path. The stream is written to
+    /// "{ts_path}.part" and renamed after the end.
+    pub async fn record(
+        &self,
+        request: &SourceRequest,
+        ts_path: &std::path::Path,
+    ) -> Result<(), anyhow::Error> {
+        let (start, end) = request.range()?;
+        if end <= chrono::Utc::now() {
+            return Err(anyhow::anyhow!("{} is already over", request.end));
+        }
+        let service = self.service(request.service_id).await?;
+        if let Ok(wait) = (start - chrono::Utc::now()).to_std() {
+            tracing::info!("Wait {}s for {} on {}", wait.as_secs(), start, service.name);
+            tokio::time::delay_for(wait).await;
+        }
+        let part_path = ts_path.with_extension("ts.part");
+        tracing::info!("Record {} into {}", service.name, part_path.display());
+        let written = self.stream(&service, &part_path, end).await?;
+        tracing::info!("Recorded {} bytes of {}", written, service.name);
+        std::fs::rename(&part_path, ts_path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,183 @@
+/// Watch recordings being written and alert while there is still time to restart the tuner
+#[derive(serde::Deserialize)]
+pub struct MonitorConfig {
+    /// Directory of "*.ts" and "*.ts.part" being recorded. Defaults to all of base_dir.
+    pub dir: Option<std::path::PathBuf>,
+    /// Seconds between polls
+    #[serde(default = "default_interval")]
+    pub interval: u64,
+    /// Alert when PCR doesn't advance for this many seconds
+    #[serde(default = "default_stall_secs")]
+    pub stall_secs: u64,
+    /// Alert when this many drops are found in a poll
+    #[serde(default = "default_max_drops")]
+    pub max_drops: u64,
+    /// Alert when the ratio of scrambled packets in a poll reaches this
+    #[serde(default = "default_max_scrambled_ratio")]
+    pub max_scrambled_ratio: f64,
+    /// Files not modified for this many seconds are finished
+    #[serde(default = "default_idle_secs")]
+    pub idle_secs: u64,
+}
+
+fn default_interval() -> u64 {
+    10
+}
+
+fn default_stall_secs() -> u64 {
+    30
+}
+
+fn default_max_drops() -> u64 {
+    100
+}
+
+fn default_max_scrambled_ratio() -> f64 {
+    0.9
+}
+
+fn default_idle_secs() -> u64 {
+    120
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Problem {
+    PcrStall,
+    Drops,
+    Scrambled,
+}
+
+#[derive(Debug)]
+pub struct Alert {
+    pub path: std::path::PathBuf,
+    pub message: String,
+}
+
+struct Watched {
+    offset: u64,
+    monitor: tsutils::integrity::Monitor,
+    previous: tsutils::integrity::IntegrityReport,
+    pcr_advanced_at: std::time::Instant,
+    /// Problems alerted and not recovered yet
+    alerted: std::collections::HashSet<Problem>,
+}
+
+/// Recordings being watched
+pub struct Watcher<'a> {
+    config: &'a MonitorConfig,
+    files: std::collections::HashMap<std::path::PathBuf, Watched>,
+}
+
+impl<'a> Watcher<'a> {
+    pub fn new(config: &'a MonitorConfig) -> Self {
+        Self {
+            config,
+            files: std::collections::HashMap::new(),
+        }
+    }
+
+    /// Read packets written since the last poll and return problems which began in this poll.
+    /// Files are watched from their end when they are found.
+    pub fn poll(&mut self, dirs: &[std::path::PathBuf]) -> Result<Vec<Alert>, anyhow::Error> {
+        use std::io::{Read as _, Seek as _};
+
+        let idle = std::time::Duration::from_secs(self.config.idle_secs);
+        let mut recording = vec![];
+        for entry in dirs
+            .iter()
+            .map(std::fs::read_dir)
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .flatten()
+        {
+            let entry = entry?;
+            let fname = entry.file_name().to_string_lossy().into_owned();
+            if !(fname.ends_with(".ts") || fname.ends_with(".ts.part")) {
+                continue;
+            }
+            let metadata = entry.metadata()?;
+            if metadata
+                .modified()?
+                .elapsed()
+                .is_ok_and(|elapsed| elapsed < idle)
+            {
+                recording.push((entry.path(), metadata.len()));
+            }
+        }
+        self.files
+            .retain(|path, _| recording.iter().any(|(p, _)| p == path));
+
+        let mut alerts = vec![];
+        for (path, len) in recording {
+            let watched = match self.files.entry(path.clone()) {
+                std::collections::hash_map::Entry::Occupied(entry) => entry.into_mut(),
+                std::collections::hash_map::Entry::Vacant(entry) => {
+                    tracing::info!("Watch {}", path.display());
+                    entry.insert(Watched {
+                        offset: len - len % 188,
+                        monitor: tsutils::integrity::Monitor::new(),
+                        previous: Default::default(),
+                        pcr_advanced_at: std::time::Instant::now(),
+                        alerted: Default::default(),
+                    });
+                    continue;
+                }
+            };
+            let end = len - len % 188;
+            if end > watched.offset {
+                let mut file = std::fs::File::open(&path)?;
+                file.seek(std::io::SeekFrom::Start(watched.offset))?;
+                let mut buf = vec![0; (end - watched.offset) as usize];
+                file.read_exact(&mut buf)?;
+                for packet in buf.chunks(188) {
+                    watched.monitor.push(packet);
+                }
+                watched.offset = end;
+            }
+
+            let report = &watched.monitor.report;
+            let previous = &watched.previous;
+            if report.pcr_duration > previous.pcr_duration {
+                watched.pcr_advanced_at = std::time::Instant::now();
+            }
+            let packets = report.packets - previous.packets;
+            let drops = report.drops - previous.drops;
+            let scrambled = report.scrambled_packets - previous.scrambled_packets;
+            let problems = [
+                (
+                    Problem::PcrStall,
+                    watched.pcr_advanced_at.elapsed().as_secs() >= self.config.stall_secs,
+                    format!(
+                        "PCR has not advanced for {}s",
+                        watched.pcr_advanced_at.elapsed().as_secs()
+                    ),
+                ),
+                (
+                    Problem::Drops,
+                    drops >= self.config.max_drops,
+                    format!("{} drops in {} packets", drops, packets),
+                ),
+                (
+                    Problem::Scrambled,
+                    packets > 0
+                        && scrambled as f64 / packets as f64 >= self.config.max_scrambled_ratio,
+                    format!("{} of {} packets are scrambled", scrambled, packets),
+                ),
+            ];
+            for (problem, found, message) in problems.iter() {
+                if *found {
+                    if watched.alerted.insert(*problem) {
+                        alerts.push(Alert {
+                            path: path.clone(),
+                            message: message.clone(),
+                        });
+                    }
+                } else if watched.alerted.remove(problem) {
+                    tracing::info!("{}: recovered from {:?}", path.display(), problem);
+                }
+            }
+            watched.previous = report.clone();
+        }
+        Ok(alerts)
+    }
+}
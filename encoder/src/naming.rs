@@ -0,0 +1,127 @@
+/// Name outputs after broadcast metadata instead of the source TS
+#[derive(serde::Deserialize)]
+pub struct NamingConfig {
+    /// Path of the output relative to the directory of the source TS, e.g.
+    /// "{date}/{channel}/{title}_{resolution}.mp4". The extension is replaced with the one of
+    /// each output and the suffix of the output is appended to the file stem.
+    ///
+    /// Available variables are {date}, {time}, {channel}, {channel_number}, {title}, {event_id},
+    /// {resolution} and {stem} (the file stem of the source TS). {channel_number} and names
+    /// missing from SDT are looked up in the service map. Unknown values expand to "unknown".
+    pub template: String,
+}
+
+const UNKNOWN: &str = "unknown";
+
+/// Values of the template variables
+pub struct Variables(Vec<(&'static str, Option<String>)>);
+
+impl Variables {
+    pub fn new(
+        source_path: &std::path::Path,
+        info: &crate::analysis::SourceInfo,
+        height: Option<u32>,
+        services: &crate::scan::ServiceMap,
+    ) -> Self {
+        use chrono::TimeZone as _;
+
+        let scanned = info
+            .service_id
+            .and_then(|service_id| services.find(service_id));
+        let event = info.main_event();
+        let start_time = event.map(|event| {
+            chrono::FixedOffset::east_opt(9 * 60 * 60)
+                .unwrap()
+                .timestamp_opt(event.start_time, 0)
+                .unwrap()
+        });
+        let stem = source_path.file_stem().unwrap().to_string_lossy();
+        Self(vec![
+            (
+                "date",
+                start_time.map(|time| time.format("%Y-%m-%d").to_string()),
+            ),
+            (
+                "time",
+                start_time.map(|time| time.format("%H%M").to_string()),
+            ),
+            (
+                "channel",
+                info.service_name
+                    .clone()
+                    .or_else(|| scanned.and_then(|service| service.name.clone())),
+            ),
+            (
+                "channel_number",
+                scanned.map(|service| format!("{:03}", service.channel_number)),
+            ),
+            ("title", event.and_then(|event| event.name.clone())),
+            ("event_id", event.map(|event| event.event_id.to_string())),
+            ("resolution", height.map(|height| format!("{}p", height))),
+            ("stem", Some(stem.into_owned())),
+        ])
+    }
+
+    /// Expand variables in s with sanitized values
+    pub fn expand(&self, s: &str) -> String {
+        let mut expanded = s.to_owned();
+        for (name, value) in &self.0 {
+            let value = value.as_deref().map(sanitize);
+            expanded = expanded.replace(
+                &format!("{{{}}}", name),
+                value
+                    .as_deref()
+                    .filter(|v| !v.is_empty())
+                    .unwrap_or(UNKNOWN),
+            );
+        }
+        expanded
+    }
+}
+
+impl NamingConfig {
+    /// Render the template into a path without extension relative to the source directory
+    pub fn render(&self, variables: &Variables) -> std::path::PathBuf {
+        let template = std::path::Path::new(&self.template).with_extension("");
+        template
+            .components()
+            .filter_map(|component| match component {
+                std::path::Component::Normal(component) => {
+                    Some(variables.expand(&component.to_string_lossy()))
+                }
+                // Keep the outputs under the source directory
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Make a broadcast string usable as a path component
+fn sanitize(s: &str) -> String {
+    let s: String = s
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let s = s.trim().trim_start_matches('.');
+    // Most filesystems limit a file name to 255 bytes
+    let mut end = s.len().min(200);
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    s[..end].to_owned()
+}
+
+/// Height of the best video stream of the TS
+pub fn probe_height(ts_path: &std::path::Path) -> Result<Option<u32>, anyhow::Error> {
+    let ictx = ffmpeg::format::input(&ts_path)?;
+    let stream = match ictx.streams().best(ffmpeg::media::Type::Video) {
+        Some(stream) => stream,
+        None => return Ok(None),
+    };
+    let height = stream.codec().decoder().video()?.height();
+    Ok(if height == 0 { None } else { Some(height) })
+}
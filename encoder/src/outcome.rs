@@ -0,0 +1,81 @@
+/// Outcome of a job of sqs-encode sent to webhooks and result queues. The monitor binary sends
+/// alerts of recordings in the same shape.
+#[derive(serde::Serialize)]
+pub struct JobOutcome<'a> {
+    pub status: Status,
+    pub message_id: &'a str,
+    /// Path of the source TS
+    pub file: &'a std::path::Path,
+    /// None for the profile in [encoder] section
+    pub profile: Option<&'a str>,
+    pub report: Option<&'a crate::Report>,
+    pub error: Option<String>,
+    /// Estimated finish time of a started job in RFC 3339
+    pub eta: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    /// An encode started, sent with its ETA
+    Started,
+    Succeeded,
+    Failed,
+    /// A recording is going bad
+    Alert,
+    /// Summary of the recent jobs sent by the digest binary
+    Digest,
+}
+
+impl Status {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Started => "started",
+            Self::Succeeded => "succeeded",
+            Self::Failed => "failed",
+            Self::Alert => "alert",
+            Self::Digest => "digest",
+        }
+    }
+}
+
+impl<'a> JobOutcome<'a> {
+    pub fn new(
+        message_id: &'a str,
+        file: &'a std::path::Path,
+        profile: &'a crate::ProfileConfig,
+        result: &'a Result<crate::Report, anyhow::Error>,
+    ) -> Self {
+        let (status, report, error) = match result {
+            Ok(report) => (Status::Succeeded, Some(report), None),
+            Err(e) => (Status::Failed, None, Some(format!("{:#}", e))),
+        };
+        Self {
+            status,
+            message_id,
+            file,
+            profile: profile.name.as_deref(),
+            report,
+            error,
+            eta: None,
+        }
+    }
+
+    /// Outcome sent when an encode starts
+    pub fn started(
+        message_id: &'a str,
+        file: &'a std::path::Path,
+        profile: &'a crate::ProfileConfig,
+        eta: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> Self {
+        Self {
+            status: Status::Started,
+            message_id,
+            file,
+            profile: profile.name.as_deref(),
+            report: None,
+            error: None,
+            eta: eta.map(|eta| eta.to_rfc3339()),
+        }
+    }
+}
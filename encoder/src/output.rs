@@ -0,0 +1,194 @@
+/// An output file written by the same ffmpeg invocation as the other outputs of the profile
+#[derive(serde::Deserialize)]
+pub struct OutputConfig {
+    /// The output is written to "{source stem}{suffix}.{extension}"
+    #[serde(default)]
+    pub suffix: String,
+    /// Defaults to the one of the container of the profile
+    pub extension: Option<String>,
+    /// Appended to ffmpeg_args of the profile
+    #[serde(default)]
+    pub ffmpeg_args: Vec<String>,
+    /// Skip the video verification. Put -vn in ffmpeg_args too.
+    #[serde(default)]
+    pub audio_only: bool,
+    /// Write an HLS or DASH ladder instead. extension is ignored.
+    pub streaming: Option<crate::streaming::StreamingConfig>,
+}
+
+/// Container of the outputs of a profile. Matroska keeps every audio track, ASS subtitles and
+/// chapters as they are.
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Container {
+    #[default]
+    Mp4,
+    Mkv,
+}
+
+impl Container {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Container::Mp4 => "mp4",
+            Container::Mkv => "mkv",
+        }
+    }
+}
+
+/// An output resolved against the source path
+pub struct Output<'a> {
+    /// The file verified after encoding. It is the playlist for streaming outputs.
+    pub path: std::path::PathBuf,
+    pub ffmpeg_args: std::borrow::Cow<'a, [String]>,
+    pub audio_only: bool,
+    pub streaming: Option<&'a crate::streaming::StreamingConfig>,
+}
+
+impl<'a> Output<'a> {
+    /// Output arguments and the path given to ffmpeg
+    pub fn ffmpeg_output(&self, common_args: &[String]) -> (Vec<String>, std::path::PathBuf) {
+        let mut args = [common_args, &self.ffmpeg_args[..]].concat();
+        match self.streaming {
+            Some(streaming) => {
+                let (streaming_args, path) = streaming.output_args(self.path.parent().unwrap());
+                args.extend(streaming_args);
+                (args, path)
+            }
+            None => (args, self.part_path()),
+        }
+    }
+
+    /// Written by ffmpeg and renamed to path after the encode succeeds, so that a file at path is
+    /// never a partial one. The extension is kept for ffmpeg to choose the muxer.
+    pub fn part_path(&self) -> std::path::PathBuf {
+        match self.path.extension() {
+            Some(extension) => self
+                .path
+                .with_extension(format!("part.{}", extension.to_string_lossy())),
+            None => self.path.with_extension("part"),
+        }
+    }
+
+    /// Move the written part to path
+    pub fn commit(&self) -> Result<(), std::io::Error> {
+        if self.streaming.is_none() {
+            std::fs::rename(self.part_path(), &self.path)?;
+        }
+        Ok(())
+    }
+
+    /// Create the directory of the output
+    pub fn prepare(&self) -> Result<(), std::io::Error> {
+        std::fs::create_dir_all(self.path.parent().unwrap())
+    }
+
+    /// Remove the partial output
+    pub fn remove(&self) -> Result<(), std::io::Error> {
+        if self.streaming.is_some() {
+            let dir = self.path.parent().unwrap();
+            if dir.exists() {
+                std::fs::remove_dir_all(dir)?;
+            }
+        } else {
+            for path in [self.part_path(), self.path.clone()] {
+                if path.exists() {
+                    std::fs::remove_file(path)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Outputs of the profile. When no outputs are declared, a single file in the container of the
+/// profile, or an audio file for audio profiles, is written with ffmpeg_args of the profile. named is the path rendered with the naming template of the profile.
+pub fn outputs<'a>(
+    profile: &'a crate::ProfileConfig,
+    source_path: &std::path::Path,
+    named: Option<&std::path::Path>,
+) -> Vec<Output<'a>> {
+    let ts_path = crate::filtered_path(profile, source_path);
+    let (dir, stem) = match named {
+        Some(named) => {
+            let path = source_path.parent().unwrap().join(named);
+            let stem = path.file_name().unwrap().to_string_lossy().into_owned();
+            (path.parent().unwrap().to_owned(), stem)
+        }
+        None => (
+            ts_path.parent().unwrap().to_owned(),
+            ts_path.file_stem().unwrap().to_string_lossy().into_owned(),
+        ),
+    };
+    if profile.outputs.is_empty() {
+        vec![match profile.audio {
+            Some(ref audio) => Output {
+                path: dir.join(format!("{}.{}", stem, audio.codec.extension())),
+                ffmpeg_args: std::borrow::Cow::Owned(audio.ffmpeg_args()),
+                audio_only: true,
+                streaming: None,
+            },
+            None => Output {
+                path: dir.join(format!("{}.{}", stem, profile.container.extension())),
+                ffmpeg_args: std::borrow::Cow::Borrowed(&[]),
+                audio_only: false,
+                streaming: None,
+            },
+        }]
+    } else {
+        profile
+            .outputs
+            .iter()
+            .map(|output| Output {
+                path: match output.streaming {
+                    Some(ref streaming) => {
+                        streaming.playlist_path(&dir.join(format!("{}{}", stem, output.suffix)))
+                    }
+                    None => dir.join(format!(
+                        "{}{}.{}",
+                        stem,
+                        output.suffix,
+                        output
+                            .extension
+                            .as_deref()
+                            .unwrap_or_else(|| profile.container.extension())
+                    )),
+                },
+                ffmpeg_args: std::borrow::Cow::Borrowed(&output.ffmpeg_args),
+                audio_only: output.audio_only,
+                streaming: output.streaming.as_ref(),
+            })
+            .collect()
+    }
+}
+
+/// Files written for the outputs paired with their names relative to the output directory.
+/// Files of streaming outputs are named "{directory}/{file}".
+pub fn files(
+    outputs: &[Output],
+    sidecar: bool,
+) -> Result<Vec<(std::path::PathBuf, String)>, std::io::Error> {
+    let mut files = vec![];
+    for output in outputs {
+        let dir = output.path.parent().unwrap();
+        if output.streaming.is_some() {
+            let dir_name = dir.file_name().unwrap().to_string_lossy();
+            for entry in std::fs::read_dir(dir)? {
+                let entry = entry?;
+                if entry.file_type()?.is_file() {
+                    let name = format!("{}/{}", dir_name, entry.file_name().to_string_lossy());
+                    files.push((entry.path(), name));
+                }
+            }
+        } else {
+            let mut paths = vec![output.path.clone()];
+            if sidecar {
+                paths.push(crate::sidecar::Sidecar::path(&output.path));
+            }
+            for path in paths {
+                let name = path.file_name().unwrap().to_string_lossy().into_owned();
+                files.push((path, name));
+            }
+        }
+    }
+    Ok(files)
+}
@@ -0,0 +1,253 @@
+/// Stages run by the recutils-pipeline binary
+#[derive(serde::Deserialize)]
+pub struct PipelineConfig {
+    #[serde(default = "default_stages")]
+    pub stages: Vec<Stage>,
+    /// Attempts of each stage before the pipeline fails. Failures caused by the arguments or the
+    /// input, e.g. the exit status 65 of repair_command for a malformed TS, are not retried.
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+    /// Seconds waited before retrying a stage
+    #[serde(default = "default_retry_interval")]
+    pub retry_interval: u64,
+    /// Command of the repair stage writing {output} from {input}, e.g.
+    /// ["clean-ts", "{input}", "{output}"]. The source TS is replaced with the output.
+    #[serde(default)]
+    pub repair_command: Vec<String>,
+}
+
+fn default_stages() -> Vec<Stage> {
+    vec![Stage::Record, Stage::Encode, Stage::Notify]
+}
+
+fn default_max_attempts() -> u32 {
+    3
+}
+
+fn default_retry_interval() -> u64 {
+    60
+}
+
+/// Filtering, trimming and uploading are done in the encode stage as configured in the profile
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, serde::Deserialize, serde::Serialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Stage {
+    /// Record the requested service unless the source TS exists
+    Record,
+    /// Run repair_command on the source TS
+    Repair,
+    Encode,
+    /// Send the outcome to the webhooks, emails and the publish destinations. The failure of the other
+    /// stages is also sent when this stage is configured.
+    Notify,
+}
+
+/// Progress of a pipeline persisted in "{stem}.pipeline.json" next to the source TS so that an interrupted
+/// pipeline resumes from the unfinished stage
+#[derive(Debug, Default, serde::Deserialize, serde::Serialize)]
+pub struct PipelineState {
+    pub request: Option<crate::mirakurun::SourceRequest>,
+    pub profile: Option<String>,
+    #[serde(default)]
+    pub completed: Vec<Stage>,
+    /// Failed attempts of each stage
+    #[serde(default)]
+    pub attempts: std::collections::BTreeMap<Stage, u32>,
+    pub error: Option<String>,
+}
+
+impl PipelineState {
+    pub fn path(ts_path: &std::path::Path) -> std::path::PathBuf {
+        ts_path.with_extension("pipeline.json")
+    }
+
+    pub fn load(path: &std::path::Path) -> Result<Option<Self>, anyhow::Error> {
+        match std::fs::read(path) {
+            Ok(body) => Ok(Some(serde_json::from_slice(&body)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn save(&self, path: &std::path::Path) -> Result<(), anyhow::Error> {
+        let tmp_path = path.with_extension("json.tmp");
+        std::fs::write(&tmp_path, serde_json::to_vec_pretty(self)?)?;
+        std::fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+}
+
+/// Run the stages which are not completed in the state, saving the state after each attempt
+pub async fn run(
+    config: &crate::Config,
+    pipeline: &PipelineConfig,
+    ts_path: &std::path::Path,
+    state: &mut PipelineState,
+    state_path: &std::path::Path,
+) -> Result<(), anyhow::Error> {
+    let mut report = None;
+    for &stage in &pipeline.stages {
+        if state.completed.contains(&stage) {
+            continue;
+        }
+        loop {
+            tracing::info!("{}: {:?}", ts_path.display(), stage);
+            let result = match stage {
+                Stage::Record => record(config, state, ts_path).await,
+                Stage::Repair => repair(pipeline, ts_path).await,
+                Stage::Encode => match config.resolve(state.profile.as_deref(), ts_path) {
+                    Ok((profile, channel)) => {
+                        crate::encode_with_state(profile, channel, ts_path, None)
+                            .await
+                            .map(|r| report = Some(r))
+                    }
+                    Err(e) => Err(e),
+                },
+                Stage::Notify => {
+                    notify(config, state, ts_path, &Ok(report.take())).await;
+                    Ok(())
+                }
+            };
+            match result {
+                Ok(()) => {
+                    state.completed.push(stage);
+                    state.error = None;
+                    state.save(state_path)?;
+                    break;
+                }
+                Err(e) => {
+                    let attempts = state.attempts.entry(stage).or_insert(0);
+                    *attempts += 1;
+                    let attempts = *attempts;
+                    state.error = Some(format!("{:#}", e));
+                    state.save(state_path)?;
+                    if attempts >= pipeline.max_attempts
+                        || !crate::exit::is_retryable(crate::exit::classify(&e))
+                    {
+                        let e = e.context(format!("{:?} failed {} times", stage, attempts));
+                        if pipeline.stages.contains(&Stage::Notify) {
+                            notify(config, state, ts_path, &Err(&e)).await;
+                        }
+                        return Err(e);
+                    }
+                    tracing::warn!(
+                        "{}: {:?} failed ({}/{}): {:#}",
+                        ts_path.display(),
+                        stage,
+                        attempts,
+                        pipeline.max_attempts,
+                        e
+                    );
+                    tokio::time::delay_for(std::time::Duration::from_secs(pipeline.retry_interval))
+                        .await;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn record(
+    config: &crate::Config,
+    state: &PipelineState,
+    ts_path: &std::path::Path,
+) -> Result<(), anyhow::Error> {
+    if ts_path.exists() {
+        return Ok(());
+    }
+    match state.request {
+        Some(ref request) => crate::record::record(config, request, ts_path).await,
+        None => Err(crate::exit::StatusError::new(
+            crate::exit::Status::InputNotFound,
+            format!("{} does not exist", ts_path.display()),
+        )
+        .into()),
+    }
+}
+
+async fn repair(pipeline: &PipelineConfig, ts_path: &std::path::Path) -> Result<(), anyhow::Error> {
+    let output = ts_path.with_extension("repair.ts");
+    let args = pipeline
+        .repair_command
+        .iter()
+        .map(|arg| {
+            arg.replace("{input}", &ts_path.display().to_string())
+                .replace("{output}", &output.display().to_string())
+        })
+        .collect::<Vec<_>>();
+    let (program, args) = args
+        .split_first()
+        .ok_or_else(|| anyhow::anyhow!("pipeline.repair_command is empty"))?;
+    let status = tokio::process::Command::new(program)
+        .args(args)
+        .status()
+        .await?;
+    if !status.success() {
+        std::fs::remove_file(&output).ok();
+        // Killed by a signal when there is no code
+        let exit_status = status.code().map_or(
+            crate::exit::Status::Interrupted,
+            crate::exit::Status::from_code,
+        );
+        return Err(crate::exit::StatusError::new(
+            exit_status,
+            format!("{} failed: {}", program, status),
+        )
+        .into());
+    }
+    std::fs::rename(&output, ts_path)?;
+    Ok(())
+}
+
+/// Failures of the notification are logged and don't fail the pipeline
+async fn notify(
+    config: &crate::Config,
+    state: &PipelineState,
+    ts_path: &std::path::Path,
+    result: &Result<Option<crate::Report>, &anyhow::Error>,
+) {
+    let stem = ts_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let (status, report, error) = match result {
+        Ok(report) => (crate::outcome::Status::Succeeded, report.as_ref(), None),
+        Err(e) => (
+            crate::outcome::Status::Failed,
+            None,
+            Some(format!("{:#}", e)),
+        ),
+    };
+    let outcome = crate::outcome::JobOutcome {
+        status,
+        message_id: &stem,
+        file: ts_path,
+        profile: state.profile.as_deref(),
+        report,
+        error,
+        eta: None,
+    };
+    for webhook in &config.webhooks {
+        if let Err(e) = webhook.notify(&outcome).await {
+            tracing::warn!("{}", e);
+        }
+    }
+    for email in &config.emails {
+        if let Err(e) = email.notify(&outcome).await {
+            tracing::warn!("{}", e);
+        }
+    }
+    if let Some(ref publish) = config.publish {
+        let result = async {
+            publish
+                .publish(&config.sqs.client()?, &config.redis, &outcome)
+                .await
+        }
+        .await;
+        if let Err(e) = result {
+            tracing::warn!("Failed to publish the outcome: {}", e);
+        }
+    }
+}
@@ -0,0 +1,279 @@
+/// What a job would do, resolved from the configuration and SI of the source without encoding,
+/// uploading, notifying or deleting anything
+#[derive(Debug, serde::Serialize)]
+pub struct Plan {
+    pub source_path: std::path::PathBuf,
+    pub profile: Option<String>,
+    /// ffmpeg commands in the order they are tried
+    pub commands: Vec<Vec<String>>,
+    pub outputs: Vec<std::path::PathBuf>,
+    /// Deleted after all outputs are verified. Archived sources are in the notes.
+    pub deletions: Vec<std::path::PathBuf>,
+    /// Webhook URLs and destinations of the outcome
+    pub notifications: Vec<String>,
+    /// Steps decided while encoding, which are left out of the commands
+    pub notes: Vec<String>,
+}
+
+impl Plan {
+    pub fn print(&self) {
+        println!(
+            "{} (profile {})",
+            self.source_path.display(),
+            self.profile.as_deref().unwrap_or("-")
+        );
+        for command in &self.commands {
+            println!("  command: {}", command.join(" "));
+        }
+        for output in &self.outputs {
+            println!("  output: {}", output.display());
+        }
+        for path in &self.deletions {
+            println!("  delete: {}", path.display());
+        }
+        for target in &self.notifications {
+            println!("  notify: {}", target);
+        }
+        for note in &self.notes {
+            println!("  note: {}", note);
+        }
+    }
+}
+
+/// Resolve the job of the source with the profile and the overrides of the channel. The filtered
+/// TS doesn't exist yet, so the source is analyzed instead.
+pub fn plan(
+    config: &crate::Config,
+    profile: &crate::ProfileConfig,
+    channel: Option<&crate::channels::ChannelConfig>,
+    source_path: &std::path::Path,
+) -> Result<Plan, anyhow::Error> {
+    let ts_path = crate::filtered_path(profile, source_path);
+    let is_ts = crate::is_transport_stream(source_path)?;
+    let mut notes = vec![];
+    if !is_ts {
+        notes.push(format!(
+            "{} is not MPEG-TS, so the filter and the analysis are skipped",
+            source_path.display()
+        ));
+    }
+    if let (Some(filter), true) = (&profile.filter, is_ts) {
+        notes.push(format!(
+            "keep audio and video of service {} in {}",
+            filter
+                .service_id
+                .map(|service_id| service_id.to_string())
+                .unwrap_or_else(|| "-".to_owned()),
+            ts_path.display()
+        ));
+    }
+
+    let trim_config = channel
+        .and_then(|channel| channel.trim.as_ref())
+        .or(profile.trim.as_ref());
+    let dual_mono_config = channel
+        .and_then(|channel| channel.dual_mono.as_ref())
+        .or(profile.dual_mono.as_ref());
+    let source_info = if !is_ts {
+        None
+    } else if trim_config.is_some()
+        || dual_mono_config.is_some()
+        || profile.naming.is_some()
+        || profile.upload.is_some()
+        || profile.transfer.is_some()
+    {
+        let service_id = profile.filter.as_ref().and_then(|f| f.service_id);
+        Some(crate::analysis::analyze(source_path, service_id)?)
+    } else {
+        None
+    };
+    let variables = match source_info {
+        Some(ref info) => Some(crate::naming::Variables::new(
+            source_path,
+            info,
+            crate::naming::probe_height(source_path)?,
+            &profile.services,
+        )),
+        None => None,
+    };
+    let named = crate::output_name(profile, channel, source_path, variables.as_ref());
+    let outputs = crate::output::outputs(profile, source_path, named.as_deref());
+
+    let mut input_args = vec![];
+    if let (Some(trim), Some(info)) = (trim_config, &source_info) {
+        if let Some(range) = crate::trim::find_range(trim, info) {
+            input_args.extend(vec![
+                "-ss".to_owned(),
+                format!("{:.3}", range.start),
+                "-to".to_owned(),
+                format!("{:.3}", range.end),
+            ]);
+        }
+    }
+    let has_video = !outputs.iter().all(|output| output.audio_only);
+    if profile.deinterlace.is_some() && has_video {
+        notes.push("the deinterlace filter is chosen with idet".to_owned());
+    }
+    if profile.loudnorm.is_some() {
+        notes.push("loudnorm is measured before encoding".to_owned());
+    }
+    if profile.two_pass.is_some() {
+        notes.push("each command is preceded by the analysis pass".to_owned());
+    }
+    let dual_mono = match (dual_mono_config, &source_info) {
+        (Some(config), Some(info)) => info
+            .main_event()
+            .and_then(|event| event.dual_mono.as_ref())
+            .map(|dual_mono| (config, dual_mono)),
+        _ => None,
+    };
+    let output_args = match dual_mono {
+        Some((config, dual_mono)) => config.output_args(dual_mono, &[]),
+        None => vec![],
+    };
+
+    // (input_args, ffmpeg_args, additional video filters) tried in order
+    let mut attempts = vec![];
+    match profile.library {
+        Some(ref library) => notes.push(format!(
+            "encode in-process with {} before the fallback",
+            library.video_codec
+        )),
+        None => {
+            if let Some(ref hwaccel) = profile.hwaccel {
+                notes.push("only the first hwaccel which works on the host is tried".to_owned());
+                for &name in &hwaccel.prefer {
+                    if let Some(args) = hwaccel.args(name) {
+                        attempts.push((
+                            &args.input_args,
+                            &args.ffmpeg_args,
+                            &args.video_filters[..],
+                        ));
+                    }
+                }
+            }
+            attempts.push((&profile.input_args, &profile.ffmpeg_args, &[]));
+        }
+    }
+    if let Some(ref fallback) = profile.fallback {
+        attempts.push((
+            &fallback.input_args,
+            fallback
+                .ffmpeg_args
+                .as_ref()
+                .unwrap_or(&profile.ffmpeg_args),
+            &[],
+        ));
+    }
+    let commands = attempts
+        .into_iter()
+        .map(|(attempt_input_args, attempt_args, extra_video_filters)| {
+            let filters = [&profile.video_filters[..], extra_video_filters].concat();
+            let mut args = match dual_mono {
+                Some((config, _)) => config.ffmpeg_args(attempt_args),
+                None => attempt_args.to_vec(),
+            };
+            if has_video && !filters.is_empty() {
+                args.push("-filter:v".to_owned());
+                args.push(filters.join(","));
+            }
+            args.extend(output_args.iter().cloned());
+            let mut command = vec!["ffmpeg".to_owned()];
+            command.extend(input_args.iter().cloned());
+            command.extend(attempt_input_args.iter().cloned());
+            command.push("-i".to_owned());
+            command.push(ts_path.display().to_string());
+            for output in &outputs {
+                let (output_args, path) = output.ffmpeg_output(&args);
+                command.extend(output_args);
+                command.push(path.display().to_string());
+            }
+            command
+        })
+        .collect();
+
+    if let Some(ref encryption) = profile.encryption {
+        if profile.upload.is_some() || profile.transfer.is_some() {
+            notes.push(format!("encrypt the copies with key {}", encryption.key_id));
+        }
+    }
+    if let Some(ref upload) = profile.upload {
+        let prefix = match variables {
+            Some(ref variables) => variables.expand(&upload.prefix),
+            None => upload.prefix.clone(),
+        };
+        notes.push(format!(
+            "upload to s3://{}/{}{}",
+            upload.bucket,
+            prefix,
+            upload.bandwidth.describe()
+        ));
+    }
+    if let Some(ref transfer) = profile.transfer {
+        let dir = match variables {
+            Some(ref variables) => variables.expand(&transfer.dir),
+            None => transfer.dir.clone(),
+        };
+        notes.push(match transfer.host {
+            Some(ref host) => format!(
+                "transfer to {}:{}{}",
+                host,
+                dir,
+                transfer.bandwidth.describe()
+            ),
+            None => format!("transfer to {}{}", dir, transfer.bandwidth.describe()),
+        });
+    }
+
+    let sources = profile.cleanup.sources(&ts_path)?;
+    let deletions = match profile.cleanup.source_action() {
+        crate::cleanup::SourceAction::Delete => sources,
+        crate::cleanup::SourceAction::Keep => vec![],
+        crate::cleanup::SourceAction::Move(ref dir) => {
+            for source in &sources {
+                notes.push(format!("move {} to {}", source.display(), dir.display()));
+            }
+            vec![]
+        }
+        crate::cleanup::SourceAction::Compress { ref path, level } => {
+            for source in &sources {
+                notes.push(format!(
+                    "compress {} into {} with zstd level {}",
+                    source.display(),
+                    path.display(),
+                    level
+                ));
+            }
+            vec![]
+        }
+    };
+    let mut notifications = config
+        .webhooks
+        .iter()
+        .map(|webhook| webhook.url.clone())
+        .chain(
+            config
+                .emails
+                .iter()
+                .map(|email| format!("mailto:{}", email.to.join(","))),
+        )
+        .collect::<Vec<_>>();
+    if let Some(ref publish) = config.publish {
+        if let Some(ref queue_url) = publish.sqs_queue_url {
+            notifications.push(queue_url.clone());
+        }
+        if let Some(ref stream) = publish.redis_stream {
+            notifications.push(format!("{} {}", config.redis.url, stream));
+        }
+    }
+
+    Ok(Plan {
+        source_path: source_path.to_owned(),
+        profile: profile.name.clone(),
+        commands,
+        outputs: outputs.into_iter().map(|output| output.path).collect(),
+        deletions,
+        notifications,
+        notes,
+    })
+}
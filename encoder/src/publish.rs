@@ -0,0 +1,51 @@
+/// Publish the outcome of each job so that downstream consumers can be decoupled from
+/// sqs-encode
+#[derive(serde::Deserialize)]
+pub struct PublishConfig {
+    /// SQS queue receiving the outcome as the message body
+    pub sqs_queue_url: Option<String>,
+    /// Redis stream receiving the outcome in "outcome" field. The server in [redis] is used.
+    pub redis_stream: Option<String>,
+    /// Trim the stream to about this length
+    pub redis_max_len: Option<usize>,
+}
+
+impl PublishConfig {
+    pub async fn publish<Sqs>(
+        &self,
+        sqs_client: &Sqs,
+        redis: &crate::config::RedisConfig,
+        outcome: &crate::outcome::JobOutcome<'_>,
+    ) -> Result<(), anyhow::Error>
+    where
+        Sqs: rusoto_sqs::Sqs,
+    {
+        let body = serde_json::to_string(outcome)?;
+        if let Some(ref queue_url) = self.sqs_queue_url {
+            sqs_client
+                .send_message(rusoto_sqs::SendMessageRequest {
+                    queue_url: queue_url.clone(),
+                    message_body: body.clone(),
+                    ..Default::default()
+                })
+                .await
+                .map_err(|e| anyhow::anyhow!("failed to call sqs:SendMessage: {}", e))?;
+        }
+        if let Some(ref stream) = self.redis_stream {
+            let client = redis::Client::open(redis.url.as_str())?;
+            let mut conn = client.get_async_connection().await?;
+            let mut cmd = redis::cmd("XADD");
+            cmd.arg(stream);
+            if let Some(max_len) = self.redis_max_len {
+                cmd.arg("MAXLEN").arg("~").arg(max_len);
+            }
+            let _: String = cmd
+                .arg("*")
+                .arg("outcome")
+                .arg(&body)
+                .query_async(&mut conn)
+                .await?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,162 @@
+/// Compare sampled intervals of the output against the source with a full-reference metric
+#[derive(Clone, serde::Deserialize)]
+pub struct QualityConfig {
+    #[serde(default)]
+    pub metric: Metric,
+    /// Minimum mean score. VMAF is 0-100, SSIM is 0-1 and PSNR is in dB.
+    pub threshold: f64,
+    #[serde(default)]
+    pub on_failure: QualityAction,
+    /// Number of intervals evenly spaced in the output
+    #[serde(default = "default_samples")]
+    pub samples: u32,
+    /// In seconds
+    #[serde(default = "default_sample_duration")]
+    pub sample_duration: f64,
+}
+
+fn default_samples() -> u32 {
+    5
+}
+
+fn default_sample_duration() -> f64 {
+    10.0
+}
+
+#[derive(Clone, Copy, Debug, Default, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Metric {
+    #[default]
+    Vmaf,
+    Ssim,
+    Psnr,
+}
+
+#[derive(Clone, Copy, Debug, Default, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QualityAction {
+    #[default]
+    Fail,
+    Warn,
+}
+
+impl Metric {
+    fn name(self) -> &'static str {
+        match self {
+            Metric::Vmaf => "vmaf",
+            Metric::Ssim => "ssim",
+            Metric::Psnr => "psnr",
+        }
+    }
+
+    fn filter(self) -> &'static str {
+        match self {
+            Metric::Vmaf => "libvmaf",
+            Metric::Ssim => "ssim",
+            Metric::Psnr => "psnr",
+        }
+    }
+
+    /// Parse the summary printed by the filter
+    fn parse(self, stderr: &str) -> Option<f64> {
+        let (line_key, value_key) = match self {
+            Metric::Vmaf => ("VMAF score", "VMAF score:"),
+            Metric::Ssim => ("SSIM Y:", "All:"),
+            Metric::Psnr => ("PSNR y:", "average:"),
+        };
+        let line = stderr.lines().rev().find(|line| line.contains(line_key))?;
+        let value = &line[line.find(value_key)? + value_key.len()..];
+        value.split_whitespace().next()?.parse().ok()
+    }
+}
+
+impl QualityConfig {
+    /// Score of an interval starting at position of the output
+    fn measure(&self, target: &crate::verify::Target, position: f64) -> Result<f64, anyhow::Error> {
+        let source_position = position + target.range.map(|(start, _)| start).unwrap_or(0.0);
+        let reference_filters = target
+            .reference_filters
+            .iter()
+            .map(|filter| format!("{},", filter))
+            .collect::<String>();
+        let mut command = std::process::Command::new("ffmpeg");
+        if let Some(work_dir) = target.work_dir {
+            command.current_dir(work_dir);
+        }
+        let output = command
+            .arg("-nostats")
+            .arg("-ss")
+            .arg(format!("{:.3}", position))
+            .arg("-t")
+            .arg(format!("{:.3}", self.sample_duration))
+            .arg("-i")
+            .arg(target.output_path)
+            .arg("-ss")
+            .arg(format!("{:.3}", source_position))
+            .arg("-t")
+            .arg(format!("{:.3}", self.sample_duration))
+            .arg("-i")
+            .arg(target.ts_path)
+            .arg("-lavfi")
+            .arg(format!(
+                // The metrics need the same pixel format, which differs for 10-bit outputs
+                "[0:v:0]format=yuv420p,setpts=PTS-STARTPTS[main];[1:v:0]{}format=yuv420p,setpts=PTS-STARTPTS[source];[source][main]scale2ref[ref][distorted];[distorted][ref]{}",
+                reference_filters,
+                self.metric.filter()
+            ))
+            .args(["-f", "null", "-"])
+            .output()?;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !output.status.success() {
+            return Err(anyhow::anyhow!(
+                "{} measurement failed with {}",
+                self.metric.name(),
+                output.status
+            ));
+        }
+        self.metric
+            .parse(&stderr)
+            .ok_or_else(|| anyhow::anyhow!("{} score is not found", self.metric.name()))
+    }
+}
+
+impl crate::verify::Verifier for QualityConfig {
+    fn name(&self) -> &'static str {
+        self.metric.name()
+    }
+
+    fn verify(
+        &self,
+        target: &crate::verify::Target,
+        scores: &mut crate::verify::Scores,
+    ) -> Result<(), anyhow::Error> {
+        if target.audio_only || self.samples == 0 {
+            return Ok(());
+        }
+        let duration = target.expected_duration as f64 / 1_000_000.0;
+        let span = (duration - self.sample_duration).max(0.0);
+        let mut total = 0.0;
+        for i in 0..self.samples {
+            let position = span * (i as f64 + 0.5) / self.samples as f64;
+            total += self.measure(target, position)?;
+        }
+        let score = total / self.samples as f64;
+        scores.insert(self.metric.name().to_owned(), score);
+
+        if score < self.threshold {
+            let message = format!(
+                "{} score {:.3} is below the threshold {}",
+                self.metric.name(),
+                score,
+                self.threshold
+            );
+            match self.on_failure {
+                QualityAction::Fail => return Err(anyhow::anyhow!(message)),
+                QualityAction::Warn => {
+                    tracing::warn!("{}: {}", target.output_path.display(), message)
+                }
+            }
+        }
+        Ok(())
+    }
+}
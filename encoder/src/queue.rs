@@ -0,0 +1,260 @@
+/// Backend of pending jobs whose bodies are the file names (or the JSON requests) handled by
+/// sqs-encode
+pub enum Queue {
+    /// Redis list pushed to the tail, like "jobs" read by redis-to-sqs
+    RedisList {
+        conn: redis::aio::Connection,
+        key: String,
+    },
+    /// Redis stream with the body in "job" field
+    RedisStream {
+        conn: redis::aio::Connection,
+        key: String,
+    },
+    Sqs {
+        client: rusoto_sqs::SqsClient,
+        queue_url: String,
+    },
+}
+
+/// Job taken from a queue, which stays in the queue until it's acked
+pub struct Job {
+    pub body: String,
+    /// Stream entry ID or SQS receipt handle
+    handle: Option<String>,
+}
+
+/// Messages received from SQS without being acked become visible again after this many seconds
+const SQS_VISIBILITY_TIMEOUT: i64 = 60;
+
+impl Queue {
+    /// Open "redis-list[:KEY]", "redis-stream[:KEY]" or "sqs[:QUEUE_URL]". KEY defaults to
+    /// "jobs" and QUEUE_URL defaults to queue_url of [sqs]. The server in [redis] is used.
+    pub async fn open(spec: &str, config: &crate::Config) -> Result<Self, anyhow::Error> {
+        let (kind, arg) = match spec.find(':') {
+            Some(i) => (&spec[..i], Some(&spec[i + 1..])),
+            None => (spec, None),
+        };
+        let key = arg.unwrap_or("jobs").to_owned();
+        match kind {
+            "redis-list" | "redis-stream" => {
+                let client = redis::Client::open(config.redis.url.as_str())?;
+                let conn = client.get_async_connection().await?;
+                Ok(if kind == "redis-list" {
+                    Self::RedisList { conn, key }
+                } else {
+                    Self::RedisStream { conn, key }
+                })
+            }
+            "sqs" => Ok(Self::Sqs {
+                client: config.sqs.client()?,
+                queue_url: arg.unwrap_or(&config.sqs.queue_url).to_owned(),
+            }),
+            _ => Err(anyhow::anyhow!("unknown queue {}", spec)),
+        }
+    }
+
+    /// Bodies of the jobs in the queue without removing them, or None for SQS where listing
+    /// would hide the messages from the consumers
+    pub async fn list(&mut self) -> Result<Option<Vec<String>>, anyhow::Error> {
+        match self {
+            Self::RedisList { conn, key } => Ok(Some(
+                redis::cmd("LRANGE")
+                    .arg(&*key)
+                    .arg(0)
+                    .arg(-1)
+                    .query_async(conn)
+                    .await?,
+            )),
+            Self::RedisStream { conn, key } => {
+                let entries = xrange(conn, key, None).await?;
+                Ok(Some(entries.into_iter().map(|(_, body)| body).collect()))
+            }
+            Self::Sqs { .. } => Ok(None),
+        }
+    }
+
+    /// Take a batch of jobs from the head. Jobs of Redis stay in the queue and are taken again
+    /// until they are acked, while SQS messages are hidden for SQS_VISIBILITY_TIMEOUT.
+    pub async fn take(&mut self) -> Result<Vec<Job>, anyhow::Error> {
+        use rusoto_sqs::Sqs as _;
+
+        match self {
+            Self::RedisList { conn, key } => {
+                let bodies: Vec<String> = redis::cmd("LRANGE")
+                    .arg(&*key)
+                    .arg(0)
+                    .arg(9)
+                    .query_async(conn)
+                    .await?;
+                Ok(bodies
+                    .into_iter()
+                    .map(|body| Job { body, handle: None })
+                    .collect())
+            }
+            Self::RedisStream { conn, key } => Ok(xrange(conn, key, Some(10))
+                .await?
+                .into_iter()
+                .map(|(id, body)| Job {
+                    body,
+                    handle: Some(id),
+                })
+                .collect()),
+            Self::Sqs { client, queue_url } => {
+                let output = client
+                    .receive_message(rusoto_sqs::ReceiveMessageRequest {
+                        queue_url: queue_url.clone(),
+                        max_number_of_messages: Some(10),
+                        visibility_timeout: Some(SQS_VISIBILITY_TIMEOUT),
+                        wait_time_seconds: Some(1),
+                        ..Default::default()
+                    })
+                    .await
+                    .map_err(|e| anyhow::anyhow!("failed to call sqs:ReceiveMessage: {}", e))?;
+                Ok(output
+                    .messages
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter_map(|message| {
+                        Some(Job {
+                            body: message.body?,
+                            handle: Some(message.receipt_handle?),
+                        })
+                    })
+                    .collect())
+            }
+        }
+    }
+
+    /// Remove the taken job from the queue
+    pub async fn ack(&mut self, job: &Job) -> Result<(), anyhow::Error> {
+        use rusoto_sqs::Sqs as _;
+
+        match self {
+            Self::RedisList { conn, key } => {
+                let _: i64 = redis::cmd("LREM")
+                    .arg(&*key)
+                    .arg(1)
+                    .arg(&job.body)
+                    .query_async(conn)
+                    .await?;
+            }
+            Self::RedisStream { conn, key } => {
+                let _: i64 = redis::cmd("XDEL")
+                    .arg(&*key)
+                    .arg(job.handle.as_ref().unwrap())
+                    .query_async(conn)
+                    .await?;
+            }
+            Self::Sqs { client, queue_url } => {
+                client
+                    .delete_message(rusoto_sqs::DeleteMessageRequest {
+                        queue_url: queue_url.clone(),
+                        receipt_handle: job.handle.clone().unwrap(),
+                    })
+                    .await
+                    .map_err(|e| anyhow::anyhow!("failed to call sqs:DeleteMessage: {}", e))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Append a job to the tail
+    pub async fn push(&mut self, body: &str) -> Result<(), anyhow::Error> {
+        use rusoto_sqs::Sqs as _;
+
+        match self {
+            Self::RedisList { conn, key } => {
+                let _: i64 = redis::cmd("RPUSH")
+                    .arg(&*key)
+                    .arg(body)
+                    .query_async(conn)
+                    .await?;
+            }
+            Self::RedisStream { conn, key } => {
+                let _: String = redis::cmd("XADD")
+                    .arg(&*key)
+                    .arg("*")
+                    .arg("job")
+                    .arg(body)
+                    .query_async(conn)
+                    .await?;
+            }
+            Self::Sqs { client, queue_url } => {
+                client
+                    .send_message(rusoto_sqs::SendMessageRequest {
+                        queue_url: queue_url.clone(),
+                        message_body: body.to_owned(),
+                        ..Default::default()
+                    })
+                    .await
+                    .map_err(|e| anyhow::anyhow!("failed to call sqs:SendMessage: {}", e))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Entries of the stream as (ID, job)
+async fn xrange(
+    conn: &mut redis::aio::Connection,
+    key: &str,
+    count: Option<usize>,
+) -> Result<Vec<(String, String)>, anyhow::Error> {
+    let mut cmd = redis::cmd("XRANGE");
+    cmd.arg(key).arg("-").arg("+");
+    if let Some(count) = count {
+        cmd.arg("COUNT").arg(count);
+    }
+    let entries: Vec<(String, Vec<(String, String)>)> = cmd.query_async(conn).await?;
+    Ok(entries
+        .into_iter()
+        .filter_map(|(id, fields)| {
+            let body = fields.into_iter().find(|(k, _)| k == "job")?.1;
+            Some((id, body))
+        })
+        .collect())
+}
+
+#[derive(Debug, Default)]
+pub struct MigrateReport {
+    pub moved: u64,
+    /// Jobs dropped because the same body was already in the destination or moved before
+    pub duplicates: u64,
+}
+
+/// Move all jobs from one queue to the tail of another in order. Each job is acked after it's
+/// pushed, so an interrupted migration may leave a job in both queues but never loses one. The
+/// consumers of `from` should be stopped during the migration.
+///
+/// With dedup, jobs whose body is already in `to` (unless it's SQS) or moved earlier are
+/// removed from `from` without being pushed.
+pub async fn migrate(
+    from: &mut Queue,
+    to: &mut Queue,
+    dedup: bool,
+) -> Result<MigrateReport, anyhow::Error> {
+    let mut seen = std::collections::HashSet::new();
+    if dedup {
+        seen.extend(to.list().await?.unwrap_or_default());
+    }
+    let mut report = MigrateReport::default();
+    loop {
+        let jobs = from.take().await?;
+        if jobs.is_empty() {
+            break;
+        }
+        for job in jobs {
+            if dedup && !seen.insert(job.body.clone()) {
+                tracing::info!("Drop duplicate {}", job.body);
+                report.duplicates += 1;
+            } else {
+                tracing::info!("Move {}", job.body);
+                to.push(&job.body).await?;
+                report.moved += 1;
+            }
+            from.ack(&job).await?;
+        }
+    }
+    Ok(report)
+}
@@ -0,0 +1,166 @@
+/// Capture of sources by the record binary
+#[derive(Default, serde::Deserialize)]
+pub struct RecordConfig {
+    /// Seconds recorded before the start
+    #[serde(default)]
+    pub margin_before: u64,
+    /// Seconds recorded after the end
+    #[serde(default)]
+    pub margin_after: u64,
+    /// Record with the supervised tuner command instead of Mirakurun. The channel is looked up
+    /// in tuner_channels.
+    pub tuner: Option<crate::tuner::TunerConfig>,
+    /// Record with this command instead of Mirakurun, e.g.
+    /// ["recpt1", "--b25", "--strip", "--sid", "{service_id}", "{channel}", "{duration}", "{output}"]
+    pub tuner_command: Option<Vec<String>>,
+    /// Substituted for {channel} in tuner_command and passed to tuner, keyed by service_id.
+    /// Defaults to the service_id.
+    #[serde(default)]
+    pub tuner_channels: std::collections::HashMap<String, String>,
+    /// Don't send the file name to sqs.queue_url after recording
+    #[serde(default)]
+    pub no_enqueue: bool,
+}
+
+impl RecordConfig {
+    /// The request widened by the margins. The file name is kept.
+    pub fn with_margins(
+        &self,
+        request: &crate::mirakurun::SourceRequest,
+    ) -> Result<crate::mirakurun::SourceRequest, anyhow::Error> {
+        let (start, end) = request.range()?;
+        let start = start - chrono::Duration::seconds(self.margin_before as i64);
+        let end = end + chrono::Duration::seconds(self.margin_after as i64);
+        Ok(crate::mirakurun::SourceRequest {
+            service_id: request.service_id,
+            start: start.to_rfc3339(),
+            end: end.to_rfc3339(),
+            name: Some(request.file_name()?),
+        })
+    }
+
+    /// Wait until the start of the request and run tuner_command for the duration. The command
+    /// writes to "{ts_path}.part", which is renamed after it exits successfully.
+    pub async fn run_tuner_command(
+        &self,
+        command: &[String],
+        request: &crate::mirakurun::SourceRequest,
+        ts_path: &std::path::Path,
+    ) -> Result<(), anyhow::Error> {
+        let end = wait_for_start(request).await?;
+        let duration = (end - chrono::Utc::now()).num_seconds().max(1);
+        let service_id = request.service_id.to_string();
+        let channel = self.channel(request.service_id);
+        let part_path = ts_path.with_extension("ts.part");
+        let args = command
+            .iter()
+            .map(|arg| {
+                arg.replace("{service_id}", &service_id)
+                    .replace("{channel}", &channel)
+                    .replace("{duration}", &duration.to_string())
+                    .replace("{output}", &part_path.display().to_string())
+            })
+            .collect::<Vec<_>>();
+        let (program, args) = args
+            .split_first()
+            .ok_or_else(|| anyhow::anyhow!("tuner_command is empty"))?;
+        tracing::info!("Record {} with {:?}", part_path.display(), args);
+        let mut child = tokio::process::Command::new(program)
+            .args(args)
+            .stderr(std::process::Stdio::piped())
+            .spawn()?;
+        let stderr = child.stderr.take().unwrap();
+        let (status, signal) =
+            futures::future::join(child, crate::tuner::watch_signal(stderr)).await;
+        let status = status?;
+        if !status.success() {
+            return Err(anyhow::anyhow!("{} failed: {}", program, status));
+        }
+        std::fs::rename(&part_path, ts_path)?;
+        if !signal.samples.is_empty() {
+            signal.write(ts_path)?;
+        }
+        Ok(())
+    }
+
+    /// Wait until the start of the request and capture it with the tuner into "{ts_path}.part",
+    /// which is renamed after the end
+    pub async fn run_tuner(
+        &self,
+        tuner: &crate::tuner::TunerConfig,
+        request: &crate::mirakurun::SourceRequest,
+        ts_path: &std::path::Path,
+    ) -> Result<(), anyhow::Error> {
+        let end = wait_for_start(request).await?;
+        let part_path = ts_path.with_extension("ts.part");
+        let report = tuner
+            .capture(
+                &self.channel(request.service_id),
+                Some(request.service_id),
+                &part_path,
+                end,
+            )
+            .await?;
+        tracing::info!(
+            "Recorded {}: {} bytes, {} restarts, min signal {:?}",
+            part_path.display(),
+            report.bytes,
+            report.restarts,
+            report.min_signal
+        );
+        std::fs::rename(&part_path, ts_path)?;
+        report.signal.write(ts_path)?;
+        Ok(())
+    }
+
+    fn channel(&self, service_id: u16) -> String {
+        let service_id = service_id.to_string();
+        self.tuner_channels
+            .get(&service_id)
+            .cloned()
+            .unwrap_or(service_id)
+    }
+}
+
+/// Record the request widened by the margins into ts_path with the tuner, tuner_command or
+/// Mirakurun, whichever is configured first
+pub async fn record(
+    config: &crate::Config,
+    request: &crate::mirakurun::SourceRequest,
+    ts_path: &std::path::Path,
+) -> Result<(), anyhow::Error> {
+    let record = &config.record;
+    let request = record.with_margins(request)?;
+    match (&record.tuner, &record.tuner_command, &config.mirakurun) {
+        (Some(tuner), _, _) => record.run_tuner(tuner, &request, ts_path).await,
+        (None, Some(command), _) => record.run_tuner_command(command, &request, ts_path).await,
+        (None, None, Some(mirakurun)) => {
+            crate::mirakurun::Client::new(mirakurun)
+                .record(&request, ts_path)
+                .await
+        }
+        (None, None, None) => Err(anyhow::anyhow!(
+            "either record.tuner, record.tuner_command or [mirakurun] is required"
+        )),
+    }
+}
+
+/// Wait until the start of the request and return its end
+async fn wait_for_start(
+    request: &crate::mirakurun::SourceRequest,
+) -> Result<chrono::DateTime<chrono::Utc>, anyhow::Error> {
+    let (start, end) = request.range()?;
+    if end <= chrono::Utc::now() {
+        return Err(anyhow::anyhow!("{} is already over", request.end));
+    }
+    if let Ok(wait) = (start - chrono::Utc::now()).to_std() {
+        tracing::info!(
+            "Wait {}s for {} on service {}",
+            wait.as_secs(),
+            start,
+            request.service_id
+        );
+        tokio::time::delay_for(wait).await;
+    }
+    Ok(end)
+}
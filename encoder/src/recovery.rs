@@ -0,0 +1,210 @@
+/// Bring base_dir and the job store back to a consistent state when a worker starts after a crash.
+/// The SQS message of the interrupted job becomes visible again after the visibility timeout, so
+/// only the leftovers of the job are handled here.
+#[derive(serde::Deserialize)]
+pub struct RecoveryConfig {
+    /// Partial files modified within this many seconds may be written by another worker sharing
+    /// base_dir and are kept
+    #[serde(default = "default_min_age")]
+    pub min_age: u64,
+    /// Move partial files into this directory instead of removing them
+    pub quarantine_dir: Option<std::path::PathBuf>,
+}
+
+fn default_min_age() -> u64 {
+    600
+}
+
+// Jobs don't stay running longer than this
+const MAX_JOB_HOURS: i64 = 48;
+
+/// Extensions of the outputs of every profile. Those of sources are excluded so that recordings
+/// such as "foo.sub.ts" are never taken for partial files.
+pub fn output_extensions(config: &crate::Config) -> std::collections::BTreeSet<String> {
+    let mut extensions = std::collections::BTreeSet::new();
+    for profile in std::iter::once(&config.encoder.profile).chain(config.profiles.values()) {
+        extensions.insert(profile.container.extension().to_owned());
+        if let Some(ref audio) = profile.audio {
+            extensions.insert(audio.codec.extension().to_owned());
+        }
+        for output in &profile.outputs {
+            if let Some(ref extension) = output.extension {
+                extensions.insert(extension.clone());
+            }
+        }
+    }
+    for extension in crate::config::SOURCE_EXTENSIONS {
+        extensions.remove(*extension);
+    }
+    extensions
+}
+
+/// Whether the file is written by ffmpeg before renamed to the output, i.e. "*.part.{ext}", or by
+/// metadata and caption muxing, i.e. "*.meta.{ext}" and "*.sub.{ext}", where ext is one of the
+/// output extensions
+fn is_partial(path: &std::path::Path, extensions: &std::collections::BTreeSet<String>) -> bool {
+    let name = match path.file_name().and_then(|name| name.to_str()) {
+        Some(name) => name,
+        None => return false,
+    };
+    match name.rsplit_once('.') {
+        Some((rest, extension)) if extensions.contains(extension) => {
+            rest.rsplit_once('.').is_some_and(|(stem, tag)| {
+                !stem.is_empty() && (tag == "part" || tag == "meta" || tag == "sub")
+            })
+        }
+        _ => false,
+    }
+}
+
+/// Path in dir named after the file, suffixed with a number when the name is taken
+fn quarantine_path(dir: &std::path::Path, path: &std::path::Path) -> std::path::PathBuf {
+    let name = path.file_name().unwrap();
+    let mut dest = dir.join(name);
+    let mut i = 1;
+    while dest.exists() {
+        let mut numbered = name.to_owned();
+        numbered.push(format!(".{}", i));
+        dest = dir.join(numbered);
+        i += 1;
+    }
+    dest
+}
+
+fn find_partial_files(
+    dir: &std::path::Path,
+    extensions: &std::collections::BTreeSet<String>,
+    min_age: std::time::Duration,
+    paths: &mut Vec<std::path::PathBuf>,
+) -> Result<(), anyhow::Error> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            find_partial_files(&entry.path(), extensions, min_age, paths)?;
+        } else if file_type.is_file()
+            && is_partial(&entry.path(), extensions)
+            && entry.metadata()?.modified()?.elapsed().unwrap_or_default() >= min_age
+        {
+            paths.push(entry.path());
+        }
+    }
+    Ok(())
+}
+
+impl RecoveryConfig {
+    /// Remove or quarantine partial files of the outputs with the extensions under base_dir and
+    /// return their paths
+    pub fn clean(
+        &self,
+        base_dir: &std::path::Path,
+        extensions: &std::collections::BTreeSet<String>,
+    ) -> Result<Vec<std::path::PathBuf>, anyhow::Error> {
+        let mut paths = vec![];
+        find_partial_files(
+            base_dir,
+            extensions,
+            std::time::Duration::from_secs(self.min_age),
+            &mut paths,
+        )?;
+        for path in &paths {
+            match self.quarantine_dir {
+                Some(ref dir) => {
+                    std::fs::create_dir_all(dir)?;
+                    let dest = quarantine_path(dir, path);
+                    tracing::warn!("Quarantine {} to {}", path.display(), dest.display());
+                    if std::fs::rename(path, &dest).is_err() {
+                        // rename fails across filesystems
+                        std::fs::copy(path, &dest)?;
+                        std::fs::remove_file(path)?;
+                    }
+                }
+                None => {
+                    tracing::warn!("Remove partial {}", path.display());
+                    std::fs::remove_file(path)?;
+                }
+            }
+        }
+        Ok(paths)
+    }
+}
+
+/// Whether the worker "{hostname}:{pid}" is gone. Workers on the other hosts are unknown.
+fn is_dead_on_this_host(worker: &str) -> bool {
+    let this = crate::jobs::worker_id();
+    let pid = match (worker.rsplit_once(':'), this.rsplit_once(':')) {
+        (Some((host, pid)), Some((this_host, _))) if host == this_host => pid,
+        _ => return false,
+    };
+    let pid = match pid.parse::<libc::pid_t>() {
+        Ok(pid) => pid,
+        Err(_) => return false,
+    };
+    worker != this
+        && unsafe { libc::kill(pid, 0) } != 0
+        && std::io::Error::last_os_error().raw_os_error() == Some(libc::ESRCH)
+}
+
+/// Put the jobs left running or verifying by dead workers on this host back to queued and return
+/// their files
+pub async fn reconcile(job_store: &crate::jobs::JobStore) -> Result<Vec<String>, anyhow::Error> {
+    use crate::jobs::State;
+
+    let since = chrono::Utc::now() - chrono::Duration::hours(MAX_JOB_HOURS);
+    let mut files = vec![];
+    for (file, record) in job_store.updated_since(since).await? {
+        let state = match record.get("state").map(|state| state.parse::<State>()) {
+            Some(Ok(state)) => state,
+            _ => continue,
+        };
+        if state != State::Running && state != State::Verifying {
+            continue;
+        }
+        if !record
+            .get("worker")
+            .is_some_and(|worker| is_dead_on_this_host(worker))
+        {
+            continue;
+        }
+        tracing::warn!("{} was interrupted by {}", file, record["worker"]);
+        job_store
+            .transition(&file, State::Queued, &[("recovered", "true")])
+            .await?;
+        files.push(file);
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partial_files() {
+        let extensions = ["mp4", "m4a"].iter().map(|e| e.to_string()).collect();
+        let is_partial = |name| is_partial(std::path::Path::new(name), &extensions);
+        assert!(is_partial("foo.part.mp4"));
+        assert!(is_partial("dir/foo.meta.mp4"));
+        assert!(is_partial("foo.bar.sub.m4a"));
+        assert!(!is_partial("foo.mp4"));
+        assert!(!is_partial("foo.sub.ts"));
+        assert!(!is_partial("foo.part.mkv"));
+        assert!(!is_partial(".part.mp4"));
+    }
+
+    #[test]
+    fn quarantine_paths_are_unique() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = std::path::Path::new("a/foo.part.mp4");
+        assert_eq!(
+            quarantine_path(dir.path(), path),
+            dir.path().join("foo.part.mp4")
+        );
+        std::fs::write(dir.path().join("foo.part.mp4"), b"").unwrap();
+        std::fs::write(dir.path().join("foo.part.mp4.1"), b"").unwrap();
+        assert_eq!(
+            quarantine_path(dir.path(), path),
+            dir.path().join("foo.part.mp4.2")
+        );
+    }
+}
@@ -0,0 +1,184 @@
+/// Rules reserving recordings of events in EPG
+#[derive(serde::Deserialize)]
+pub struct ReserveConfig {
+    #[serde(default)]
+    pub rules: Vec<RuleConfig>,
+    /// Recordings at the same time beyond this number are conflicts
+    #[serde(default = "default_tuners")]
+    pub tuners: usize,
+}
+
+fn default_tuners() -> usize {
+    1
+}
+
+/// All of the given conditions must match
+#[derive(serde::Deserialize)]
+pub struct RuleConfig {
+    pub name: String,
+    /// Regex matched against the event name
+    pub title: Option<String>,
+    /// Name of content_nibble_level_1, e.g. "アニメ／特撮"
+    pub genre: Option<String>,
+    /// service_id or service name
+    #[serde(default)]
+    pub channels: Vec<String>,
+    /// Local time range of the start like "23:00-26:00"
+    pub time: Option<String>,
+    /// series_id in the series descriptor
+    pub series_id: Option<u16>,
+    /// Reservations of higher priority win conflicts
+    #[serde(default)]
+    pub priority: i32,
+}
+
+#[derive(Debug)]
+pub struct Reservation {
+    pub rule: String,
+    pub priority: i32,
+    pub network_id: u16,
+    pub service_id: u16,
+    pub service_name: Option<String>,
+    pub event_id: u16,
+    /// Unix time
+    pub start_time: i64,
+    /// Unix time
+    pub end_time: i64,
+    pub name: Option<String>,
+    /// None when all tuners are taken by reservations of higher priority
+    pub tuner: Option<usize>,
+}
+
+impl Reservation {
+    /// Job message recording and encoding the event
+    pub fn source_request(&self) -> crate::mirakurun::SourceRequest {
+        use chrono::TimeZone as _;
+
+        crate::mirakurun::SourceRequest {
+            service_id: self.service_id,
+            start: chrono::Local.timestamp(self.start_time, 0).to_rfc3339(),
+            end: chrono::Local.timestamp(self.end_time, 0).to_rfc3339(),
+            name: None,
+        }
+    }
+}
+
+struct Rule<'a> {
+    config: &'a RuleConfig,
+    title: Option<regex::Regex>,
+    time: Option<(u32, u32)>,
+}
+
+impl<'a> Rule<'a> {
+    fn new(config: &'a RuleConfig) -> Result<Self, anyhow::Error> {
+        let title = match config.title {
+            Some(ref title) => Some(regex::Regex::new(title)?),
+            None => None,
+        };
+        let time = match config.time {
+            Some(ref time) => Some(
+                crate::throttle::parse_window(time)
+                    .ok_or_else(|| anyhow::anyhow!("Invalid time {} of {}", time, config.name))?,
+            ),
+            None => None,
+        };
+        Ok(Self {
+            config,
+            title,
+            time,
+        })
+    }
+
+    fn matches(&self, epg: &crate::analysis::EpgEvent) -> bool {
+        use chrono::{TimeZone as _, Timelike as _};
+
+        let event = &epg.event;
+        if let Some(ref title) = self.title {
+            if !event
+                .name
+                .as_deref()
+                .is_some_and(|name| title.is_match(name))
+            {
+                return false;
+            }
+        }
+        if let Some(ref genre) = self.config.genre {
+            if event.genre != Some(genre.as_str()) {
+                return false;
+            }
+        }
+        if !self.config.channels.is_empty()
+            && !self.config.channels.iter().any(|channel| {
+                *channel == epg.service_id.to_string()
+                    || epg.service_name.as_deref() == Some(channel.as_str())
+            })
+        {
+            return false;
+        }
+        if let Some(window) = self.time {
+            let start = chrono::Local.timestamp(event.start_time, 0);
+            if !crate::throttle::in_window(window, start.hour() * 60 + start.minute()) {
+                return false;
+            }
+        }
+        if let Some(series_id) = self.config.series_id {
+            if event.series.as_ref().map(|series| series.series_id) != Some(series_id) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+impl ReserveConfig {
+    /// Reservations of events matching the rules, ordered by start_time. An event matching
+    /// several rules is reserved by the rule of the highest priority, and tuners are assigned in
+    /// the order of priority.
+    pub fn reserve(
+        &self,
+        events: &[crate::analysis::EpgEvent],
+    ) -> Result<Vec<Reservation>, anyhow::Error> {
+        let rules = self
+            .rules
+            .iter()
+            .map(Rule::new)
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut reservations = events
+            .iter()
+            .filter_map(|epg| {
+                let rule = rules
+                    .iter()
+                    .filter(|rule| rule.matches(epg))
+                    .max_by_key(|rule| rule.config.priority)?;
+                Some(Reservation {
+                    rule: rule.config.name.clone(),
+                    priority: rule.config.priority,
+                    network_id: epg.network_id,
+                    service_id: epg.service_id,
+                    service_name: epg.service_name.clone(),
+                    event_id: epg.event.event_id,
+                    start_time: epg.event.start_time,
+                    end_time: epg.event.end_time,
+                    name: epg.event.name.clone(),
+                    tuner: None,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        reservations.sort_by_key(|reservation| (-reservation.priority, reservation.start_time));
+        let mut tuners: Vec<Vec<(i64, i64)>> = vec![vec![]; self.tuners];
+        for reservation in &mut reservations {
+            let range = (reservation.start_time, reservation.end_time);
+            reservation.tuner = tuners.iter().position(|taken| {
+                taken
+                    .iter()
+                    .all(|&(start, end)| range.1 <= start || end <= range.0)
+            });
+            if let Some(tuner) = reservation.tuner {
+                tuners[tuner].push(range);
+            }
+        }
+        reservations.sort_by_key(|reservation| (reservation.start_time, reservation.service_id));
+        Ok(reservations)
+    }
+}
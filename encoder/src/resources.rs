@@ -0,0 +1,114 @@
+/// Limits of the ffmpeg processes encoding the outputs so that a runaway encode doesn't disturb
+/// recording on the same machine. They don't apply to the in-process library encode.
+#[derive(serde::Deserialize)]
+pub struct ResourceConfig {
+    /// Niceness set with setpriority(2), e.g. 10
+    pub nice: Option<i32>,
+    /// I/O scheduling class and priority like ionice(1) (Linux only)
+    pub ionice: Option<IoniceConfig>,
+    /// cgroup v2 directory the processes are moved into, e.g. "/sys/fs/cgroup/encoder". The cpu
+    /// and memory controllers must be delegated to the user running the encoder.
+    pub cgroup: Option<std::path::PathBuf>,
+    /// Written to cpu.max of the cgroup, e.g. "200000 100000" for 2 CPUs
+    pub cpu_max: Option<String>,
+    /// Written to memory.max of the cgroup, e.g. "4G"
+    pub memory_max: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct IoniceConfig {
+    pub class: IoniceClass,
+    /// 0 (highest) to 7 (lowest). Ignored for idle.
+    #[serde(default = "default_ionice_level")]
+    pub level: u8,
+}
+
+fn default_ionice_level() -> u8 {
+    4
+}
+
+#[derive(Clone, Copy, Debug, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IoniceClass {
+    Realtime = 1,
+    BestEffort = 2,
+    Idle = 3,
+}
+
+impl IoniceConfig {
+    /// Value of ioprio_set(2)
+    fn ioprio(&self) -> libc::c_int {
+        const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+        let level = match self.class {
+            IoniceClass::Idle => 0,
+            _ => libc::c_int::from(self.level.min(7)),
+        };
+        (self.class as libc::c_int) << IOPRIO_CLASS_SHIFT | level
+    }
+}
+
+impl ResourceConfig {
+    /// Set up the cgroup and make the command apply the limits to itself before exec
+    pub fn apply(&self, command: &mut tokio::process::Command) -> Result<(), anyhow::Error> {
+        use std::os::unix::io::AsRawFd as _;
+
+        let cgroup_procs = match self.cgroup {
+            Some(ref dir) => {
+                std::fs::create_dir_all(dir)?;
+                if let Some(ref cpu_max) = self.cpu_max {
+                    std::fs::write(dir.join("cpu.max"), cpu_max)?;
+                }
+                if let Some(ref memory_max) = self.memory_max {
+                    std::fs::write(dir.join("memory.max"), memory_max)?;
+                }
+                Some(
+                    std::fs::OpenOptions::new()
+                        .write(true)
+                        .open(dir.join("cgroup.procs"))?,
+                )
+            }
+            None => None,
+        };
+        let nice = self.nice;
+        let ioprio = self.ionice.as_ref().map(IoniceConfig::ioprio);
+        let pre_exec = move || {
+            // Only async-signal-safe calls are allowed after fork
+            if let Some(ref file) = cgroup_procs {
+                // Writing "0" moves the writing process
+                if unsafe { libc::write(file.as_raw_fd(), b"0".as_ptr() as *const libc::c_void, 1) }
+                    == -1
+                {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            if let Some(nice) = nice {
+                if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, nice) } == -1 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            if let Some(ioprio) = ioprio {
+                set_ioprio(ioprio)?;
+            }
+            Ok(())
+        };
+        unsafe {
+            command.pre_exec(pre_exec);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn set_ioprio(ioprio: libc::c_int) -> Result<(), std::io::Error> {
+    const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+    if unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, ioprio) } == -1 {
+        Err(std::io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_ioprio(_ioprio: libc::c_int) -> Result<(), std::io::Error> {
+    Ok(())
+}
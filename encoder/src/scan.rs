@@ -0,0 +1,272 @@
+/// Channels scanned by the scan-channels binary
+#[derive(serde::Deserialize)]
+pub struct ScanConfig {
+    /// Tuner channels, e.g. "GR/27" for Mirakurun or "27" for tuner_command
+    #[serde(default)]
+    pub channels: Vec<String>,
+    /// Seconds of each channel captured to collect NIT, SDT and PAT
+    #[serde(default = "default_duration")]
+    pub duration: u64,
+    /// Capture each channel with this command instead of Mirakurun, e.g.
+    /// ["recpt1", "--b25", "--strip", "{channel}", "{duration}", "{output}"]
+    pub tuner_command: Option<Vec<String>>,
+    /// The service map written by scan-channels and read by load_config
+    #[serde(default = "default_path")]
+    pub path: std::path::PathBuf,
+}
+
+fn default_duration() -> u64 {
+    10
+}
+
+fn default_path() -> std::path::PathBuf {
+    std::path::PathBuf::from("services.toml")
+}
+
+/// Services found by the channel scan, which are matched by keys of [channels] and the naming
+/// template
+#[derive(Debug, Default, serde::Deserialize, serde::Serialize)]
+pub struct ServiceMap {
+    #[serde(default)]
+    pub services: Vec<ScannedService>,
+}
+
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+pub struct ScannedService {
+    /// Tuner channel carrying the service
+    pub channel: String,
+    pub network_id: u16,
+    pub transport_stream_id: u16,
+    pub service_id: u16,
+    pub name: Option<String>,
+    /// service_type of the service_descriptor
+    #[serde(default)]
+    pub service_type: Option<u8>,
+    /// Remote control key of terrestrial broadcasting
+    pub remote_control_key_id: Option<u8>,
+    /// ts_name of the TS information descriptor, e.g. the broadcaster of the channel
+    #[serde(default)]
+    pub ts_name: Option<String>,
+    /// network_name of the network name descriptor in NIT
+    #[serde(default)]
+    pub network_name: Option<String>,
+    /// 1-segment service received by partial reception of terrestrial broadcasting
+    #[serde(default)]
+    pub partial_reception: bool,
+    /// 3-digit channel number
+    pub channel_number: u16,
+}
+
+impl ServiceMap {
+    pub fn load(path: &std::path::Path) -> Result<Self, anyhow::Error> {
+        Ok(toml::from_slice(&std::fs::read(path)?)?)
+    }
+
+    pub fn save(&self, path: &std::path::Path) -> Result<(), anyhow::Error> {
+        std::fs::write(path, toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// The first service with the service_id, preferring television and radio ones.
+    /// service_id is not unique across networks, but recordings don't tell their network.
+    pub fn find(&self, service_id: u16) -> Option<&ScannedService> {
+        let mut services = self
+            .services
+            .iter()
+            .filter(|service| service.service_id == service_id);
+        let first = services.next()?;
+        if first.is_broadcast() {
+            return Some(first);
+        }
+        Some(
+            services
+                .find(|service| service.is_broadcast())
+                .unwrap_or(first),
+        )
+    }
+}
+
+impl ScannedService {
+    pub fn service_type(&self) -> Option<tsutils::sdt::ServiceType> {
+        self.service_type.map(tsutils::sdt::ServiceType::from)
+    }
+
+    /// Television or radio services, which are recorded unlike data services. Services
+    /// scanned without service_type are assumed to be.
+    pub fn is_broadcast(&self) -> bool {
+        self.service_type()
+            .is_none_or(|service_type| service_type.is_television() || service_type.is_radio())
+    }
+}
+
+/// Terrestrial networks use network_id 0x7880 - 0x7fe8
+fn is_terrestrial(network_id: u16) -> bool {
+    (0x7880..=0x7fe8).contains(&network_id)
+}
+
+/// Collect services of the channel from NIT, SDT and PAT in the TS
+pub fn scan_ts(
+    channel: &str,
+    ts_path: &std::path::Path,
+) -> Result<Vec<ScannedService>, anyhow::Error> {
+    let reader = std::io::BufReader::new(std::fs::File::open(ts_path)?);
+    let mut tracker = tsutils::filter::ProgramTracker::new();
+    let mut sdt_assembler = tsutils::psi::SectionAssembler::new();
+    let mut nit_assembler = tsutils::psi::SectionAssembler::new();
+    let mut sdt = None;
+    let mut ts_informations = std::collections::HashMap::new();
+    let mut network_name = None;
+    let mut partial_reception_services = std::collections::HashMap::new();
+
+    for buf in tsutils::packet::ts_packets(reader) {
+        let buf = buf?;
+        if buf[0] != 0x47 || (buf[1] & 0b10000000) != 0 {
+            continue;
+        }
+        let packet = tsutils::TsPacket::new(&buf);
+        match packet.pid {
+            0x0010 => {
+                for section in nit_assembler.push(&packet) {
+                    if let Ok(nit) = tsutils::NetworkInformationTable::parse(&section) {
+                        if !nit.is_actual() {
+                            continue;
+                        }
+                        if let Some(descriptor) = nit.network_name_descriptor() {
+                            network_name =
+                                Some(tsutils::arib_string::decode(descriptor.network_name));
+                        }
+                        for ts in &nit.transport_streams {
+                            if let Some(descriptor) = ts.ts_information_descriptor() {
+                                ts_informations.insert(
+                                    ts.transport_stream_id,
+                                    (
+                                        descriptor.remote_control_key_id,
+                                        tsutils::arib_string::decode(descriptor.ts_name),
+                                    ),
+                                );
+                            }
+                            if let Some(descriptor) = ts.partial_reception_descriptor() {
+                                partial_reception_services
+                                    .insert(ts.transport_stream_id, descriptor.service_ids);
+                            }
+                        }
+                    }
+                }
+            }
+            0x0011 => {
+                for section in sdt_assembler.push(&packet) {
+                    if let Ok(parsed) = tsutils::ServiceDescriptionTable::parse(&section) {
+                        if parsed.is_actual() && sdt.is_none() {
+                            sdt = Some((
+                                parsed.original_network_id,
+                                parsed.transport_stream_id,
+                                parsed
+                                    .services
+                                    .iter()
+                                    .map(|service| {
+                                        (
+                                            service.service_id,
+                                            service.service_descriptor().map(|descriptor| {
+                                                tsutils::arib_string::decode(
+                                                    descriptor.service_name,
+                                                )
+                                            }),
+                                            service.service_type(),
+                                        )
+                                    })
+                                    .collect::<Vec<_>>(),
+                            ));
+                        }
+                    }
+                }
+            }
+            _ => {
+                tracker.push(&packet)?;
+            }
+        }
+    }
+
+    let (network_id, transport_stream_id, services) =
+        sdt.ok_or_else(|| anyhow::anyhow!("SDT is not found in {}", ts_path.display()))?;
+    let programs = tracker
+        .pat()
+        .map(|pat| pat.program_map.values().cloned().collect::<Vec<_>>())
+        .unwrap_or_default();
+    let (remote_control_key_id, ts_name) = match ts_informations.remove(&transport_stream_id) {
+        Some((key, ts_name)) => (Some(key), Some(ts_name)),
+        None => (None, None),
+    };
+    let partial_reception_services = partial_reception_services
+        .remove(&transport_stream_id)
+        .unwrap_or_default();
+    let mut scanned = services
+        .into_iter()
+        // SDT also lists services which are not on air, such as temporary ones
+        .filter(|(service_id, _, _)| programs.is_empty() || programs.contains(service_id))
+        // Engineering services carry software updates of receivers
+        .filter(|(_, _, service_type)| !service_type.is_some_and(|t| t.is_engineering()))
+        .map(|(service_id, name, service_type)| ScannedService {
+            channel: channel.to_owned(),
+            network_id,
+            transport_stream_id,
+            service_id,
+            name,
+            service_type: service_type.map(u8::from),
+            remote_control_key_id,
+            ts_name: ts_name.clone(),
+            network_name: network_name.clone(),
+            partial_reception: partial_reception_services.contains(&service_id),
+            channel_number: match remote_control_key_id {
+                Some(key) if is_terrestrial(network_id) => {
+                    key as u16 * 10 + (service_id & 0b111) + 1
+                }
+                _ => service_id,
+            },
+        })
+        .collect::<Vec<_>>();
+    scanned.sort_by_key(|service| service.service_id);
+    Ok(scanned)
+}
+
+impl ScanConfig {
+    /// Capture the whole TS of the channel into ts_path for the duration
+    pub async fn capture(
+        &self,
+        mirakurun: Option<&crate::mirakurun::Client>,
+        channel: &str,
+        ts_path: &std::path::Path,
+    ) -> Result<(), anyhow::Error> {
+        match (&self.tuner_command, mirakurun) {
+            (Some(command), _) => {
+                let args = command
+                    .iter()
+                    .map(|arg| {
+                        arg.replace("{channel}", channel)
+                            .replace("{duration}", &self.duration.to_string())
+                            .replace("{output}", &ts_path.display().to_string())
+                    })
+                    .collect::<Vec<_>>();
+                let (program, args) = args
+                    .split_first()
+                    .ok_or_else(|| anyhow::anyhow!("tuner_command is empty"))?;
+                let status = tokio::process::Command::new(program)
+                    .args(args)
+                    .status()
+                    .await?;
+                if !status.success() {
+                    return Err(anyhow::anyhow!("{} failed: {}", program, status));
+                }
+            }
+            (None, Some(client)) => {
+                let until = chrono::Utc::now() + chrono::Duration::seconds(self.duration as i64);
+                client.stream_channel(channel, ts_path, until).await?;
+            }
+            (None, None) => {
+                return Err(anyhow::anyhow!(
+                    "either scan.tuner_command or [mirakurun] is required"
+                ));
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,484 @@
+const SKIPPABLE_MAGIC_NUMBER: u32 = 0x184D_2A5E;
+const SEEKABLE_MAGIC_NUMBER: u32 = 0x8F92_EAB1;
+const FOOTER_SIZE: u64 = 9;
+const PACKET_SIZE: usize = 188;
+
+/// Decompressed size of each frame
+pub const DEFAULT_FRAME_SIZE: usize = PACKET_SIZE * 4096;
+
+/// Write TS archives in the seekable format of zstd (contrib/seekable_format), where packets are
+/// compressed into independent frames followed by a seek table so that a part of the archive is
+/// read without decompressing the rest. Frames of frame_size bytes are aligned to packets when
+/// the input starts at a packet boundary. finish() must be called to write the seek table.
+pub struct SeekableWriter<W> {
+    inner: W,
+    level: i32,
+    frame_size: usize,
+    buf: Vec<u8>,
+    /// (compressed size, decompressed size) of the written frames
+    entries: Vec<(u32, u32)>,
+}
+
+impl<W> SeekableWriter<W>
+where
+    W: std::io::Write,
+{
+    pub fn new(inner: W, level: i32) -> Self {
+        Self::with_frame_size(inner, level, DEFAULT_FRAME_SIZE)
+    }
+
+    /// frame_size is rounded down to packets
+    pub fn with_frame_size(inner: W, level: i32, frame_size: usize) -> Self {
+        let frame_size = std::cmp::max(frame_size / PACKET_SIZE, 1) * PACKET_SIZE;
+        Self {
+            inner,
+            level,
+            frame_size,
+            buf: Vec::with_capacity(frame_size),
+            entries: vec![],
+        }
+    }
+
+    fn write_frame(&mut self, len: usize) -> std::io::Result<()> {
+        let compressed = zstd::encode_all(&self.buf[..len], self.level)?;
+        self.inner.write_all(&compressed)?;
+        self.entries.push((compressed.len() as u32, len as u32));
+        self.buf.drain(..len);
+        Ok(())
+    }
+
+    /// Write the last frame and the seek table, and return the inner writer
+    pub fn finish(mut self) -> std::io::Result<W> {
+        if !self.buf.is_empty() {
+            self.write_frame(self.buf.len())?;
+        }
+        let table_size = self.entries.len() as u64 * 8 + FOOTER_SIZE;
+        let mut table = Vec::with_capacity(8 + table_size as usize);
+        table.extend_from_slice(&SKIPPABLE_MAGIC_NUMBER.to_le_bytes());
+        table.extend_from_slice(&(table_size as u32).to_le_bytes());
+        for (compressed_size, decompressed_size) in &self.entries {
+            table.extend_from_slice(&compressed_size.to_le_bytes());
+            table.extend_from_slice(&decompressed_size.to_le_bytes());
+        }
+        table.extend_from_slice(&(self.entries.len() as u32).to_le_bytes());
+        // Without checksums
+        table.push(0);
+        table.extend_from_slice(&SEEKABLE_MAGIC_NUMBER.to_le_bytes());
+        self.inner.write_all(&table)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W> std::io::Write for SeekableWriter<W>
+where
+    W: std::io::Write,
+{
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        self.buf.extend_from_slice(data);
+        while self.buf.len() >= self.frame_size {
+            self.write_frame(self.frame_size)?;
+        }
+        Ok(data.len())
+    }
+
+    /// Frames are written only when they are full or finished
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[derive(Debug)]
+struct Frame {
+    compressed_offset: u64,
+    compressed_size: u32,
+    decompressed_offset: u64,
+    decompressed_size: u32,
+}
+
+/// Decompressed view of an archive, which decompresses only the frames being read. It can be
+/// passed to tsutils::packet::ts_packets.
+pub struct SeekableReader<R> {
+    inner: R,
+    frames: Vec<Frame>,
+    len: u64,
+    position: u64,
+    /// Index and content of the last decompressed frame
+    cache: Option<(usize, Vec<u8>)>,
+}
+
+fn read_u32<R>(reader: &mut R) -> std::io::Result<u32>
+where
+    R: std::io::Read,
+{
+    let mut buf = [0; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn invalid_data(message: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
+
+impl<R> SeekableReader<R>
+where
+    R: std::io::Read + std::io::Seek,
+{
+    /// Read the seek table at the end of the archive
+    pub fn new(mut inner: R) -> std::io::Result<Self> {
+        use std::io::SeekFrom;
+
+        let end = inner.seek(SeekFrom::End(0))?;
+        if end < 8 + FOOTER_SIZE {
+            return Err(invalid_data("too short for a seekable zstd archive"));
+        }
+        inner.seek(SeekFrom::Start(end - FOOTER_SIZE))?;
+        let num_frames = read_u32(&mut inner)? as u64;
+        let mut descriptor = [0; 1];
+        inner.read_exact(&mut descriptor)?;
+        if read_u32(&mut inner)? != SEEKABLE_MAGIC_NUMBER {
+            return Err(invalid_data("seek table is not found"));
+        }
+        let entry_size = if descriptor[0] & 0b1000_0000 != 0 {
+            12
+        } else {
+            8
+        };
+        let table_size = num_frames * entry_size + FOOTER_SIZE;
+        if end < 8 + table_size {
+            return Err(invalid_data("seek table is truncated"));
+        }
+        inner.seek(SeekFrom::Start(end - table_size - 8))?;
+        if read_u32(&mut inner)? != SKIPPABLE_MAGIC_NUMBER
+            || read_u32(&mut inner)? as u64 != table_size
+        {
+            return Err(invalid_data("seek table is corrupted"));
+        }
+        let mut frames = Vec::with_capacity(num_frames as usize);
+        let mut compressed_offset = 0;
+        let mut decompressed_offset = 0;
+        for _ in 0..num_frames {
+            let compressed_size = read_u32(&mut inner)?;
+            let decompressed_size = read_u32(&mut inner)?;
+            if entry_size == 12 {
+                // Checksums are verified by the frames themselves
+                read_u32(&mut inner)?;
+            }
+            frames.push(Frame {
+                compressed_offset,
+                compressed_size,
+                decompressed_offset,
+                decompressed_size,
+            });
+            compressed_offset += compressed_size as u64;
+            decompressed_offset += decompressed_size as u64;
+        }
+        if compressed_offset > end - table_size - 8 {
+            return Err(invalid_data("frames exceed the archive"));
+        }
+        Ok(Self {
+            inner,
+            frames,
+            len: decompressed_offset,
+            position: 0,
+            cache: None,
+        })
+    }
+
+    /// Decompressed size
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn load_frame(&mut self, index: usize) -> std::io::Result<()> {
+        if self.cache.as_ref().is_some_and(|(i, _)| *i == index) {
+            return Ok(());
+        }
+        let frame = &self.frames[index];
+        self.inner
+            .seek(std::io::SeekFrom::Start(frame.compressed_offset))?;
+        let mut compressed = vec![0; frame.compressed_size as usize];
+        self.inner.read_exact(&mut compressed)?;
+        let decompressed = zstd::decode_all(&compressed[..])?;
+        if decompressed.len() != frame.decompressed_size as usize {
+            return Err(invalid_data("frame size differs from the seek table"));
+        }
+        self.cache = Some((index, decompressed));
+        Ok(())
+    }
+}
+
+impl<R> std::io::Read for SeekableReader<R>
+where
+    R: std::io::Read + std::io::Seek,
+{
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.position >= self.len || buf.is_empty() {
+            return Ok(0);
+        }
+        let position = self.position;
+        let index = self.frames.partition_point(|frame| {
+            frame.decompressed_offset + frame.decompressed_size as u64 <= position
+        });
+        self.load_frame(index)?;
+        let offset = (position - self.frames[index].decompressed_offset) as usize;
+        let data = &self.cache.as_ref().unwrap().1[offset..];
+        let n = std::cmp::min(data.len(), buf.len());
+        buf[..n].copy_from_slice(&data[..n]);
+        self.position += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R> std::io::Seek for SeekableReader<R>
+where
+    R: std::io::Read + std::io::Seek,
+{
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        let position = match pos {
+            std::io::SeekFrom::Start(offset) => offset as i64,
+            std::io::SeekFrom::Current(offset) => self.position as i64 + offset,
+            std::io::SeekFrom::End(offset) => self.len as i64 + offset,
+        };
+        if position < 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "seek before the start",
+            ));
+        }
+        self.position = position as u64;
+        Ok(self.position)
+    }
+}
+
+/// Open a TS, or its archive when the path ends with ".zst"
+pub fn open(path: &std::path::Path) -> std::io::Result<Box<dyn std::io::Read + Send>> {
+    let file = std::fs::File::open(path)?;
+    if path.extension().is_some_and(|ext| ext == "zst") {
+        Ok(Box::new(SeekableReader::new(file)?))
+    } else {
+        Ok(Box::new(file))
+    }
+}
+
+/// Compress the TS into dest through a partial file so that an interrupted compression is not
+/// taken for an archive
+pub fn compress(
+    path: &std::path::Path,
+    dest: &std::path::Path,
+    level: i32,
+) -> Result<(), anyhow::Error> {
+    let part_path = dest.with_extension("zst.part");
+    let mut reader = std::io::BufReader::new(std::fs::File::open(path)?);
+    let mut writer = SeekableWriter::new(
+        std::io::BufWriter::new(std::fs::File::create(&part_path)?),
+        level,
+    );
+    std::io::copy(&mut reader, &mut writer)?;
+    writer
+        .finish()?
+        .into_inner()
+        .map_err(|e| e.into_error())?
+        .sync_all()?;
+    std::fs::rename(&part_path, dest)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read as _, Seek as _, SeekFrom, Write as _};
+
+    const FRAME_SIZE: usize = PACKET_SIZE * 4;
+
+    fn data(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 7 % 251) as u8).collect()
+    }
+
+    fn compress(data: &[u8]) -> Vec<u8> {
+        let mut writer = SeekableWriter::with_frame_size(vec![], 3, FRAME_SIZE);
+        // In uneven writes so that frames are cut from the buffer
+        for chunk in data.chunks(1000) {
+            writer.write_all(chunk).unwrap();
+        }
+        writer.finish().unwrap()
+    }
+
+    fn reader(archive: Vec<u8>) -> SeekableReader<std::io::Cursor<Vec<u8>>> {
+        SeekableReader::new(std::io::Cursor::new(archive)).unwrap()
+    }
+
+    fn read_all<R>(reader: &mut R) -> Vec<u8>
+    where
+        R: std::io::Read,
+    {
+        let mut buf = vec![];
+        reader.read_to_end(&mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn empty() {
+        let archive = compress(&[]);
+        // Only the seek table without frames
+        assert_eq!(archive.len() as u64, 8 + FOOTER_SIZE);
+        let mut reader = reader(archive);
+        assert_eq!(reader.frames.len(), 0);
+        assert!(reader.is_empty());
+        assert_eq!(read_all(&mut reader), b"");
+        assert_eq!(reader.seek(SeekFrom::End(0)).unwrap(), 0);
+        assert_eq!(read_all(&mut reader), b"");
+    }
+
+    #[test]
+    fn smaller_than_frame() {
+        let data = data(FRAME_SIZE - 1);
+        let mut reader = reader(compress(&data));
+        assert_eq!(reader.frames.len(), 1);
+        assert_eq!(reader.len(), data.len() as u64);
+        assert_eq!(read_all(&mut reader), data);
+        // Readable by the plain decoder too
+        assert_eq!(zstd::decode_all(&compress(&data)[..]).unwrap(), data);
+    }
+
+    #[test]
+    fn exact_frames() {
+        let data = data(FRAME_SIZE * 3);
+        let mut reader = reader(compress(&data));
+        assert_eq!(reader.frames.len(), 3);
+        for (i, frame) in reader.frames.iter().enumerate() {
+            assert_eq!(frame.decompressed_offset, (FRAME_SIZE * i) as u64);
+            assert_eq!(frame.decompressed_size as usize, FRAME_SIZE);
+        }
+        assert_eq!(read_all(&mut reader), data);
+        assert_eq!(zstd::decode_all(&compress(&data)[..]).unwrap(), data);
+    }
+
+    #[test]
+    fn seek_across_frames() {
+        let data = data(FRAME_SIZE * 3 + 100);
+        let mut reader = reader(compress(&data));
+        assert_eq!(reader.frames.len(), 4);
+
+        // Read over a frame boundary
+        let start = FRAME_SIZE - 10;
+        assert_eq!(
+            reader.seek(SeekFrom::Start(start as u64)).unwrap(),
+            start as u64
+        );
+        let mut buf = [0; 20];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf[..], &data[start..start + 20]);
+
+        // Exactly at boundaries, backward and forward
+        for &position in &[FRAME_SIZE * 2, 0, FRAME_SIZE * 3, FRAME_SIZE] {
+            reader.seek(SeekFrom::Start(position as u64)).unwrap();
+            let mut buf = [0; 1];
+            reader.read_exact(&mut buf).unwrap();
+            assert_eq!(buf[0], data[position], "at {}", position);
+        }
+
+        assert_eq!(
+            reader.seek(SeekFrom::Current(-2)).unwrap(),
+            FRAME_SIZE as u64 - 1
+        );
+        reader.read_exact(&mut buf[..2]).unwrap();
+        assert_eq!(&buf[..2], &data[FRAME_SIZE - 1..FRAME_SIZE + 1]);
+
+        assert_eq!(
+            reader.seek(SeekFrom::End(-50)).unwrap(),
+            data.len() as u64 - 50
+        );
+        assert_eq!(read_all(&mut reader), &data[data.len() - 50..]);
+
+        // Past the end reads nothing, and before the start is an error
+        reader.seek(SeekFrom::End(10)).unwrap();
+        assert_eq!(read_all(&mut reader), b"");
+        assert!(reader
+            .seek(SeekFrom::Current(-(data.len() as i64) - 11))
+            .is_err());
+    }
+
+    fn open_error(archive: Vec<u8>) -> std::io::Error {
+        match SeekableReader::new(std::io::Cursor::new(archive)) {
+            Ok(_) => panic!("opened a broken archive"),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn truncated_seek_table() {
+        let archive = compress(&data(FRAME_SIZE * 2));
+        let len = archive.len();
+        // Footer or the header of the table is cut
+        for &cut in &[1, 4, FOOTER_SIZE as usize, 8 * 2 + FOOTER_SIZE as usize] {
+            let error = open_error(archive[..len - cut].to_vec());
+            assert_eq!(error.kind(), std::io::ErrorKind::InvalidData, "{}", cut);
+        }
+        // Frames are cut
+        let table_len = 8 + 8 * 2 + FOOTER_SIZE as usize;
+        let mut truncated = archive[..len - table_len - 1].to_vec();
+        truncated.extend_from_slice(&archive[len - table_len..]);
+        assert_eq!(
+            open_error(truncated).kind(),
+            std::io::ErrorKind::InvalidData
+        );
+        assert_eq!(
+            open_error(archive[..8].to_vec()).kind(),
+            std::io::ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn corrupted_seek_table() {
+        let archive = compress(&data(FRAME_SIZE * 2));
+        let len = archive.len();
+        let table_len = 8 + 8 * 2 + FOOTER_SIZE as usize;
+
+        // Seekable magic number
+        let mut corrupted = archive.clone();
+        corrupted[len - 1] ^= 0xff;
+        assert_eq!(
+            open_error(corrupted).kind(),
+            std::io::ErrorKind::InvalidData
+        );
+        // Skippable magic number
+        let mut corrupted = archive.clone();
+        corrupted[len - table_len] ^= 0xff;
+        assert_eq!(
+            open_error(corrupted).kind(),
+            std::io::ErrorKind::InvalidData
+        );
+        // Number of frames
+        let mut corrupted = archive.clone();
+        corrupted[len - FOOTER_SIZE as usize] = 3;
+        assert_eq!(
+            open_error(corrupted).kind(),
+            std::io::ErrorKind::InvalidData
+        );
+        // Compressed size beyond the frames
+        let mut corrupted = archive.clone();
+        corrupted[len - table_len + 8 + 3] = 0x7f;
+        assert_eq!(
+            open_error(corrupted).kind(),
+            std::io::ErrorKind::InvalidData
+        );
+
+        // Decompressed size differs from the frame, which is found when the frame is read
+        let mut corrupted = archive.clone();
+        corrupted[len - table_len + 8 + 4] ^= 0x01;
+        let mut reader = reader(corrupted);
+        let mut buf = [0; 1];
+        assert_eq!(
+            reader.read(&mut buf).unwrap_err().kind(),
+            std::io::ErrorKind::InvalidData
+        );
+        // The other frame is intact
+        reader.seek(SeekFrom::End(-1)).unwrap();
+        reader.read_exact(&mut buf).unwrap();
+    }
+}
@@ -0,0 +1,103 @@
+/// Summary of a completed job written next to each output as "{output}.json"
+#[derive(serde::Serialize)]
+pub struct Sidecar<'a> {
+    pub source_path: &'a std::path::Path,
+    /// None for the profile in [encoder] section
+    pub profile: Option<&'a str>,
+    /// "library" or the description of the ffmpeg attempt which succeeded
+    pub method: &'a str,
+    pub input_args: &'a [String],
+    pub output_args: &'a [String],
+    /// In seconds
+    pub expected_duration: f64,
+    pub durations: Durations,
+    pub scores: &'a crate::verify::Scores,
+    pub integrity: Option<Integrity>,
+    /// None unless precheck is configured
+    pub precheck: Option<&'a crate::PrecheckReport>,
+    /// Reported by the tuner while recording the source, without the samples
+    pub signal: Option<crate::tuner::SignalReport>,
+    /// Wall time of the whole job in seconds
+    pub encode_time: f64,
+    pub sha256: Option<&'a str>,
+    /// key_id of the encryption applied to the uploaded and transferred copies
+    pub encryption_key_id: Option<&'a str>,
+    /// The event continued on another service, to merge the output with the recording of it
+    pub relay: Option<Relay<'a>>,
+}
+
+/// Event relay of the main event of the source
+#[derive(serde::Serialize)]
+pub struct Relay<'a> {
+    /// The main event of the source
+    pub event_id: u16,
+    pub to: &'a crate::analysis::EventRelay,
+}
+
+impl<'a> Relay<'a> {
+    pub fn new(info: &'a crate::analysis::SourceInfo) -> Option<Self> {
+        let event = info.main_event()?;
+        event.relays.first().map(|to| Self {
+            event_id: event.event_id,
+            to,
+        })
+    }
+}
+
+/// Durations of the output in seconds
+#[derive(serde::Serialize)]
+pub struct Durations {
+    pub container: f64,
+    pub video: Option<f64>,
+    pub audio: Option<f64>,
+}
+
+impl From<&crate::verify::StreamDurations> for Durations {
+    fn from(durations: &crate::verify::StreamDurations) -> Self {
+        let secs = |micro: i64| micro as f64 / 1_000_000.0;
+        Self {
+            container: secs(durations.container),
+            video: durations.video.map(secs),
+            audio: durations.audio.map(secs),
+        }
+    }
+}
+
+/// tsutils integrity report of the source TS
+#[derive(serde::Serialize)]
+pub struct Integrity {
+    pub packets: u64,
+    pub sync_errors: u64,
+    pub transport_errors: u64,
+    pub drops: u64,
+    pub scrambled_packets: u64,
+    /// In seconds, measured with PCR
+    pub duration: f64,
+}
+
+impl From<&tsutils::integrity::IntegrityReport> for Integrity {
+    fn from(report: &tsutils::integrity::IntegrityReport) -> Self {
+        Self {
+            packets: report.packets,
+            sync_errors: report.sync_errors,
+            transport_errors: report.transport_errors,
+            drops: report.drops,
+            scrambled_packets: report.scrambled_packets,
+            duration: report.duration_secs(),
+        }
+    }
+}
+
+impl<'a> Sidecar<'a> {
+    pub fn path(output_path: &std::path::Path) -> std::path::PathBuf {
+        let mut path = output_path.as_os_str().to_owned();
+        path.push(".json");
+        path.into()
+    }
+
+    pub fn write(&self, output_path: &std::path::Path) -> Result<(), anyhow::Error> {
+        let file = std::fs::File::create(Self::path(output_path))?;
+        serde_json::to_writer_pretty(std::io::BufWriter::new(file), self)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,78 @@
+/// Watch the interruption notice of EC2 spot instances through the instance metadata service.
+/// The notice is given two minutes before the interruption.
+#[derive(Clone, serde::Deserialize)]
+pub struct SpotConfig {
+    /// Seconds between polls
+    #[serde(default = "default_poll_interval")]
+    pub poll_interval: u64,
+    #[serde(default = "default_endpoint")]
+    pub endpoint: String,
+}
+
+fn default_poll_interval() -> u64 {
+    5
+}
+
+fn default_endpoint() -> String {
+    "http://169.254.169.254".to_owned()
+}
+
+impl SpotConfig {
+    /// Resolve with the notice once the instance is scheduled to be interrupted
+    pub async fn wait_for_interruption(self) -> String {
+        let client = hyper::Client::new();
+        let mut interval =
+            tokio::time::interval(tokio::time::Duration::from_secs(self.poll_interval));
+        loop {
+            interval.tick().await;
+            match self.poll(&client).await {
+                Ok(Some(notice)) => return notice,
+                Ok(None) => {}
+                Err(e) => tracing::warn!("Failed to poll the spot interruption notice: {}", e),
+            }
+        }
+    }
+
+    async fn poll(
+        &self,
+        client: &hyper::Client<hyper::client::HttpConnector>,
+    ) -> Result<Option<String>, anyhow::Error> {
+        // IMDSv2
+        let resp = client
+            .request(
+                hyper::Request::put(format!("{}/latest/api/token", self.endpoint))
+                    .header("x-aws-ec2-metadata-token-ttl-seconds", "60")
+                    .body(hyper::Body::empty())?,
+            )
+            .await?;
+        if !resp.status().is_success() {
+            return Err(anyhow::anyhow!(
+                "IMDS token request failed with {}",
+                resp.status()
+            ));
+        }
+        let token = hyper::body::to_bytes(resp.into_body()).await?;
+
+        let resp = client
+            .request(
+                hyper::Request::get(format!(
+                    "{}/latest/meta-data/spot/instance-action",
+                    self.endpoint
+                ))
+                .header("x-aws-ec2-metadata-token", token.as_ref())
+                .body(hyper::Body::empty())?,
+            )
+            .await?;
+        match resp.status() {
+            hyper::StatusCode::NOT_FOUND => Ok(None),
+            status if status.is_success() => {
+                let body = hyper::body::to_bytes(resp.into_body()).await?;
+                Ok(Some(String::from_utf8_lossy(&body).into_owned()))
+            }
+            status => Err(anyhow::anyhow!(
+                "IMDS instance-action request failed with {}",
+                status
+            )),
+        }
+    }
+}
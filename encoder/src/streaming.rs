@@ -0,0 +1,135 @@
+/// Write an HLS or DASH ladder into the directory "{source stem}{suffix}" instead of a single file
+#[derive(serde::Deserialize)]
+pub struct StreamingConfig {
+    #[serde(default)]
+    pub format: StreamingFormat,
+    /// In seconds
+    #[serde(default = "default_segment_duration")]
+    pub segment_duration: f64,
+    /// Segment file name without extension. {variant} is replaced with the variant name and
+    /// {number} with the 5-digit sequence number.
+    #[serde(default = "default_segment_template")]
+    pub segment_template: String,
+    pub variants: Vec<Variant>,
+}
+
+#[derive(Clone, Copy, Default, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StreamingFormat {
+    #[default]
+    Hls,
+    Dash,
+}
+
+/// A rendition in the ladder. Each variant has the first video and audio stream of the source.
+#[derive(serde::Deserialize)]
+pub struct Variant {
+    pub name: String,
+    /// e.g. "1280x720"
+    pub size: Option<String>,
+    /// e.g. "3M"
+    pub video_bitrate: Option<String>,
+    /// e.g. "128k"
+    pub audio_bitrate: Option<String>,
+}
+
+fn default_segment_duration() -> f64 {
+    6.0
+}
+
+fn default_segment_template() -> String {
+    "{variant}_{number}".to_owned()
+}
+
+impl StreamingConfig {
+    /// The playlist (HLS) or manifest (DASH) referring to all variants
+    pub fn playlist_path(&self, dir: &std::path::Path) -> std::path::PathBuf {
+        match self.format {
+            StreamingFormat::Hls => dir.join("master.m3u8"),
+            StreamingFormat::Dash => dir.join("manifest.mpd"),
+        }
+    }
+
+    /// Output arguments and the path given to ffmpeg. Do not put -map in ffmpeg_args.
+    pub fn output_args(&self, dir: &std::path::Path) -> (Vec<String>, std::path::PathBuf) {
+        let mut args = vec![];
+        for (i, variant) in self.variants.iter().enumerate() {
+            args.extend(vec![
+                "-map".to_owned(),
+                "0:v:0".to_owned(),
+                "-map".to_owned(),
+                "0:a:0".to_owned(),
+            ]);
+            if let Some(ref size) = variant.size {
+                args.push(format!("-s:v:{}", i));
+                args.push(size.clone());
+            }
+            if let Some(ref bitrate) = variant.video_bitrate {
+                args.push(format!("-b:v:{}", i));
+                args.push(bitrate.clone());
+            }
+            if let Some(ref bitrate) = variant.audio_bitrate {
+                args.push(format!("-b:a:{}", i));
+                args.push(bitrate.clone());
+            }
+        }
+
+        match self.format {
+            StreamingFormat::Hls => {
+                let var_stream_map = self
+                    .variants
+                    .iter()
+                    .enumerate()
+                    .map(|(i, variant)| format!("v:{},a:{},name:{}", i, i, variant.name))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                let segment = self
+                    .segment_template
+                    .replace("{variant}", "%v")
+                    .replace("{number}", "%05d");
+                args.extend(vec![
+                    "-f".to_owned(),
+                    "hls".to_owned(),
+                    "-hls_time".to_owned(),
+                    self.segment_duration.to_string(),
+                    "-hls_playlist_type".to_owned(),
+                    "vod".to_owned(),
+                    "-hls_segment_filename".to_owned(),
+                    dir.join(format!("{}.ts", segment))
+                        .to_str()
+                        .unwrap()
+                        .to_owned(),
+                    "-master_pl_name".to_owned(),
+                    "master.m3u8".to_owned(),
+                    "-var_stream_map".to_owned(),
+                    var_stream_map,
+                ]);
+                (args, dir.join("%v.m3u8"))
+            }
+            StreamingFormat::Dash => {
+                // DASH has no variant name, so the representation id is used
+                let segment = self
+                    .segment_template
+                    .replace("{variant}", "$RepresentationID$")
+                    .replace("{number}", "$Number%05d$");
+                let init = self
+                    .segment_template
+                    .replace("{variant}", "$RepresentationID$")
+                    .replace("{number}", "init");
+                args.extend(vec![
+                    "-f".to_owned(),
+                    "dash".to_owned(),
+                    "-seg_duration".to_owned(),
+                    self.segment_duration.to_string(),
+                    "-adaptation_sets".to_owned(),
+                    "id=0,streams=v id=1,streams=a".to_owned(),
+                    "-init_seg_name".to_owned(),
+                    format!("{}.m4s", init),
+                    "-media_seg_name".to_owned(),
+                    format!("{}.m4s", segment),
+                ]);
+                (args, self.playlist_path(dir))
+            }
+        }
+    }
+}
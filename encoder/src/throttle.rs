@@ -0,0 +1,93 @@
+/// Hold off new encodes while the box is busy recording. Running encodes are not interrupted.
+#[derive(serde::Deserialize)]
+pub struct ThrottleConfig {
+    /// Local time ranges like "19:00-26:00". Hours beyond 24 continue into the next day.
+    #[serde(default)]
+    pub windows: Vec<String>,
+    /// 1-minute load average
+    pub max_load: Option<f64>,
+    /// Busy while a file in this directory was modified within active_secs, e.g. a TS being
+    /// recorded
+    pub recording_dir: Option<std::path::PathBuf>,
+    #[serde(default = "default_active_secs")]
+    pub active_secs: u64,
+    /// Seconds between checks while throttled
+    #[serde(default = "default_poll_interval")]
+    pub poll_interval: u64,
+}
+
+fn default_active_secs() -> u64 {
+    60
+}
+
+fn default_poll_interval() -> u64 {
+    60
+}
+
+/// Minutes of the day of "HH:MM"
+fn parse_minutes(s: &str) -> Option<u32> {
+    let (hours, minutes) = s.trim().split_once(':')?;
+    let hours: u32 = hours.parse().ok()?;
+    let minutes: u32 = minutes.parse().ok()?;
+    if hours < 48 && minutes < 60 {
+        Some(hours * 60 + minutes)
+    } else {
+        None
+    }
+}
+
+/// (start, end) in minutes of the day of "HH:MM-HH:MM"
+pub(crate) fn parse_window(window: &str) -> Option<(u32, u32)> {
+    let (start, end) = window.split_once('-')?;
+    Some((parse_minutes(start)?, parse_minutes(end)?))
+}
+
+/// Whether the minutes of the day are in the window, which may continue into the next day
+pub(crate) fn in_window((start, end): (u32, u32), minutes: u32) -> bool {
+    (start <= minutes && minutes < end) || (start <= minutes + 24 * 60 && minutes + 24 * 60 < end)
+}
+
+fn load_average() -> Result<f64, anyhow::Error> {
+    let mut loadavg = [0.0; 1];
+    if unsafe { libc::getloadavg(loadavg.as_mut_ptr(), 1) } == 1 {
+        Ok(loadavg[0])
+    } else {
+        Err(anyhow::anyhow!("getloadavg failed"))
+    }
+}
+
+impl ThrottleConfig {
+    /// Why new encodes should wait now, if any
+    pub fn reason(&self) -> Result<Option<String>, anyhow::Error> {
+        use chrono::Timelike as _;
+
+        let now = chrono::Local::now();
+        let minutes = now.hour() * 60 + now.minute();
+        for window in &self.windows {
+            let range = parse_window(window)
+                .ok_or_else(|| anyhow::anyhow!("Invalid throttle window {}", window))?;
+            if in_window(range, minutes) {
+                return Ok(Some(format!("in the window {}", window)));
+            }
+        }
+
+        if let Some(max_load) = self.max_load {
+            let load = load_average()?;
+            if load > max_load {
+                return Ok(Some(format!("load average {:.2} > {:.2}", load, max_load)));
+            }
+        }
+
+        if let Some(ref dir) = self.recording_dir {
+            let active = std::time::Duration::from_secs(self.active_secs);
+            for entry in std::fs::read_dir(dir)? {
+                let entry = entry?;
+                let modified = entry.metadata()?.modified()?;
+                if modified.elapsed().is_ok_and(|elapsed| elapsed < active) {
+                    return Ok(Some(format!("{} is being written", entry.path().display())));
+                }
+            }
+        }
+        Ok(None)
+    }
+}
@@ -0,0 +1,478 @@
+/// Transcode with the ffmpeg library in the encoder process instead of spawning ffmpeg. The best
+/// video and audio streams are encoded into a single output, and ffmpeg_args of the profile are
+/// not used.
+#[derive(Clone, serde::Deserialize)]
+pub struct LibraryConfig {
+    #[serde(default = "default_video_codec")]
+    pub video_codec: String,
+    /// Private options of the video codec, e.g. { crf = "21", preset = "slow" }
+    #[serde(default)]
+    pub video_options: std::collections::HashMap<String, String>,
+    pub width: u32,
+    pub height: u32,
+    #[serde(default = "default_audio_codec")]
+    pub audio_codec: String,
+    /// In bits per second
+    #[serde(default = "default_audio_bit_rate")]
+    pub audio_bit_rate: usize,
+    #[serde(default = "default_audio_sample_rate")]
+    pub audio_sample_rate: u32,
+}
+
+fn default_video_codec() -> String {
+    "libx264".to_owned()
+}
+
+fn default_audio_codec() -> String {
+    "aac".to_owned()
+}
+
+fn default_audio_bit_rate() -> usize {
+    128 * 1000
+}
+
+fn default_audio_sample_rate() -> u32 {
+    48000
+}
+
+/// Reported for each encoded video frame
+#[derive(Debug)]
+pub struct Progress {
+    /// In seconds from the beginning of the output
+    pub position: f64,
+    /// In seconds
+    pub duration: f64,
+}
+
+/// What to encode from the source
+pub struct Job {
+    pub ts_path: std::path::PathBuf,
+    pub output_path: std::path::PathBuf,
+    /// (start, end) in seconds from the beginning of the TS
+    pub range: Option<(f64, f64)>,
+    /// Applied before scaling
+    pub video_filters: Vec<String>,
+    pub audio_filters: Vec<String>,
+}
+
+impl LibraryConfig {
+    /// Run the transcode in a blocking thread
+    pub async fn run<F>(&self, job: Job, progress: F) -> Result<(), anyhow::Error>
+    where
+        F: FnMut(Progress) + Send + 'static,
+    {
+        let config = self.clone();
+        tokio::task::spawn_blocking(move || transcode(&config, &job, progress)).await?
+    }
+}
+
+struct VideoTranscoder {
+    input_index: usize,
+    output_index: usize,
+    input_time_base: ffmpeg::Rational,
+    decoder: ffmpeg::decoder::Video,
+    filter: ffmpeg::filter::Graph,
+    /// Same as the time base of the filtered frames
+    encoder_time_base: ffmpeg::Rational,
+    encoder: ffmpeg::encoder::video::Encoder,
+}
+
+struct AudioTranscoder {
+    input_index: usize,
+    output_index: usize,
+    input_time_base: ffmpeg::Rational,
+    decoder: ffmpeg::decoder::Audio,
+    filter: ffmpeg::filter::Graph,
+    filtered_time_base: ffmpeg::Rational,
+    encoder: ffmpeg::encoder::audio::Encoder,
+}
+
+fn transcode<F>(config: &LibraryConfig, job: &Job, mut progress: F) -> Result<(), anyhow::Error>
+where
+    F: FnMut(Progress),
+{
+    let mut ictx = ffmpeg::format::input(&job.ts_path)?;
+    let mut octx = ffmpeg::format::output(&job.output_path)?;
+    let global_header = octx
+        .format()
+        .flags()
+        .contains(ffmpeg::format::flag::Flags::GLOBAL_HEADER);
+
+    let mut video = VideoTranscoder::new(config, job, &ictx, &mut octx, global_header)?;
+    let mut audio = AudioTranscoder::new(config, job, &ictx, &mut octx, global_header)?;
+    octx.write_header()?;
+
+    // In seconds, the timestamp of the first video frame in the TS
+    let clock_start = ictx
+        .stream(video.input_index)
+        .map(|stream| stream.start_time())
+        .filter(|&t| t != ffmpeg::ffi::AV_NOPTS_VALUE)
+        .map(|t| t as f64 * f64::from(video.input_time_base))
+        .unwrap_or(0.0);
+    let (start, end) = match job.range {
+        Some((start, end)) => (start, Some(end)),
+        None => (0.0, None),
+    };
+    if start > 0.0 {
+        // In AV_TIME_BASE
+        let position = ((clock_start + start) * 1_000_000.0) as i64;
+        ictx.seek(position, ..position)?;
+    }
+    let duration = match end {
+        Some(end) => end - start,
+        None => ictx.duration() as f64 * f64::from(ffmpeg::rescale::TIME_BASE),
+    };
+    // Both are in seconds from the beginning of the TS
+    let in_range = |time: f64| time >= start && end.map(|end| time < end).unwrap_or(true);
+
+    let mut video_frame = ffmpeg::frame::Video::empty();
+    let mut audio_frame = ffmpeg::frame::Audio::empty();
+    let mut finished = false;
+    for (stream, packet) in ictx.packets() {
+        // Broken packets are skipped like ffmpeg does
+        if stream.index() == video.input_index
+            && video
+                .decoder
+                .decode(&packet, &mut video_frame)
+                .unwrap_or(false)
+        {
+            let time = video.time_of(&video_frame) - clock_start;
+            if let Some(end) = end {
+                finished = time >= end;
+            }
+            if in_range(time) {
+                video.set_output_pts(&mut video_frame, start + clock_start);
+                video.filter.get("in").unwrap().source().add(&video_frame)?;
+                video.receive_frames(&mut octx)?;
+                progress(Progress {
+                    position: time - start,
+                    duration,
+                });
+            }
+        } else if stream.index() == audio.input_index
+            && audio
+                .decoder
+                .decode(&packet, &mut audio_frame)
+                .unwrap_or(false)
+        {
+            let time = audio.time_of(&audio_frame) - clock_start;
+            if in_range(time) {
+                audio.set_output_pts(&mut audio_frame, start + clock_start);
+                audio.filter.get("in").unwrap().source().add(&audio_frame)?;
+                audio.receive_frames(&mut octx)?;
+            }
+        }
+        if finished {
+            break;
+        }
+    }
+
+    video.filter.get("in").unwrap().source().flush()?;
+    video.receive_frames(&mut octx)?;
+    video.flush(&mut octx)?;
+    audio.filter.get("in").unwrap().source().flush()?;
+    audio.receive_frames(&mut octx)?;
+    audio.flush(&mut octx)?;
+    octx.write_trailer()?;
+    Ok(())
+}
+
+fn find_encoder(name: &str) -> Result<ffmpeg::Codec, anyhow::Error> {
+    ffmpeg::encoder::find_by_name(name).ok_or_else(|| anyhow::anyhow!("Unknown encoder {}", name))
+}
+
+fn write_packet(
+    packet: &mut ffmpeg::Packet,
+    encoder_time_base: ffmpeg::Rational,
+    output_index: usize,
+    octx: &mut ffmpeg::format::context::Output,
+) -> Result<(), anyhow::Error> {
+    let output_time_base = octx.stream(output_index).unwrap().time_base();
+    packet.set_stream(output_index);
+    packet.rescale_ts(encoder_time_base, output_time_base);
+    packet.write_interleaved(octx)?;
+    Ok(())
+}
+
+/// Time base of the frames taken from the buffersink "out", which differs from the one given to
+/// the buffer source when a filter changes it, e.g. yadif=1 doubles the frame rate
+fn sink_time_base(filter: &mut ffmpeg::filter::Graph) -> ffmpeg::Rational {
+    let out = filter.get("out").unwrap();
+    unsafe { ffmpeg::ffi::av_buffersink_get_time_base(out.as_ptr()) }.into()
+}
+
+fn sink_frame_rate(filter: &mut ffmpeg::filter::Graph) -> ffmpeg::Rational {
+    let out = filter.get("out").unwrap();
+    unsafe { ffmpeg::ffi::av_buffersink_get_frame_rate(out.as_ptr()) }.into()
+}
+
+impl VideoTranscoder {
+    fn new(
+        config: &LibraryConfig,
+        job: &Job,
+        ictx: &ffmpeg::format::context::Input,
+        octx: &mut ffmpeg::format::context::Output,
+        global_header: bool,
+    ) -> Result<Self, anyhow::Error> {
+        let input = ictx
+            .streams()
+            .best(ffmpeg::media::Type::Video)
+            .ok_or_else(|| anyhow::anyhow!("No video stream"))?;
+        let mut decoder = input.codec().decoder().video()?;
+        decoder.set_parameters(input.parameters())?;
+        let input_time_base = input.time_base();
+
+        let mut filter = ffmpeg::filter::Graph::new();
+        let aspect_ratio = decoder.aspect_ratio();
+        filter.add(
+            &ffmpeg::filter::find("buffer").unwrap(),
+            "in",
+            &format!(
+                "video_size={}x{}:pix_fmt={}:time_base={}:pixel_aspect={}",
+                decoder.width(),
+                decoder.height(),
+                decoder.format().name(),
+                input_time_base,
+                if aspect_ratio.numerator() == 0 {
+                    ffmpeg::Rational::new(1, 1)
+                } else {
+                    aspect_ratio
+                }
+            ),
+        )?;
+        filter.add(&ffmpeg::filter::find("buffersink").unwrap(), "out", "")?;
+        filter
+            .get("out")
+            .unwrap()
+            .set_pixel_format(ffmpeg::format::Pixel::YUV420P);
+        let spec = job
+            .video_filters
+            .iter()
+            .cloned()
+            .chain(vec![
+                format!("scale={}:{}", config.width, config.height),
+                "setsar=1".to_owned(),
+            ])
+            .collect::<Vec<_>>()
+            .join(",");
+        filter.output("in", 0)?.input("out", 0)?.parse(&spec)?;
+        filter.validate()?;
+
+        // The filters may change the time base and the frame rate, e.g. yadif=1
+        let encoder_time_base = sink_time_base(&mut filter);
+        let frame_rate = sink_frame_rate(&mut filter);
+        let codec = find_encoder(&config.video_codec)?.video()?;
+        let mut output = octx.add_stream(codec)?;
+        let mut encoder = output.codec().encoder().video()?;
+        if global_header {
+            encoder.set_flags(ffmpeg::codec::flag::Flags::GLOBAL_HEADER);
+        }
+        encoder.set_width(config.width);
+        encoder.set_height(config.height);
+        encoder.set_format(ffmpeg::format::Pixel::YUV420P);
+        encoder.set_time_base(encoder_time_base);
+        encoder.set_frame_rate(Some(if frame_rate.numerator() == 0 {
+            input.avg_frame_rate()
+        } else {
+            frame_rate
+        }));
+        output.set_time_base(encoder_time_base);
+        let mut options = ffmpeg::Dictionary::new();
+        for (key, value) in &config.video_options {
+            options.set(key, value);
+        }
+        let encoder = encoder.open_as_with(codec, options)?;
+        output.set_parameters(&encoder);
+
+        Ok(Self {
+            input_index: input.index(),
+            output_index: output.index(),
+            input_time_base,
+            decoder,
+            filter,
+            encoder_time_base,
+            encoder,
+        })
+    }
+
+    /// In seconds
+    fn time_of(&self, frame: &ffmpeg::frame::Video) -> f64 {
+        frame.timestamp().unwrap_or(0) as f64 * f64::from(self.input_time_base)
+    }
+
+    fn set_output_pts(&self, frame: &mut ffmpeg::frame::Video, offset: f64) {
+        let offset = (offset / f64::from(self.input_time_base)) as i64;
+        let timestamp = frame.timestamp().map(|t| t - offset);
+        frame.set_pts(timestamp);
+    }
+
+    fn receive_frames(
+        &mut self,
+        octx: &mut ffmpeg::format::context::Output,
+    ) -> Result<(), anyhow::Error> {
+        let mut filtered = ffmpeg::frame::Video::empty();
+        let mut packet = ffmpeg::Packet::empty();
+        while self
+            .filter
+            .get("out")
+            .unwrap()
+            .sink()
+            .frame(&mut filtered)
+            .is_ok()
+        {
+            if self.encoder.encode(&filtered, &mut packet)? {
+                write_packet(&mut packet, self.encoder_time_base, self.output_index, octx)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self, octx: &mut ffmpeg::format::context::Output) -> Result<(), anyhow::Error> {
+        let mut packet = ffmpeg::Packet::empty();
+        while self.encoder.flush(&mut packet)? {
+            write_packet(&mut packet, self.encoder_time_base, self.output_index, octx)?;
+        }
+        Ok(())
+    }
+}
+
+impl AudioTranscoder {
+    fn new(
+        config: &LibraryConfig,
+        job: &Job,
+        ictx: &ffmpeg::format::context::Input,
+        octx: &mut ffmpeg::format::context::Output,
+        global_header: bool,
+    ) -> Result<Self, anyhow::Error> {
+        let input = ictx
+            .streams()
+            .best(ffmpeg::media::Type::Audio)
+            .ok_or_else(|| anyhow::anyhow!("No audio stream"))?;
+        let mut decoder = input.codec().decoder().audio()?;
+        decoder.set_parameters(input.parameters())?;
+        let input_time_base = input.time_base();
+
+        let codec = find_encoder(&config.audio_codec)?.audio()?;
+        let mut output = octx.add_stream(codec)?;
+        let mut encoder = output.codec().encoder().audio()?;
+        if global_header {
+            encoder.set_flags(ffmpeg::codec::flag::Flags::GLOBAL_HEADER);
+        }
+        let channel_layout = ffmpeg::ChannelLayout::STEREO;
+        encoder.set_rate(config.audio_sample_rate as i32);
+        encoder.set_channel_layout(channel_layout);
+        encoder.set_channels(channel_layout.channels());
+        encoder.set_format(
+            codec
+                .formats()
+                .and_then(|mut formats| formats.next())
+                .ok_or_else(|| {
+                    anyhow::anyhow!("Unknown sample formats of {}", config.audio_codec)
+                })?,
+        );
+        encoder.set_bit_rate(config.audio_bit_rate);
+        encoder.set_time_base((1, config.audio_sample_rate as i32));
+        output.set_time_base((1, config.audio_sample_rate as i32));
+        let encoder = encoder.open_as(codec)?;
+        output.set_parameters(&encoder);
+
+        let mut filter = ffmpeg::filter::Graph::new();
+        filter.add(
+            &ffmpeg::filter::find("abuffer").unwrap(),
+            "in",
+            &format!(
+                "time_base={}:sample_rate={}:sample_fmt={}:channel_layout=0x{:x}",
+                input_time_base,
+                decoder.rate(),
+                decoder.format().name(),
+                decoder.channel_layout().bits()
+            ),
+        )?;
+        filter.add(&ffmpeg::filter::find("abuffersink").unwrap(), "out", "")?;
+        {
+            let mut out = filter.get("out").unwrap();
+            out.set_sample_format(encoder.format());
+            out.set_channel_layout(encoder.channel_layout());
+            out.set_sample_rate(encoder.rate());
+        }
+        let spec = if job.audio_filters.is_empty() {
+            "anull".to_owned()
+        } else {
+            job.audio_filters.join(",")
+        };
+        filter.output("in", 0)?.input("out", 0)?.parse(&spec)?;
+        filter.validate()?;
+        if !codec
+            .capabilities()
+            .contains(ffmpeg::codec::capabilities::Capabilities::VARIABLE_FRAME_SIZE)
+        {
+            filter
+                .get("out")
+                .unwrap()
+                .sink()
+                .set_frame_size(encoder.frame_size());
+        }
+        let filtered_time_base = sink_time_base(&mut filter);
+
+        Ok(Self {
+            input_index: input.index(),
+            output_index: output.index(),
+            input_time_base,
+            decoder,
+            filter,
+            filtered_time_base,
+            encoder,
+        })
+    }
+
+    /// In seconds
+    fn time_of(&self, frame: &ffmpeg::frame::Audio) -> f64 {
+        frame.timestamp().unwrap_or(0) as f64 * f64::from(self.input_time_base)
+    }
+
+    fn set_output_pts(&self, frame: &mut ffmpeg::frame::Audio, offset: f64) {
+        let offset = (offset / f64::from(self.input_time_base)) as i64;
+        let timestamp = frame.timestamp().map(|t| t - offset);
+        frame.set_pts(timestamp);
+    }
+
+    fn encoder_time_base(&self) -> ffmpeg::Rational {
+        ffmpeg::Rational::new(1, self.encoder.rate() as i32)
+    }
+
+    fn receive_frames(
+        &mut self,
+        octx: &mut ffmpeg::format::context::Output,
+    ) -> Result<(), anyhow::Error> {
+        let time_base = self.encoder_time_base();
+        let mut filtered = ffmpeg::frame::Audio::empty();
+        let mut packet = ffmpeg::Packet::empty();
+        while self
+            .filter
+            .get("out")
+            .unwrap()
+            .sink()
+            .frame(&mut filtered)
+            .is_ok()
+        {
+            // Filtered frames are in the time base of the sink, usually the one of the input stream
+            let pts = filtered
+                .pts()
+                .map(|pts| ffmpeg::Rescale::rescale(&pts, self.filtered_time_base, time_base));
+            filtered.set_pts(pts);
+            if self.encoder.encode(&filtered, &mut packet)? {
+                write_packet(&mut packet, time_base, self.output_index, octx)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self, octx: &mut ffmpeg::format::context::Output) -> Result<(), anyhow::Error> {
+        let time_base = self.encoder_time_base();
+        let mut packet = ffmpeg::Packet::empty();
+        while self.encoder.flush(&mut packet)? {
+            write_packet(&mut packet, time_base, self.output_index, octx)?;
+        }
+        Ok(())
+    }
+}
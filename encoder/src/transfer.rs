@@ -0,0 +1,218 @@
+/// Transfer the outputs to a NAS over SSH or to a mounted share after verification. Each file is
+/// written to a temporary name, verified with its size and SHA-256, and then renamed.
+#[derive(serde::Deserialize)]
+pub struct TransferConfig {
+    /// Transfer with scp and ssh when set. Otherwise dir is a local path such as a mounted share.
+    pub host: Option<String>,
+    /// Destination directory. Variables of the naming template are available, e.g.
+    /// "/archive/{channel}".
+    pub dir: String,
+    /// Passed to both ssh and scp, e.g. ["-i", "/path/to/key"]
+    #[serde(default)]
+    pub ssh_args: Vec<String>,
+    /// Remove the local outputs after the transfer
+    #[serde(default)]
+    pub remove_local: bool,
+    /// Rate limit and time windows of the transfer. The limit is passed to scp with -l.
+    #[serde(default)]
+    pub bandwidth: crate::bandwidth::BandwidthConfig,
+}
+
+/// Chunk size of local copies paced by the bandwidth limit
+const COPY_CHUNK_SIZE: usize = 1024 * 1024;
+
+impl TransferConfig {
+    pub fn needs_source_info(&self) -> bool {
+        self.dir.contains('{')
+    }
+
+    /// Transfer the files into dir keeping their relative names and return the time spent
+    /// waiting for the windows
+    pub async fn transfer(
+        &self,
+        dir: &str,
+        files: &[(std::path::PathBuf, String)],
+    ) -> Result<std::time::Duration, anyhow::Error> {
+        let mut waited = std::time::Duration::default();
+        for (path, name) in files {
+            waited += self.bandwidth.wait_for_window().await?;
+            let dest = format!("{}/{}", dir.trim_end_matches('/'), name);
+            match self.host {
+                Some(ref host) => {
+                    tracing::info!("Transfer {} to {}:{}", path.display(), host, dest);
+                    self.transfer_ssh(host, path, &dest).await?;
+                }
+                None => {
+                    tracing::info!("Transfer {} to {}", path.display(), dest);
+                    self.transfer_local(path, std::path::Path::new(&dest))
+                        .await?;
+                }
+            }
+        }
+        if self.remove_local {
+            for (path, _) in files {
+                std::fs::remove_file(path)?;
+            }
+        }
+        Ok(waited)
+    }
+
+    async fn transfer_ssh(
+        &self,
+        host: &str,
+        path: &std::path::Path,
+        dest: &str,
+    ) -> Result<(), anyhow::Error> {
+        let (parent, tmp_dest) = temporary_name(dest);
+        self.ssh(host, &format!("mkdir -p {}", shell_quote(parent)))
+            .await?;
+        let mut command = tokio::process::Command::new("scp");
+        command.arg("-q");
+        if let Some(limit) = self.bandwidth.limit {
+            // In Kbit/s
+            command
+                .arg("-l")
+                .arg(std::cmp::max(limit * 8 / 1000, 1).to_string());
+        }
+        let status = command
+            .args(&self.ssh_args)
+            .arg(path)
+            .arg(format!("{}:{}", host, tmp_dest))
+            .status()
+            .await?;
+        if !status.success() {
+            return Err(anyhow::anyhow!("scp failed with {}", status));
+        }
+
+        let size = std::fs::metadata(path)?.len();
+        let checksum = crate::checksum::sha256(path)?;
+        let remote = self
+            .ssh(
+                host,
+                &format!("stat -c %s {0} && sha256sum {0}", shell_quote(&tmp_dest)),
+            )
+            .await?;
+        let mut words = remote.split_whitespace();
+        let remote_size = words.next().and_then(|s| s.parse::<u64>().ok());
+        let remote_checksum = words.next();
+        if remote_size != Some(size) || remote_checksum != Some(&checksum) {
+            self.ssh(host, &format!("rm -f {}", shell_quote(&tmp_dest)))
+                .await?;
+            return Err(anyhow::anyhow!(
+                "Transferred {}:{} differs from {} (size {:?}, sha256 {:?})",
+                host,
+                tmp_dest,
+                path.display(),
+                remote_size,
+                remote_checksum
+            ));
+        }
+        self.ssh(
+            host,
+            &format!("mv {} {}", shell_quote(&tmp_dest), shell_quote(dest)),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Run the command on the host and return its stdout
+    async fn ssh(&self, host: &str, command: &str) -> Result<String, anyhow::Error> {
+        let output = tokio::process::Command::new("ssh")
+            .args(&self.ssh_args)
+            .arg(host)
+            .arg(command)
+            .stderr(std::process::Stdio::inherit())
+            .output()
+            .await?;
+        if output.status.success() {
+            Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+        } else {
+            Err(anyhow::anyhow!(
+                "ssh {} {} failed with {}",
+                host,
+                command,
+                output.status
+            ))
+        }
+    }
+
+    async fn transfer_local(
+        &self,
+        path: &std::path::Path,
+        dest: &std::path::Path,
+    ) -> Result<(), anyhow::Error> {
+        let dest_str = dest.to_string_lossy();
+        let (parent, tmp_dest) = temporary_name(&dest_str);
+        std::fs::create_dir_all(parent)?;
+        if self.bandwidth.limit.is_some() {
+            copy_limited(path, std::path::Path::new(&tmp_dest), &self.bandwidth).await?;
+        } else {
+            std::fs::copy(path, &tmp_dest)?;
+        }
+        verify_local(path, dest, &tmp_dest)
+    }
+}
+
+/// Copy in chunks paced by the limiter
+async fn copy_limited(
+    path: &std::path::Path,
+    dest: &std::path::Path,
+    bandwidth: &crate::bandwidth::BandwidthConfig,
+) -> Result<(), anyhow::Error> {
+    use std::io::{Read as _, Write as _};
+
+    let mut reader = std::fs::File::open(path)?;
+    let mut writer = std::fs::File::create(dest)?;
+    let mut limiter = bandwidth.limiter();
+    let mut buf = vec![0; COPY_CHUNK_SIZE];
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        limiter.consume(n as u64).await;
+        writer.write_all(&buf[..n])?;
+    }
+    writer.sync_all()?;
+    Ok(())
+}
+
+/// Compare the copy with the source and rename it to dest
+fn verify_local(
+    path: &std::path::Path,
+    dest: &std::path::Path,
+    tmp_dest: &str,
+) -> Result<(), anyhow::Error> {
+    let size = std::fs::metadata(path)?.len();
+    let tmp_size = std::fs::metadata(tmp_dest)?.len();
+    let checksum = crate::checksum::sha256(path)?;
+    let tmp_checksum = crate::checksum::sha256(std::path::Path::new(tmp_dest))?;
+    if size != tmp_size || checksum != tmp_checksum {
+        std::fs::remove_file(tmp_dest)?;
+        return Err(anyhow::anyhow!(
+            "Transferred {} differs from {} (size {}, sha256 {})",
+            tmp_dest,
+            path.display(),
+            tmp_size,
+            tmp_checksum
+        ));
+    }
+    std::fs::rename(tmp_dest, dest)?;
+    Ok(())
+}
+
+/// Parent directory and the temporary path of dest, i.e. "{parent}/.{name}.part"
+fn temporary_name(dest: &str) -> (&str, String) {
+    match dest.rfind('/') {
+        Some(i) => (
+            &dest[..i],
+            format!("{}/.{}.part", &dest[..i], &dest[i + 1..]),
+        ),
+        None => (".", format!(".{}.part", dest)),
+    }
+}
+
+/// Quote s for the POSIX shell on the remote host
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r#"'\''"#))
+}
@@ -0,0 +1,43 @@
+/// Margins kept around the broadcast event found in EIT
+#[derive(serde::Deserialize)]
+pub struct TrimConfig {
+    /// In seconds
+    #[serde(default)]
+    pub margin_before: f64,
+    /// In seconds
+    #[serde(default)]
+    pub margin_after: f64,
+}
+
+/// Range in seconds from the beginning of the TS
+#[derive(Debug)]
+pub struct TrimRange {
+    pub event_id: u16,
+    pub start: f64,
+    pub end: f64,
+}
+
+/// Return the range of the main event with margins. Return None when the whole TS should be
+/// kept. Where the event actually started or ended in the TS is preferred to the schedule in
+/// EIT, e.g. when it is delayed by an overrunning sports program.
+pub fn find_range(config: &TrimConfig, info: &crate::analysis::SourceInfo) -> Option<TrimRange> {
+    let event = info.main_event()?;
+    let scheduled_start = info.position_of(event.start_time)?;
+    let scheduled_end = info.position_of(event.end_time)?;
+    let (started, ended) = info.on_air(event.event_id);
+    // A delayed event is expected to end late as much
+    let delay = started
+        .map(|started| (started - scheduled_start).max(0.0))
+        .unwrap_or(0.0);
+    let start = (started.unwrap_or(scheduled_start) - config.margin_before).max(0.0);
+    let end = (ended.unwrap_or(scheduled_end + delay) + config.margin_after).min(info.duration);
+    if start <= 0.0 && end >= info.duration {
+        None
+    } else {
+        Some(TrimRange {
+            event_id: event.event_id,
+            start,
+            end,
+        })
+    }
+}
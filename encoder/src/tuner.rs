@@ -0,0 +1,513 @@
+/// Capture command of tuner devices, which is restarted when it exits before the end
+#[derive(serde::Deserialize)]
+pub struct TunerConfig {
+    #[serde(default)]
+    pub kind: TunerKind,
+    /// Devices tried in order while they are busy, e.g. "/dev/pt3video0" for recpt1 or adapter
+    /// numbers for recdvb and dvbv5. The default device of the command is used when empty.
+    #[serde(default)]
+    pub devices: Vec<String>,
+    /// Command line of kind = "command" writing TS to stdout, with {channel}, {service_id} and
+    /// {device}
+    #[serde(default)]
+    pub command: Vec<String>,
+    /// Channel file of dvbv5-zap
+    pub channels_file: Option<std::path::PathBuf>,
+    /// The capture fails after this many restarts
+    #[serde(default = "default_max_restarts")]
+    pub max_restarts: u32,
+    /// Seconds waited before a restart
+    #[serde(default = "default_retry_interval")]
+    pub retry_interval: u64,
+}
+
+fn default_max_restarts() -> u32 {
+    5
+}
+
+fn default_retry_interval() -> u64 {
+    5
+}
+
+#[derive(Clone, Copy, Default, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TunerKind {
+    #[default]
+    Recpt1,
+    Recdvb,
+    Dvbv5,
+    Command,
+}
+
+/// Capture command writing TS of the channel to stdout and reporting to stderr
+pub trait Tuner: Send + Sync {
+    fn command(
+        &self,
+        device: Option<&str>,
+        channel: &str,
+        service_id: Option<u16>,
+    ) -> tokio::process::Command;
+
+    /// Signal quality (C/N) in dB reported by the line of stderr. The value after "C/N" is
+    /// taken when the line also reports the signal strength in dBm, like dvbv5-zap.
+    fn signal(&self, line: &str) -> Option<f64> {
+        let line = match line.find("C/N") {
+            Some(i) => &line[i..],
+            None => line,
+        };
+        let head = line[..line.find("dB")?].trim_end();
+        let start = head
+            .rfind(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .map_or(0, |i| i + 1);
+        head[start..].parse().ok()
+    }
+
+    /// Uncorrected blocks counted by the frontend, e.g. "UCB= 12" of dvbv5-zap
+    fn errors(&self, line: &str) -> Option<u64> {
+        let tail = line[line.find("UCB")? + 3..]
+            .trim_start_matches(|c: char| c == '=' || c == ':' || c.is_whitespace());
+        let end = tail
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(tail.len());
+        tail[..end].parse().ok()
+    }
+
+    /// Whether the line of stderr says the device is used by another process
+    fn is_busy(&self, line: &str) -> bool {
+        let line = line.to_ascii_lowercase();
+        line.contains("busy") || line.contains("cannot tune")
+    }
+}
+
+struct Recpt1;
+
+impl Tuner for Recpt1 {
+    fn command(
+        &self,
+        device: Option<&str>,
+        channel: &str,
+        service_id: Option<u16>,
+    ) -> tokio::process::Command {
+        let mut command = tokio::process::Command::new("recpt1");
+        command.args(["--b25", "--strip"]);
+        if let Some(device) = device {
+            command.args(["--device", device]);
+        }
+        if let Some(service_id) = service_id {
+            command.args(["--sid", &service_id.to_string()]);
+        }
+        command.args([channel, "-", "-"]);
+        command
+    }
+}
+
+struct Recdvb;
+
+impl Tuner for Recdvb {
+    fn command(
+        &self,
+        device: Option<&str>,
+        channel: &str,
+        service_id: Option<u16>,
+    ) -> tokio::process::Command {
+        let mut command = tokio::process::Command::new("recdvb");
+        command.args(["--b25", "--strip"]);
+        if let Some(device) = device {
+            command.args(["--dev", device]);
+        }
+        if let Some(service_id) = service_id {
+            command.args(["--sid", &service_id.to_string()]);
+        }
+        command.args([channel, "-", "-"]);
+        command
+    }
+}
+
+struct Dvbv5<'a> {
+    channels_file: Option<&'a std::path::Path>,
+}
+
+impl<'a> Tuner for Dvbv5<'a> {
+    /// dvbv5-zap doesn't select services, so service_id is ignored
+    fn command(
+        &self,
+        device: Option<&str>,
+        channel: &str,
+        _service_id: Option<u16>,
+    ) -> tokio::process::Command {
+        let mut command = tokio::process::Command::new("dvbv5-zap");
+        if let Some(device) = device {
+            command.args(["-a", device]);
+        }
+        if let Some(channels_file) = self.channels_file {
+            command.arg("-c").arg(channels_file);
+        }
+        command.args(["-P", "-o", "-", channel]);
+        command
+    }
+}
+
+struct Custom<'a> {
+    args: &'a [String],
+}
+
+impl<'a> Tuner for Custom<'a> {
+    fn command(
+        &self,
+        device: Option<&str>,
+        channel: &str,
+        service_id: Option<u16>,
+    ) -> tokio::process::Command {
+        let service_id = service_id.map(|id| id.to_string()).unwrap_or_default();
+        let args = self
+            .args
+            .iter()
+            .map(|arg| {
+                arg.replace("{channel}", channel)
+                    .replace("{service_id}", &service_id)
+                    .replace("{device}", device.unwrap_or_default())
+            })
+            .collect::<Vec<_>>();
+        let mut command = tokio::process::Command::new(&args[0]);
+        command.args(&args[1..]);
+        command
+    }
+}
+
+/// Outcome of TunerConfig::capture
+#[derive(Debug, Default)]
+pub struct CaptureReport {
+    /// Written into the TS
+    pub bytes: u64,
+    pub restarts: u32,
+    /// The worst signal reported during the capture
+    pub min_signal: Option<f64>,
+    pub signal: SignalReport,
+}
+
+/// Seconds summarized into a sample of SignalReport
+const SAMPLE_INTERVAL_SECS: f64 = 10.0;
+
+/// C/N and error counts reported by the tuner during a capture, written next to the TS as
+/// "{ts}.signal.json" so that drops found by tsutils can be told from RF problems
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct SignalReport {
+    pub min_cn: Option<f64>,
+    pub mean_cn: Option<f64>,
+    /// Uncorrected blocks summed over the runs of the command
+    pub errors: u64,
+    pub restarts: u32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub samples: Vec<SignalSample>,
+}
+
+/// The worst C/N and the error count within SAMPLE_INTERVAL_SECS
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct SignalSample {
+    /// Seconds since the start of the capture
+    pub offset: f64,
+    pub cn: Option<f64>,
+    /// Uncorrected blocks counted up to the sample since the capture started
+    pub errors: u64,
+}
+
+impl SignalReport {
+    pub fn path(ts_path: &std::path::Path) -> std::path::PathBuf {
+        let mut path = ts_path.as_os_str().to_owned();
+        path.push(".signal.json");
+        path.into()
+    }
+
+    pub fn write(&self, ts_path: &std::path::Path) -> Result<(), anyhow::Error> {
+        let file = std::fs::File::create(Self::path(ts_path))?;
+        serde_json::to_writer(std::io::BufWriter::new(file), self)?;
+        Ok(())
+    }
+
+    /// The report recorded with the TS, if any
+    pub fn read(ts_path: &std::path::Path) -> Result<Option<Self>, anyhow::Error> {
+        match std::fs::File::open(Self::path(ts_path)) {
+            Ok(file) => Ok(Some(serde_json::from_reader(std::io::BufReader::new(
+                file,
+            ))?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Without the samples, e.g. for the sidecars
+    pub fn summary(&self) -> Self {
+        Self {
+            min_cn: self.min_cn,
+            mean_cn: self.mean_cn,
+            errors: self.errors,
+            restarts: self.restarts,
+            samples: vec![],
+        }
+    }
+
+    /// Append the samples of a run, whose error counter starts from zero
+    fn extend(&mut self, run: SignalRun) {
+        let base = self.errors;
+        for mut sample in run.samples {
+            sample.errors += base;
+            self.samples.push(sample);
+        }
+        self.errors = base + run.errors;
+        let cns = self
+            .samples
+            .iter()
+            .filter_map(|sample| sample.cn)
+            .collect::<Vec<_>>();
+        self.min_cn = cns.iter().copied().reduce(f64::min);
+        self.mean_cn = if cns.is_empty() {
+            None
+        } else {
+            Some(cns.iter().sum::<f64>() / cns.len() as f64)
+        };
+    }
+}
+
+/// Signal reported by stderr of a run of the command
+struct SignalRun {
+    started: std::time::Instant,
+    samples: Vec<SignalSample>,
+    /// The last counter of the run
+    errors: u64,
+}
+
+impl SignalRun {
+    fn new(started: std::time::Instant) -> Self {
+        Self {
+            started,
+            samples: vec![],
+            errors: 0,
+        }
+    }
+
+    fn observe(&mut self, cn: Option<f64>, errors: Option<u64>) {
+        if cn.is_none() && errors.is_none() {
+            return;
+        }
+        if let Some(errors) = errors {
+            self.errors = errors;
+        }
+        let offset = self.started.elapsed().as_secs_f64();
+        match self.samples.last_mut() {
+            Some(last) if offset - last.offset < SAMPLE_INTERVAL_SECS => {
+                if let Some(cn) = cn {
+                    last.cn = Some(last.cn.map_or(cn, |min| min.min(cn)));
+                }
+                last.errors = self.errors;
+            }
+            _ => self.samples.push(SignalSample {
+                offset,
+                cn,
+                errors: self.errors,
+            }),
+        }
+    }
+}
+
+/// What stderr of a run told
+struct StderrSummary {
+    busy: bool,
+    min_signal: Option<f64>,
+    signal: SignalRun,
+}
+
+impl TunerConfig {
+    pub fn tuner(&self) -> Result<Box<dyn Tuner + '_>, anyhow::Error> {
+        Ok(match self.kind {
+            TunerKind::Recpt1 => Box::new(Recpt1),
+            TunerKind::Recdvb => Box::new(Recdvb),
+            TunerKind::Dvbv5 => Box::new(Dvbv5 {
+                channels_file: self.channels_file.as_deref(),
+            }),
+            TunerKind::Command => {
+                if self.command.is_empty() {
+                    return Err(anyhow::anyhow!("tuner.command is empty"));
+                }
+                Box::new(Custom {
+                    args: &self.command,
+                })
+            }
+        })
+    }
+
+    /// Write TS of the channel into ts_path until the time. The command is restarted on the
+    /// next device when it exits early, and only whole packets are written so that the TS stays
+    /// continuous across restarts.
+    pub async fn capture(
+        &self,
+        channel: &str,
+        service_id: Option<u16>,
+        ts_path: &std::path::Path,
+        until: chrono::DateTime<chrono::Utc>,
+    ) -> Result<CaptureReport, anyhow::Error> {
+        let tuner = self.tuner()?;
+        let devices = if self.devices.is_empty() {
+            vec![None]
+        } else {
+            self.devices.iter().map(|d| Some(d.as_str())).collect()
+        };
+        let mut file = std::io::BufWriter::new(std::fs::File::create(ts_path)?);
+        let mut report = CaptureReport::default();
+        let started = std::time::Instant::now();
+        let mut device_index = 0;
+        while let Ok(remaining) = (until - chrono::Utc::now()).to_std() {
+            let device = devices[device_index % devices.len()];
+            let mut command = tuner.command(device, channel, service_id);
+            command
+                .kill_on_drop(true)
+                .stdout(std::process::Stdio::piped())
+                .stderr(std::process::Stdio::piped());
+            let mut child = command.spawn()?;
+            tracing::info!("Capture {} with {:?}", channel, command);
+            let stdout = child.stdout.take().unwrap();
+            let stderr = child.stderr.take().unwrap();
+            let (copied, summary) = futures::future::join(
+                copy_packets(stdout, &mut file, &mut child, remaining),
+                watch_stderr(stderr, tuner.as_ref(), started),
+            )
+            .await;
+            let (bytes, finished) = copied?;
+            report.bytes += bytes;
+            if let Some(signal) = summary.min_signal {
+                report.min_signal =
+                    Some(report.min_signal.map_or(signal, |min: f64| min.min(signal)));
+            }
+            report.signal.extend(summary.signal);
+            let status = child.await?;
+            if finished {
+                break;
+            }
+
+            report.restarts += 1;
+            report.signal.restarts = report.restarts;
+            if report.restarts > self.max_restarts {
+                return Err(anyhow::anyhow!(
+                    "Capture of {} failed after {} restarts: {}",
+                    channel,
+                    self.max_restarts,
+                    status
+                ));
+            }
+            if summary.busy {
+                device_index += 1;
+            }
+            tracing::warn!(
+                "Capture of {} exited early with {}{}, restarting",
+                channel,
+                status,
+                if summary.busy { " (busy)" } else { "" }
+            );
+            tokio::time::delay_for(std::time::Duration::from_secs(self.retry_interval)).await;
+        }
+        std::io::Write::flush(&mut file)?;
+        Ok(report)
+    }
+}
+
+const PACKET_SIZE: usize = 188;
+
+/// Write whole packets from stdout until the duration elapses, when the child is killed. Returns
+/// the written bytes and whether the duration elapsed.
+async fn copy_packets<R, W>(
+    mut stdout: R,
+    file: &mut W,
+    child: &mut tokio::process::Child,
+    duration: std::time::Duration,
+) -> Result<(u64, bool), anyhow::Error>
+where
+    R: tokio::io::AsyncRead + Unpin,
+    W: std::io::Write,
+{
+    use tokio::io::AsyncReadExt as _;
+
+    let deadline = tokio::time::delay_for(duration);
+    futures::pin_mut!(deadline);
+    let mut buf = vec![0; PACKET_SIZE * 512];
+    let mut pending = vec![];
+    let mut synced = false;
+    let mut written = 0;
+    loop {
+        match futures::future::select(stdout.read(&mut buf), &mut deadline).await {
+            futures::future::Either::Left((n, _)) => {
+                let n = match n {
+                    Ok(n) => n,
+                    Err(e) => {
+                        // Let stderr be closed
+                        child.kill().ok();
+                        return Err(e.into());
+                    }
+                };
+                if n == 0 {
+                    return Ok((written, false));
+                }
+                pending.extend_from_slice(&buf[..n]);
+                if !synced {
+                    // The first bytes of a run may start in the middle of a packet
+                    match (0..pending.len().saturating_sub(PACKET_SIZE))
+                        .find(|&i| pending[i] == 0x47 && pending[i + PACKET_SIZE] == 0x47)
+                    {
+                        Some(i) => {
+                            pending.drain(..i);
+                            synced = true;
+                        }
+                        None => continue,
+                    }
+                }
+                let whole = pending.len() / PACKET_SIZE * PACKET_SIZE;
+                if let Err(e) = file.write_all(&pending[..whole]) {
+                    child.kill().ok();
+                    return Err(e.into());
+                }
+                written += whole as u64;
+                pending.drain(..whole);
+            }
+            futures::future::Either::Right(_) => {
+                child.kill()?;
+                return Ok((written, true));
+            }
+        }
+    }
+}
+
+async fn watch_stderr<R>(stderr: R, tuner: &dyn Tuner, started: std::time::Instant) -> StderrSummary
+where
+    R: tokio::io::AsyncRead + Unpin,
+{
+    use futures::StreamExt as _;
+    use tokio::io::AsyncBufReadExt as _;
+
+    let mut summary = StderrSummary {
+        busy: false,
+        min_signal: None,
+        signal: SignalRun::new(started),
+    };
+    let mut lines = tokio::io::BufReader::new(stderr).lines();
+    while let Some(Ok(line)) = lines.next().await {
+        if tuner.is_busy(&line) {
+            summary.busy = true;
+        }
+        let signal = tuner.signal(&line);
+        if let Some(signal) = signal {
+            summary.min_signal = Some(summary.min_signal.map_or(signal, |min| min.min(signal)));
+        }
+        summary.signal.observe(signal, tuner.errors(&line));
+        tracing::debug!("{}", line);
+    }
+    summary
+}
+
+/// Read the signal from stderr of a tuner_command, which is parsed like recpt1
+pub(crate) async fn watch_signal<R>(stderr: R) -> SignalReport
+where
+    R: tokio::io::AsyncRead + Unpin,
+{
+    let summary = watch_stderr(stderr, &Recpt1, std::time::Instant::now()).await;
+    let mut report = SignalReport::default();
+    report.extend(summary.signal);
+    report
+}
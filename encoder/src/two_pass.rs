@@ -0,0 +1,78 @@
+/// Run an analysis pass before the encode, e.g. for bitrate-targeted encodes with libx264.
+/// Give the target bitrate with -b:v in ffmpeg_args.
+#[derive(serde::Deserialize)]
+pub struct TwoPassConfig {
+    /// Appended to the output arguments of the first pass
+    #[serde(default = "default_first_pass_args")]
+    pub first_pass_args: Vec<String>,
+}
+
+fn default_first_pass_args() -> Vec<String> {
+    vec!["-an".to_owned()]
+}
+
+/// Statistics written by the first pass. They are removed on drop.
+pub struct PassLog {
+    dir: tempfile::TempDir,
+}
+
+impl PassLog {
+    fn args(&self, pass: u8) -> Vec<String> {
+        vec![
+            "-pass".to_owned(),
+            pass.to_string(),
+            "-passlogfile".to_owned(),
+            self.dir.path().join("passlog").to_str().unwrap().to_owned(),
+        ]
+    }
+
+    /// Arguments appended to each output of the second pass
+    pub fn second_pass_args(&self) -> Vec<String> {
+        self.args(2)
+    }
+}
+
+impl TwoPassConfig {
+    /// Return the error of ffmpeg when the first pass fails. Each output is discarded in the first
+    /// pass but keeps its own statistics file. stderr is written to log_path like the encode.
+    pub async fn first_pass(
+        &self,
+        work_dir: &std::path::Path,
+        resources: Option<&crate::resources::ResourceConfig>,
+        log_path: Option<std::path::PathBuf>,
+        ts_path: &std::path::Path,
+        input_args: &[String],
+        outputs: &[(Vec<String>, std::path::PathBuf)],
+    ) -> Result<Result<PassLog, crate::failure::FfmpegError>, anyhow::Error> {
+        let passlog = PassLog {
+            dir: tempfile::tempdir_in(work_dir)?,
+        };
+        let mut command = tokio::process::Command::new("ffmpeg");
+        command.kill_on_drop(true).current_dir(work_dir);
+        if let Some(resources) = resources {
+            resources.apply(&mut command)?;
+        }
+        command.args(input_args).arg("-i").arg(ts_path);
+        for (ffmpeg_args, _) in outputs {
+            command
+                .args(ffmpeg_args)
+                .args(passlog.args(1))
+                .args(&self.first_pass_args)
+                .args(["-f", "null", "-"]);
+        }
+        let mut child = command.stderr(std::process::Stdio::piped()).spawn()?;
+        let stderr = child.stderr.take().unwrap();
+        let (status, stderr) = futures::future::try_join(
+            async { Ok(child.await?) },
+            crate::failure::capture(stderr, log_path.as_deref()),
+        )
+        .await?;
+        if status.success() {
+            Ok(Ok(passlog))
+        } else {
+            Ok(Err(crate::failure::FfmpegError::new(
+                status, &stderr, log_path,
+            )))
+        }
+    }
+}
@@ -0,0 +1,303 @@
+/// Upload the outputs to S3 or an S3-compatible storage such as GCS after verification. The
+/// sources are deleted only after every upload is verified.
+#[derive(serde::Deserialize)]
+pub struct UploadConfig {
+    pub bucket: String,
+    /// Prepended to the file names. Variables of the naming template are available, e.g.
+    /// "{date}/{channel}/".
+    #[serde(default)]
+    pub prefix: String,
+    pub storage_class: Option<String>,
+    /// Defaults to the region from the environment
+    pub region: Option<String>,
+    /// Endpoint of S3-compatible storages, e.g. "https://storage.googleapis.com"
+    pub endpoint: Option<String>,
+    /// In bytes. Files larger than this are uploaded in multiple parts.
+    #[serde(default = "default_part_size")]
+    pub part_size: usize,
+    /// Attempts of each request
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+    /// Delete the local outputs after the upload
+    #[serde(default)]
+    pub delete_outputs: bool,
+    /// Rate limit and time windows of the upload, which is paced per request so that a smaller
+    /// part_size smooths the rate
+    #[serde(default)]
+    pub bandwidth: crate::bandwidth::BandwidthConfig,
+}
+
+fn default_part_size() -> usize {
+    64 * 1024 * 1024
+}
+
+fn default_max_attempts() -> u32 {
+    3
+}
+
+/// Retry the request up to max_attempts times with exponential backoff
+macro_rules! with_retry {
+    ($max_attempts:expr, $name:expr, $request:expr) => {{
+        let mut result = Err(anyhow::anyhow!("{} is not attempted", $name));
+        for i in 0..$max_attempts {
+            if i > 0 {
+                tokio::time::delay_for(std::time::Duration::from_secs(1 << i)).await;
+            }
+            match $request.await {
+                Ok(resp) => {
+                    result = Ok(resp);
+                    break;
+                }
+                Err(e) => {
+                    tracing::warn!("[{}] failed to call {}: {}", i, $name, e);
+                    result = Err(anyhow::anyhow!("{} failed: {}", $name, e));
+                }
+            }
+        }
+        result
+    }};
+}
+
+impl UploadConfig {
+    pub fn needs_source_info(&self) -> bool {
+        self.prefix.contains('{')
+    }
+
+    fn client(&self) -> Result<rusoto_s3::S3Client, anyhow::Error> {
+        let region = match (&self.region, &self.endpoint) {
+            (region, Some(endpoint)) => rusoto_core::Region::Custom {
+                name: region.clone().unwrap_or_else(|| "us-east-1".to_owned()),
+                endpoint: endpoint.clone(),
+            },
+            (Some(region), None) => region.parse()?,
+            (None, None) => rusoto_core::Region::default(),
+        };
+        Ok(rusoto_s3::S3Client::new(region))
+    }
+
+    /// Upload the files under the prefix and return the time spent waiting for the windows.
+    /// Files of streaming outputs should be given with the names relative to the output
+    /// directory so that the directory structure is kept.
+    pub async fn upload(
+        &self,
+        prefix: &str,
+        files: &[(std::path::PathBuf, String)],
+    ) -> Result<std::time::Duration, anyhow::Error> {
+        let client = self.client()?;
+        let mut waited = std::time::Duration::default();
+        let mut limiter = self.bandwidth.limiter();
+        for (path, name) in files {
+            waited += self.bandwidth.wait_for_window().await?;
+            let key = format!("{}{}", prefix, name);
+            tracing::info!("Upload {} to s3://{}/{}", path.display(), self.bucket, key);
+            self.upload_file(&client, &mut limiter, path, &key).await?;
+        }
+        if self.delete_outputs {
+            for (path, _) in files {
+                std::fs::remove_file(path)?;
+            }
+        }
+        Ok(waited)
+    }
+
+    async fn upload_file(
+        &self,
+        client: &rusoto_s3::S3Client,
+        limiter: &mut crate::bandwidth::Limiter,
+        path: &std::path::Path,
+        key: &str,
+    ) -> Result<(), anyhow::Error> {
+        use rusoto_s3::S3 as _;
+        use std::io::Read as _;
+
+        let size = std::fs::metadata(path)?.len();
+        let mut file = std::fs::File::open(path)?;
+        let expected_etag = if size as usize <= self.part_size {
+            let mut body = vec![];
+            file.read_to_end(&mut body)?;
+            let digest = md5::compute(&body);
+            limiter.consume(body.len() as u64).await;
+            with_retry!(
+                self.max_attempts,
+                "s3:PutObject",
+                client.put_object(rusoto_s3::PutObjectRequest {
+                    bucket: self.bucket.clone(),
+                    key: key.to_owned(),
+                    body: Some(body.clone().into()),
+                    content_length: Some(body.len() as i64),
+                    content_md5: Some(base64::encode(&digest.0)),
+                    storage_class: self.storage_class.clone(),
+                    ..Default::default()
+                })
+            )?;
+            format!("\"{:x}\"", digest)
+        } else {
+            let upload_id = with_retry!(
+                self.max_attempts,
+                "s3:CreateMultipartUpload",
+                client.create_multipart_upload(rusoto_s3::CreateMultipartUploadRequest {
+                    bucket: self.bucket.clone(),
+                    key: key.to_owned(),
+                    storage_class: self.storage_class.clone(),
+                    ..Default::default()
+                })
+            )?
+            .upload_id
+            .ok_or_else(|| anyhow::anyhow!("s3:CreateMultipartUpload returned no upload_id"))?;
+            match self
+                .upload_parts(client, limiter, &mut file, key, &upload_id)
+                .await
+            {
+                Ok(etag) => etag,
+                Err(e) => {
+                    let _ = client
+                        .abort_multipart_upload(rusoto_s3::AbortMultipartUploadRequest {
+                            bucket: self.bucket.clone(),
+                            key: key.to_owned(),
+                            upload_id,
+                            ..Default::default()
+                        })
+                        .await;
+                    return Err(e);
+                }
+            }
+        };
+
+        let head = with_retry!(
+            self.max_attempts,
+            "s3:HeadObject",
+            client.head_object(rusoto_s3::HeadObjectRequest {
+                bucket: self.bucket.clone(),
+                key: key.to_owned(),
+                ..Default::default()
+            })
+        )?;
+        if head.content_length != Some(size as i64) {
+            return Err(anyhow::anyhow!(
+                "Size mismatch of s3://{}/{}: expected {}, actual {:?}",
+                self.bucket,
+                key,
+                size,
+                head.content_length
+            ));
+        }
+        // The parts are verified by the storage with Content-MD5 when they are uploaded
+        let md5_etag = head
+            .e_tag
+            .as_deref()
+            .filter(|etag| is_md5_etag(etag, head.server_side_encryption.as_deref()));
+        if md5_etag.is_some() && md5_etag != Some(&expected_etag) {
+            return Err(anyhow::anyhow!(
+                "ETag mismatch of s3://{}/{}: expected {}, actual {:?}",
+                self.bucket,
+                key,
+                expected_etag,
+                head.e_tag
+            ));
+        }
+        Ok(())
+    }
+
+    /// Upload the file in parts and return the expected ETag of the object
+    async fn upload_parts(
+        &self,
+        client: &rusoto_s3::S3Client,
+        limiter: &mut crate::bandwidth::Limiter,
+        file: &mut std::fs::File,
+        key: &str,
+        upload_id: &str,
+    ) -> Result<String, anyhow::Error> {
+        use rusoto_s3::S3 as _;
+        use std::io::Read as _;
+
+        let mut parts = vec![];
+        let mut digests = vec![];
+        loop {
+            let mut body = Vec::with_capacity(self.part_size);
+            file.by_ref()
+                .take(self.part_size as u64)
+                .read_to_end(&mut body)?;
+            if body.is_empty() {
+                break;
+            }
+            let part_number = parts.len() as i64 + 1;
+            let digest = md5::compute(&body);
+            limiter.consume(body.len() as u64).await;
+            let resp = with_retry!(
+                self.max_attempts,
+                "s3:UploadPart",
+                client.upload_part(rusoto_s3::UploadPartRequest {
+                    bucket: self.bucket.clone(),
+                    key: key.to_owned(),
+                    upload_id: upload_id.to_owned(),
+                    part_number,
+                    body: Some(body.clone().into()),
+                    content_length: Some(body.len() as i64),
+                    content_md5: Some(base64::encode(&digest.0)),
+                    ..Default::default()
+                })
+            )?;
+            parts.push(rusoto_s3::CompletedPart {
+                e_tag: resp.e_tag,
+                part_number: Some(part_number),
+            });
+            digests.extend_from_slice(&digest.0);
+        }
+
+        let part_count = parts.len();
+        with_retry!(
+            self.max_attempts,
+            "s3:CompleteMultipartUpload",
+            client.complete_multipart_upload(rusoto_s3::CompleteMultipartUploadRequest {
+                bucket: self.bucket.clone(),
+                key: key.to_owned(),
+                upload_id: upload_id.to_owned(),
+                multipart_upload: Some(rusoto_s3::CompletedMultipartUpload {
+                    parts: Some(parts.clone()),
+                }),
+                ..Default::default()
+            })
+        )?;
+        // ETag of multipart objects is MD5 of the concatenated MD5 of the parts
+        Ok(format!("\"{:x}-{}\"", md5::compute(&digests), part_count))
+    }
+}
+
+/// Whether the ETag is MD5 of the object, or MD5 of the MD5 of the parts followed by the number
+/// of parts. ETags of objects encrypted with SSE-KMS and of multipart uploads to GCS are not.
+fn is_md5_etag(etag: &str, server_side_encryption: Option<&str>) -> bool {
+    if server_side_encryption == Some("aws:kms") {
+        return false;
+    }
+    let etag = etag.trim_matches('"');
+    let (digest, parts) = match etag.split_once('-') {
+        Some((digest, parts)) => (digest, Some(parts)),
+        None => (etag, None),
+    };
+    digest.len() == 32
+        && digest.bytes().all(|b| b.is_ascii_hexdigit())
+        && parts.map_or(true, |parts| {
+            !parts.is_empty() && parts.bytes().all(|b| b.is_ascii_digit())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn md5_etags() {
+        assert!(is_md5_etag("\"9e107d9d372bb6826bd81d3542a419d6\"", None));
+        assert!(is_md5_etag(
+            "\"9e107d9d372bb6826bd81d3542a419d6-12\"",
+            Some("AES256")
+        ));
+        assert!(!is_md5_etag(
+            "\"9e107d9d372bb6826bd81d3542a419d6\"",
+            Some("aws:kms")
+        ));
+        // GCS XML multipart upload
+        assert!(!is_md5_etag("\"CJjC4f3zsfECEAE=\"", None));
+        assert!(!is_md5_etag("\"9e107d9d372bb6826bd81d3542a419d6-\"", None));
+    }
+}
@@ -0,0 +1,421 @@
+/// Checks applied to each output after encoding
+#[derive(Clone, serde::Deserialize)]
+pub struct VerifyConfig {
+    #[serde(default = "default_checks")]
+    pub checks: Vec<Check>,
+    /// Allowed ratio of the difference of the number of video frames
+    #[serde(default = "default_frame_count_tolerance")]
+    pub frame_count_tolerance: f64,
+    /// Allowed difference of durations in seconds
+    #[serde(default = "default_duration_tolerance")]
+    pub duration_tolerance: f64,
+    /// Opt-in and slow since it decodes both the output and the source
+    pub quality: Option<crate::quality::QualityConfig>,
+    pub blank: Option<crate::blank::BlankConfig>,
+}
+
+impl Default for VerifyConfig {
+    fn default() -> Self {
+        Self {
+            checks: default_checks(),
+            frame_count_tolerance: default_frame_count_tolerance(),
+            duration_tolerance: default_duration_tolerance(),
+            quality: None,
+            blank: None,
+        }
+    }
+}
+
+fn default_checks() -> Vec<Check> {
+    vec![Check::Ffmpeg]
+}
+
+fn default_frame_count_tolerance() -> f64 {
+    0.01
+}
+
+fn default_duration_tolerance() -> f64 {
+    1.0
+}
+
+#[derive(Clone, Copy, Debug, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Check {
+    /// Duration of the output and its audio and video streams probed with ffmpeg
+    Ffmpeg,
+    /// Duration of the output against the PCR duration of the source measured with tsutils
+    Tsutils,
+    /// Number of video frames of the output against the source
+    FrameCount,
+}
+
+/// What a verifier looks at
+pub struct Target<'a> {
+    pub ts_path: &'a std::path::Path,
+    pub output_path: &'a std::path::Path,
+    pub audio_only: bool,
+    /// Expected duration of the output in microseconds
+    pub expected_duration: i64,
+    /// Encoded range of the TS in seconds
+    pub range: Option<(f64, f64)>,
+    /// Video filters applied to the source while encoding, e.g. deinterlacing
+    pub reference_filters: &'a [String],
+    /// Working directory of ffmpeg run by verifiers
+    pub work_dir: Option<&'a std::path::Path>,
+}
+
+/// Scores measured by verifiers keyed by metric name
+pub type Scores = std::collections::BTreeMap<String, f64>;
+
+pub trait Verifier {
+    fn name(&self) -> &'static str;
+    fn verify(&self, target: &Target, scores: &mut Scores) -> Result<(), anyhow::Error>;
+}
+
+impl<V: Verifier> Verifier for &V {
+    fn name(&self) -> &'static str {
+        (**self).name()
+    }
+
+    fn verify(&self, target: &Target, scores: &mut Scores) -> Result<(), anyhow::Error> {
+        (**self).verify(target, scores)
+    }
+}
+
+impl VerifyConfig {
+    pub fn verifiers(&self) -> Vec<Box<dyn Verifier + Send + Sync + '_>> {
+        let tolerance = (self.duration_tolerance * 1_000_000.0) as i64;
+        let mut verifiers = self
+            .checks
+            .iter()
+            .map(|check| -> Box<dyn Verifier + Send + Sync> {
+                match check {
+                    Check::Ffmpeg => Box::new(FfmpegVerifier { tolerance }),
+                    Check::Tsutils => Box::new(TsutilsVerifier { tolerance }),
+                    Check::FrameCount => Box::new(FrameCountVerifier {
+                        tolerance: self.frame_count_tolerance,
+                    }),
+                }
+            })
+            .collect::<Vec<_>>();
+        if let Some(ref blank) = self.blank {
+            verifiers.push(Box::new(blank));
+        }
+        if let Some(ref quality) = self.quality {
+            verifiers.push(Box::new(quality));
+        }
+        verifiers
+    }
+
+    /// Run all checks and return the first failure
+    pub fn verify(&self, target: &Target) -> Result<Scores, anyhow::Error> {
+        let mut scores = Scores::new();
+        for verifier in self.verifiers() {
+            verifier.verify(target, &mut scores).map_err(|e| {
+                crate::exit::StatusError::new(
+                    crate::exit::Status::VerificationFailed,
+                    format!("{} verification failed: {}", verifier.name(), e),
+                )
+            })?;
+        }
+        Ok(scores)
+    }
+}
+
+/// Durations in microseconds
+#[derive(Debug, Default)]
+pub struct StreamDurations {
+    pub container: i64,
+    pub video: Option<i64>,
+    pub audio: Option<i64>,
+}
+
+impl StreamDurations {
+    pub fn probe(path: &std::path::Path) -> Result<Self, anyhow::Error> {
+        let ictx = ffmpeg::format::input(&path)?;
+        let mut durations = Self {
+            container: ictx.duration(),
+            ..Self::default()
+        };
+        for stream in ictx.streams() {
+            let slot = match stream.codec().medium() {
+                ffmpeg::media::Type::Video => &mut durations.video,
+                ffmpeg::media::Type::Audio => &mut durations.audio,
+                _ => continue,
+            };
+            if slot.is_some() {
+                continue;
+            }
+            let duration = match stream.duration() {
+                // Matroska has no duration per stream but the muxer of ffmpeg writes DURATION tags
+                ffmpeg::ffi::AV_NOPTS_VALUE => stream
+                    .metadata()
+                    .get("DURATION")
+                    .and_then(parse_tag_duration)
+                    .ok_or_else(|| {
+                        anyhow::anyhow!(
+                            "Unknown duration of stream {} in {}",
+                            stream.index(),
+                            path.display()
+                        )
+                    })?,
+                duration => (duration as f64 * f64::from(stream.time_base()) * 1_000_000.0) as i64,
+            };
+            *slot = Some(duration);
+        }
+        Ok(durations)
+    }
+}
+
+/// Parse "HH:MM:SS.nnnnnnnnn" of the Matroska DURATION tag into microseconds
+fn parse_tag_duration(tag: &str) -> Option<i64> {
+    let mut parts = tag.splitn(3, ':');
+    let hours: i64 = parts.next()?.parse().ok()?;
+    let minutes: i64 = parts.next()?.parse().ok()?;
+    let seconds: f64 = parts.next()?.parse().ok()?;
+    Some((hours * 3600 + minutes * 60) * 1_000_000 + (seconds * 1_000_000.0).round() as i64)
+}
+
+fn check_duration(expected: i64, actual: i64, tolerance: i64) -> Result<(), anyhow::Error> {
+    if (expected - actual).abs() > tolerance {
+        Err(anyhow::anyhow!(
+            "Duration mismatch: expected {}, actual {} (microsecond)",
+            expected,
+            actual
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+pub(crate) fn check_streams(
+    durations: &StreamDurations,
+    audio_only: bool,
+    tolerance: i64,
+) -> Result<(), anyhow::Error> {
+    let audio = durations
+        .audio
+        .ok_or_else(|| anyhow::anyhow!("No audio stream"))?;
+    if audio_only {
+        return Ok(());
+    }
+    let video = durations
+        .video
+        .ok_or_else(|| anyhow::anyhow!("No video stream"))?;
+    if (audio - video).abs() > tolerance {
+        return Err(anyhow::anyhow!(
+            "Duration mismatch! audio:{} video:{} (microsecond)",
+            audio,
+            video
+        ));
+    }
+    Ok(())
+}
+
+fn check_frame_count(source: u64, output: u64, tolerance: f64) -> Result<(), anyhow::Error> {
+    let difference = (source as f64 - output as f64).abs();
+    if source == 0 || difference / source as f64 > tolerance {
+        Err(anyhow::anyhow!(
+            "Frame count mismatch: source {}, output {}",
+            source,
+            output
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Tolerances are in microseconds
+pub struct FfmpegVerifier {
+    pub tolerance: i64,
+}
+
+impl Verifier for FfmpegVerifier {
+    fn name(&self) -> &'static str {
+        "ffmpeg"
+    }
+
+    fn verify(&self, target: &Target, _scores: &mut Scores) -> Result<(), anyhow::Error> {
+        let durations = StreamDurations::probe(target.output_path)?;
+        check_duration(
+            target.expected_duration,
+            durations.container,
+            self.tolerance,
+        )?;
+        check_streams(&durations, target.audio_only, self.tolerance)
+    }
+}
+
+pub struct TsutilsVerifier {
+    pub tolerance: i64,
+}
+
+impl Verifier for TsutilsVerifier {
+    fn name(&self) -> &'static str {
+        "tsutils"
+    }
+
+    fn verify(&self, target: &Target, _scores: &mut Scores) -> Result<(), anyhow::Error> {
+        let expected = match target.range {
+            Some((start, end)) => end - start,
+            None => {
+                let reader = std::io::BufReader::new(std::fs::File::open(target.ts_path)?);
+                tsutils::integrity::check(reader)?.duration_secs()
+            }
+        };
+        let durations = StreamDurations::probe(target.output_path)?;
+        check_duration(
+            (expected * 1_000_000.0) as i64,
+            durations.container,
+            self.tolerance,
+        )
+    }
+}
+
+pub struct FrameCountVerifier {
+    pub tolerance: f64,
+}
+
+/// Count packets of the best video stream whose position in seconds is in the range
+fn count_video_frames(
+    path: &std::path::Path,
+    range: Option<(f64, f64)>,
+) -> Result<u64, anyhow::Error> {
+    let mut ictx = ffmpeg::format::input(&path)?;
+    let (index, time_base, start_time) = {
+        let stream = ictx
+            .streams()
+            .best(ffmpeg::media::Type::Video)
+            .ok_or_else(|| anyhow::anyhow!("No video stream in {}", path.display()))?;
+        let start_time = match stream.start_time() {
+            ffmpeg::ffi::AV_NOPTS_VALUE => 0,
+            t => t,
+        };
+        (stream.index(), stream.time_base(), start_time)
+    };
+    let mut count = 0;
+    for (stream, packet) in ictx.packets() {
+        if stream.index() != index {
+            continue;
+        }
+        let in_range = match (range, packet.pts()) {
+            (Some((start, end)), Some(pts)) => {
+                let position = (pts - start_time) as f64 * f64::from(time_base);
+                start <= position && position < end
+            }
+            _ => true,
+        };
+        if in_range {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+impl Verifier for FrameCountVerifier {
+    fn name(&self) -> &'static str {
+        "frame_count"
+    }
+
+    fn verify(&self, target: &Target, _scores: &mut Scores) -> Result<(), anyhow::Error> {
+        if target.audio_only {
+            return Ok(());
+        }
+        let source = count_video_frames(target.ts_path, target.range)?;
+        let output = count_video_frames(target.output_path, None)?;
+        check_frame_count(source, output, self.tolerance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duration_within_eps() {
+        assert!(check_duration(10_000_000, 10_500_000, 1_000_000).is_ok());
+        assert!(check_duration(10_000_000, 9_000_000, 1_000_000).is_ok());
+        assert!(check_duration(10_000_000, 11_000_001, 1_000_000).is_err());
+        assert!(check_duration(10_000_000, 0, 1_000_000).is_err());
+        assert!(check_duration(10_000_000, 12_000_000, 3_000_000).is_ok());
+    }
+
+    #[test]
+    fn streams_of_video_output() {
+        let durations = StreamDurations {
+            container: 10_000_000,
+            video: Some(10_000_000),
+            audio: Some(9_900_000),
+        };
+        assert!(check_streams(&durations, false, 1_000_000).is_ok());
+
+        let durations = StreamDurations {
+            container: 10_000_000,
+            video: Some(10_000_000),
+            audio: Some(5_000_000),
+        };
+        assert!(check_streams(&durations, false, 1_000_000).is_err());
+
+        let durations = StreamDurations {
+            container: 10_000_000,
+            video: None,
+            audio: Some(10_000_000),
+        };
+        assert!(check_streams(&durations, false, 1_000_000).is_err());
+    }
+
+    #[test]
+    fn streams_of_audio_only_output() {
+        let durations = StreamDurations {
+            container: 10_000_000,
+            video: None,
+            audio: Some(10_000_000),
+        };
+        assert!(check_streams(&durations, true, 1_000_000).is_ok());
+
+        let durations = StreamDurations {
+            container: 10_000_000,
+            video: Some(10_000_000),
+            audio: None,
+        };
+        assert!(check_streams(&durations, true, 1_000_000).is_err());
+    }
+
+    #[test]
+    fn matroska_duration_tag() {
+        assert_eq!(
+            parse_tag_duration("00:29:59.968000000"),
+            Some(1_799_968_000)
+        );
+        assert_eq!(
+            parse_tag_duration("01:00:00.000000000"),
+            Some(3_600_000_000)
+        );
+        assert_eq!(parse_tag_duration("29:59.968"), None);
+    }
+
+    #[test]
+    fn frame_count_tolerance() {
+        assert!(check_frame_count(1000, 1000, 0.0).is_ok());
+        assert!(check_frame_count(1000, 995, 0.01).is_ok());
+        assert!(check_frame_count(1000, 980, 0.01).is_err());
+        assert!(check_frame_count(1000, 1020, 0.01).is_err());
+        assert!(check_frame_count(0, 0, 0.01).is_err());
+    }
+
+    #[test]
+    fn default_config() {
+        let config: VerifyConfig = toml::from_str("").unwrap();
+        assert_eq!(config.verifiers().len(), 1);
+        assert_eq!(config.verifiers()[0].name(), "ffmpeg");
+
+        let config: VerifyConfig =
+            toml::from_str(r#"checks = ["ffmpeg", "tsutils", "frame_count"]"#).unwrap();
+        let names = config
+            .verifiers()
+            .iter()
+            .map(|verifier| verifier.name())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["ffmpeg", "tsutils", "frame_count"]);
+    }
+}
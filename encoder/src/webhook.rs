@@ -0,0 +1,109 @@
+#[derive(serde::Deserialize)]
+pub struct WebhookConfig {
+    /// {status}, {message_id} and {file} (file name of the source TS) are replaced with
+    /// URL-encoded values
+    pub url: String,
+    /// Sign the body with HMAC-SHA256 in X-Signature-256 header when set
+    pub secret: Option<String>,
+    /// Defaults to succeeded, failed and alert
+    #[serde(default = "default_on")]
+    pub on: Vec<crate::outcome::Status>,
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+}
+
+fn default_on() -> Vec<crate::outcome::Status> {
+    vec![
+        crate::outcome::Status::Succeeded,
+        crate::outcome::Status::Failed,
+        crate::outcome::Status::Alert,
+    ]
+}
+
+fn default_max_attempts() -> u32 {
+    3
+}
+
+impl WebhookConfig {
+    fn url(&self, outcome: &crate::outcome::JobOutcome) -> String {
+        let file = outcome
+            .file
+            .file_name()
+            .map(|fname| fname.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        self.url
+            .replace("{status}", outcome.status.as_str())
+            .replace("{message_id}", &url_encode(outcome.message_id))
+            .replace("{file}", &url_encode(&file))
+    }
+
+    /// POST the outcome as JSON. It fails after max_attempts attempts.
+    pub async fn notify(
+        &self,
+        outcome: &crate::outcome::JobOutcome<'_>,
+    ) -> Result<(), anyhow::Error> {
+        if !self.on.contains(&outcome.status) {
+            return Ok(());
+        }
+        self.post(&self.url(outcome), serde_json::to_vec(outcome)?)
+            .await
+    }
+
+    /// POST the digest as JSON if "digest" is in on. {status} in the URL is replaced with
+    /// "digest" and the other variables are empty.
+    pub async fn send_digest(&self, digest: &crate::digest::Digest) -> Result<(), anyhow::Error> {
+        if !self.on.contains(&crate::outcome::Status::Digest) {
+            return Ok(());
+        }
+        let url = self
+            .url
+            .replace("{status}", crate::outcome::Status::Digest.as_str())
+            .replace("{message_id}", "")
+            .replace("{file}", "");
+        self.post(&url, serde_json::to_vec(digest)?).await
+    }
+
+    async fn post(&self, url: &str, body: Vec<u8>) -> Result<(), anyhow::Error> {
+        let signature = self.secret.as_ref().map(|secret| sign(secret, &body));
+        let client =
+            hyper::Client::builder().build::<_, hyper::Body>(hyper_rustls::HttpsConnector::new());
+
+        for i in 0..self.max_attempts {
+            if i > 0 {
+                tokio::time::delay_for(std::time::Duration::from_secs(1 << i)).await;
+            }
+            let mut builder = hyper::Request::post(url).header("content-type", "application/json");
+            if let Some(ref signature) = signature {
+                builder = builder.header("x-signature-256", format!("sha256={}", signature));
+            }
+            let request = builder.body(hyper::Body::from(body.clone()))?;
+            match client.request(request).await {
+                Ok(resp) if resp.status().is_success() => return Ok(()),
+                Ok(resp) => tracing::warn!("[{}] webhook {} returned {}", i, url, resp.status()),
+                Err(e) => tracing::warn!("[{}] webhook {} failed: {}", i, url, e),
+            }
+        }
+        Err(anyhow::anyhow!("webhook {} failed", url))
+    }
+}
+
+/// Hex-encoded HMAC-SHA256 of the body
+fn sign(secret: &str, body: &[u8]) -> String {
+    use hmac::{Mac as _, NewMac as _};
+
+    let mut mac = hmac::Hmac::<sha2::Sha256>::new_varkey(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(body);
+    format!("{:x}", mac.finalize().into_bytes())
+}
+
+fn url_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
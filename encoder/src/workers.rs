@@ -0,0 +1,138 @@
+/// Register each sqs-encode worker in Redis with heartbeats so that workers sharing storage can
+/// recover the locks of crashed workers instead of waiting for them to expire
+#[derive(serde::Deserialize)]
+pub struct WorkersConfig {
+    #[serde(default = "default_prefix")]
+    pub prefix: String,
+    /// Seconds between heartbeats
+    #[serde(default = "default_heartbeat_interval")]
+    pub heartbeat_interval: u64,
+    /// A worker is considered dead after this many seconds without heartbeats
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+}
+
+fn default_prefix() -> String {
+    "encoder:worker:".to_owned()
+}
+
+fn default_heartbeat_interval() -> u64 {
+    15
+}
+
+fn default_timeout() -> u64 {
+    60
+}
+
+#[derive(Clone)]
+pub struct Registry {
+    client: redis::Client,
+    prefix: String,
+    heartbeat_interval: u64,
+    timeout: u64,
+    worker: String,
+}
+
+impl Registry {
+    pub fn new(
+        config: &WorkersConfig,
+        redis: &crate::config::RedisConfig,
+    ) -> Result<Self, anyhow::Error> {
+        Ok(Self {
+            client: redis::Client::open(redis.url.as_str())?,
+            prefix: config.prefix.clone(),
+            heartbeat_interval: config.heartbeat_interval,
+            timeout: config.timeout,
+            worker: crate::jobs::worker_id(),
+        })
+    }
+
+    /// Store the status of this worker in "{prefix}{worker id}" expiring after the timeout
+    pub async fn heartbeat(&self, state: &crate::admin::WorkerState) -> Result<(), anyhow::Error> {
+        let mut status = state.status();
+        status["worker"] = self.worker.clone().into();
+        status["at"] = chrono::Utc::now().to_rfc3339().into();
+        let mut conn = self.client.get_async_connection().await?;
+        let () = redis::cmd("SET")
+            .arg(format!("{}{}", self.prefix, self.worker))
+            .arg(serde_json::to_string(&status)?)
+            .arg("EX")
+            .arg(self.timeout)
+            .query_async(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    /// Send heartbeats from a dedicated thread with its own runtime, so that they keep going while
+    /// the runtime of the worker is busy, e.g. with synchronous work of the encode
+    pub fn spawn(
+        self,
+        state: std::sync::Arc<crate::admin::WorkerState>,
+    ) -> Result<std::thread::JoinHandle<()>, anyhow::Error> {
+        let mut runtime = tokio::runtime::Builder::new()
+            .basic_scheduler()
+            .enable_all()
+            .build()?;
+        let handle = std::thread::Builder::new()
+            .name("worker-heartbeat".to_owned())
+            .spawn(move || runtime.block_on(self.run(state)))?;
+        Ok(handle)
+    }
+
+    /// Send heartbeats until the process exits
+    pub async fn run(self, state: std::sync::Arc<crate::admin::WorkerState>) {
+        let mut interval =
+            tokio::time::interval(tokio::time::Duration::from_secs(self.heartbeat_interval));
+        loop {
+            interval.tick().await;
+            if let Err(e) = self.heartbeat(&state).await {
+                tracing::warn!("Failed to send a heartbeat: {}", e);
+            }
+        }
+    }
+
+    pub async fn unregister(&self) -> Result<(), anyhow::Error> {
+        let mut conn = self.client.get_async_connection().await?;
+        let _: i64 = redis::cmd("DEL")
+            .arg(format!("{}{}", self.prefix, self.worker))
+            .query_async(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn is_alive(&self, worker: &str) -> Result<bool, anyhow::Error> {
+        let mut conn = self.client.get_async_connection().await?;
+        let exists: bool = redis::cmd("EXISTS")
+            .arg(format!("{}{}", self.prefix, worker))
+            .query_async(&mut conn)
+            .await?;
+        Ok(exists)
+    }
+
+    /// Last heartbeats of the live workers
+    pub async fn list(&self) -> Result<Vec<serde_json::Value>, anyhow::Error> {
+        let mut conn = self.client.get_async_connection().await?;
+        let mut cursor = 0u64;
+        let mut workers = vec![];
+        loop {
+            let (next, keys): (u64, Vec<String>) = redis::cmd("SCAN")
+                .arg(cursor)
+                .arg("MATCH")
+                .arg(format!("{}*", self.prefix))
+                .query_async(&mut conn)
+                .await?;
+            for key in keys {
+                let status: Option<String> =
+                    redis::cmd("GET").arg(&key).query_async(&mut conn).await?;
+                if let Some(status) = status {
+                    workers.push(serde_json::from_str(&status)?);
+                }
+            }
+            if next == 0 {
+                break;
+            }
+            cursor = next;
+        }
+        Ok(workers)
+    }
+}
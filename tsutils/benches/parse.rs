@@ -0,0 +1,96 @@
+#[macro_use]
+extern crate criterion;
+extern crate tsutils;
+
+mod synthetic;
+
+use criterion::{Criterion, Throughput};
+
+// 32 MiB, about 10 seconds of a broadcast
+const STREAM_SIZE: usize = 32 << 20;
+
+fn packets(c: &mut Criterion) {
+    let stream = synthetic::stream(STREAM_SIZE);
+    let mut group = c.benchmark_group("packets");
+    group.throughput(Throughput::Bytes(stream.len() as u64));
+    group.sample_size(20);
+    group.bench_function("ts_packets", |b| {
+        b.iter(|| {
+            let mut pids = 0u64;
+            for buf in tsutils::packet::ts_packets(stream.as_slice()) {
+                let buf = buf.unwrap();
+                pids += tsutils::TsPacket::new(&buf).pid as u64;
+            }
+            pids
+        })
+    });
+    group.bench_function("integrity", |b| {
+        b.iter(|| tsutils::integrity::check(stream.as_slice()).unwrap())
+    });
+    group.finish();
+}
+
+fn sections(c: &mut Criterion) {
+    let stream = synthetic::stream(STREAM_SIZE);
+    let eit_packets: Vec<_> = stream.chunks(188)
+        .filter(|buf| tsutils::TsPacket::new(buf).pid == synthetic::EIT_PID)
+        .collect();
+    let mut group = c.benchmark_group("sections");
+    group.throughput(Throughput::Bytes((eit_packets.len() * 188) as u64));
+    group.bench_function("assemble", |b| {
+        b.iter(|| {
+            let mut assembler = tsutils::psi::SectionAssembler::new();
+            let mut n = 0;
+            for buf in &eit_packets {
+                n += assembler.push(&tsutils::TsPacket::new(buf)).len();
+            }
+            n
+        })
+    });
+
+    let mut assembler = tsutils::psi::SectionAssembler::new();
+    let sections: Vec<_> = eit_packets.iter()
+        .flat_map(|buf| assembler.push(&tsutils::TsPacket::new(buf)))
+        .collect();
+    group.throughput(Throughput::Bytes(sections.iter().map(|s| s.len() as u64).sum()));
+    group.bench_function("eit", |b| {
+        b.iter(|| {
+            let mut names = 0;
+            for section in &sections {
+                let eit = tsutils::EventInformationTable::parse(section).unwrap();
+                for event in &eit.events {
+                    for (tag, body) in tsutils::descriptor::descriptors(event.descriptors) {
+                        if tag == tsutils::descriptor::ShortEventDescriptor::TAG {
+                            let descriptor =
+                                tsutils::descriptor::ShortEventDescriptor::parse(body).unwrap();
+                            names += tsutils::arib_string::decode(descriptor.event_name).len();
+                        }
+                    }
+                }
+            }
+            names
+        })
+    });
+    group.finish();
+}
+
+fn filters(c: &mut Criterion) {
+    let stream = synthetic::stream(STREAM_SIZE);
+    let mut group = c.benchmark_group("filters");
+    group.throughput(Throughput::Bytes(stream.len() as u64));
+    group.sample_size(20);
+    group.bench_function("drop_av", |b| {
+        b.iter(|| {
+            let mut out = Vec::with_capacity(stream.len());
+            tsutils::filter::drop_av(stream.as_slice(), &mut out).unwrap();
+            out.len()
+        })
+    });
+    group.bench_function("compare", |b| {
+        b.iter(|| tsutils::compare::summarize(stream.as_slice()).unwrap().packets)
+    });
+    group.finish();
+}
+
+criterion_group!(benches, packets, sections, filters);
+criterion_main!(benches);
@@ -0,0 +1,226 @@
+// Synthetic stream resembling a broadcast TS, so that benchmarks need no real captures.  Each
+// cycle has PAT, PMT, an EIT section spanning several packets, video packets with PCR and PES
+// headers, and audio packets.  Payloads are pseudo-random but deterministic.
+
+extern crate tsutils;
+
+pub const PMT_PID: u16 = 0x01f0;
+pub const PCR_PID: u16 = 0x0100;
+pub const VIDEO_PID: u16 = 0x0100;
+pub const AUDIO_PID: u16 = 0x0110;
+pub const EIT_PID: u16 = 0x0012;
+
+const SERVICE_ID: u16 = 0x0400;
+const VIDEO_PACKETS_PER_CYCLE: u64 = 300;
+const AUDIO_PACKETS_PER_CYCLE: u64 = 30;
+
+struct Writer {
+    out: Vec<u8>,
+    continuity_counters: std::collections::HashMap<u16, u8>,
+    seed: u32,
+}
+
+impl Writer {
+    fn header(&mut self, pid: u16, start: bool, adaptation_field: bool) -> [u8; 4] {
+        let cc = self.continuity_counters.entry(pid).or_insert(0);
+        let header = [0x47,
+                      (start as u8) << 6 | (pid >> 8) as u8,
+                      pid as u8,
+                      if adaptation_field { 0x30 } else { 0x10 } | *cc];
+        *cc = (*cc + 1) & 0x0f;
+        header
+    }
+
+    fn random_bytes(&mut self, len: usize) -> Vec<u8> {
+        (0..len)
+            .map(|_| {
+                // Numerical Recipes LCG
+                self.seed = self.seed.wrapping_mul(1664525).wrapping_add(1013904223);
+                (self.seed >> 24) as u8
+            })
+            .collect()
+    }
+
+    // Split a section into packets starting with pointer_field and padded with 0xff
+    fn section(&mut self, pid: u16, section: &[u8]) {
+        let mut payload = vec![0];
+        payload.extend_from_slice(section);
+        for (i, chunk) in payload.chunks(184).enumerate() {
+            let header = self.header(pid, i == 0, false);
+            self.out.extend_from_slice(&header);
+            self.out.extend_from_slice(chunk);
+            self.out.extend(std::iter::repeat_n(0xff, 184 - chunk.len()));
+        }
+    }
+
+    fn video(&mut self, n: u64) {
+        let pts = 90000 + n * 3003 / 10;
+        if n.is_multiple_of(10) {
+            // PCR in the adaptation field and a PES header
+            let header = self.header(VIDEO_PID, true, true);
+            self.out.extend_from_slice(&header);
+            let base = pts - 9000;
+            self.out.extend_from_slice(&[7,
+                                         0x10,
+                                         (base >> 25) as u8,
+                                         (base >> 17) as u8,
+                                         (base >> 9) as u8,
+                                         (base >> 1) as u8,
+                                         ((base & 1) as u8) << 7 | 0x7e,
+                                         0]);
+            self.out.extend_from_slice(&[0x00,
+                                         0x00,
+                                         0x01,
+                                         0xe0,
+                                         0x00,
+                                         0x00,
+                                         0x80,
+                                         0x80,
+                                         5,
+                                         0x21 | ((pts >> 29) as u8 & 0x0e),
+                                         (pts >> 22) as u8,
+                                         (pts >> 14) as u8 & 0xfe | 1,
+                                         (pts >> 7) as u8,
+                                         (pts << 1) as u8 | 1]);
+            let payload = self.random_bytes(184 - 8 - 14);
+            self.out.extend_from_slice(&payload);
+        } else {
+            let header = self.header(VIDEO_PID, false, false);
+            self.out.extend_from_slice(&header);
+            let payload = self.random_bytes(184);
+            self.out.extend_from_slice(&payload);
+        }
+    }
+
+    fn audio(&mut self) {
+        let header = self.header(AUDIO_PID, false, false);
+        self.out.extend_from_slice(&header);
+        let payload = self.random_bytes(184);
+        self.out.extend_from_slice(&payload);
+    }
+}
+
+fn finish_section(mut section: Vec<u8>) -> Vec<u8> {
+    let section_length = section.len() - 3 + 4;
+    section[1] = 0xb0 | (section_length >> 8) as u8;
+    section[2] = section_length as u8;
+    let crc = tsutils::psi::crc32(&section);
+    section.extend_from_slice(&[(crc >> 24) as u8, (crc >> 16) as u8, (crc >> 8) as u8, crc as u8]);
+    section
+}
+
+fn pat() -> Vec<u8> {
+    finish_section(vec![0x00,
+                        0,
+                        0,
+                        0x7f,
+                        0xe0,
+                        0xc1,
+                        0,
+                        0,
+                        (SERVICE_ID >> 8) as u8,
+                        SERVICE_ID as u8,
+                        0xe0 | (PMT_PID >> 8) as u8,
+                        PMT_PID as u8])
+}
+
+fn pmt() -> Vec<u8> {
+    finish_section(vec![0x02,
+                        0,
+                        0,
+                        (SERVICE_ID >> 8) as u8,
+                        SERVICE_ID as u8,
+                        0xc1,
+                        0,
+                        0,
+                        0xe0 | (PCR_PID >> 8) as u8,
+                        PCR_PID as u8,
+                        0xf0,
+                        0,
+                        0x02,
+                        0xe0 | (VIDEO_PID >> 8) as u8,
+                        VIDEO_PID as u8,
+                        0xf0,
+                        0,
+                        0x0f,
+                        0xe0 | (AUDIO_PID >> 8) as u8,
+                        AUDIO_PID as u8,
+                        0xf0,
+                        0])
+}
+
+// EIT[schedule actual] with events of 30 minutes carrying short event descriptors
+fn eit(writer: &mut Writer, section_number: u8) -> Vec<u8> {
+    let mut section = vec![0x50,
+                           0,
+                           0,
+                           (SERVICE_ID >> 8) as u8,
+                           SERVICE_ID as u8,
+                           0xc1,
+                           section_number,
+                           0xff,
+                           0x7f,
+                           0xe0,
+                           0x7f,
+                           0xe0,
+                           0xff,
+                           0x50];
+    for i in 0..8u16 {
+        let event_id = section_number as u16 * 8 + i;
+        let hour = (event_id / 2) % 24;
+        let minute = (event_id % 2) * 30;
+        let bcd = |v: u16| ((v / 10) << 4 | (v % 10)) as u8;
+        // "\x0e" switches to alphanumeric characters
+        let mut name = vec![0x0e];
+        name.extend(format!("Event {}", event_id).bytes());
+        let mut text = vec![0x0e];
+        text.extend(writer.random_bytes(48).iter().map(|b| 0x21 + b % 0x5e));
+        let mut descriptor = vec![0x4d, 0];
+        descriptor.extend_from_slice(b"jpn");
+        descriptor.push(name.len() as u8);
+        descriptor.extend_from_slice(&name);
+        descriptor.push(text.len() as u8);
+        descriptor.extend_from_slice(&text);
+        descriptor[1] = (descriptor.len() - 2) as u8;
+        section.extend_from_slice(&[(event_id >> 8) as u8,
+                                    event_id as u8,
+                                    0xe5,
+                                    0x4a,
+                                    bcd(hour),
+                                    bcd(minute),
+                                    0x00,
+                                    0x00,
+                                    0x30,
+                                    0x00,
+                                    0x10 | (descriptor.len() >> 8) as u8,
+                                    descriptor.len() as u8]);
+        section.extend_from_slice(&descriptor);
+    }
+    finish_section(section)
+}
+
+/// A stream of at least `size` bytes
+pub fn stream(size: usize) -> Vec<u8> {
+    let mut writer = Writer {
+        out: Vec::with_capacity(size + 188 * 400),
+        continuity_counters: std::collections::HashMap::new(),
+        seed: 1,
+    };
+    let pat = pat();
+    let pmt = pmt();
+    let mut cycle = 0u64;
+    while writer.out.len() < size {
+        writer.section(0x0000, &pat);
+        writer.section(PMT_PID, &pmt);
+        let eit = eit(&mut writer, cycle as u8);
+        writer.section(EIT_PID, &eit);
+        for i in 0..VIDEO_PACKETS_PER_CYCLE {
+            writer.video(cycle * VIDEO_PACKETS_PER_CYCLE + i);
+            if i % (VIDEO_PACKETS_PER_CYCLE / AUDIO_PACKETS_PER_CYCLE) == 0 {
+                writer.audio();
+            }
+        }
+        cycle += 1;
+    }
+    writer.out
+}
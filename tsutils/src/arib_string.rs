@@ -0,0 +1,296 @@
+// ARIB STD-B24 Part 1 Chapter 7 (8-unit code)
+
+// Used for characters which cannot be represented
+const GETA: char = '\u{3013}';
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Charset {
+    Kanji,
+    Alphanumeric,
+    Hiragana,
+    Katakana,
+    JisKatakana,
+    JisX0213Plane1,
+    JisX0213Plane2,
+    AdditionalSymbols,
+    // Mosaic, DRCS and unknown sets are not rendered
+    Unsupported1,
+    Unsupported2,
+}
+
+impl Charset {
+    fn one_byte(f: u8) -> Self {
+        match f {
+            0x4a | 0x36 => Charset::Alphanumeric,
+            0x30 | 0x37 => Charset::Hiragana,
+            0x31 | 0x38 => Charset::Katakana,
+            0x49 => Charset::JisKatakana,
+            _ => Charset::Unsupported1,
+        }
+    }
+
+    fn two_byte(f: u8) -> Self {
+        match f {
+            0x42 => Charset::Kanji,
+            0x39 => Charset::JisX0213Plane1,
+            0x3a => Charset::JisX0213Plane2,
+            0x3b => Charset::AdditionalSymbols,
+            _ => Charset::Unsupported2,
+        }
+    }
+
+    fn is_two_byte(&self) -> bool {
+        matches!(*self,
+                 Charset::Kanji | Charset::JisX0213Plane1 | Charset::JisX0213Plane2 |
+                 Charset::AdditionalSymbols | Charset::Unsupported2)
+    }
+}
+
+// Row 90 of the additional symbols
+const ADDITIONAL_SYMBOLS_90: [(u8, &str); 35] = [(0x50, "[HV]"),
+                                                 (0x51, "[SD]"),
+                                                 (0x52, "[P]"),
+                                                 (0x53, "[W]"),
+                                                 (0x54, "[MV]"),
+                                                 (0x55, "[手]"),
+                                                 (0x56, "[字]"),
+                                                 (0x57, "[双]"),
+                                                 (0x58, "[デ]"),
+                                                 (0x59, "[S]"),
+                                                 (0x5a, "[二]"),
+                                                 (0x5b, "[多]"),
+                                                 (0x5c, "[解]"),
+                                                 (0x5d, "[SS]"),
+                                                 (0x5e, "[B]"),
+                                                 (0x5f, "[N]"),
+                                                 (0x60, "■"),
+                                                 (0x61, "●"),
+                                                 (0x62, "[天]"),
+                                                 (0x63, "[交]"),
+                                                 (0x64, "[映]"),
+                                                 (0x65, "[無]"),
+                                                 (0x66, "[料]"),
+                                                 (0x67, "[年齢制限]"),
+                                                 (0x68, "[前]"),
+                                                 (0x69, "[後]"),
+                                                 (0x6a, "[再]"),
+                                                 (0x6b, "[新]"),
+                                                 (0x6c, "[初]"),
+                                                 (0x6d, "[終]"),
+                                                 (0x6e, "[生]"),
+                                                 (0x6f, "[販]"),
+                                                 (0x70, "[声]"),
+                                                 (0x71, "[吹]"),
+                                                 (0x72, "[PPV]")];
+
+const HIRAGANA_KATAKANA_SYMBOLS: [char; 8] = ['ゝ', 'ゞ', 'ー', '。', '「', '」', '、', '・'];
+
+/// Decode ARIB 8-unit coded string used in SI descriptors into UTF-8.  Control codes and
+/// characters which cannot be represented in Unicode are dropped or replaced with GETA MARK.
+pub fn decode(bytes: &[u8]) -> String {
+    let mut g = [Charset::Kanji, Charset::Alphanumeric, Charset::Hiragana, Charset::Katakana];
+    let mut gl = 0;
+    let mut gr = 2;
+    let mut single_shift = None;
+    let mut out = String::new();
+
+    let mut i = 0;
+    while i < bytes.len() {
+        let b = bytes[i];
+        match b {
+            0x21..=0x7e | 0xa1..=0xfe => {
+                let charset = if b < 0x80 {
+                    single_shift.take().map(|n: usize| g[n]).unwrap_or(g[gl])
+                } else {
+                    g[gr]
+                };
+                if charset.is_two_byte() {
+                    match bytes.get(i + 1) {
+                        Some(&b2) => push_two_byte(&mut out, charset, b & 0x7f, b2 & 0x7f),
+                        None => break,
+                    }
+                    i += 2;
+                } else {
+                    push_one_byte(&mut out, charset, b & 0x7f);
+                    i += 1;
+                }
+            }
+            0x20 | 0xa0 => {
+                out.push(' ');
+                i += 1;
+            }
+            // APR
+            0x0d => {
+                out.push('\n');
+                i += 1;
+            }
+            // LS1
+            0x0e => {
+                gl = 1;
+                i += 1;
+            }
+            // LS0
+            0x0f => {
+                gl = 0;
+                i += 1;
+            }
+            // SS2
+            0x19 => {
+                single_shift = Some(2);
+                i += 1;
+            }
+            // SS3
+            0x1d => {
+                single_shift = Some(3);
+                i += 1;
+            }
+            // ESC
+            0x1b => {
+                i += 1 + designate(&bytes[(i + 1)..], &mut g, &mut gl, &mut gr);
+            }
+            // PAPF, COL (except for palette), FLC, POL, WMM, HLC, RPC
+            0x16 | 0x90 | 0x91 | 0x93 | 0x94 | 0x97 | 0x98 => {
+                if b == 0x90 && bytes.get(i + 1) == Some(&0x20) {
+                    i += 3;
+                } else {
+                    i += 2;
+                }
+            }
+            // APS
+            0x1c => {
+                i += 3;
+            }
+            // CDC
+            0x92 => {
+                if bytes.get(i + 1) == Some(&0x20) {
+                    i += 3;
+                } else {
+                    i += 2;
+                }
+            }
+            // TIME
+            0x9d => {
+                i += 3;
+            }
+            // CSI
+            0x9b => {
+                i += 1;
+                while i < bytes.len() && !(0x40..=0x6f).contains(&bytes[i]) {
+                    i += 1;
+                }
+                i += 1;
+            }
+            _ => {
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+// Return the number of consumed bytes after ESC
+fn designate(bytes: &[u8], g: &mut [Charset; 4], gl: &mut usize, gr: &mut usize) -> usize {
+    let b1 = match bytes.first() {
+        Some(&b) => b,
+        None => return 0,
+    };
+    match b1 {
+        // LS2, LS3, LS1R, LS2R, LS3R
+        0x6e => *gl = 2,
+        0x6f => *gl = 3,
+        0x7e => *gr = 1,
+        0x7d => *gr = 2,
+        0x7c => *gr = 3,
+        0x28..=0x2b => {
+            let n = (b1 - 0x28) as usize;
+            return match bytes.get(1) {
+                // DRCS
+                Some(&0x20) => {
+                    g[n] = Charset::Unsupported1;
+                    3
+                }
+                Some(&f) => {
+                    g[n] = Charset::one_byte(f);
+                    2
+                }
+                None => 1,
+            };
+        }
+        0x24 => {
+            return match bytes.get(1) {
+                Some(&b2) if (0x28..=0x2b).contains(&b2) => {
+                    let n = (b2 - 0x28) as usize;
+                    match bytes.get(2) {
+                        // DRCS
+                        Some(&0x20) => {
+                            g[n] = Charset::Unsupported2;
+                            4
+                        }
+                        Some(&f) => {
+                            g[n] = Charset::two_byte(f);
+                            3
+                        }
+                        None => 2,
+                    }
+                }
+                Some(&f) => {
+                    g[0] = Charset::two_byte(f);
+                    2
+                }
+                None => 1,
+            };
+        }
+        _ => {}
+    }
+    1
+}
+
+fn push_one_byte(out: &mut String, charset: Charset, b: u8) {
+    match charset {
+        Charset::Alphanumeric => out.push(b as char),
+        Charset::Hiragana => {
+            if b <= 0x73 {
+                out.push(std::char::from_u32(0x3041 + (b - 0x21) as u32).unwrap());
+            } else if b >= 0x77 {
+                out.push(HIRAGANA_KATAKANA_SYMBOLS[(b - 0x77) as usize]);
+            }
+        }
+        Charset::Katakana => {
+            if b <= 0x76 {
+                out.push(std::char::from_u32(0x30a1 + (b - 0x21) as u32).unwrap());
+            } else {
+                out.push(HIRAGANA_KATAKANA_SYMBOLS[(b - 0x77) as usize]);
+            }
+        }
+        Charset::JisKatakana if b <= 0x5f => {
+            out.push(std::char::from_u32(0xff61 + (b - 0x21) as u32).unwrap());
+        }
+        _ => {}
+    }
+}
+
+fn push_two_byte(out: &mut String, charset: Charset, b1: u8, b2: u8) {
+    match charset {
+        Charset::Kanji | Charset::JisX0213Plane1 | Charset::AdditionalSymbols => {
+            if b1 >= 0x7a {
+                if b1 == 0x7a {
+                    if let Some(&(_, s)) = ADDITIONAL_SYMBOLS_90.iter().find(|&&(c, _)| c == b2) {
+                        out.push_str(s);
+                        return;
+                    }
+                }
+                out.push(GETA);
+            } else {
+                let euc = [b1 | 0x80, b2 | 0x80];
+                let (s, malformed) = ::encoding_rs::EUC_JP.decode_without_bom_handling(&euc);
+                if malformed {
+                    out.push(GETA);
+                } else {
+                    out.push_str(&s);
+                }
+            }
+        }
+        Charset::JisX0213Plane2 => out.push(GETA),
+        _ => {}
+    }
+}
@@ -0,0 +1,90 @@
+extern crate clap;
+extern crate env_logger;
+extern crate tsutils;
+
+// Usage: tsutils-analyze [--only ANALYZER[,ANALYZER...]] [--checkpoint CHECKPOINT] [--progress]
+//                        FILE
+// Run the analyzers (integrity, pcr, format and epg by default) over FILE and write their
+// reports to stdout in JSON Lines.  See tsutils::report::Report for the schema.  With
+// --checkpoint, the progress is saved to CHECKPOINT every 1M packets and an interrupted run
+// resumes from it.  With --progress, the progress is written to stderr.  --completions SHELL
+// prints the shell completion and --help-json prints the options in JSON.
+const CHECKPOINT_INTERVAL: u64 = 1000000;
+
+fn main() {
+    env_logger::init().unwrap();
+    command().run(run);
+}
+
+fn run(matches: &clap::ArgMatches) -> Result<tsutils::cli::Status, tsutils::cli::Failure> {
+    let only: Option<Vec<&str>> = matches.values_of("only").map(|names| names.collect());
+    let checkpoint = matches.value_of("checkpoint");
+    let progress = matches.is_present("progress");
+    let path = matches.value_of("FILE").unwrap();
+    let enabled = |name: &str| only.as_ref().is_none_or(|only| only.contains(&name));
+
+    let mut integrity = tsutils::integrity::Monitor::new();
+    let mut pcr = tsutils::pcr::JitterAnalyzer::new();
+    let mut format = tsutils::format::FormatAnalyzer::new();
+    let mut epg = tsutils::epg::CoverageAnalyzer::new();
+    let mut analyzers: Vec<&mut dyn tsutils::report::Analyzer> = vec![];
+    if enabled("integrity") {
+        analyzers.push(&mut integrity);
+    }
+    if enabled("pcr") {
+        analyzers.push(&mut pcr);
+    }
+    if enabled("format") {
+        analyzers.push(&mut format);
+    }
+    if enabled("epg") {
+        analyzers.push(&mut epg);
+    }
+    let file = std::fs::File::open(path).map_err(|e| tsutils::cli::Failure::io(path, &e))?;
+    let stdout = std::io::stdout();
+    let mut out = std::io::BufWriter::new(stdout.lock());
+    let mut reader = tsutils::progress::Watched::new(std::io::BufReader::new(file));
+    if progress {
+        if let Ok(metadata) = std::fs::metadata(path) {
+            reader = reader.total(metadata.len());
+        }
+        reader = reader.on_progress(|progress| {
+            match (progress.percent(), progress.eta()) {
+                (Some(percent), Some(eta)) => {
+                    eprintln!("{:.1}% (ETA {}s)", percent, eta.as_secs())
+                }
+                _ => eprintln!("{} bytes", progress.bytes),
+            }
+        });
+    }
+    let callback = |report: tsutils::report::Report| report.write_line(&mut out);
+    let result = match checkpoint {
+        Some(checkpoint) => {
+            tsutils::checkpoint::analyze(reader,
+                                         &mut analyzers,
+                                         checkpoint,
+                                         CHECKPOINT_INTERVAL,
+                                         callback)
+        }
+        None => tsutils::report::analyze(reader, &mut analyzers, callback),
+    };
+    result.map_err(|e| tsutils::cli::Failure::io(path, &e))?;
+    Ok(tsutils::cli::Status::Ok)
+}
+
+fn command() -> tsutils::cli::Command {
+    tsutils::cli::Command {
+        name: "tsutils-analyze",
+        about: "Analyze a TS file and write the reports in JSON Lines",
+        args: vec![
+            tsutils::cli::Arg::option("only", "ANALYZER", "Run only the analyzers")
+                .delimited()
+                .possible_values(&["integrity", "pcr", "format", "epg"]),
+            tsutils::cli::Arg::option("checkpoint",
+                                      "CHECKPOINT",
+                                      "Save the progress to CHECKPOINT and resume from it"),
+            tsutils::cli::Arg::flag("progress", "Write the progress to stderr"),
+            tsutils::cli::Arg::positional("FILE", "TS file to analyze"),
+        ],
+    }
+}
@@ -0,0 +1,75 @@
+extern crate clap;
+extern crate env_logger;
+extern crate tsutils;
+
+// Usage: tsutils-compare [--json] BEFORE AFTER
+// Print what was removed or changed from BEFORE to AFTER, e.g. the input and the output of a
+// filter.  Exit with 1 when they differ like diff(1).  --json prints the differences as
+// reports of JSON Lines like tsutils-analyze.  --completions SHELL prints the shell completion
+// and --help-json prints the options in JSON.
+fn main() {
+    env_logger::init().unwrap();
+    command().run(run);
+}
+
+fn run(matches: &clap::ArgMatches) -> Result<tsutils::cli::Status, tsutils::cli::Failure> {
+    let before_path = matches.value_of("BEFORE").unwrap();
+    let after_path = matches.value_of("AFTER").unwrap();
+    let before = summarize(before_path)?;
+    let after = summarize(after_path)?;
+    let differences = tsutils::compare::compare(&before, &after);
+    if matches.is_present("json") {
+        let stdout = std::io::stdout();
+        let mut out = stdout.lock();
+        for difference in &differences {
+            difference.to_report()
+                .write_line(&mut out)
+                .map_err(|e| tsutils::cli::Failure::io("stdout", &e))?;
+        }
+    } else {
+        print_differences(before_path, &before, after_path, &after, &differences);
+    }
+    if differences.is_empty() {
+        Ok(tsutils::cli::Status::Ok)
+    } else {
+        Ok(tsutils::cli::Status::VerificationFailed)
+    }
+}
+
+fn command() -> tsutils::cli::Command {
+    tsutils::cli::Command {
+        name: "tsutils-compare",
+        about: "Print what was removed or changed from BEFORE to AFTER",
+        args: vec![
+            tsutils::cli::Arg::flag("json", "Print the differences in JSON Lines"),
+            tsutils::cli::Arg::positional("BEFORE", "TS file before the change"),
+            tsutils::cli::Arg::positional("AFTER", "TS file after the change"),
+        ],
+    }
+}
+
+fn print_differences(before_path: &str,
+                     before: &tsutils::compare::Summary,
+                     after_path: &str,
+                     after: &tsutils::compare::Summary,
+                     differences: &[tsutils::compare::Difference]) {
+    println!("{}: {} packets, {} PIDs, {} programs",
+             before_path,
+             before.packets,
+             before.pids.len(),
+             before.programs.len());
+    println!("{}: {} packets, {} PIDs, {} programs",
+             after_path,
+             after.packets,
+             after.pids.len(),
+             after.programs.len());
+    for difference in differences {
+        println!("{}", difference);
+    }
+}
+
+fn summarize(path: &str) -> Result<tsutils::compare::Summary, tsutils::cli::Failure> {
+    let file = std::fs::File::open(path).map_err(|e| tsutils::cli::Failure::io(path, &e))?;
+    tsutils::compare::summarize(std::io::BufReader::new(file))
+        .map_err(|e| tsutils::cli::Failure::filter(path, &e))
+}
@@ -0,0 +1,99 @@
+extern crate clap;
+extern crate env_logger;
+extern crate tsutils;
+
+// Usage: tsutils-demux-to-files [--pid PID]... [--service SERVICE_ID]... [-o DIR] FILE
+// Write each PID or service to "{stem}_pid{PID}.ts" or "{stem}_sid{SERVICE_ID}.ts" in DIR
+// (the current directory by default).  Without --pid and --service, each service in the first
+// PAT is written.  PIDs are decimal or hexadecimal with 0x.  --completions SHELL prints the
+// shell completion and --help-json prints the options in JSON.
+fn main() {
+    env_logger::init().unwrap();
+    command().run(run);
+}
+
+fn run(matches: &clap::ArgMatches) -> Result<tsutils::cli::Status, tsutils::cli::Failure> {
+    let mut selections = vec![];
+    for pid in matches.values_of("pid").into_iter().flatten() {
+        selections.push(tsutils::demux::Selection::Pid(parse_u16(pid)?));
+    }
+    for service_id in matches.values_of("service").into_iter().flatten() {
+        selections.push(tsutils::demux::Selection::Service(parse_u16(service_id)?));
+    }
+    let dir = std::path::Path::new(matches.value_of("output-dir").unwrap_or("."));
+    let path = matches.value_of("FILE").unwrap();
+    let open = || {
+        std::fs::File::open(path)
+            .map(std::io::BufReader::new)
+            .map_err(|e| tsutils::cli::Failure::io(path, &e))
+    };
+    if selections.is_empty() {
+        let mut services = first_pat_services(open()?)
+            .map_err(|e| tsutils::cli::Failure::io(path, &e))?;
+        services.sort();
+        selections.extend(services.into_iter().map(tsutils::demux::Selection::Service));
+    }
+
+    let stem = std::path::Path::new(path)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut paths = vec![];
+    let mut outputs = vec![];
+    for selection in selections {
+        let name = match selection {
+            tsutils::demux::Selection::Pid(pid) => format!("{}_pid{:04x}.ts", stem, pid),
+            tsutils::demux::Selection::Service(service_id) => {
+                format!("{}_sid{}.ts", stem, service_id)
+            }
+        };
+        let output_path = dir.join(name);
+        let file = std::fs::File::create(&output_path)
+            .map_err(|e| tsutils::cli::Failure::io(&output_path.to_string_lossy(), &e))?;
+        outputs.push(tsutils::demux::Output::new(selection, std::io::BufWriter::new(file)));
+        paths.push(output_path);
+    }
+    tsutils::demux::demux(open()?, &mut outputs)
+        .map_err(|e| tsutils::cli::Failure::filter(path, &e))?;
+    for (output_path, output) in paths.iter().zip(outputs.iter()) {
+        println!("{}: {} packets", output_path.display(), output.packets);
+    }
+    Ok(tsutils::cli::Status::Ok)
+}
+
+fn parse_u16(s: &str) -> Result<u16, tsutils::cli::Failure> {
+    let n = match s.strip_prefix("0x") {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    n.map_err(|e| tsutils::cli::Failure::new(tsutils::cli::Status::Usage, format!("{}: {}", s, e)))
+}
+
+fn first_pat_services<R>(reader: R) -> Result<Vec<u16>, std::io::Error>
+    where R: std::io::Read
+{
+    let mut tracker = tsutils::filter::ProgramTracker::new();
+    for buf in tsutils::packet::ts_packets(reader) {
+        let buf = buf?;
+        if tracker.push(&tsutils::TsPacket::new(&buf)).unwrap_or(false) {
+            if let Some(pat) = tracker.pat() {
+                return Ok(pat.program_map.values().cloned().collect());
+            }
+        }
+    }
+    Ok(vec![])
+}
+
+fn command() -> tsutils::cli::Command {
+    tsutils::cli::Command {
+        name: "tsutils-demux-to-files",
+        about: "Write each PID or service to its own TS file",
+        args: vec![
+            tsutils::cli::Arg::option("pid", "PID", "Write the PID").multiple(),
+            tsutils::cli::Arg::option("service", "SERVICE_ID", "Write the service").multiple(),
+            tsutils::cli::Arg::option("output-dir", "DIR", "Directory to write the files to")
+                .short('o'),
+            tsutils::cli::Arg::positional("FILE", "TS file to read"),
+        ],
+    }
+}
@@ -1,135 +1,161 @@
+extern crate clap;
 extern crate env_logger;
-extern crate tsutils;
-
 #[macro_use]
-extern crate log;
+extern crate serde_json;
+extern crate tsutils;
 
+// Usage: tsutils-drop-av [--service-id SERVICE_ID]... [--si psi|eit-pf|si|all]
+//                        [--drop-scrambled|--drop-descrambled] [--max-psi-buffer BYTES]
+//                        [--verbose] [--json] INPUT OUTPUT
+// Write INPUT without audio and video to OUTPUT, keeping the SI tables chosen by --si (all by
+// default).  With --service-id, only audio and video of the services are dropped and the other
+// services are kept as is.  PAT and PMT buffered for each PID are discarded over
+// --max-psi-buffer (4096 by default), e.g. when a scrambled PMT never ends.  The numbers of
+// packets and bytes kept are printed to stderr, with each PID and why it was kept or dropped by
+// --verbose.  --json prints them to stdout in JSON instead, see
+// tsutils::filter::DropAvReport::to_json() for the schema.  --completions SHELL prints the
+// shell completion and --help-json prints the options in JSON.
 fn main() {
     env_logger::init().unwrap();
-
-    let mut args = std::env::args().skip(1);
-    if let Some(input_path) = args.next() {
-        if let Some(output_path) = args.next() {
-            let input = std::fs::File::open(input_path).unwrap();
-            let output = std::fs::File::create(output_path).unwrap();
-            drop_av(input, output).unwrap();
-            return;
-        }
-    }
-    std::process::exit(1);
-}
-
-#[derive(Debug)]
-pub enum Error {
-    Io(std::io::Error),
-    PsiParseError(tsutils::psi::ParseError),
-    Custom(std::borrow::Cow<'static, str>),
+    command().run(run);
 }
 
-impl From<std::io::Error> for Error {
-    fn from(e: std::io::Error) -> Self {
-        Error::Io(e)
+fn run(matches: &clap::ArgMatches) -> Result<tsutils::cli::Status, tsutils::cli::Failure> {
+    let policy = matches.value_of("si")
+        .map_or(tsutils::filter::SiPolicy::All, |si| si.parse().unwrap());
+    let mut service_ids = vec![];
+    for service_id in matches.values_of("service-id").into_iter().flatten() {
+        service_ids.push(service_id.parse().map_err(|e| {
+            tsutils::cli::Failure::new(tsutils::cli::Status::Usage,
+                                       format!("{}: {}", service_id, e))
+        })?);
     }
-}
+    let max_payload = match matches.value_of("max-psi-buffer") {
+        Some(bytes) => {
+            bytes.parse().map_err(|e| {
+                tsutils::cli::Failure::new(tsutils::cli::Status::Usage, format!("{}: {}", bytes, e))
+            })?
+        }
+        None => tsutils::filter::DEFAULT_MAX_PAYLOAD,
+    };
+    // Some(only_descrambled) to drop scrambled packets
+    let drop_scrambled = if matches.is_present("drop-descrambled") {
+        Some(true)
+    } else if matches.is_present("drop-scrambled") {
+        Some(false)
+    } else {
+        None
+    };
+    let input_path = matches.value_of("INPUT").unwrap();
+    let output_path = matches.value_of("OUTPUT").unwrap();
+    let input = std::fs::File::open(input_path)
+        .map_err(|e| tsutils::cli::Failure::io(input_path, &e))?;
+    let output = std::fs::File::create(output_path)
+        .map_err(|e| tsutils::cli::Failure::io(output_path, &e))?;
+    let mut writer = std::io::BufWriter::new(output);
+    let (report, scrambled) = match drop_scrambled {
+        Some(only_descrambled) => {
+            let mut writer = tsutils::filter::DropScrambled::new(&mut writer, only_descrambled);
+            let report =
+                tsutils::filter::drop_av_of_services(input,
+                                                     &mut writer,
+                                                     &service_ids,
+                                                     policy,
+                                                     max_payload)
+                    .map_err(|e| tsutils::cli::Failure::filter(input_path, &e))?;
+            (report, Some(writer.into_inner().1))
+        }
+        None => {
+            let report =
+                tsutils::filter::drop_av_of_services(input,
+                                                     &mut writer,
+                                                     &service_ids,
+                                                     policy,
+                                                     max_payload)
+                    .map_err(|e| tsutils::cli::Failure::filter(input_path, &e))?;
+            (report, None)
+        }
+    };
+    std::io::Write::flush(&mut writer).map_err(|e| tsutils::cli::Failure::io(output_path, &e))?;
+    drop(writer);
+    let input_bytes = file_size(input_path)?;
+    let output_bytes = file_size(output_path)?;
 
-impl From<&'static str> for Error {
-    fn from(e: &'static str) -> Self {
-        Error::Custom(std::borrow::Cow::from(e))
+    if matches.is_present("json") {
+        let mut json = report.to_json();
+        json["input_bytes"] = input_bytes.into();
+        json["output_bytes"] = output_bytes.into();
+        if let Some(ref scrambled) = scrambled {
+            json["scrambled_packets"] = scrambled.dropped
+                .iter()
+                .map(|(pid, dropped)| json!({"pid": pid, "dropped": dropped}))
+                .collect::<Vec<_>>()
+                .into();
+        }
+        println!("{}", json);
+        return Ok(tsutils::cli::Status::Ok);
     }
-}
-
-impl From<String> for Error {
-    fn from(e: String) -> Self {
-        Error::Custom(std::borrow::Cow::from(e))
+    if matches.is_present("verbose") {
+        for (pid, stats) in &report.pids {
+            eprintln!("PID 0x{:04x}: kept {} of {} packets ({})",
+                      pid,
+                      stats.output_packets,
+                      stats.input_packets,
+                      stats.class);
+        }
     }
-}
-
-impl From<tsutils::psi::ParseError> for Error {
-    fn from(e: tsutils::psi::ParseError) -> Self {
-        Error::PsiParseError(e)
+    if let Some(ref scrambled) = scrambled {
+        for (pid, dropped) in &scrambled.dropped {
+            eprintln!("Dropped {} scrambled packets of PID 0x{:04x}", dropped, pid);
+        }
     }
+    if report.evicted_payloads > 0 {
+        eprintln!("Discarded {} incomplete PAT or PMT payloads over {} bytes",
+                  report.evicted_payloads,
+                  max_payload);
+    }
+    eprintln!("Kept {} of {} packets, {} -> {} bytes ({:+.1}%)",
+              report.output_packets(),
+              report.input_packets(),
+              input_bytes,
+              output_bytes,
+              if input_bytes == 0 {
+                  0.0
+              } else {
+                  (output_bytes as f64 - input_bytes as f64) * 100.0 / input_bytes as f64
+              });
+    Ok(tsutils::cli::Status::Ok)
 }
 
-fn drop_av<R, W>(reader: R, mut writer: W) -> Result<(), Error>
-    where R: std::io::Read,
-          W: std::io::Write
-{
-    let mut pat = None;
-    let mut payloads: std::collections::HashMap<u16, Vec<u8>> = std::collections::HashMap::new();
-    let mut av_pids = std::collections::HashSet::new();
-    let mut nonav_pids = std::collections::HashSet::new();
-    let mut tracking_pids = std::collections::HashSet::new();
-    tracking_pids.insert(0);
-
-    for buf in tsutils::packet::ts_packets(reader) {
-        let buf = try!(buf);
-        let packet = tsutils::TsPacket::new(&buf);
-        if !packet.check_sync_byte() {
-            return Err(Error::from("sync_byte failed"));
-        }
-        if packet.transport_error_indicator {
-            return Err(Error::from("transport_error_indicator is set"));
-        }
-
-        if packet.payload_unit_start_indicator {
-            if let Some(payload) = payloads.remove(&packet.pid) {
-                match packet.pid {
-                    0x0000 => {
-                        let t = try!(tsutils::ProgramAssociationTable::parse(&payload));
-                        tracking_pids.extend(t.program_map.keys());
-                        pat = Some(t);
-                    }
-                    _ => {
-                        if let Some(ref pat) = pat {
-                            if let Some(&program_number) = pat.program_map.get(&packet.pid) {
-                                let pmt = try!(tsutils::ProgramMapTable::parse(&payload));
-                                if pmt.program_number != program_number {
-                                    return Err(Error::from(format!("Inconsistent \
-                                                                    program_number for PID={}: \
-                                                                    PAT says {} but PMT says {}",
-                                                                   packet.pid,
-                                                                   program_number,
-                                                                   pmt.program_number)));
-                                }
-                                for es in pmt.es_info {
-                                    if !av_pids.contains(&es.elementary_pid) &&
-                                       !nonav_pids.contains(&es.elementary_pid) {
-                                        match es.stream_type {
-                                            0x0f => {
-                                                // Audio
-                                                av_pids.insert(es.elementary_pid);
-                                            }
-                                            0x02 | 0x1b => {
-                                                // Video
-                                                av_pids.insert(es.elementary_pid);
-                                            }
-                                            _ => {
-                                                debug!("non-AV stream_type={:x} pid={:x}",
-                                                       es.stream_type,
-                                                       es.elementary_pid);
-                                                nonav_pids.insert(es.elementary_pid);
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-        }
-
-        if tracking_pids.contains(&packet.pid) {
-            if let Some(data_bytes) = packet.data_bytes {
-                payloads.entry(packet.pid)
-                    .or_insert(Vec::new())
-                    .extend_from_slice(data_bytes);
-            }
-        }
+fn file_size(path: &str) -> Result<u64, tsutils::cli::Failure> {
+    std::fs::metadata(path)
+        .map(|metadata| metadata.len())
+        .map_err(|e| tsutils::cli::Failure::io(path, &e))
+}
 
-        if !av_pids.contains(&packet.pid) {
-            try!(writer.write(&buf));
-        }
+fn command() -> tsutils::cli::Command {
+    tsutils::cli::Command {
+        name: "tsutils-drop-av",
+        about: "Drop audio and video packets",
+        args: vec![
+            tsutils::cli::Arg::option("service-id",
+                                      "SERVICE_ID",
+                                      "Drop audio and video only of the service")
+                .multiple(),
+            tsutils::cli::Arg::option("si", "POLICY", "SI tables to keep (all by default)")
+                .possible_values(&["psi", "eit-pf", "si", "all"]),
+            tsutils::cli::Arg::flag("drop-scrambled", "Drop scrambled packets"),
+            tsutils::cli::Arg::flag("drop-descrambled",
+                                    "Drop scrambled packets only after the first clear packet of \
+                                     each PID")
+                .conflicts_with(&["drop-scrambled"]),
+            tsutils::cli::Arg::option("max-psi-buffer",
+                                      "BYTES",
+                                      "Limit of PAT and PMT buffered for each PID"),
+            tsutils::cli::Arg::flag("verbose", "Print the packets kept of each PID and why"),
+            tsutils::cli::Arg::flag("json", "Print the packets and bytes kept in JSON"),
+            tsutils::cli::Arg::positional("INPUT", "TS file to read"),
+            tsutils::cli::Arg::positional("OUTPUT", "TS file to write"),
+        ],
     }
-    Ok(())
 }
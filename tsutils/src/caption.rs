@@ -0,0 +1,129 @@
+// ARIB STD-B24 Part 1 Chapter 9 and Part 3 Chapter 9
+
+// component_tag of the main caption stream
+const CAPTION_COMPONENT_TAG: u8 = 0x30;
+
+/// A caption statement of the first language with PTS (90kHz) of its PES.  The text is empty when
+/// the statement only clears the screen.
+#[derive(Debug)]
+pub struct Caption {
+    pub pts: u64,
+    pub text: String,
+}
+
+/// Collect caption statements of the service.  When service_id is None, the program with the
+/// smallest program_number is used.
+pub fn extract<R>(reader: R, service_id: Option<u16>) -> Result<Vec<Caption>, super::filter::Error>
+    where R: std::io::Read
+{
+    let mut tracker = super::filter::ProgramTracker::new();
+    let mut pmt_assembler = super::psi::SectionAssembler::new();
+    let mut caption_pid = None;
+    let mut pes = vec![];
+    let mut captions = vec![];
+
+    for buf in super::packet::ts_packets(reader) {
+        let buf = buf?;
+        if buf[0] != 0x47 || (buf[1] & 0b10000000) != 0 {
+            continue;
+        }
+        let packet = super::TsPacket::new(&buf);
+        tracker.push(&packet)?;
+        let pmt_pid = service_id.or_else(|| {
+                tracker.pat().and_then(|pat| pat.program_map.values().min().cloned())
+            })
+            .and_then(|service_id| tracker.programs().get(&service_id))
+            .map(|program| program.pmt_pid);
+        if Some(packet.pid) == pmt_pid {
+            for section in pmt_assembler.push(&packet) {
+                // ProgramMapTable::parse expects pointer_field
+                let mut payload = vec![0];
+                payload.extend_from_slice(&section);
+                if let Ok(pmt) = super::ProgramMapTable::parse(&payload) {
+                    caption_pid = find_caption_pid(&pmt);
+                }
+            }
+        }
+
+        if caption_pid.is_some() && Some(packet.pid) == caption_pid {
+            if let Some(data_bytes) = packet.data_bytes {
+                if packet.payload_unit_start_indicator {
+                    captions.extend(parse_pes(&pes));
+                    pes.clear();
+                }
+                pes.extend_from_slice(data_bytes);
+            }
+        }
+    }
+    captions.extend(parse_pes(&pes));
+    Ok(captions)
+}
+
+fn find_caption_pid(pmt: &super::ProgramMapTable) -> Option<u16> {
+    pmt.es_info
+        .iter()
+        .find(|es| es.stream_type == 0x06 && es.component_tag() == Some(CAPTION_COMPONENT_TAG))
+        .map(|es| es.elementary_pid)
+}
+
+fn read_u24(bytes: &[u8]) -> usize {
+    (bytes[0] as usize) << 16 | (bytes[1] as usize) << 8 | bytes[2] as usize
+}
+
+// Synchronized PES of private_stream_1 carrying a caption statement
+fn parse_pes(pes: &[u8]) -> Option<Caption> {
+    // ISO/IEC 13818-1 2.4.3.6
+    if pes.len() < 9 || pes[0..3] != [0x00, 0x00, 0x01] || pes[3] != 0xbd {
+        return None;
+    }
+    let pts_dts_flags = pes[7] >> 6;
+    if pts_dts_flags & 0b10 == 0 || pes.len() < 14 {
+        return None;
+    }
+    let pts = ((pes[9] & 0b00001110) as u64) << 29 | (pes[10] as u64) << 22 |
+              ((pes[11] & 0b11111110) as u64) << 14 | (pes[12] as u64) << 7 |
+              (pes[13] as u64) >> 1;
+    let data = pes.get((9 + pes[8] as usize)..)?;
+
+    // ARIB STD-B24 Part 3 5.2
+    if data.len() < 3 || data[0] != 0x80 || data[1] != 0xff {
+        return None;
+    }
+    let group = data.get((3 + (data[2] & 0x0f) as usize)..)?;
+    // Part 1 9.2
+    if group.len() < 5 {
+        return None;
+    }
+    let data_group_id = group[0] >> 2;
+    // 0 is caption management, 1 is the statement of the first language
+    if data_group_id & 0x0f != 1 {
+        return None;
+    }
+    let data_group_size = (group[3] as usize) << 8 | group[4] as usize;
+    let body = group.get(5..(5 + data_group_size))?;
+
+    // Part 1 9.3.2
+    let tmd = body.first()? >> 6;
+    let mut index = if tmd == 1 || tmd == 2 { 6 } else { 1 };
+    let data_unit_loop_length = read_u24(body.get(index..(index + 3))?);
+    index += 3;
+    let units = body.get(index..(index + data_unit_loop_length))?;
+
+    // Part 1 9.4
+    let mut text = String::new();
+    let mut units = units;
+    while units.len() >= 5 && units[0] == 0x1f {
+        let data_unit_parameter = units[1];
+        let data_unit_size = read_u24(&units[2..5]);
+        let unit = units.get(5..(5 + data_unit_size))?;
+        // Statement body
+        if data_unit_parameter == 0x20 {
+            text.push_str(&super::arib_string::decode(unit));
+        }
+        units = &units[(5 + data_unit_size)..];
+    }
+    Some(Caption {
+        pts: pts,
+        text: text.trim().to_owned(),
+    })
+}
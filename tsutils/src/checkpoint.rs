@@ -0,0 +1,143 @@
+/// Progress of a long analysis saved to a file, e.g. of an 8-hour recording, so that it resumes
+/// from the packet after the checkpoint instead of the beginning after an interruption.
+///
+/// Only analyzers resume.  The filters (filter::drop_av(), filter::keep_av(), demux::demux()
+/// and edit::edit()) don't take checkpoints since their state includes the output written so
+/// far, so an interrupted filter is run again from the beginning into a new output.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Checkpoint {
+    /// Number of packets analyzed, and the byte offset to resume from is packet * 188
+    pub packet: u64,
+    /// State of each analyzer, in the same order as the analyzers
+    pub states: Vec<serde_json::Value>,
+}
+
+impl Checkpoint {
+    /// Return None when the file does not exist.
+    pub fn load<P>(path: P) -> Result<Option<Self>, std::io::Error>
+        where P: AsRef<std::path::Path>
+    {
+        let file = match std::fs::File::open(path) {
+            Ok(file) => file,
+            Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let value: serde_json::Value = serde_json::from_reader(std::io::BufReader::new(file))?;
+        let packet = value.get("packet").and_then(|packet| packet.as_u64());
+        let states = value.get("states").and_then(|states| states.as_array());
+        match (packet, states) {
+            (Some(packet), Some(states)) => {
+                Ok(Some(Checkpoint {
+                    packet: packet,
+                    states: states.clone(),
+                }))
+            }
+            _ => Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "Malformed checkpoint")),
+        }
+    }
+
+    /// Write to a temporary file and rename it so that an interruption does not leave a broken
+    /// checkpoint.
+    pub fn save<P>(&self, path: P) -> Result<(), std::io::Error>
+        where P: AsRef<std::path::Path>
+    {
+        let path = path.as_ref();
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        {
+            let mut writer = std::io::BufWriter::new(std::fs::File::create(&tmp_path)?);
+            serde_json::to_writer(&mut writer,
+                                  &json!({
+                                      "packet": self.packet,
+                                      "states": self.states,
+                                  }))?;
+            std::io::Write::flush(&mut writer)?;
+        }
+        std::fs::rename(tmp_path, path)
+    }
+}
+
+/// Like report::analyze(), but save a checkpoint to the path every interval packets and resume
+/// from the checkpoint if it exists.  Reports found between the last checkpoint and the
+/// interruption are passed to the callback again after resuming.  The checkpoint is removed
+/// after the last packet.  Checkpoints are not saved when any of the analyzers does not
+/// support them.
+pub fn analyze<R, P, F>(mut reader: R,
+                        analyzers: &mut [&mut dyn super::report::Analyzer],
+                        path: P,
+                        interval: u64,
+                        mut callback: F)
+                        -> Result<(), std::io::Error>
+    where R: std::io::Read + std::io::Seek,
+          P: AsRef<std::path::Path>,
+          F: FnMut(super::report::Report) -> Result<(), std::io::Error>
+{
+    let path = path.as_ref();
+    let mut start = 0;
+    if let Some(checkpoint) = Checkpoint::load(path)? {
+        if checkpoint.states.len() == analyzers.len() &&
+           analyzers.iter_mut()
+            .zip(&checkpoint.states)
+            .all(|(analyzer, state)| analyzer.restore(state)) {
+            info!("Resume from packet {} of the checkpoint {}", checkpoint.packet, path.display());
+            start = checkpoint.packet;
+            reader.seek(std::io::SeekFrom::Start(start * 188))?;
+        } else {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData,
+                                           format!("{} is not a checkpoint of the analyzers",
+                                                   path.display())));
+        }
+    }
+
+    let mut reports = vec![];
+    let mut index = start;
+    for buf in super::packet::ts_packets(reader) {
+        let buf = buf?;
+        let packet = if super::report::is_broken(&buf) {
+            None
+        } else {
+            Some(super::TsPacket::new(&buf))
+        };
+        for analyzer in analyzers.iter_mut() {
+            analyzer.push(index, &buf, packet.as_ref(), &mut reports);
+        }
+        for report in reports.drain(..) {
+            callback(report)?;
+        }
+        index += 1;
+        if (index - start).is_multiple_of(interval) {
+            let states: Option<Vec<_>> =
+                analyzers.iter().map(|analyzer| analyzer.checkpoint()).collect();
+            if let Some(states) = states {
+                let checkpoint = Checkpoint {
+                    packet: index,
+                    states: states,
+                };
+                checkpoint.save(path)?;
+            }
+        }
+    }
+    for analyzer in analyzers.iter_mut() {
+        analyzer.finish(&mut reports);
+    }
+    for report in reports {
+        callback(report)?;
+    }
+    match std::fs::remove_file(path) {
+        Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+// Bytes such as partial sections in checkpoints
+pub(crate) fn hex(bytes: &[u8]) -> serde_json::Value {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect::<String>().into()
+}
+
+pub(crate) fn unhex(value: &serde_json::Value) -> Option<Vec<u8>> {
+    let s = value.as_str()?;
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len()).step_by(2).map(|i| u8::from_str_radix(s.get(i..(i + 2))?, 16).ok()).collect()
+}
@@ -0,0 +1,347 @@
+/// Exit status of the binaries so that wrapping scripts can branch on the type of the failure.
+/// The codes follow diff(1) and sysexits(3): 1 when the input was read but failed the check,
+/// 2 for the other failures, and the codes of sysexits(3) for the specific failures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    /// e.g. tsutils-compare found differences
+    VerificationFailed,
+    Failure,
+    /// Wrong arguments
+    Usage,
+    /// Malformed input, e.g. lost sync_byte or broken PSI
+    ParseError,
+    InputNotFound,
+    /// Cancelled with progress::CancellationToken or interrupted by a signal
+    Interrupted,
+}
+
+impl Status {
+    pub fn code(self) -> i32 {
+        match self {
+            Status::Ok => 0,
+            Status::VerificationFailed => 1,
+            Status::Failure => 2,
+            Status::Usage => 64,
+            Status::ParseError => 65,
+            Status::InputNotFound => 66,
+            Status::Interrupted => 130,
+        }
+    }
+
+    /// Status of a child process following the convention, e.g. of a binary run by a pipeline
+    pub fn from_code(code: i32) -> Self {
+        match code {
+            0 => Status::Ok,
+            1 => Status::VerificationFailed,
+            64 => Status::Usage,
+            65 => Status::ParseError,
+            66 => Status::InputNotFound,
+            130 => Status::Interrupted,
+            _ => Status::Failure,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Status::Ok => "ok",
+            Status::VerificationFailed => "verification_failed",
+            Status::Failure => "failure",
+            Status::Usage => "usage",
+            Status::ParseError => "parse_error",
+            Status::InputNotFound => "input_not_found",
+            Status::Interrupted => "interrupted",
+        }
+    }
+}
+
+/// Error ending a binary with the status
+#[derive(Debug)]
+pub struct Failure {
+    pub status: Status,
+    pub message: String,
+}
+
+impl Failure {
+    pub fn new<S>(status: Status, message: S) -> Self
+        where S: Into<String>
+    {
+        Failure {
+            status: status,
+            message: message.into(),
+        }
+    }
+
+    /// Classify the error of reading or writing the path
+    pub fn io(path: &str, e: &std::io::Error) -> Self {
+        let status = if super::progress::is_cancelled(e) ||
+                        e.kind() == std::io::ErrorKind::Interrupted {
+            Status::Interrupted
+        } else {
+            match e.kind() {
+                std::io::ErrorKind::NotFound => Status::InputNotFound,
+                std::io::ErrorKind::InvalidData => Status::ParseError,
+                _ => Status::Failure,
+            }
+        };
+        Failure::new(status, format!("{}: {}", path, e))
+    }
+
+    /// Classify the error of processing the path
+    pub fn filter(path: &str, e: &super::filter::Error) -> Self {
+        match *e {
+            super::filter::Error::Io(ref e) => Failure::io(path, e),
+            // Custom errors are malformed streams such as lost sync_byte
+            super::filter::Error::PsiParseError(_) |
+            super::filter::Error::Custom(_) => {
+                Failure::new(Status::ParseError, format!("{}: {}", path, e))
+            }
+        }
+    }
+}
+
+/// Definition of the command line of a tsutils binary, from which the clap parser, shell
+/// completions (--completions SHELL) and a machine-readable description (--help-json) are
+/// generated.  All binaries accept --error-json PATH to write the failure in JSON, e.g.
+/// {"status": "input_not_found", "code": 66, "message": "..."}.
+pub struct Command {
+    pub name: &'static str,
+    pub about: &'static str,
+    pub args: Vec<Arg>,
+}
+
+/// An option, a flag or a positional argument
+pub struct Arg {
+    pub name: &'static str,
+    /// None for positional arguments
+    pub long: Option<&'static str>,
+    pub short: Option<char>,
+    /// None for flags without a value
+    pub value_name: Option<&'static str>,
+    pub help: &'static str,
+    pub required: bool,
+    /// Whether it can be given more than once
+    pub multiple: bool,
+    /// Comma-separated values are split, e.g. "--only integrity,pcr"
+    pub delimited: bool,
+    pub possible_values: &'static [&'static str],
+    pub conflicts_with: &'static [&'static str],
+}
+
+impl Arg {
+    pub fn positional(name: &'static str, help: &'static str) -> Self {
+        Arg {
+            name: name,
+            long: None,
+            short: None,
+            value_name: Some(name),
+            help: help,
+            required: true,
+            multiple: false,
+            delimited: false,
+            possible_values: &[],
+            conflicts_with: &[],
+        }
+    }
+
+    pub fn flag(long: &'static str, help: &'static str) -> Self {
+        Arg {
+            name: long,
+            long: Some(long),
+            value_name: None,
+            required: false,
+            ..Self::positional(long, help)
+        }
+    }
+
+    pub fn option(long: &'static str, value_name: &'static str, help: &'static str) -> Self {
+        Arg {
+            name: long,
+            long: Some(long),
+            value_name: Some(value_name),
+            required: false,
+            ..Self::positional(long, help)
+        }
+    }
+
+    pub fn short(mut self, short: char) -> Self {
+        self.short = Some(short);
+        self
+    }
+
+    pub fn multiple(mut self) -> Self {
+        self.multiple = true;
+        self
+    }
+
+    pub fn delimited(mut self) -> Self {
+        self.delimited = true;
+        self
+    }
+
+    pub fn possible_values(mut self, values: &'static [&'static str]) -> Self {
+        self.possible_values = values;
+        self
+    }
+
+    pub fn conflicts_with(mut self, names: &'static [&'static str]) -> Self {
+        self.conflicts_with = names;
+        self
+    }
+
+    fn to_clap(&self) -> clap::Arg<'static, 'static> {
+        let mut arg = clap::Arg::with_name(self.name).help(self.help).required(self.required);
+        if let Some(long) = self.long {
+            arg = arg.long(long);
+        }
+        if let Some(short) = self.short {
+            arg = arg.short(short.encode_utf8(&mut [0; 4]));
+        }
+        if let (Some(value_name), Some(_)) = (self.value_name, self.long) {
+            arg = arg.takes_value(true).value_name(value_name);
+        }
+        if self.multiple {
+            arg = arg.multiple(true).number_of_values(1);
+        }
+        if self.delimited {
+            arg = arg.use_delimiter(true);
+        }
+        if !self.possible_values.is_empty() {
+            arg = arg.possible_values(self.possible_values);
+        }
+        if !self.conflicts_with.is_empty() {
+            arg = arg.conflicts_with_all(self.conflicts_with);
+        }
+        arg
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        json!({
+            "name": self.name,
+            "long": self.long,
+            "short": self.short.map(|short| short.to_string()),
+            "value_name": self.value_name,
+            "help": self.help,
+            "required": self.required,
+            "multiple": self.multiple,
+            "delimited": self.delimited,
+            "possible_values": self.possible_values,
+            "conflicts_with": self.conflicts_with,
+        })
+    }
+}
+
+impl Command {
+    pub fn app(&self) -> clap::App<'static, 'static> {
+        let mut app = clap::App::new(self.name)
+            .about(self.about)
+            .setting(clap::AppSettings::ArgRequiredElseHelp)
+            .arg(clap::Arg::with_name("completions")
+                .long("completions")
+                .value_name("SHELL")
+                .possible_values(&clap::Shell::variants())
+                .help("Print the completion script for the shell"))
+            .arg(clap::Arg::with_name("help-json")
+                .long("help-json")
+                .help("Print the options and arguments in JSON"))
+            .arg(clap::Arg::with_name("error-json")
+                .long("error-json")
+                .value_name("PATH")
+                .help("Write the failure to PATH in JSON"));
+        for arg in &self.args {
+            app = app.arg(arg.to_clap());
+        }
+        app
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "name": self.name,
+            "about": self.about,
+            "args": self.args.iter().map(|arg| arg.to_json()).collect::<Vec<_>>(),
+        })
+    }
+
+    /// Parse the arguments of the process.  --completions and --help-json are handled before the
+    /// required arguments are checked, and mistakes exit with Status::Usage after the usage.
+    pub fn get_matches(&self) -> clap::ArgMatches<'static> {
+        let args: Vec<String> = std::env::args().collect();
+        if args.iter().any(|arg| arg == "--help-json") {
+            println!("{}", self.to_json());
+            std::process::exit(0);
+        }
+        // Taken before parsing so that usage errors are reported as well
+        let error_json = args.iter()
+            .position(|arg| arg == "--error-json")
+            .and_then(|i| args.get(i + 1))
+            .cloned();
+        if let Some(i) = args.iter().position(|arg| arg == "--completions") {
+            match args.get(i + 1).and_then(|shell| shell.parse::<clap::Shell>().ok()) {
+                Some(shell) => {
+                    self.app().gen_completions_to(self.name, shell, &mut std::io::stdout());
+                    std::process::exit(0);
+                }
+                None => {
+                    let message = format!("--completions requires one of {}",
+                                          clap::Shell::variants().join(", "));
+                    self.exit(error_json.as_deref(),
+                              Err(Failure::new(Status::Usage, message)));
+                }
+            }
+        }
+        match self.app().get_matches_from_safe(args) {
+            Ok(matches) => matches,
+            Err(e) => {
+                match e.kind {
+                    clap::ErrorKind::HelpDisplayed |
+                    clap::ErrorKind::VersionDisplayed => {
+                        println!("{}", e.message);
+                        std::process::exit(0);
+                    }
+                    _ => {
+                        self.exit(error_json.as_deref(),
+                                  Err(Failure::new(Status::Usage, e.message)))
+                    }
+                }
+            }
+        }
+    }
+
+    /// Parse the arguments, run main and exit with its status.  The message of Failure is
+    /// written to stderr and to --error-json PATH if given.
+    pub fn run<F>(&self, main: F) -> !
+        where F: FnOnce(&clap::ArgMatches) -> Result<Status, Failure>
+    {
+        let matches = self.get_matches();
+        self.exit(matches.value_of("error-json"), main(&matches))
+    }
+
+    fn exit(&self, error_json: Option<&str>, result: Result<Status, Failure>) -> ! {
+        let failure = match result {
+            Ok(status) => {
+                if status != Status::Ok {
+                    write_error_json(error_json, self.name, status, None);
+                }
+                std::process::exit(status.code());
+            }
+            Err(failure) => failure,
+        };
+        eprintln!("{}", failure.message);
+        write_error_json(error_json, self.name, failure.status, Some(&failure.message));
+        std::process::exit(failure.status.code());
+    }
+}
+
+fn write_error_json(path: Option<&str>, program: &str, status: Status, message: Option<&str>) {
+    if let Some(path) = path {
+        let report = json!({
+            "program": program,
+            "status": status.as_str(),
+            "code": status.code(),
+            "message": message,
+        });
+        if let Err(e) = std::fs::write(path, format!("{}\n", report)) {
+            eprintln!("{}: {}", path, e);
+        }
+    }
+}
@@ -0,0 +1,343 @@
+// PSI/SI PIDs whose sections are compared in addition to PAT and PMT: NIT, SDT, EIT and TOT
+const SI_PIDS: [u16; 4] = [0x0010, 0x0011, 0x0012, 0x0014];
+
+/// Structure of a stream to be compared with another one
+#[derive(Debug, Default)]
+pub struct Summary {
+    pub packets: u64,
+    pub pids: std::collections::BTreeMap<u16, PidSummary>,
+    /// Programs of the last PAT and PMT keyed by program_number
+    pub programs: std::collections::BTreeMap<u16, ProgramSummary>,
+    /// Distinct sections as (table_id, CRC_32) of PAT, PMT and SI_PIDS
+    pub sections: std::collections::BTreeMap<u16, std::collections::BTreeSet<(u8, u32)>>,
+}
+
+#[derive(Debug, Default)]
+pub struct PidSummary {
+    pub packets: u64,
+    /// PTS (90kHz) of the first and the last PES
+    pub pts_range: Option<(u64, u64)>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ProgramSummary {
+    pub pmt_pid: u16,
+    pub pcr_pid: u16,
+    /// (stream_type, elementary_PID)
+    pub streams: Vec<(u8, u16)>,
+}
+
+/// Scan the whole stream.  Packets with a wrong sync_byte or transport_error_indicator are
+/// counted but not inspected.
+pub fn summarize<R>(reader: R) -> Result<Summary, super::filter::Error>
+    where R: std::io::Read
+{
+    let mut summary = Summary::default();
+    let mut tracker = super::filter::ProgramTracker::new();
+    let mut assemblers = std::collections::HashMap::new();
+
+    for buf in super::packet::ts_packets(reader) {
+        let buf = buf?;
+        summary.packets += 1;
+        let packet = super::TsPacket::new(&buf);
+        if !packet.check_sync_byte() || packet.transport_error_indicator {
+            continue;
+        }
+        let pid_summary = summary.pids.entry(packet.pid).or_insert_with(PidSummary::default);
+        pid_summary.packets += 1;
+
+        tracker.push(&packet)?;
+        let is_psi = packet.pid == 0x0000 || SI_PIDS.contains(&packet.pid) ||
+                     tracker.pat()
+            .map(|pat| pat.program_map.contains_key(&packet.pid))
+            .unwrap_or(false);
+        if is_psi {
+            let assembler = assemblers.entry(packet.pid)
+                .or_insert_with(super::psi::SectionAssembler::new);
+            for section in assembler.push(&packet) {
+                if section.len() >= 7 {
+                    let n = section.len();
+                    let crc32 = (section[n - 4] as u32) << 24 | (section[n - 3] as u32) << 16 |
+                                (section[n - 2] as u32) << 8 |
+                                section[n - 1] as u32;
+                    summary.sections
+                        .entry(packet.pid)
+                        .or_insert_with(std::collections::BTreeSet::new)
+                        .insert((section[0], crc32));
+                }
+            }
+        } else if packet.payload_unit_start_indicator {
+            if let Some(pts) = packet.data_bytes.and_then(parse_pts) {
+                pid_summary.pts_range = Some(match pid_summary.pts_range {
+                    Some((first, _)) => (first, pts),
+                    None => (pts, pts),
+                });
+            }
+        }
+    }
+
+    for (&program_number, program) in tracker.programs() {
+        summary.programs.insert(program_number,
+                                ProgramSummary {
+                                    pmt_pid: program.pmt_pid,
+                                    pcr_pid: program.pcr_pid,
+                                    streams: program.streams.clone(),
+                                });
+    }
+    Ok(summary)
+}
+
+// PTS in the header of the PES starting at the packet
+fn parse_pts(data_bytes: &[u8]) -> Option<u64> {
+    // ISO/IEC 13818-1 2.4.3.6
+    if data_bytes.len() < 14 || data_bytes[0..3] != [0x00, 0x00, 0x01] {
+        return None;
+    }
+    match data_bytes[3] {
+        // program_stream_map, padding_stream, private_stream_2, ECM, EMM, directory and DSM-CC
+        // have no PTS
+        0xbc | 0xbe | 0xbf | 0xf0 | 0xf1 | 0xf2 | 0xf8 | 0xff => return None,
+        _ => {}
+    }
+    if data_bytes[7] >> 6 & 0b10 == 0 {
+        return None;
+    }
+    let pes = data_bytes;
+    Some(((pes[9] & 0b00001110) as u64) << 29 | (pes[10] as u64) << 22 |
+         ((pes[11] & 0b11111110) as u64) << 14 | (pes[12] as u64) << 7 |
+         (pes[13] as u64) >> 1)
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Difference {
+    PidRemoved { pid: u16, packets: u64 },
+    PidAdded { pid: u16, packets: u64 },
+    PacketCount { pid: u16, before: u64, after: u64 },
+    PtsRange {
+        pid: u16,
+        before: Option<(u64, u64)>,
+        after: Option<(u64, u64)>,
+    },
+    ProgramRemoved { program_number: u16 },
+    ProgramAdded { program_number: u16 },
+    ProgramChanged {
+        program_number: u16,
+        removed_streams: Vec<(u8, u16)>,
+        added_streams: Vec<(u8, u16)>,
+        pcr_pid: Option<(u16, u16)>,
+    },
+    /// Numbers of distinct sections found only before or after
+    Sections {
+        pid: u16,
+        removed: usize,
+        added: usize,
+    },
+}
+
+/// Differences from `before` to `after`, e.g. from the input to the output of a filter
+pub fn compare(before: &Summary, after: &Summary) -> Vec<Difference> {
+    let mut differences = vec![];
+
+    for (&pid, b) in &before.pids {
+        match after.pids.get(&pid) {
+            None => {
+                differences.push(Difference::PidRemoved {
+                    pid: pid,
+                    packets: b.packets,
+                })
+            }
+            Some(a) => {
+                if a.packets != b.packets {
+                    differences.push(Difference::PacketCount {
+                        pid: pid,
+                        before: b.packets,
+                        after: a.packets,
+                    });
+                }
+                if a.pts_range != b.pts_range {
+                    differences.push(Difference::PtsRange {
+                        pid: pid,
+                        before: b.pts_range,
+                        after: a.pts_range,
+                    });
+                }
+            }
+        }
+    }
+    for (&pid, a) in &after.pids {
+        if !before.pids.contains_key(&pid) {
+            differences.push(Difference::PidAdded {
+                pid: pid,
+                packets: a.packets,
+            });
+        }
+    }
+
+    for (&program_number, b) in &before.programs {
+        match after.programs.get(&program_number) {
+            None => differences.push(Difference::ProgramRemoved { program_number: program_number }),
+            Some(a) if a != b => {
+                differences.push(Difference::ProgramChanged {
+                    program_number: program_number,
+                    removed_streams: b.streams
+                        .iter()
+                        .filter(|s| !a.streams.contains(s))
+                        .cloned()
+                        .collect(),
+                    added_streams: a.streams
+                        .iter()
+                        .filter(|s| !b.streams.contains(s))
+                        .cloned()
+                        .collect(),
+                    pcr_pid: if a.pcr_pid != b.pcr_pid {
+                        Some((b.pcr_pid, a.pcr_pid))
+                    } else {
+                        None
+                    },
+                })
+            }
+            Some(_) => {}
+        }
+    }
+    for &program_number in after.programs.keys() {
+        if !before.programs.contains_key(&program_number) {
+            differences.push(Difference::ProgramAdded { program_number: program_number });
+        }
+    }
+
+    let empty = std::collections::BTreeSet::new();
+    let pids: std::collections::BTreeSet<_> =
+        before.sections.keys().chain(after.sections.keys()).collect();
+    for &pid in pids {
+        let b = before.sections.get(&pid).unwrap_or(&empty);
+        let a = after.sections.get(&pid).unwrap_or(&empty);
+        let removed = b.difference(a).count();
+        let added = a.difference(b).count();
+        // A removed PID is already reported
+        if (removed != 0 || added != 0) && after.pids.contains_key(&pid) &&
+           before.pids.contains_key(&pid) {
+            differences.push(Difference::Sections {
+                pid: pid,
+                removed: removed,
+                added: added,
+            });
+        }
+    }
+    differences
+}
+
+impl Difference {
+    /// Event named after the variant in snake case, with the fields as data
+    pub fn to_report(&self) -> super::report::Report {
+        let report = |event| super::report::Report::new("compare", event);
+        let range = |range: &Option<(u64, u64)>| match *range {
+            Some((first, last)) => serde_json::Value::from(vec![first, last]),
+            None => serde_json::Value::Null,
+        };
+        let streams = |streams: &[(u8, u16)]| {
+            serde_json::Value::from(streams.iter()
+                .map(|&(stream_type, pid)| serde_json::Value::from(vec![stream_type as u16, pid]))
+                .collect::<Vec<_>>())
+        };
+        match *self {
+            Difference::PidRemoved { pid, packets } => {
+                report("pid_removed").pid(pid).field("packets", packets)
+            }
+            Difference::PidAdded { pid, packets } => {
+                report("pid_added").pid(pid).field("packets", packets)
+            }
+            Difference::PacketCount { pid, before, after } => {
+                report("packet_count").pid(pid).field("before", before).field("after", after)
+            }
+            Difference::PtsRange { pid, ref before, ref after } => {
+                report("pts_range")
+                    .pid(pid)
+                    .field("before", range(before))
+                    .field("after", range(after))
+            }
+            Difference::ProgramRemoved { program_number } => {
+                report("program_removed").field("program_number", program_number)
+            }
+            Difference::ProgramAdded { program_number } => {
+                report("program_added").field("program_number", program_number)
+            }
+            Difference::ProgramChanged { program_number,
+                                         ref removed_streams,
+                                         ref added_streams,
+                                         pcr_pid } => {
+                let mut r = report("program_changed")
+                    .field("program_number", program_number)
+                    .field("removed_streams", streams(removed_streams))
+                    .field("added_streams", streams(added_streams));
+                if let Some((before, after)) = pcr_pid {
+                    r = r.field("pcr_pid", vec![before, after]);
+                }
+                r
+            }
+            Difference::Sections { pid, removed, added } => {
+                report("sections").pid(pid).field("removed", removed).field("added", added)
+            }
+        }
+    }
+}
+
+fn format_pts_range(range: &Option<(u64, u64)>) -> String {
+    match *range {
+        Some((first, last)) => {
+            format!("{:.3}-{:.3}", first as f64 / 90000.0, last as f64 / 90000.0)
+        }
+        None => "none".to_owned(),
+    }
+}
+
+impl std::fmt::Display for Difference {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            Difference::PidRemoved { pid, packets } => {
+                write!(f, "PID 0x{:04x}: removed ({} packets)", pid, packets)
+            }
+            Difference::PidAdded { pid, packets } => {
+                write!(f, "PID 0x{:04x}: added ({} packets)", pid, packets)
+            }
+            Difference::PacketCount { pid, before, after } => {
+                write!(f, "PID 0x{:04x}: {} -> {} packets", pid, before, after)
+            }
+            Difference::PtsRange { pid, ref before, ref after } => {
+                write!(f,
+                       "PID 0x{:04x}: PTS {} -> {}",
+                       pid,
+                       format_pts_range(before),
+                       format_pts_range(after))
+            }
+            Difference::ProgramRemoved { program_number } => {
+                write!(f, "Program {}: removed", program_number)
+            }
+            Difference::ProgramAdded { program_number } => {
+                write!(f, "Program {}: added", program_number)
+            }
+            Difference::ProgramChanged { program_number,
+                                         ref removed_streams,
+                                         ref added_streams,
+                                         pcr_pid } => {
+                write!(f, "Program {}:", program_number)?;
+                for &(stream_type, pid) in removed_streams {
+                    write!(f, " -0x{:04x}(stream_type=0x{:02x})", pid, stream_type)?;
+                }
+                for &(stream_type, pid) in added_streams {
+                    write!(f, " +0x{:04x}(stream_type=0x{:02x})", pid, stream_type)?;
+                }
+                if let Some((before, after)) = pcr_pid {
+                    write!(f, " PCR_PID 0x{:04x} -> 0x{:04x}", before, after)?;
+                }
+                Ok(())
+            }
+            Difference::Sections { pid, removed, added } => {
+                write!(f,
+                       "PID 0x{:04x}: {} sections removed, {} sections added",
+                       pid,
+                       removed,
+                       added)
+            }
+        }
+    }
+}
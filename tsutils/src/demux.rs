@@ -0,0 +1,161 @@
+/// Packets written to each output of demux()
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Selection {
+    /// One PID.  When it is an elementary stream or PCR of a program, PCR and PAT and PMT listing
+    /// only the program and the stream are written as well so that players can play it.
+    Pid(u16),
+    /// All elementary streams and PCR of the program with PAT listing only the program and its
+    /// PMT
+    Service(u16),
+}
+
+/// Output of demux()
+pub struct Output<W> {
+    pub selection: Selection,
+    pub writer: W,
+    /// Number of packets written, including PAT and PMT
+    pub packets: u64,
+    // Packets of these PIDs are copied as is
+    pids: std::collections::HashSet<u16>,
+    // PMT_PID and rewritten PAT and PMT payloads, written each time PAT of the input starts
+    psi: Option<(u16, Vec<u8>, Vec<u8>)>,
+    pat_continuity_counter: u8,
+    pmt_continuity_counter: u8,
+}
+
+impl<W> Output<W>
+    where W: std::io::Write
+{
+    pub fn new(selection: Selection, writer: W) -> Self {
+        Output {
+            selection: selection,
+            writer: writer,
+            packets: 0,
+            pids: std::collections::HashSet::new(),
+            psi: None,
+            pat_continuity_counter: 0,
+            pmt_continuity_counter: 0,
+        }
+    }
+
+    // Choose the PIDs and rewrite PAT and PMT for the selection
+    fn update(&mut self,
+              pat: &super::ProgramAssociationTable,
+              pmts: &std::collections::HashMap<u16, Vec<u8>>)
+              -> Result<(), super::psi::ParseError> {
+        self.pids.clear();
+        self.psi = None;
+        for (&pmt_pid, payload) in pmts {
+            if !pat.program_map.contains_key(&pmt_pid) {
+                continue;
+            }
+            let mut pmt = super::ProgramMapTable::parse(payload)?;
+            match self.selection {
+                Selection::Service(service_id) => {
+                    if pmt.program_number != service_id {
+                        continue;
+                    }
+                }
+                Selection::Pid(pid) => {
+                    if pmt.pcr_pid != pid && !pmt.es_info.iter().any(|es| es.elementary_pid == pid) {
+                        continue;
+                    }
+                    pmt.es_info.retain(|es| es.elementary_pid == pid);
+                }
+            }
+            self.pids.insert(pmt.pcr_pid);
+            self.pids.extend(pmt.es_info.iter().map(|es| es.elementary_pid));
+            let pat = super::ProgramAssociationTable {
+                program_map: std::iter::once((pmt_pid, pmt.program_number)).collect(),
+                ..*pat
+            };
+            self.psi = Some((pmt_pid, pat.to_bytes(), pmt.to_bytes()));
+            return Ok(());
+        }
+        // Other PIDs such as SI are copied without PSI
+        if let Selection::Pid(pid) = self.selection {
+            self.pids.insert(pid);
+        }
+        Ok(())
+    }
+
+    fn write_psi(&mut self) -> std::io::Result<()> {
+        if let Some((pmt_pid, ref pat, ref pmt)) = self.psi {
+            super::psi::write_payload(&mut self.writer, 0x0000, &mut self.pat_continuity_counter, pat)?;
+            super::psi::write_payload(&mut self.writer, pmt_pid, &mut self.pmt_continuity_counter, pmt)?;
+            self.packets += (pat.len().div_ceil(184) + pmt.len().div_ceil(184)) as u64;
+        }
+        Ok(())
+    }
+
+    fn write_packet(&mut self, packet: &[u8]) -> std::io::Result<()> {
+        self.writer.write_all(packet)?;
+        self.packets += 1;
+        Ok(())
+    }
+}
+
+/// Split the stream into the outputs in one pass, e.g. to isolate a problematic stream.  PAT
+/// and PMT are rewritten for each output and written where PAT of the input starts.
+pub fn demux<R, W>(reader: R, outputs: &mut [Output<W>]) -> Result<(), super::filter::Error>
+    where R: std::io::Read,
+          W: std::io::Write
+{
+    let mut pat: Option<super::ProgramAssociationTable> = None;
+    // Payloads of the latest PMT keyed by PMT_PID, starting with pointer_field
+    let mut pmts = std::collections::HashMap::new();
+    let mut assemblers: std::collections::HashMap<u16, super::psi::SectionAssembler> =
+        std::collections::HashMap::new();
+
+    for buf in super::packet::ts_packets(reader) {
+        let buf = buf?;
+        let packet = super::TsPacket::new(&buf);
+        if !packet.check_sync_byte() {
+            return Err(super::filter::Error::from("sync_byte failed"));
+        }
+
+        let is_psi = packet.pid == 0x0000 ||
+                     pat.as_ref().is_some_and(|pat| pat.program_map.contains_key(&packet.pid));
+        if is_psi {
+            let mut updated = false;
+            let sections = assemblers.entry(packet.pid).or_default().push(&packet);
+            for section in sections {
+                // parse() of PAT and PMT expects pointer_field
+                let mut payload = vec![0];
+                payload.extend_from_slice(&section);
+                if packet.pid == 0x0000 {
+                    if section[0] == 0x00 {
+                        let new_pat = super::ProgramAssociationTable::parse(&payload)?;
+                        if pat.as_ref() != Some(&new_pat) {
+                            pat = Some(new_pat);
+                            updated = true;
+                        }
+                    }
+                } else if section[0] == 0x02 && pmts.get(&packet.pid) != Some(&payload) {
+                    pmts.insert(packet.pid, payload);
+                    updated = true;
+                }
+            }
+            if let (true, Some(pat)) = (updated, pat.as_ref()) {
+                for output in outputs.iter_mut() {
+                    output.update(pat, &pmts)?;
+                }
+            }
+            if packet.pid == 0x0000 && packet.payload_unit_start_indicator {
+                for output in outputs.iter_mut() {
+                    output.write_psi()?;
+                }
+            }
+        }
+
+        for output in outputs.iter_mut() {
+            if output.pids.contains(&packet.pid) {
+                output.write_packet(&buf)?;
+            }
+        }
+    }
+    for output in outputs.iter_mut() {
+        output.writer.flush()?;
+    }
+    Ok(())
+}
@@ -0,0 +1,533 @@
+/// Iterate over descriptor loop and yield (descriptor_tag, descriptor body).
+pub struct Descriptors<'a> {
+    payload: &'a [u8],
+}
+
+impl<'a> Iterator for Descriptors<'a> {
+    type Item = (u8, &'a [u8]);
+
+    fn next(&mut self) -> Option<(u8, &'a [u8])> {
+        if self.payload.len() < 2 {
+            return None;
+        }
+        let descriptor_tag = self.payload[0];
+        let descriptor_length = self.payload[1] as usize;
+        if self.payload.len() < 2 + descriptor_length {
+            return None;
+        }
+        let body = &self.payload[2..(2 + descriptor_length)];
+        self.payload = &self.payload[(2 + descriptor_length)..];
+        Some((descriptor_tag, body))
+    }
+}
+
+pub fn descriptors<'a>(payload: &'a [u8]) -> Descriptors<'a> {
+    Descriptors { payload: payload }
+}
+
+#[derive(Debug)]
+pub struct ServiceDescriptor<'a> {
+    pub service_type: u8,
+    pub service_provider_name: &'a [u8],
+    pub service_name: &'a [u8],
+}
+
+impl<'a> ServiceDescriptor<'a> {
+    pub const TAG: u8 = 0x48;
+
+    pub fn parse(body: &'a [u8]) -> Option<Self> {
+        // ARIB STD-B10 Part 2 6.2.13
+        let service_type = *body.first()?;
+        let provider_length = *body.get(1)? as usize;
+        let service_provider_name = body.get(2..(2 + provider_length))?;
+        let index = 2 + provider_length;
+        let name_length = *body.get(index)? as usize;
+        let service_name = body.get((index + 1)..(index + 1 + name_length))?;
+        Some(ServiceDescriptor {
+            service_type: service_type,
+            service_provider_name: service_provider_name,
+            service_name: service_name,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct ShortEventDescriptor<'a> {
+    pub iso_639_language_code: &'a [u8],
+    pub event_name: &'a [u8],
+    pub text: &'a [u8],
+}
+
+impl<'a> ShortEventDescriptor<'a> {
+    pub const TAG: u8 = 0x4d;
+
+    pub fn parse(body: &'a [u8]) -> Option<Self> {
+        // ARIB STD-B10 Part 2 6.2.15
+        let iso_639_language_code = body.get(0..3)?;
+        let event_name_length = *body.get(3)? as usize;
+        let event_name = body.get(4..(4 + event_name_length))?;
+        let index = 4 + event_name_length;
+        let text_length = *body.get(index)? as usize;
+        let text = body.get((index + 1)..(index + 1 + text_length))?;
+        Some(ShortEventDescriptor {
+            iso_639_language_code: iso_639_language_code,
+            event_name: event_name,
+            text: text,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct ContentNibble {
+    pub content_nibble_level_1: u8,
+    pub content_nibble_level_2: u8,
+    pub user_nibble_1: u8,
+    pub user_nibble_2: u8,
+}
+
+impl ContentNibble {
+    pub const TAG: u8 = 0x54;
+
+    /// Parse content descriptor.
+    pub fn parse_descriptor(body: &[u8]) -> Vec<Self> {
+        // ARIB STD-B10 Part 2 6.2.4
+        body.chunks(2)
+            .filter(|chunk| chunk.len() == 2)
+            .map(|chunk| {
+                ContentNibble {
+                    content_nibble_level_1: chunk[0] >> 4,
+                    content_nibble_level_2: chunk[0] & 0x0f,
+                    user_nibble_1: chunk[1] >> 4,
+                    user_nibble_2: chunk[1] & 0x0f,
+                }
+            })
+            .collect()
+    }
+
+    /// Name of content_nibble_level_1 (ARIB STD-B10 Part 2 Annex H)
+    pub fn genre_name(&self) -> Option<&'static str> {
+        match self.content_nibble_level_1 {
+            0x0 => Some("ニュース／報道"),
+            0x1 => Some("スポーツ"),
+            0x2 => Some("情報／ワイドショー"),
+            0x3 => Some("ドラマ"),
+            0x4 => Some("音楽"),
+            0x5 => Some("バラエティ"),
+            0x6 => Some("映画"),
+            0x7 => Some("アニメ／特撮"),
+            0x8 => Some("ドキュメンタリー／教養"),
+            0x9 => Some("劇場／公演"),
+            0xa => Some("趣味／教育"),
+            0xb => Some("福祉"),
+            0xf => Some("その他"),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct AudioComponentDescriptor<'a> {
+    pub stream_content: u8,
+    pub component_type: u8,
+    pub component_tag: u8,
+    pub stream_type: u8,
+    pub simulcast_group_tag: u8,
+    pub es_multi_lingual_flag: bool,
+    pub main_component_flag: bool,
+    pub quality_indicator: u8,
+    pub sampling_rate: u8,
+    pub iso_639_language_code: &'a [u8],
+    pub iso_639_language_code_2: Option<&'a [u8]>,
+    pub text: &'a [u8],
+}
+
+impl<'a> AudioComponentDescriptor<'a> {
+    pub const TAG: u8 = 0xc4;
+
+    pub fn parse(body: &'a [u8]) -> Option<Self> {
+        // ARIB STD-B10 Part 2 6.2.26
+        if body.len() < 9 {
+            return None;
+        }
+        let es_multi_lingual_flag = (body[5] & 0b10000000) != 0;
+        let (iso_639_language_code_2, index) = if es_multi_lingual_flag {
+            (Some(body.get(9..12)?), 12)
+        } else {
+            (None, 9)
+        };
+        Some(AudioComponentDescriptor {
+            stream_content: body[0] & 0b00001111,
+            component_type: body[1],
+            component_tag: body[2],
+            stream_type: body[3],
+            simulcast_group_tag: body[4],
+            es_multi_lingual_flag: es_multi_lingual_flag,
+            main_component_flag: (body[5] & 0b01000000) != 0,
+            quality_indicator: (body[5] & 0b00110000) >> 4,
+            sampling_rate: (body[5] & 0b00001110) >> 1,
+            iso_639_language_code: &body[6..9],
+            iso_639_language_code_2: iso_639_language_code_2,
+            text: &body[index..],
+        })
+    }
+
+    /// 1/0+1/0 mode (e.g. Japanese and English in left and right channels)
+    pub fn is_dual_mono(&self) -> bool {
+        self.component_type == 0x02
+    }
+}
+
+#[derive(Debug)]
+pub struct SeriesDescriptor<'a> {
+    pub series_id: u16,
+    pub repeat_label: u8,
+    pub program_pattern: u8,
+    pub expire_date_valid_flag: bool,
+    pub expire_date: u16,
+    pub episode_number: u16,
+    pub last_episode_number: u16,
+    pub series_name: &'a [u8],
+}
+
+impl<'a> SeriesDescriptor<'a> {
+    pub const TAG: u8 = 0xd5;
+
+    pub fn parse(body: &'a [u8]) -> Option<Self> {
+        // ARIB STD-B10 Part 2 6.2.33
+        if body.len() < 8 {
+            return None;
+        }
+        Some(SeriesDescriptor {
+            series_id: (body[0] as u16) << 8 | body[1] as u16,
+            repeat_label: body[2] >> 4,
+            program_pattern: (body[2] & 0b00001110) >> 1,
+            expire_date_valid_flag: (body[2] & 0b00000001) != 0,
+            expire_date: (body[3] as u16) << 8 | body[4] as u16,
+            episode_number: (body[5] as u16) << 4 | (body[6] >> 4) as u16,
+            last_episode_number: ((body[6] & 0x0f) as u16) << 8 | body[7] as u16,
+            series_name: &body[8..],
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct TsInformationDescriptor<'a> {
+    /// Remote control key assigned to the TS, which makes 3-digit channel numbers of
+    /// terrestrial broadcasting with service_id
+    pub remote_control_key_id: u8,
+    pub transmission_type_count: u8,
+    pub ts_name: &'a [u8],
+    pub transmission_types: Vec<TransmissionType>,
+}
+
+/// Services sent by a transmission type, e.g. a hierarchical layer of terrestrial broadcasting
+#[derive(Debug)]
+pub struct TransmissionType {
+    pub transmission_type_info: u8,
+    pub service_ids: Vec<u16>,
+}
+
+impl<'a> TsInformationDescriptor<'a> {
+    pub const TAG: u8 = 0xcd;
+
+    pub fn parse(body: &'a [u8]) -> Option<Self> {
+        // ARIB STD-B10 Part 2 6.2.42
+        let remote_control_key_id = *body.first()?;
+        let flags = *body.get(1)?;
+        let length_of_ts_name = (flags >> 2) as usize;
+        let transmission_type_count = flags & 0b00000011;
+        let ts_name = body.get(2..(2 + length_of_ts_name))?;
+        let mut index = 2 + length_of_ts_name;
+        let mut transmission_types = vec![];
+        for _ in 0..transmission_type_count {
+            let transmission_type_info = *body.get(index)?;
+            let num_of_service = *body.get(index + 1)? as usize;
+            index += 2;
+            let service_ids = body.get(index..(index + 2 * num_of_service))?
+                .chunks(2)
+                .map(|id| (id[0] as u16) << 8 | id[1] as u16)
+                .collect();
+            index += 2 * num_of_service;
+            transmission_types.push(TransmissionType {
+                transmission_type_info: transmission_type_info,
+                service_ids: service_ids,
+            });
+        }
+        Some(TsInformationDescriptor {
+            remote_control_key_id: remote_control_key_id,
+            transmission_type_count: transmission_type_count,
+            ts_name: ts_name,
+            transmission_types: transmission_types,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct NetworkNameDescriptor<'a> {
+    pub network_name: &'a [u8],
+}
+
+impl<'a> NetworkNameDescriptor<'a> {
+    pub const TAG: u8 = 0x40;
+
+    pub fn parse(body: &'a [u8]) -> Option<Self> {
+        // ARIB STD-B10 Part 2 6.2.11
+        Some(NetworkNameDescriptor { network_name: body })
+    }
+}
+
+/// Which hierarchy of terrestrial broadcasting the elementary stream is sent by.  Streams of
+/// the same service in the low quality hierarchy survive bad reception.
+#[derive(Debug)]
+pub struct HierarchicalTransmissionDescriptor {
+    /// true for the high quality hierarchy
+    pub quality_level: bool,
+    /// PID of the stream in the other hierarchy, or 0x1fff when there is none
+    pub reference_pid: u16,
+}
+
+impl HierarchicalTransmissionDescriptor {
+    pub const TAG: u8 = 0xc0;
+
+    pub fn parse(body: &[u8]) -> Option<Self> {
+        // ARIB STD-B10 Part 2 6.2.22
+        let body = body.get(0..3)?;
+        Some(HierarchicalTransmissionDescriptor {
+            quality_level: (body[0] & 0b00000001) != 0,
+            reference_pid: ((body[1] & 0b00011111) as u16) << 8 | body[2] as u16,
+        })
+    }
+}
+
+/// Services of the TS received by partial reception, i.e. 1-segment broadcasting
+#[derive(Debug)]
+pub struct PartialReceptionDescriptor {
+    pub service_ids: Vec<u16>,
+}
+
+impl PartialReceptionDescriptor {
+    pub const TAG: u8 = 0xfb;
+
+    pub fn parse(body: &[u8]) -> Option<Self> {
+        // ARIB STD-B10 Part 2 6.2.32
+        Some(PartialReceptionDescriptor {
+            service_ids: body.chunks(2)
+                .filter(|id| id.len() == 2)
+                .map(|id| (id[0] as u16) << 8 | id[1] as u16)
+                .collect(),
+        })
+    }
+}
+
+/// Decoding hints of a video stream, e.g. still pictures sent by radio and data services
+#[derive(Debug)]
+pub struct VideoDecodeControlDescriptor {
+    pub still_picture_flag: bool,
+    pub sequence_end_code_flag: bool,
+    /// 0b0000 for 1080p, 0b0001 for 1080i, 0b0010 for 720p, 0b0011 for 480p, 0b0100 for 480i,
+    /// 0b0101 for 240p, 0b0110 for 120p and 0b0111 for 2160p
+    pub video_encode_format: u8,
+}
+
+impl VideoDecodeControlDescriptor {
+    pub const TAG: u8 = 0xc8;
+
+    pub fn parse(body: &[u8]) -> Option<Self> {
+        // ARIB STD-B10 Part 2 6.2.30
+        let flags = *body.first()?;
+        Some(VideoDecodeControlDescriptor {
+            still_picture_flag: (flags & 0b10000000) != 0,
+            sequence_end_code_flag: (flags & 0b01000000) != 0,
+            video_encode_format: (flags & 0b00111100) >> 2,
+        })
+    }
+}
+
+/// Data broadcasting of an event in EIT
+#[derive(Debug)]
+pub struct DataContentDescriptor<'a> {
+    pub data_component_id: u16,
+    /// component_tag of the stream to start with
+    pub entry_component: u8,
+    pub selector: &'a [u8],
+    /// component_tag of the other streams used by the content
+    pub component_refs: &'a [u8],
+    pub iso_639_language_code: &'a [u8],
+    pub text: &'a [u8],
+}
+
+impl<'a> DataContentDescriptor<'a> {
+    pub const TAG: u8 = 0xc7;
+
+    pub fn parse(body: &'a [u8]) -> Option<Self> {
+        // ARIB STD-B10 Part 2 6.2.28
+        let data_component_id = (*body.first()? as u16) << 8 | *body.get(1)? as u16;
+        let entry_component = *body.get(2)?;
+        let selector_length = *body.get(3)? as usize;
+        let selector = body.get(4..(4 + selector_length))?;
+        let mut index = 4 + selector_length;
+        let num_of_component_ref = *body.get(index)? as usize;
+        let component_refs = body.get((index + 1)..(index + 1 + num_of_component_ref))?;
+        index += 1 + num_of_component_ref;
+        let iso_639_language_code = body.get(index..(index + 3))?;
+        let text_length = *body.get(index + 3)? as usize;
+        let text = body.get((index + 4)..(index + 4 + text_length))?;
+        Some(DataContentDescriptor {
+            data_component_id: data_component_id,
+            entry_component: entry_component,
+            selector: selector,
+            component_refs: component_refs,
+            iso_639_language_code: iso_639_language_code,
+            text: text,
+        })
+    }
+}
+
+/// Reference from an event in EIT to the descriptions in LDT
+#[derive(Debug)]
+pub struct LdtLinkageDescriptor {
+    pub original_service_id: u16,
+    pub transport_stream_id: u16,
+    pub original_network_id: u16,
+    pub descriptions: Vec<LinkedDescription>,
+}
+
+#[derive(Debug)]
+pub struct LinkedDescription {
+    pub description_id: u16,
+    pub description_type: u8,
+    pub user_defined: u8,
+}
+
+impl LdtLinkageDescriptor {
+    pub const TAG: u8 = 0xdc;
+
+    pub fn parse(body: &[u8]) -> Option<Self> {
+        // ARIB STD-B10 Part 2 6.2.40
+        let ids = body.get(0..6)?;
+        Some(LdtLinkageDescriptor {
+            original_service_id: (ids[0] as u16) << 8 | ids[1] as u16,
+            transport_stream_id: (ids[2] as u16) << 8 | ids[3] as u16,
+            original_network_id: (ids[4] as u16) << 8 | ids[5] as u16,
+            descriptions: body[6..]
+                .chunks(4)
+                .filter(|description| description.len() == 4)
+                .map(|description| {
+                    LinkedDescription {
+                        description_id: (description[0] as u16) << 8 | description[1] as u16,
+                        description_type: description[2] & 0b00001111,
+                        user_defined: description[3],
+                    }
+                })
+                .collect(),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct HyperlinkDescriptor<'a> {
+    pub hyper_linkage_type: u8,
+    pub link_destination_type: u8,
+    pub selector: &'a [u8],
+    pub private_data: &'a [u8],
+}
+
+impl<'a> HyperlinkDescriptor<'a> {
+    pub const TAG: u8 = 0xc5;
+
+    pub fn parse(body: &'a [u8]) -> Option<Self> {
+        // ARIB STD-B10 Part 2 6.2.29
+        let hyper_linkage_type = *body.first()?;
+        let link_destination_type = *body.get(1)?;
+        let selector_length = *body.get(2)? as usize;
+        let selector = body.get(3..(3 + selector_length))?;
+        Some(HyperlinkDescriptor {
+            hyper_linkage_type: hyper_linkage_type,
+            link_destination_type: link_destination_type,
+            selector: selector,
+            private_data: &body[(3 + selector_length)..],
+        })
+    }
+
+    /// Event linked by link_to_event (link_destination_type 0x02) as (original_network_id,
+    /// transport_stream_id, service_id, event_id)
+    pub fn linked_event(&self) -> Option<(u16, u16, u16, u16)> {
+        if self.link_destination_type != 0x02 || self.selector.len() < 8 {
+            return None;
+        }
+        let s = self.selector;
+        Some(((s[0] as u16) << 8 | s[1] as u16,
+              (s[2] as u16) << 8 | s[3] as u16,
+              (s[4] as u16) << 8 | s[5] as u16,
+              (s[6] as u16) << 8 | s[7] as u16))
+    }
+}
+
+/// Events grouped with the event in EIT, e.g. the continuation of an event relayed to another
+/// service
+#[derive(Debug)]
+pub struct EventGroupDescriptor<'a> {
+    pub group_type: u8,
+    pub events: Vec<GroupedEvent>,
+    pub private_data: &'a [u8],
+}
+
+/// Event of the group.  The network and the TS are given only to the events in other networks.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupedEvent {
+    pub original_network_id: Option<u16>,
+    pub transport_stream_id: Option<u16>,
+    pub service_id: u16,
+    pub event_id: u16,
+}
+
+impl<'a> EventGroupDescriptor<'a> {
+    pub const TAG: u8 = 0xd6;
+    pub const EVENT_SHARING: u8 = 0x1;
+    pub const EVENT_RELAY: u8 = 0x2;
+    pub const EVENT_MOVEMENT: u8 = 0x3;
+    pub const EVENT_RELAY_TO_OTHER_NETWORKS: u8 = 0x4;
+    pub const EVENT_MOVEMENT_FROM_OTHER_NETWORKS: u8 = 0x5;
+
+    pub fn parse(body: &'a [u8]) -> Option<Self> {
+        // ARIB STD-B10 Part 2 6.2.34
+        let group_type = *body.first()? >> 4;
+        let event_count = (body[0] & 0b00001111) as usize;
+        let mut events: Vec<_> = body.get(1..(1 + 4 * event_count))?
+            .chunks(4)
+            .map(|event| {
+                GroupedEvent {
+                    original_network_id: None,
+                    transport_stream_id: None,
+                    service_id: (event[0] as u16) << 8 | event[1] as u16,
+                    event_id: (event[2] as u16) << 8 | event[3] as u16,
+                }
+            })
+            .collect();
+        let rest = &body[(1 + 4 * event_count)..];
+        let private_data = if group_type == Self::EVENT_RELAY_TO_OTHER_NETWORKS ||
+                              group_type == Self::EVENT_MOVEMENT_FROM_OTHER_NETWORKS {
+            events.extend(rest.chunks(8).filter(|event| event.len() == 8).map(|event| {
+                GroupedEvent {
+                    original_network_id: Some((event[0] as u16) << 8 | event[1] as u16),
+                    transport_stream_id: Some((event[2] as u16) << 8 | event[3] as u16),
+                    service_id: (event[4] as u16) << 8 | event[5] as u16,
+                    event_id: (event[6] as u16) << 8 | event[7] as u16,
+                }
+            }));
+            &rest[rest.len()..]
+        } else {
+            rest
+        };
+        Some(EventGroupDescriptor {
+            group_type: group_type,
+            events: events,
+            private_data: private_data,
+        })
+    }
+
+    /// Whether the event continues on the grouped events
+    pub fn is_relay(&self) -> bool {
+        self.group_type == Self::EVENT_RELAY || self.group_type == Self::EVENT_RELAY_TO_OTHER_NETWORKS
+    }
+}
@@ -0,0 +1,224 @@
+/// Edit of a stream applied by edit().  Packets are specified by their index in the input.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operation {
+    /// Drop the packets in the range
+    Drop(std::ops::Range<u64>),
+    /// Insert the packets before the packet at the index, or at the end when the index is past
+    /// the last packet.  continuity_counter of them is overwritten.
+    Insert {
+        before: u64,
+        packets: Vec<[u8; 188]>,
+    },
+    /// Overwrite header fields of the packets in the range, only of the PID if given
+    Overwrite {
+        range: std::ops::Range<u64>,
+        pid: Option<u16>,
+        transport_priority: Option<bool>,
+        transport_scrambling_control: Option<u8>,
+    },
+    /// Move the packets of a PID to another PID in the whole stream.  PAT and PMT referring to
+    /// the PID are rewritten as well.
+    RemapPid {
+        from: u16,
+        to: u16,
+    },
+}
+
+// continuity_counter of one PID in the output
+#[derive(Default)]
+struct Continuity {
+    // continuity_counter of the last packet written
+    last: Option<u8>,
+    // Added to continuity_counter of input packets
+    offset: u8,
+    // Whether offset is recalculated at the next input packet, e.g. after dropped or inserted
+    // packets
+    resync: bool,
+    // PID in the input of the last packet written
+    source: Option<u16>,
+}
+
+impl Continuity {
+    fn next(&self) -> u8 {
+        self.last.map(|last| (last + 1) & 0b00001111).unwrap_or(0)
+    }
+
+    // Return continuity_counter of the packet from the input PID, or of an inserted packet when
+    // source is None
+    fn assign(&mut self, source: Option<u16>, continuity_counter: u8, has_payload: bool) -> u8 {
+        let resync = self.resync || source.is_none() || self.source != source;
+        let assigned = match (self.last, resync) {
+            (None, _) => continuity_counter,
+            (Some(_), false) => (continuity_counter + self.offset) & 0b00001111,
+            (Some(last), true) if has_payload => (last + 1) & 0b00001111,
+            (Some(last), true) => last,
+        };
+        if source.is_some() {
+            self.offset = assigned.wrapping_sub(continuity_counter) & 0b00001111;
+        }
+        // Packets after inserted ones are renumbered as well
+        self.resync = source.is_none();
+        self.last = Some(assigned);
+        self.source = source;
+        assigned
+    }
+}
+
+/// Apply the operations in one pass.  continuity_counter is renumbered only where packets are
+/// dropped, inserted or remapped so that the other discontinuities in the input are kept.
+pub fn edit<R, W>(reader: R, mut writer: W, operations: &[Operation]) -> Result<(), super::filter::Error>
+    where R: std::io::Read,
+          W: std::io::Write
+{
+    let remap: std::collections::HashMap<u16, u16> = operations.iter()
+        .filter_map(|operation| match *operation {
+            Operation::RemapPid { from, to } => Some((from, to)),
+            _ => None,
+        })
+        .collect();
+    let mut continuities: std::collections::HashMap<u16, Continuity> =
+        std::collections::HashMap::new();
+    // PAT and PMT are assembled and rewritten only when PIDs are remapped
+    let mut pmt_pids = std::collections::HashSet::new();
+    let mut assemblers: std::collections::HashMap<u16, super::psi::SectionAssembler> =
+        std::collections::HashMap::new();
+
+    let mut index = 0;
+    for buf in super::packet::ts_packets(reader) {
+        let mut buf = buf?;
+        let position = index;
+        index += 1;
+        for operation in operations {
+            if let Operation::Insert { before, ref packets } = *operation {
+                if before == position {
+                    for packet in packets {
+                        write_inserted(&mut writer, &mut continuities, &remap, packet)?;
+                    }
+                }
+            }
+        }
+
+        let pid = pid(&buf);
+        let dropped = operations.iter().any(|operation| match *operation {
+            Operation::Drop(ref range) => range.contains(&position),
+            _ => false,
+        });
+        if dropped {
+            continuities.entry(*remap.get(&pid).unwrap_or(&pid)).or_default().resync = true;
+            continue;
+        }
+
+        for operation in operations {
+            if let Operation::Overwrite { ref range,
+                                          pid: only,
+                                          transport_priority,
+                                          transport_scrambling_control } = *operation {
+                if range.contains(&position) && only.is_none_or(|only| only == pid) {
+                    if let Some(priority) = transport_priority {
+                        buf[1] = buf[1] & !0b00100000 | (priority as u8) << 5;
+                    }
+                    if let Some(scrambling) = transport_scrambling_control {
+                        buf[3] = buf[3] & 0b00111111 | (scrambling & 0b11) << 6;
+                    }
+                }
+            }
+        }
+
+        if !remap.is_empty() && (pid == 0x0000 || pmt_pids.contains(&pid)) {
+            let packet = super::TsPacket::new(&buf);
+            let sections = assemblers.entry(pid).or_default().push(&packet);
+            for section in sections {
+                let payload = rewrite_section(&section, &remap, &mut pmt_pids)?;
+                let out_pid = *remap.get(&pid).unwrap_or(&pid);
+                let continuity = continuities.entry(out_pid).or_default();
+                let mut continuity_counter = continuity.next();
+                super::psi::write_payload(&mut writer, out_pid, &mut continuity_counter, &payload)?;
+                continuity.last = Some(continuity_counter.wrapping_sub(1) & 0b00001111);
+                continuity.resync = true;
+            }
+            continue;
+        }
+
+        let out_pid = *remap.get(&pid).unwrap_or(&pid);
+        if out_pid != pid {
+            set_pid(&mut buf, out_pid);
+        }
+        let continuity_counter = continuities.entry(out_pid)
+            .or_default()
+            .assign(Some(pid), buf[3] & 0b00001111, has_payload(&buf));
+        buf[3] = buf[3] & 0b11110000 | continuity_counter;
+        writer.write_all(&buf)?;
+    }
+
+    for operation in operations {
+        if let Operation::Insert { before, ref packets } = *operation {
+            if before >= index {
+                for packet in packets {
+                    write_inserted(&mut writer, &mut continuities, &remap, packet)?;
+                }
+            }
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn write_inserted<W>(writer: &mut W,
+                     continuities: &mut std::collections::HashMap<u16, Continuity>,
+                     remap: &std::collections::HashMap<u16, u16>,
+                     packet: &[u8; 188])
+                     -> std::io::Result<()>
+    where W: std::io::Write
+{
+    let mut buf = *packet;
+    let pid = pid(&buf);
+    let out_pid = *remap.get(&pid).unwrap_or(&pid);
+    set_pid(&mut buf, out_pid);
+    let continuity_counter = continuities.entry(out_pid)
+        .or_default()
+        .assign(None, buf[3] & 0b00001111, has_payload(&buf));
+    buf[3] = buf[3] & 0b11110000 | continuity_counter;
+    writer.write_all(&buf)
+}
+
+// Rewrite PIDs referred by PAT or PMT, and return the payload starting with pointer_field.
+// PMT_PIDs in PAT are recorded to pmt_pids.
+fn rewrite_section(section: &[u8],
+                   remap: &std::collections::HashMap<u16, u16>,
+                   pmt_pids: &mut std::collections::HashSet<u16>)
+                   -> Result<Vec<u8>, super::psi::ParseError> {
+    let map = |pid: u16| *remap.get(&pid).unwrap_or(&pid);
+    // parse() of PAT and PMT expects pointer_field
+    let mut payload = vec![0];
+    payload.extend_from_slice(section);
+    match section[0] {
+        0x00 => {
+            let mut pat = super::ProgramAssociationTable::parse(&payload)?;
+            pmt_pids.extend(pat.program_map.keys());
+            pat.program_map = pat.program_map.into_iter().map(|(pid, n)| (map(pid), n)).collect();
+            Ok(pat.to_bytes())
+        }
+        0x02 => {
+            let mut pmt = super::ProgramMapTable::parse(&payload)?;
+            pmt.pcr_pid = map(pmt.pcr_pid);
+            for es in &mut pmt.es_info {
+                es.elementary_pid = map(es.elementary_pid);
+            }
+            Ok(pmt.to_bytes())
+        }
+        _ => Ok(payload),
+    }
+}
+
+fn pid(packet: &[u8]) -> u16 {
+    ((packet[1] & 0b00011111) as u16) << 8 | packet[2] as u16
+}
+
+fn set_pid(packet: &mut [u8], pid: u16) {
+    packet[1] = packet[1] & 0b11100000 | (pid >> 8) as u8 & 0b00011111;
+    packet[2] = pid as u8;
+}
+
+fn has_payload(packet: &[u8]) -> bool {
+    packet[3] & 0b00010000 != 0
+}
@@ -0,0 +1,132 @@
+#[derive(Debug)]
+pub struct EventInformationTable<'a> {
+    pub table_id: u8,
+    pub service_id: u16,
+    pub version_number: u8,
+    pub current_next_indicator: bool,
+    pub section_number: u8,
+    pub last_section_number: u8,
+    pub transport_stream_id: u16,
+    pub original_network_id: u16,
+    pub segment_last_section_number: u8,
+    pub last_table_id: u8,
+    pub events: Vec<Event<'a>>,
+    pub crc32: u32,
+}
+
+impl<'a> EventInformationTable<'a> {
+    /// Parse a section assembled by psi::SectionAssembler.
+    pub fn parse(section: &'a [u8]) -> Result<Self, super::psi::ParseError> {
+        // ARIB STD-B10 Part 2 5.2.7
+        if section.len() < 18 {
+            return Err(super::psi::ParseError::InsufficientLength);
+        }
+        let table_id = section[0];
+        if !(0x4e..=0x6f).contains(&table_id) {
+            return Err(super::psi::ParseError::IncorrectTableId {
+                expected: 0x4e,
+                actual: table_id,
+            });
+        }
+        let section_syntax_indicator = (section[1] & 0b10000000) != 0;
+        if !section_syntax_indicator {
+            return Err(super::psi::ParseError::IncorrectSectionSyntaxIndicator);
+        }
+        let section_length = ((section[1] & 0b00001111) as usize) << 8 | section[2] as usize;
+        if section.len() < 3 + section_length || section_length < 15 {
+            return Err(super::psi::ParseError::InsufficientLength);
+        }
+        let service_id = (section[3] as u16) << 8 | section[4] as u16;
+        let version_number = (section[5] & 0b00111110) >> 1;
+        let current_next_indicator = (section[5] & 0b00000001) != 0;
+        let section_number = section[6];
+        let last_section_number = section[7];
+        let transport_stream_id = (section[8] as u16) << 8 | section[9] as u16;
+        let original_network_id = (section[10] as u16) << 8 | section[11] as u16;
+        let segment_last_section_number = section[12];
+        let last_table_id = section[13];
+
+        let end = 3 + section_length - 4;
+        let mut index = 14;
+        let mut events = vec![];
+        while index < end {
+            if index + 12 > end {
+                return Err(super::psi::ParseError::InsufficientLength);
+            }
+            let event = Event::new(&section[index..end])?;
+            index += event.size();
+            events.push(event);
+        }
+        let crc32 = (section[end] as u32) << 24 | (section[end + 1] as u32) << 16 |
+                    (section[end + 2] as u32) << 8 |
+                    (section[end + 3] as u32);
+
+        Ok(EventInformationTable {
+            table_id: table_id,
+            service_id: service_id,
+            version_number: version_number,
+            current_next_indicator: current_next_indicator,
+            section_number: section_number,
+            last_section_number: last_section_number,
+            transport_stream_id: transport_stream_id,
+            original_network_id: original_network_id,
+            segment_last_section_number: segment_last_section_number,
+            last_table_id: last_table_id,
+            events: events,
+            crc32: crc32,
+        })
+    }
+
+    /// EIT[p/f actual]
+    pub fn is_present_following_actual(&self) -> bool {
+        self.table_id == 0x4e
+    }
+}
+
+#[derive(Debug)]
+pub struct Event<'a> {
+    pub event_id: u16,
+    /// Unix time
+    pub start_time: Option<i64>,
+    /// In seconds
+    pub duration: Option<u32>,
+    pub running_status: u8,
+    pub free_ca_mode: bool,
+    pub descriptors: &'a [u8],
+}
+
+impl<'a> Event<'a> {
+    fn new(payload: &'a [u8]) -> Result<Self, super::psi::ParseError> {
+        let event_id = (payload[0] as u16) << 8 | payload[1] as u16;
+        let start_time = super::time::parse_jst_time(&payload[2..7]);
+        let duration = super::time::parse_bcd_duration(&payload[7..10]);
+        let running_status = (payload[10] & 0b11100000) >> 5;
+        let free_ca_mode = (payload[10] & 0b00010000) != 0;
+        let descriptors_loop_length = ((payload[10] & 0b00001111) as usize) << 8 |
+                                      payload[11] as usize;
+        if payload.len() < 12 + descriptors_loop_length {
+            return Err(super::psi::ParseError::InsufficientLength);
+        }
+        let descriptors = &payload[12..(12 + descriptors_loop_length)];
+        Ok(Event {
+            event_id: event_id,
+            start_time: start_time,
+            duration: duration,
+            running_status: running_status,
+            free_ca_mode: free_ca_mode,
+            descriptors: descriptors,
+        })
+    }
+
+    pub fn size(&self) -> usize {
+        12 + self.descriptors.len()
+    }
+
+    /// Unix time
+    pub fn end_time(&self) -> Option<i64> {
+        match (self.start_time, self.duration) {
+            (Some(start_time), Some(duration)) => Some(start_time + duration as i64),
+            _ => None,
+        }
+    }
+}
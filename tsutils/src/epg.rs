@@ -0,0 +1,179 @@
+const ANALYZER: &str = "epg";
+// EIT, and L-EIT and H-EIT of ARIB STD-B10
+const EIT_PIDS: [u16; 3] = [0x0012, 0x0026, 0x0027];
+
+// (start_time, end_time) keyed by event_id
+type Events = std::collections::BTreeMap<u16, (i64, i64)>;
+
+/// Measure how much time EIT of each service covers, to find services whose EPG is missing or
+/// has holes in the recording.
+///
+/// Events: gap (data: the IDs like coverage, start and end in Unix time of a hole between
+/// events) and coverage per service (data: original_network_id, transport_stream_id,
+/// service_id, events, start and end in Unix time, covered seconds and the number of gaps),
+/// reported after the last packet, and summary (data: the numbers of services, EIT sections
+/// and malformed sections).
+#[derive(Default)]
+pub struct CoverageAnalyzer {
+    assemblers: std::collections::HashMap<u16, super::psi::SectionAssembler>,
+    // Keyed by (original_network_id, transport_stream_id, service_id)
+    services: std::collections::BTreeMap<(u16, u16, u16), Events>,
+    sections: u64,
+    errors: u64,
+}
+
+impl CoverageAnalyzer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl super::report::Analyzer for CoverageAnalyzer {
+    fn push(&mut self,
+            _index: u64,
+            _buf: &[u8],
+            packet: Option<&super::TsPacket>,
+            _reports: &mut Vec<super::report::Report>) {
+        let packet = match packet {
+            Some(packet) if EIT_PIDS.contains(&packet.pid) => packet,
+            _ => return,
+        };
+        let sections = self.assemblers
+            .entry(packet.pid)
+            .or_default()
+            .push(packet);
+        for section in sections {
+            let eit = match super::EventInformationTable::parse(&section) {
+                Ok(eit) => eit,
+                Err(_) => {
+                    self.errors += 1;
+                    continue;
+                }
+            };
+            self.sections += 1;
+            let events = self.services
+                .entry((eit.original_network_id, eit.transport_stream_id, eit.service_id))
+                .or_default();
+            for event in &eit.events {
+                if let (Some(start_time), Some(end_time)) = (event.start_time, event.end_time()) {
+                    events.insert(event.event_id, (start_time, end_time));
+                }
+            }
+        }
+    }
+
+    fn finish(&mut self, reports: &mut Vec<super::report::Report>) {
+        for (&(original_network_id, transport_stream_id, service_id), events) in &self.services {
+            let mut ranges: Vec<_> = events.values().cloned().collect();
+            ranges.sort();
+            let mut covered = 0;
+            let mut gaps = 0;
+            let mut end: Option<i64> = None;
+            for &(start_time, end_time) in &ranges {
+                match end {
+                    Some(end) if start_time > end => {
+                        gaps += 1;
+                        reports.push(super::report::Report::new(ANALYZER, "gap")
+                            .field("original_network_id", original_network_id)
+                            .field("transport_stream_id", transport_stream_id)
+                            .field("service_id", service_id)
+                            .field("start", end)
+                            .field("end", start_time));
+                    }
+                    _ => {}
+                }
+                // Overlapping events are counted once
+                let from = end.map(|end| std::cmp::max(end, start_time)).unwrap_or(start_time);
+                if end_time > from {
+                    covered += end_time - from;
+                }
+                end = Some(end.map(|end| std::cmp::max(end, end_time)).unwrap_or(end_time));
+            }
+            let mut coverage = super::report::Report::new(ANALYZER, "coverage")
+                .field("original_network_id", original_network_id)
+                .field("transport_stream_id", transport_stream_id)
+                .field("service_id", service_id)
+                .field("events", ranges.len())
+                .field("covered", covered)
+                .field("gaps", gaps);
+            if let (Some(first), Some(end)) = (ranges.first(), end) {
+                coverage = coverage.field("start", first.0).field("end", end);
+            }
+            reports.push(coverage);
+        }
+        reports.push(super::report::Report::new(ANALYZER, "summary")
+            .field("services", self.services.len())
+            .field("sections", self.sections)
+            .field("errors", self.errors));
+    }
+
+    fn checkpoint(&self) -> Option<serde_json::Value> {
+        let assemblers: Vec<_> = self.assemblers
+            .iter()
+            .map(|(&pid, assembler)| json!([pid, assembler.checkpoint()]))
+            .collect();
+        let services: Vec<_> = self.services
+            .iter()
+            .map(|(&(original_network_id, transport_stream_id, service_id), events)| {
+                let events: Vec<_> = events.iter()
+                    .map(|(&event_id, &(start_time, end_time))| {
+                        json!([event_id, start_time, end_time])
+                    })
+                    .collect();
+                json!([original_network_id, transport_stream_id, service_id, events])
+            })
+            .collect();
+        Some(json!({
+            "assemblers": assemblers,
+            "services": services,
+            "sections": self.sections,
+            "errors": self.errors,
+        }))
+    }
+
+    fn restore(&mut self, state: &serde_json::Value) -> bool {
+        match CoverageAnalyzer::from_checkpoint(state) {
+            Some(analyzer) => {
+                *self = analyzer;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl CoverageAnalyzer {
+    // Inverse of checkpoint()
+    fn from_checkpoint(state: &serde_json::Value) -> Option<Self> {
+        let assemblers = state.get("assemblers")?
+            .as_array()?
+            .iter()
+            .map(|assembler| {
+                Some((assembler.get(0)?.as_u64()? as u16,
+                      super::psi::SectionAssembler::restore(assembler.get(1)?)?))
+            })
+            .collect::<Option<_>>()?;
+        let services = state.get("services")?
+            .as_array()?
+            .iter()
+            .map(|service| {
+                let id = |i| service.get(i).and_then(|id| id.as_u64()).map(|id| id as u16);
+                let events = service.get(3)?
+                    .as_array()?
+                    .iter()
+                    .map(|event| {
+                        Some((event.get(0)?.as_u64()? as u16,
+                              (event.get(1)?.as_i64()?, event.get(2)?.as_i64()?)))
+                    })
+                    .collect::<Option<_>>()?;
+                Some(((id(0)?, id(1)?, id(2)?), events))
+            })
+            .collect::<Option<_>>()?;
+        Some(CoverageAnalyzer {
+            assemblers: assemblers,
+            services: services,
+            sections: state.get("sections")?.as_u64()?,
+            errors: state.get("errors")?.as_u64()?,
+        })
+    }
+}
@@ -0,0 +1,759 @@
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    PsiParseError(super::psi::ParseError),
+    Custom(std::borrow::Cow<'static, str>),
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<&'static str> for Error {
+    fn from(e: &'static str) -> Self {
+        Error::Custom(std::borrow::Cow::from(e))
+    }
+}
+
+impl From<String> for Error {
+    fn from(e: String) -> Self {
+        Error::Custom(std::borrow::Cow::from(e))
+    }
+}
+
+impl From<super::psi::ParseError> for Error {
+    fn from(e: super::psi::ParseError) -> Self {
+        Error::PsiParseError(e)
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            Error::Io(ref e) => write!(f, "{}", e),
+            Error::PsiParseError(ref e) => write!(f, "PSI parse error: {:?}", e),
+            Error::Custom(ref msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl Error {
+    /// Whether the reader was cancelled with progress::CancellationToken
+    pub fn is_cancelled(&self) -> bool {
+        match *self {
+            Error::Io(ref e) => super::progress::is_cancelled(e),
+            _ => false,
+        }
+    }
+}
+
+pub fn is_av_stream_type(stream_type: u8) -> bool {
+    match stream_type {
+        // Audio
+        0x0f => true,
+        // Video
+        0x02 | 0x1b => true,
+        _ => false,
+    }
+}
+
+#[derive(Debug)]
+pub struct Program {
+    pub pmt_pid: u16,
+    pub version_number: u8,
+    pub pcr_pid: u16,
+    /// (stream_type, elementary_PID)
+    pub streams: Vec<(u8, u16)>,
+}
+
+/// Default limit of the payload buffered for a PID by ProgramTracker, large enough for PAT and
+/// PMT sections which are at most 1024 bytes
+pub const DEFAULT_MAX_PAYLOAD: usize = 4096;
+
+/// Assemble PAT and PMT sections and keep track of the programs in the stream.
+pub struct ProgramTracker {
+    pat: Option<super::ProgramAssociationTable>,
+    programs: std::collections::HashMap<u16, Program>,
+    payloads: std::collections::HashMap<u16, Vec<u8>>,
+    max_payload: usize,
+    evicted: u64,
+}
+
+impl Default for ProgramTracker {
+    fn default() -> Self {
+        ProgramTracker {
+            pat: None,
+            programs: std::collections::HashMap::new(),
+            payloads: std::collections::HashMap::new(),
+            max_payload: DEFAULT_MAX_PAYLOAD,
+            evicted: 0,
+        }
+    }
+}
+
+impl ProgramTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limit of the payload buffered for a PID until the next payload_unit_start_indicator.  A
+    /// payload growing over the limit, e.g. of a scrambled PMT which never ends, is discarded
+    /// with a warning so that long runs on pathological streams keep bounded memory.
+    pub fn max_payload(mut self, bytes: usize) -> Self {
+        self.max_payload = bytes;
+        self
+    }
+
+    /// Number of payloads discarded over the limit
+    pub fn evicted(&self) -> u64 {
+        self.evicted
+    }
+
+    pub fn pat(&self) -> Option<&super::ProgramAssociationTable> {
+        self.pat.as_ref()
+    }
+
+    /// Programs keyed by program_number
+    pub fn programs(&self) -> &std::collections::HashMap<u16, Program> {
+        &self.programs
+    }
+
+    /// Return true when a PAT or PMT is updated by the packet.  A broken section is an error,
+    /// but the section starting in the packet is still tracked so that the caller may go on.
+    pub fn push(&mut self, packet: &super::TsPacket) -> Result<bool, Error> {
+        let mut updated = Ok(false);
+        if packet.payload_unit_start_indicator {
+            if let Some(mut payload) = self.payloads.remove(&packet.pid) {
+                // The bytes before the position pointed by pointer_field end the previous
+                // section
+                if let Some(continuation) = packet.section_continuation() {
+                    payload.extend_from_slice(continuation);
+                }
+                updated = self.parse_section(packet.pid, &payload);
+            }
+        }
+
+        if self.is_tracking(packet.pid) {
+            if packet.payload_unit_start_indicator {
+                if let Some(section) = packet.section_start() {
+                    // parse() of PAT and PMT expects pointer_field
+                    let mut payload = vec![0];
+                    payload.extend_from_slice(section);
+                    self.payloads.insert(packet.pid, payload);
+                }
+            } else if let Some(payload) = self.payloads.get_mut(&packet.pid) {
+                if let Some(data_bytes) = packet.data_bytes {
+                    payload.extend_from_slice(data_bytes);
+                }
+            }
+            let max_payload = self.max_payload;
+            if self.payloads.get(&packet.pid).is_some_and(|payload| payload.len() > max_payload) {
+                warn!("Discard the incomplete section of PID 0x{:04x} over {} bytes",
+                      packet.pid,
+                      max_payload);
+                self.payloads.remove(&packet.pid);
+                self.evicted += 1;
+            }
+        }
+        updated
+    }
+
+    /// PAT, PMT and partial sections for checkpoint::Checkpoint
+    pub fn checkpoint(&self) -> serde_json::Value {
+        let programs: Vec<_> = self.programs
+            .iter()
+            .map(|(&program_number, program)| {
+                json!([program_number,
+                       program.pmt_pid,
+                       program.version_number,
+                       program.pcr_pid,
+                       program.streams])
+            })
+            .collect();
+        let payloads: Vec<_> = self.payloads
+            .iter()
+            .map(|(&pid, payload)| json!([pid, super::checkpoint::hex(payload)]))
+            .collect();
+        json!({
+            "pat": self.pat.as_ref().map(|pat| super::checkpoint::hex(&pat.to_bytes())),
+            "programs": programs,
+            "payloads": payloads,
+        })
+    }
+
+    pub fn restore(state: &serde_json::Value) -> Option<Self> {
+        let pat = match *state.get("pat")? {
+            serde_json::Value::Null => None,
+            ref pat => {
+                Some(super::ProgramAssociationTable::parse(&super::checkpoint::unhex(pat)?).ok()?)
+            }
+        };
+        let programs = state.get("programs")?
+            .as_array()?
+            .iter()
+            .map(|program| {
+                let streams = program.get(4)?
+                    .as_array()?
+                    .iter()
+                    .map(|stream| {
+                        Some((stream.get(0)?.as_u64()? as u8, stream.get(1)?.as_u64()? as u16))
+                    })
+                    .collect::<Option<_>>()?;
+                Some((program.get(0)?.as_u64()? as u16,
+                      Program {
+                          pmt_pid: program.get(1)?.as_u64()? as u16,
+                          version_number: program.get(2)?.as_u64()? as u8,
+                          pcr_pid: program.get(3)?.as_u64()? as u16,
+                          streams: streams,
+                      }))
+            })
+            .collect::<Option<_>>()?;
+        let payloads = state.get("payloads")?
+            .as_array()?
+            .iter()
+            .map(|payload| {
+                Some((payload.get(0)?.as_u64()? as u16,
+                      super::checkpoint::unhex(payload.get(1)?)?))
+            })
+            .collect::<Option<_>>()?;
+        Some(ProgramTracker {
+            pat: pat,
+            programs: programs,
+            payloads: payloads,
+            ..Self::default()
+        })
+    }
+
+    fn is_tracking(&self, pid: u16) -> bool {
+        pid == 0x0000 ||
+        self.pat.as_ref().map(|pat| pat.program_map.contains_key(&pid)).unwrap_or(false)
+    }
+
+    fn parse_section(&mut self, pid: u16, payload: &[u8]) -> Result<bool, Error> {
+        if pid == 0x0000 {
+            self.pat = Some(super::ProgramAssociationTable::parse(payload)?);
+            return Ok(true);
+        }
+        let program_number = match self.pat.as_ref().and_then(|pat| pat.program_map.get(&pid)) {
+            Some(&program_number) => program_number,
+            None => return Ok(false),
+        };
+        let pmt = super::ProgramMapTable::parse(payload)?;
+        if pmt.program_number != program_number {
+            return Err(Error::from(format!("Inconsistent program_number for PID={}: PAT says \
+                                            {} but PMT says {}",
+                                           pid,
+                                           program_number,
+                                           pmt.program_number)));
+        }
+        self.programs.insert(program_number,
+                             Program {
+                                 pmt_pid: pid,
+                                 version_number: pmt.version_number,
+                                 pcr_pid: pmt.pcr_pid,
+                                 streams: pmt.es_info
+                                     .iter()
+                                     .map(|es| (es.stream_type, es.elementary_pid))
+                                     .collect(),
+                             });
+        Ok(true)
+    }
+}
+
+/// SI kept by the filters besides PAT, PMT and the elementary streams.  Players need different
+/// minimum SI, e.g. some of them show the event name only with EIT[p/f].  Each policy keeps the
+/// SI of the previous ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SiPolicy {
+    /// PAT and PMT only
+    Psi,
+    /// EIT[p/f actual] (table_id=0x4e) in addition
+    EitPresentFollowing,
+    /// SDT, TDT and TOT in addition
+    ServiceInformation,
+    /// Every PID outside the programs, e.g. NIT, CAT, ECM, EIT[schedule] and data broadcasting
+    All,
+}
+
+impl SiPolicy {
+    // Whether all packets of the PID outside the programs are kept
+    fn keeps_pid(&self, pid: u16) -> bool {
+        match *self {
+            SiPolicy::Psi | SiPolicy::EitPresentFollowing => false,
+            // SDT and TDT/TOT
+            SiPolicy::ServiceInformation => pid == 0x0011 || pid == 0x0014,
+            SiPolicy::All => true,
+        }
+    }
+
+    // Whether EIT[p/f actual] sections are picked out of PID 0x0012
+    fn picks_eit_present_following(&self) -> bool {
+        *self == SiPolicy::EitPresentFollowing || *self == SiPolicy::ServiceInformation
+    }
+}
+
+impl std::str::FromStr for SiPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "psi" => Ok(SiPolicy::Psi),
+            "eit-pf" => Ok(SiPolicy::EitPresentFollowing),
+            "si" => Ok(SiPolicy::ServiceInformation),
+            "all" => Ok(SiPolicy::All),
+            _ => Err(format!("Unknown SI policy {} (psi, eit-pf, si or all)", s)),
+        }
+    }
+}
+
+// Reassemble the sections of one PID and packetize only the selected ones again, each section
+// starting a new packet
+#[derive(Default)]
+struct SectionFilter {
+    assembler: super::psi::SectionAssembler,
+    continuity_counter: u8,
+}
+
+impl SectionFilter {
+    // Return the number of packets written
+    fn push<W, F>(&mut self,
+                  packet: &super::TsPacket,
+                  writer: &mut W,
+                  select: F)
+                  -> Result<u64, Error>
+        where W: std::io::Write,
+              F: Fn(&[u8]) -> bool
+    {
+        let mut written = 0;
+        for section in self.assembler.push(packet) {
+            if !select(&section) {
+                continue;
+            }
+            let mut payload = Vec::with_capacity(section.len() + 1);
+            // pointer_field
+            payload.push(0);
+            payload.extend_from_slice(&section);
+            super::psi::write_payload(writer, packet.pid, &mut self.continuity_counter, &payload)?;
+            written += payload.len().div_ceil(184) as u64;
+        }
+        Ok(written)
+    }
+}
+
+/// Drop audio and video packets and keep the others.
+pub fn drop_av<R, W>(reader: R, writer: W) -> Result<(), Error>
+    where R: std::io::Read,
+          W: std::io::Write
+{
+    drop_av_with_policy(reader, writer, SiPolicy::All).map(|_| ())
+}
+
+/// Why drop_av_with_policy() kept or dropped the packets of a PID
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PidClass {
+    Pat,
+    /// PMT or PCR, kept unless PCR is carried by audio or video
+    Program,
+    /// Audio or video elementary stream with the stream_type, dropped
+    Av(u8),
+    /// Other elementary stream with the stream_type such as captions and data broadcasting, kept
+    NonAv(u8),
+    /// Elementary stream with the stream_type or PCR of a service not selected, kept
+    OtherService(Option<u8>),
+    /// SI kept by the policy
+    Si,
+    /// EIT of which only the present/following sections are kept
+    EitPresentFollowing,
+    /// Neither referred by PAT or PMT nor kept by the policy, dropped
+    Unreferenced,
+}
+
+impl PidClass {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            PidClass::Pat => "pat",
+            PidClass::Program => "program",
+            PidClass::Av(_) => "av",
+            PidClass::NonAv(_) => "non_av",
+            PidClass::OtherService(_) => "other_service",
+            PidClass::Si => "si",
+            PidClass::EitPresentFollowing => "eit_pf",
+            PidClass::Unreferenced => "unreferenced",
+        }
+    }
+
+    pub fn stream_type(&self) -> Option<u8> {
+        match *self {
+            PidClass::Av(stream_type) | PidClass::NonAv(stream_type) => Some(stream_type),
+            PidClass::OtherService(stream_type) => stream_type,
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for PidClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            PidClass::Pat => write!(f, "PAT"),
+            PidClass::Program => write!(f, "PMT or PCR"),
+            PidClass::Av(stream_type) => write!(f, "audio/video stream_type=0x{:02x}", stream_type),
+            PidClass::NonAv(stream_type) => write!(f, "non-AV stream_type=0x{:02x}", stream_type),
+            PidClass::OtherService(Some(stream_type)) => {
+                write!(f, "another service, stream_type=0x{:02x}", stream_type)
+            }
+            PidClass::OtherService(None) => write!(f, "PCR of another service"),
+            PidClass::Si => write!(f, "SI kept by the policy"),
+            PidClass::EitPresentFollowing => write!(f, "EIT filtered to present/following"),
+            PidClass::Unreferenced => write!(f, "not in PAT or PMT"),
+        }
+    }
+}
+
+/// Packets of a PID read and written by drop_av_with_policy()
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PidStats {
+    /// Class when the last packet of the PID was read
+    pub class: PidClass,
+    pub input_packets: u64,
+    pub output_packets: u64,
+}
+
+/// Statistics of drop_av_with_policy() and drop_av_of_services()
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct DropAvReport {
+    pub pids: std::collections::BTreeMap<u16, PidStats>,
+    /// Incomplete PAT or PMT payloads discarded over max_payload
+    pub evicted_payloads: u64,
+}
+
+impl DropAvReport {
+    pub fn input_packets(&self) -> u64 {
+        self.pids.values().map(|stats| stats.input_packets).sum()
+    }
+
+    pub fn output_packets(&self) -> u64 {
+        self.pids.values().map(|stats| stats.output_packets).sum()
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        let pids: Vec<_> = self.pids
+            .iter()
+            .map(|(pid, stats)| {
+                json!({
+                    "pid": pid,
+                    "class": stats.class.as_str(),
+                    "stream_type": stats.class.stream_type(),
+                    "input_packets": stats.input_packets,
+                    "output_packets": stats.output_packets,
+                })
+            })
+            .collect();
+        json!({
+            "input_packets": self.input_packets(),
+            "output_packets": self.output_packets(),
+            "input_bytes": self.input_packets() * 188,
+            "output_bytes": self.output_packets() * 188,
+            "evicted_payloads": self.evicted_payloads,
+            "pids": pids,
+        })
+    }
+}
+
+/// Drop audio and video packets and keep the other streams of the programs and the SI allowed by
+/// the policy.  Return how many packets of each PID were kept and why.
+pub fn drop_av_with_policy<R, W>(reader: R,
+                                 writer: W,
+                                 policy: SiPolicy)
+                                 -> Result<DropAvReport, Error>
+    where R: std::io::Read,
+          W: std::io::Write
+{
+    drop_av_of_services(reader, writer, &[], policy, DEFAULT_MAX_PAYLOAD)
+}
+
+/// Like drop_av_with_policy(), but drop audio and video only of the services, or of all services
+/// when service_ids is empty.  Streams and PCR of the other services are kept as is even when a
+/// selected service shares them.  max_payload limits the PAT and PMT buffered for each PID as
+/// ProgramTracker::max_payload(), and packets are written in the order of the input regardless.
+pub fn drop_av_of_services<R, W>(reader: R,
+                                 mut writer: W,
+                                 service_ids: &[u16],
+                                 policy: SiPolicy,
+                                 max_payload: usize)
+                                 -> Result<DropAvReport, Error>
+    where R: std::io::Read,
+          W: std::io::Write
+{
+    let mut tracker = ProgramTracker::new().max_payload(max_payload);
+    // stream_type keyed by PID of the selected services
+    let mut av_pids = std::collections::HashMap::new();
+    let mut nonav_pids = std::collections::HashMap::new();
+    // Elementary streams and PCR of the other services, with stream_type unless only PCR
+    let mut sibling_pids = std::collections::HashMap::new();
+    // PMT and PCR, which are kept unless PCR is carried by audio or video
+    let mut program_pids = std::collections::HashSet::new();
+    let mut eit_filter = SectionFilter::default();
+    let mut report = DropAvReport::default();
+
+    for buf in super::packet::ts_packets(reader) {
+        let buf = buf?;
+        let packet = super::TsPacket::new(&buf);
+        check_packet(&packet)?;
+
+        if tracker.push(&packet)? {
+            for (program_number, program) in tracker.programs() {
+                program_pids.insert(program.pmt_pid);
+                program_pids.insert(program.pcr_pid);
+                if !service_ids.is_empty() && !service_ids.contains(program_number) {
+                    sibling_pids.entry(program.pcr_pid).or_insert(None);
+                    for &(stream_type, pid) in &program.streams {
+                        sibling_pids.insert(pid, Some(stream_type));
+                    }
+                    continue;
+                }
+                for &(stream_type, pid) in &program.streams {
+                    if !av_pids.contains_key(&pid) && !nonav_pids.contains_key(&pid) {
+                        if is_av_stream_type(stream_type) {
+                            av_pids.insert(pid, stream_type);
+                        } else {
+                            debug!("non-AV stream_type={:x} pid={:x}", stream_type, pid);
+                            nonav_pids.insert(pid, stream_type);
+                        }
+                    }
+                }
+            }
+        }
+
+        let (class, written) = if let Some(&stream_type) = sibling_pids.get(&packet.pid) {
+            writer.write_all(&buf)?;
+            (PidClass::OtherService(stream_type), 1)
+        } else if let Some(&stream_type) = av_pids.get(&packet.pid) {
+            (PidClass::Av(stream_type), 0)
+        } else if packet.pid == 0x0000 {
+            writer.write_all(&buf)?;
+            (PidClass::Pat, 1)
+        } else if let Some(&stream_type) = nonav_pids.get(&packet.pid) {
+            writer.write_all(&buf)?;
+            (PidClass::NonAv(stream_type), 1)
+        } else if program_pids.contains(&packet.pid) {
+            writer.write_all(&buf)?;
+            (PidClass::Program, 1)
+        } else if policy.keeps_pid(packet.pid) {
+            writer.write_all(&buf)?;
+            (PidClass::Si, 1)
+        } else if packet.pid == 0x0012 && policy.picks_eit_present_following() {
+            let written = eit_filter.push(&packet, &mut writer, |section| section[0] == 0x4e)?;
+            (PidClass::EitPresentFollowing, written)
+        } else {
+            (PidClass::Unreferenced, 0)
+        };
+        let stats = report.pids.entry(packet.pid).or_insert(PidStats {
+            class: class,
+            input_packets: 0,
+            output_packets: 0,
+        });
+        stats.class = class;
+        stats.input_packets += 1;
+        stats.output_packets += written;
+    }
+    report.evicted_payloads = tracker.evicted();
+    Ok(report)
+}
+
+/// Keep PAT, PMT, PCR and audio/video packets of one program and drop the others (data
+/// broadcasting, EIT, other services such as one-seg).  When service_id is None, the program
+/// with the smallest program_number is kept.
+pub fn keep_av<R, W>(reader: R, writer: W, service_id: Option<u16>) -> Result<(), Error>
+    where R: std::io::Read,
+          W: std::io::Write
+{
+    keep_av_with_policy(reader, writer, service_id, SiPolicy::Psi)
+}
+
+/// Keep PAT, PMT, PCR and audio/video packets of one program and the SI allowed by the policy.
+/// EIT[p/f actual] is kept only for the program.
+pub fn keep_av_with_policy<R, W>(reader: R,
+                                 mut writer: W,
+                                 service_id: Option<u16>,
+                                 policy: SiPolicy)
+                                 -> Result<(), Error>
+    where R: std::io::Read,
+          W: std::io::Write
+{
+    let mut tracker = ProgramTracker::new();
+    let mut keep_pids = std::collections::HashSet::new();
+    // PMT and elementary streams of all programs, which are not SI
+    let mut program_pids = std::collections::HashSet::new();
+    let mut program_number = None;
+    let mut eit_filter = SectionFilter::default();
+
+    for buf in super::packet::ts_packets(reader) {
+        let buf = buf?;
+        let packet = super::TsPacket::new(&buf);
+        check_packet(&packet)?;
+
+        if tracker.push(&packet)? {
+            keep_pids.clear();
+            if let Some(pat) = tracker.pat() {
+                program_number = match service_id {
+                    Some(service_id) => Some(service_id),
+                    None => pat.program_map.values().min().cloned(),
+                };
+                if let Some(pmt_pid) = pat.program_map
+                    .iter()
+                    .find(|&(_, n)| Some(*n) == program_number)
+                    .map(|(&pid, _)| pid) {
+                    keep_pids.insert(pmt_pid);
+                }
+                program_pids.extend(pat.program_map.keys());
+                if let Some(program) = program_number.and_then(|n| tracker.programs().get(&n)) {
+                    keep_pids.insert(program.pcr_pid);
+                    for &(stream_type, pid) in &program.streams {
+                        if is_av_stream_type(stream_type) {
+                            keep_pids.insert(pid);
+                        }
+                    }
+                }
+                for program in tracker.programs().values() {
+                    program_pids.extend(program.streams.iter().map(|&(_, pid)| pid));
+                }
+            }
+        }
+
+        if packet.pid == 0x0000 || keep_pids.contains(&packet.pid) {
+            writer.write_all(&buf)?;
+        } else if program_pids.contains(&packet.pid) {
+            continue;
+        } else if policy.keeps_pid(packet.pid) {
+            writer.write_all(&buf)?;
+        } else if packet.pid == 0x0012 && policy.picks_eit_present_following() {
+            eit_filter.push(&packet, &mut writer, |section| {
+                section[0] == 0x4e && section.len() >= 5 &&
+                Some((section[3] as u16) << 8 | section[4] as u16) == program_number
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// Scrambled packets dropped by DropScrambled
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ScrambledReport {
+    /// Number of dropped packets keyed by PID
+    pub dropped: std::collections::BTreeMap<u16, u64>,
+}
+
+impl ScrambledReport {
+    pub fn total(&self) -> u64 {
+        self.dropped.values().sum()
+    }
+}
+
+/// Writer which drops packets with non-zero transport_scrambling_control and passes the others
+/// to the inner writer, so that a partially scrambled capture can be given to encoders.  When
+/// only_descrambled is set, scrambled packets are dropped only after the first clear packet of
+/// their PID, i.e. once descrambling is seen working for it, and entirely scrambled PIDs are kept
+/// as is.  The packets are decided as they are written, so the scrambled packets before the first
+/// clear one of a PID, e.g. the head of a capture whose descrambling started late, are kept and
+/// not counted in ScrambledReport.  Drop all scrambled packets for such captures.
+pub struct DropScrambled<W> {
+    inner: W,
+    only_descrambled: bool,
+    buf: Vec<u8>,
+    clear_pids: std::collections::HashSet<u16>,
+    report: ScrambledReport,
+}
+
+impl<W> DropScrambled<W>
+    where W: std::io::Write
+{
+    pub fn new(inner: W, only_descrambled: bool) -> Self {
+        DropScrambled {
+            inner: inner,
+            only_descrambled: only_descrambled,
+            buf: Vec::with_capacity(188),
+            clear_pids: std::collections::HashSet::new(),
+            report: ScrambledReport::default(),
+        }
+    }
+
+    pub fn report(&self) -> &ScrambledReport {
+        &self.report
+    }
+
+    pub fn into_inner(self) -> (W, ScrambledReport) {
+        (self.inner, self.report)
+    }
+
+    fn write_packet(&mut self) -> std::io::Result<()> {
+        let keep = {
+            let packet = super::TsPacket::new(&self.buf);
+            if packet.transport_scrambling_control == 0 {
+                self.clear_pids.insert(packet.pid);
+                true
+            } else if self.only_descrambled && !self.clear_pids.contains(&packet.pid) {
+                true
+            } else {
+                *self.report.dropped.entry(packet.pid).or_insert(0) += 1;
+                false
+            }
+        };
+        if keep {
+            self.inner.write_all(&self.buf)?;
+        }
+        self.buf.clear();
+        Ok(())
+    }
+}
+
+impl<W> std::io::Write for DropScrambled<W>
+    where W: std::io::Write
+{
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let len = std::cmp::min(188 - self.buf.len(), buf.len());
+        self.buf.extend_from_slice(&buf[..len]);
+        if self.buf.len() == 188 {
+            self.write_packet()?;
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Drop scrambled packets and return the number of dropped packets per PID.
+pub fn drop_scrambled<R, W>(reader: R, writer: W, only_descrambled: bool) -> Result<ScrambledReport, Error>
+    where R: std::io::Read,
+          W: std::io::Write
+{
+    use std::io::Write;
+
+    let mut writer = DropScrambled::new(writer, only_descrambled);
+    for buf in super::packet::ts_packets(reader) {
+        let buf = buf?;
+        check_packet(&super::TsPacket::new(&buf))?;
+        writer.write_all(&buf)?;
+    }
+    writer.flush()?;
+    let (_, report) = writer.into_inner();
+    for (pid, dropped) in &report.dropped {
+        debug!("Dropped {} scrambled packets of pid={:x}", dropped, pid);
+    }
+    Ok(report)
+}
+
+fn check_packet(packet: &super::TsPacket) -> Result<(), Error> {
+    if !packet.check_sync_byte() {
+        return Err(Error::from("sync_byte failed"));
+    }
+    if packet.transport_error_indicator {
+        return Err(Error::from("transport_error_indicator is set"));
+    }
+    Ok(())
+}
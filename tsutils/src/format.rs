@@ -0,0 +1,162 @@
+const ANALYZER: &str = "format";
+
+/// Report changes of PAT and PMT, e.g. a stereo program switching to 5.1ch audio or a second
+/// video stream starting for multi-view broadcasting.
+///
+/// Events: pat (data: programs as [program_number, PMT PID]), pmt (data: program_number,
+/// version_number, pcr_pid, streams as [stream_type, PID], added and removed streams), error
+/// (data: message of a malformed PAT or PMT) and summary (data: the numbers of programs and of
+/// pat and pmt events).
+#[derive(Default)]
+pub struct FormatAnalyzer {
+    tracker: super::filter::ProgramTracker,
+    pat: Vec<(u16, u16)>,
+    // (pcr_pid, streams) keyed by program_number
+    programs: std::collections::BTreeMap<u16, (u16, Vec<(u8, u16)>)>,
+    pat_changes: u64,
+    pmt_changes: u64,
+}
+
+impl FormatAnalyzer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl super::report::Analyzer for FormatAnalyzer {
+    fn push(&mut self,
+            index: u64,
+            _buf: &[u8],
+            packet: Option<&super::TsPacket>,
+            reports: &mut Vec<super::report::Report>) {
+        let packet = match packet {
+            Some(packet) => packet,
+            None => return,
+        };
+        match self.tracker.push(packet) {
+            Ok(true) => {}
+            Ok(false) => return,
+            Err(e) => {
+                reports.push(super::report::Report::new(ANALYZER, "error")
+                    .packet(index)
+                    .pid(packet.pid)
+                    .field("message", e.to_string()));
+                return;
+            }
+        }
+
+        if let Some(pat) = self.tracker.pat() {
+            let mut programs: Vec<_> =
+                pat.program_map.iter().map(|(&pid, &n)| (n, pid)).collect();
+            programs.sort();
+            if programs != self.pat {
+                self.pat_changes += 1;
+                reports.push(super::report::Report::new(ANALYZER, "pat")
+                    .packet(index)
+                    .pid(0x0000)
+                    .field("programs", json_pairs(&programs)));
+                self.pat = programs;
+            }
+        }
+
+        for (&program_number, program) in self.tracker.programs() {
+            let current = (program.pcr_pid, program.streams.clone());
+            let (removed, added) = match self.programs.get(&program_number) {
+                Some(previous) if *previous == current => continue,
+                Some((_, streams)) => {
+                    (streams.iter().filter(|s| !current.1.contains(s)).cloned().collect(),
+                     current.1.iter().filter(|s| !streams.contains(s)).cloned().collect())
+                }
+                None => (vec![], current.1.clone()),
+            };
+            self.pmt_changes += 1;
+            reports.push(super::report::Report::new(ANALYZER, "pmt")
+                .packet(index)
+                .pid(program.pmt_pid)
+                .field("program_number", program_number)
+                .field("version_number", program.version_number)
+                .field("pcr_pid", program.pcr_pid)
+                .field("streams", json_pairs(&program.streams))
+                .field("added", json_pairs(&added))
+                .field("removed", json_pairs(&removed)));
+            self.programs.insert(program_number, current);
+        }
+    }
+
+    fn finish(&mut self, reports: &mut Vec<super::report::Report>) {
+        reports.push(super::report::Report::new(ANALYZER, "summary")
+            .field("programs", self.programs.len())
+            .field("pat_changes", self.pat_changes)
+            .field("pmt_changes", self.pmt_changes));
+    }
+
+    fn checkpoint(&self) -> Option<serde_json::Value> {
+        let programs: Vec<_> = self.programs
+            .iter()
+            .map(|(&program_number, &(pcr_pid, ref streams))| {
+                json!([program_number, pcr_pid, streams])
+            })
+            .collect();
+        Some(json!({
+            "tracker": self.tracker.checkpoint(),
+            "pat": self.pat,
+            "programs": programs,
+            "pat_changes": self.pat_changes,
+            "pmt_changes": self.pmt_changes,
+        }))
+    }
+
+    fn restore(&mut self, state: &serde_json::Value) -> bool {
+        match FormatAnalyzer::from_checkpoint(state) {
+            Some(analyzer) => {
+                *self = analyzer;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl FormatAnalyzer {
+    // Inverse of checkpoint()
+    fn from_checkpoint(state: &serde_json::Value) -> Option<Self> {
+        let pairs = |pairs: &serde_json::Value| {
+            pairs.as_array()?
+                .iter()
+                .map(|pair| Some((pair.get(0)?.as_u64()?, pair.get(1)?.as_u64()?)))
+                .collect::<Option<Vec<_>>>()
+        };
+        let pat = pairs(state.get("pat")?)?
+            .into_iter()
+            .map(|(n, pid)| (n as u16, pid as u16))
+            .collect();
+        let programs = state.get("programs")?
+            .as_array()?
+            .iter()
+            .map(|program| {
+                let streams = pairs(program.get(2)?)?
+                    .into_iter()
+                    .map(|(stream_type, pid)| (stream_type as u8, pid as u16))
+                    .collect();
+                Some((program.get(0)?.as_u64()? as u16,
+                      (program.get(1)?.as_u64()? as u16, streams)))
+            })
+            .collect::<Option<_>>()?;
+        Some(FormatAnalyzer {
+            tracker: super::filter::ProgramTracker::restore(state.get("tracker")?)?,
+            pat: pat,
+            programs: programs,
+            pat_changes: state.get("pat_changes")?.as_u64()?,
+            pmt_changes: state.get("pmt_changes")?.as_u64()?,
+        })
+    }
+}
+
+fn json_pairs<A, B>(pairs: &[(A, B)]) -> serde_json::Value
+    where A: Into<serde_json::Value> + Copy,
+          B: Into<serde_json::Value> + Copy
+{
+    serde_json::Value::Array(pairs.iter()
+        .map(|&(a, b)| serde_json::Value::Array(vec![a.into(), b.into()]))
+        .collect())
+}
@@ -0,0 +1,229 @@
+// 27MHz, base of 33 bits and extension of 9 bits
+const PCR_MODULO: u64 = (1 << 33) * 300;
+const ANALYZER: &str = "integrity";
+
+#[derive(Debug, Default, Clone)]
+pub struct IntegrityReport {
+    pub packets: u64,
+    pub sync_errors: u64,
+    pub transport_errors: u64,
+    pub drops: u64,
+    pub scrambled_packets: u64,
+    pub pcr_pid: Option<u16>,
+    /// Media time at the last PCR in 90kHz, continuing across PCR discontinuities
+    pub pcr_duration: u64,
+}
+
+impl IntegrityReport {
+    pub fn scrambled_ratio(&self) -> f64 {
+        if self.packets == 0 {
+            0.0
+        } else {
+            self.scrambled_packets as f64 / self.packets as f64
+        }
+    }
+
+    pub fn duration_secs(&self) -> f64 {
+        self.pcr_duration as f64 / 90000.0
+    }
+}
+
+/// Scan the whole stream and count sync byte errors, continuity_counter drops and scrambled
+/// packets.  The duration is measured with PCR of the first PID carrying PCR on a
+/// timeline::Timeline.
+pub fn check<R>(reader: R) -> Result<IntegrityReport, std::io::Error>
+    where R: std::io::Read
+{
+    let mut monitor = Monitor::new();
+    for buf in super::packet::ts_packets(reader) {
+        monitor.push(&buf?);
+    }
+    Ok(monitor.report)
+}
+
+/// Incremental version of check() for streams being written.  Compare snapshots of the report
+/// to find PCR stalls, bursts of drops or scrambled periods.
+#[derive(Debug, Default)]
+pub struct Monitor {
+    pub report: IntegrityReport,
+    continuity_counters: std::collections::HashMap<u16, u8>,
+    timeline: super::timeline::Timeline,
+    // Scrambled packets are reported once per PID
+    scrambled_pids: std::collections::HashSet<u16>,
+}
+
+impl Monitor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Inverse of checkpoint()
+    fn from_checkpoint(state: &serde_json::Value) -> Option<Self> {
+        let count = |name| state.get(name).and_then(|count| count.as_u64());
+        let timeline = super::timeline::Timeline::restore(state.get("timeline")?)?;
+        let report = IntegrityReport {
+            packets: count("packets")?,
+            sync_errors: count("sync_errors")?,
+            transport_errors: count("transport_errors")?,
+            drops: count("drops")?,
+            scrambled_packets: count("scrambled_packets")?,
+            pcr_pid: timeline.pcr_pid(),
+            pcr_duration: timeline.duration() / 300,
+        };
+        let continuity_counters = state.get("continuity_counters")?
+            .as_array()?
+            .iter()
+            .map(|pair| Some((pair.get(0)?.as_u64()? as u16, pair.get(1)?.as_u64()? as u8)))
+            .collect::<Option<_>>()?;
+        let scrambled_pids = state.get("scrambled_pids")?
+            .as_array()?
+            .iter()
+            .map(|pid| pid.as_u64().map(|pid| pid as u16))
+            .collect::<Option<_>>()?;
+        Some(Monitor {
+            report: report,
+            continuity_counters: continuity_counters,
+            timeline: timeline,
+            scrambled_pids: scrambled_pids,
+        })
+    }
+
+    /// Count a 188-byte packet.
+    pub fn push(&mut self, buf: &[u8]) {
+        self.inspect(buf, &mut vec![]);
+    }
+
+    // Count a packet and report what was counted
+    fn inspect(&mut self, buf: &[u8], reports: &mut Vec<super::report::Report>) {
+        let index = self.report.packets;
+        let report = &mut self.report;
+        report.packets += 1;
+        if buf[0] != 0x47 {
+            report.sync_errors += 1;
+            reports.push(super::report::Report::new(ANALYZER, "sync_error")
+                .packet(index)
+                .field("sync_byte", buf[0]));
+            return;
+        }
+        if (buf[1] & 0b10000000) != 0 {
+            // Do not trust the rest of the header
+            report.transport_errors += 1;
+            reports.push(super::report::Report::new(ANALYZER, "transport_error").packet(index));
+            return;
+        }
+        let packet = super::TsPacket::new(buf);
+        // The adaptation field is not parsed when any field runs past adaptation_field_length
+        if (packet.adaptation_field_control == 0b10 || packet.adaptation_field_control == 0b11) &&
+           buf[4] != 0 && packet.adaptation_field.is_none() {
+            report.transport_errors += 1;
+            reports.push(super::report::Report::new(ANALYZER, "transport_error")
+                .packet(index)
+                .field("adaptation_field_length", buf[4]));
+            return;
+        }
+        if packet.pid == 0x1fff {
+            return;
+        }
+        if packet.transport_scrambling_control != 0 {
+            if !self.scrambled_pids.contains(&packet.pid) {
+                self.scrambled_pids.insert(packet.pid);
+                reports.push(super::report::Report::new(ANALYZER, "scrambled")
+                    .packet(index)
+                    .pid(packet.pid));
+            }
+            report.scrambled_packets += 1;
+        }
+
+        let discontinuity = packet.adaptation_field
+            .as_ref()
+            .map(|af| af.discontinuity_indicator)
+            .unwrap_or(false);
+        if packet.data_bytes.is_some() {
+            if let Some(last_cc) = self.continuity_counters.insert(packet.pid,
+                                                                   packet.continuity_counter) {
+                // A duplicate packet has the same continuity_counter
+                if !discontinuity && packet.continuity_counter != last_cc &&
+                   packet.continuity_counter != (last_cc + 1) & 0x0f {
+                    debug!("Drop detected at PID={:x}: {} -> {}",
+                           packet.pid,
+                           last_cc,
+                           packet.continuity_counter);
+                    report.drops += 1;
+                    reports.push(super::report::Report::new(ANALYZER, "drop")
+                        .packet(index)
+                        .pid(packet.pid)
+                        .field("expected", (last_cc + 1) & 0x0f)
+                        .field("actual", packet.continuity_counter));
+                }
+            }
+        }
+
+        let last_pcr = self.timeline.last_pcr();
+        let segments = self.timeline.segments().len();
+        if self.timeline.push(index, &packet).is_some() {
+            report.pcr_pid = self.timeline.pcr_pid();
+            report.pcr_duration = self.timeline.duration() / 300;
+            if let (Some(last_pcr), Some(segment)) = (last_pcr, self.timeline.segments().last()) {
+                if self.timeline.segments().len() > segments && !segment.indicated {
+                    let interval = (segment.pcr + PCR_MODULO - last_pcr) % PCR_MODULO;
+                    reports.push(super::report::Report::new(ANALYZER, "pcr_gap")
+                        .packet(index)
+                        .pid(packet.pid)
+                        .field("interval", interval as f64 / 27000000.0));
+                }
+            }
+        }
+    }
+}
+
+/// Events: sync_error, transport_error, drop (data: expected, actual continuity_counter),
+/// scrambled (the first scrambled packet of the PID), pcr_gap (data: interval in seconds) and
+/// summary (data: the counts of IntegrityReport and duration in seconds).
+impl super::report::Analyzer for Monitor {
+    fn push(&mut self,
+            _index: u64,
+            buf: &[u8],
+            _packet: Option<&super::TsPacket>,
+            reports: &mut Vec<super::report::Report>) {
+        self.inspect(buf, reports);
+    }
+
+    fn finish(&mut self, reports: &mut Vec<super::report::Report>) {
+        let report = &self.report;
+        let mut summary = super::report::Report::new(ANALYZER, "summary")
+            .field("packets", report.packets)
+            .field("sync_errors", report.sync_errors)
+            .field("transport_errors", report.transport_errors)
+            .field("drops", report.drops)
+            .field("scrambled_packets", report.scrambled_packets)
+            .field("duration", report.duration_secs());
+        if let Some(pcr_pid) = report.pcr_pid {
+            summary = summary.pid(pcr_pid);
+        }
+        reports.push(summary);
+    }
+
+    fn checkpoint(&self) -> Option<serde_json::Value> {
+        let report = &self.report;
+        Some(json!({
+            "packets": report.packets,
+            "sync_errors": report.sync_errors,
+            "transport_errors": report.transport_errors,
+            "drops": report.drops,
+            "scrambled_packets": report.scrambled_packets,
+            "continuity_counters": self.continuity_counters.iter().collect::<Vec<_>>(),
+            "timeline": self.timeline.checkpoint(),
+            "scrambled_pids": self.scrambled_pids,
+        }))
+    }
+
+    fn restore(&mut self, state: &serde_json::Value) -> bool {
+        match Monitor::from_checkpoint(state) {
+            Some(monitor) => {
+                *self = monitor;
+                true
+            }
+            None => false,
+        }
+    }
+}
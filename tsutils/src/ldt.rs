@@ -0,0 +1,101 @@
+/// Linked Description Table, which carries descriptions of events referred by
+/// LDT_linkage_descriptor in EIT instead of repeating them in every EIT section
+#[derive(Debug)]
+pub struct LinkedDescriptionTable<'a> {
+    pub table_id: u8,
+    pub original_service_id: u16,
+    pub version_number: u8,
+    pub current_next_indicator: bool,
+    pub section_number: u8,
+    pub last_section_number: u8,
+    pub transport_stream_id: u16,
+    pub original_network_id: u16,
+    pub descriptions: Vec<Description<'a>>,
+    pub crc32: u32,
+}
+
+impl<'a> LinkedDescriptionTable<'a> {
+    /// Parse a section assembled by psi::SectionAssembler.
+    pub fn parse(section: &'a [u8]) -> Result<Self, super::psi::ParseError> {
+        // ARIB STD-B10 Part 2 5.2.15
+        if section.len() < 17 {
+            return Err(super::psi::ParseError::InsufficientLength);
+        }
+        let table_id = section[0];
+        if table_id != 0xc7 {
+            return Err(super::psi::ParseError::IncorrectTableId {
+                expected: 0xc7,
+                actual: table_id,
+            });
+        }
+        let section_syntax_indicator = (section[1] & 0b10000000) != 0;
+        if !section_syntax_indicator {
+            return Err(super::psi::ParseError::IncorrectSectionSyntaxIndicator);
+        }
+        let section_length = ((section[1] & 0b00001111) as usize) << 8 | section[2] as usize;
+        if section.len() < 3 + section_length || section_length < 14 {
+            return Err(super::psi::ParseError::InsufficientLength);
+        }
+        let original_service_id = (section[3] as u16) << 8 | section[4] as u16;
+        let version_number = (section[5] & 0b00111110) >> 1;
+        let current_next_indicator = (section[5] & 0b00000001) != 0;
+        let section_number = section[6];
+        let last_section_number = section[7];
+        let transport_stream_id = (section[8] as u16) << 8 | section[9] as u16;
+        let original_network_id = (section[10] as u16) << 8 | section[11] as u16;
+
+        let end = 3 + section_length - 4;
+        let mut index = 12;
+        let mut descriptions = vec![];
+        while index < end {
+            if index + 4 > end {
+                return Err(super::psi::ParseError::InsufficientLength);
+            }
+            let description = Description::new(&section[index..end])?;
+            index += description.size();
+            descriptions.push(description);
+        }
+        let crc32 = (section[end] as u32) << 24 | (section[end + 1] as u32) << 16 |
+                    (section[end + 2] as u32) << 8 |
+                    (section[end + 3] as u32);
+
+        Ok(LinkedDescriptionTable {
+            table_id: table_id,
+            original_service_id: original_service_id,
+            version_number: version_number,
+            current_next_indicator: current_next_indicator,
+            section_number: section_number,
+            last_section_number: last_section_number,
+            transport_stream_id: transport_stream_id,
+            original_network_id: original_network_id,
+            descriptions: descriptions,
+            crc32: crc32,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct Description<'a> {
+    pub description_id: u16,
+    /// Descriptors of the event such as short_event_descriptor
+    pub descriptors: &'a [u8],
+}
+
+impl<'a> Description<'a> {
+    fn new(payload: &'a [u8]) -> Result<Self, super::psi::ParseError> {
+        let description_id = (payload[0] as u16) << 8 | payload[1] as u16;
+        let descriptors_loop_length = ((payload[2] & 0b00001111) as usize) << 8 |
+                                      payload[3] as usize;
+        if payload.len() < 4 + descriptors_loop_length {
+            return Err(super::psi::ParseError::InsufficientLength);
+        }
+        Ok(Description {
+            description_id: description_id,
+            descriptors: &payload[4..(4 + descriptors_loop_length)],
+        })
+    }
+
+    pub fn size(&self) -> usize {
+        4 + self.descriptors.len()
+    }
+}
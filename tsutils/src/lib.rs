@@ -1,11 +1,48 @@
+extern crate clap;
+extern crate encoding_rs;
 #[macro_use]
 extern crate log;
+#[macro_use]
+extern crate serde_json;
+#[cfg(feature = "testgen")]
+extern crate proptest;
 
+pub mod arib_string;
+pub mod caption;
+pub mod checkpoint;
+pub mod cli;
+pub mod compare;
+pub mod demux;
+pub mod descriptor;
+pub mod edit;
+pub mod eit;
+pub mod epg;
+pub mod filter;
+pub mod format;
+pub mod integrity;
+pub mod ldt;
+pub mod nit;
 pub mod packet;
 pub mod pat;
+pub mod pcr;
 pub mod pmt;
+pub mod progress;
 pub mod psi;
+pub mod report;
+pub mod rst;
+pub mod running;
+pub mod sdt;
+pub mod time;
+pub mod timeline;
+pub mod tot;
+#[cfg(feature = "testgen")]
+pub mod testgen;
 
+pub use eit::EventInformationTable;
+pub use ldt::LinkedDescriptionTable;
+pub use nit::NetworkInformationTable;
 pub use packet::TsPacket;
 pub use pat::ProgramAssociationTable;
 pub use pmt::ProgramMapTable;
+pub use sdt::ServiceDescriptionTable;
+pub use tot::TimeOffsetTable;
@@ -0,0 +1,133 @@
+#[derive(Debug)]
+pub struct NetworkInformationTable<'a> {
+    pub table_id: u8,
+    pub network_id: u16,
+    pub version_number: u8,
+    pub current_next_indicator: bool,
+    pub section_number: u8,
+    pub last_section_number: u8,
+    pub network_descriptors: &'a [u8],
+    pub transport_streams: Vec<TransportStream<'a>>,
+    pub crc32: u32,
+}
+
+impl<'a> NetworkInformationTable<'a> {
+    /// Parse a section assembled by psi::SectionAssembler.
+    pub fn parse(section: &'a [u8]) -> Result<Self, super::psi::ParseError> {
+        // ARIB STD-B10 Part 2 5.2.4
+        if section.len() < 16 {
+            return Err(super::psi::ParseError::InsufficientLength);
+        }
+        let table_id = section[0];
+        if table_id != 0x40 && table_id != 0x41 {
+            return Err(super::psi::ParseError::IncorrectTableId {
+                expected: 0x40,
+                actual: table_id,
+            });
+        }
+        let section_syntax_indicator = (section[1] & 0b10000000) != 0;
+        if !section_syntax_indicator {
+            return Err(super::psi::ParseError::IncorrectSectionSyntaxIndicator);
+        }
+        let section_length = ((section[1] & 0b00001111) as usize) << 8 | section[2] as usize;
+        if section.len() < 3 + section_length || section_length < 13 {
+            return Err(super::psi::ParseError::InsufficientLength);
+        }
+        let network_id = (section[3] as u16) << 8 | section[4] as u16;
+        let version_number = (section[5] & 0b00111110) >> 1;
+        let current_next_indicator = (section[5] & 0b00000001) != 0;
+        let section_number = section[6];
+        let last_section_number = section[7];
+
+        let end = 3 + section_length - 4;
+        let network_descriptors_length = ((section[8] & 0b00001111) as usize) << 8 |
+                                         section[9] as usize;
+        let mut index = 10 + network_descriptors_length;
+        if index + 2 > end {
+            return Err(super::psi::ParseError::InsufficientLength);
+        }
+        let network_descriptors = &section[10..index];
+        let transport_stream_loop_length = ((section[index] & 0b00001111) as usize) << 8 |
+                                           section[index + 1] as usize;
+        index += 2;
+        if index + transport_stream_loop_length > end {
+            return Err(super::psi::ParseError::InsufficientLength);
+        }
+        let mut transport_streams = vec![];
+        while index < end {
+            if index + 6 > end {
+                return Err(super::psi::ParseError::InsufficientLength);
+            }
+            let transport_stream = TransportStream::new(&section[index..end])?;
+            index += transport_stream.size();
+            transport_streams.push(transport_stream);
+        }
+        let crc32 = (section[end] as u32) << 24 | (section[end + 1] as u32) << 16 |
+                    (section[end + 2] as u32) << 8 |
+                    (section[end + 3] as u32);
+
+        Ok(NetworkInformationTable {
+            table_id: table_id,
+            network_id: network_id,
+            version_number: version_number,
+            current_next_indicator: current_next_indicator,
+            section_number: section_number,
+            last_section_number: last_section_number,
+            network_descriptors: network_descriptors,
+            transport_streams: transport_streams,
+            crc32: crc32,
+        })
+    }
+
+    /// NIT actual
+    pub fn is_actual(&self) -> bool {
+        self.table_id == 0x40
+    }
+
+    pub fn network_name_descriptor(&self) -> Option<super::descriptor::NetworkNameDescriptor<'a>> {
+        super::descriptor::descriptors(self.network_descriptors)
+            .find(|&(tag, _)| tag == super::descriptor::NetworkNameDescriptor::TAG)
+            .and_then(|(_, body)| super::descriptor::NetworkNameDescriptor::parse(body))
+    }
+}
+
+#[derive(Debug)]
+pub struct TransportStream<'a> {
+    pub transport_stream_id: u16,
+    pub original_network_id: u16,
+    pub descriptors: &'a [u8],
+}
+
+impl<'a> TransportStream<'a> {
+    fn new(payload: &'a [u8]) -> Result<Self, super::psi::ParseError> {
+        let transport_stream_id = (payload[0] as u16) << 8 | payload[1] as u16;
+        let original_network_id = (payload[2] as u16) << 8 | payload[3] as u16;
+        let transport_descriptors_length = ((payload[4] & 0b00001111) as usize) << 8 |
+                                           payload[5] as usize;
+        if payload.len() < 6 + transport_descriptors_length {
+            return Err(super::psi::ParseError::InsufficientLength);
+        }
+        let descriptors = &payload[6..(6 + transport_descriptors_length)];
+        Ok(TransportStream {
+            transport_stream_id: transport_stream_id,
+            original_network_id: original_network_id,
+            descriptors: descriptors,
+        })
+    }
+
+    pub fn size(&self) -> usize {
+        6 + self.descriptors.len()
+    }
+
+    pub fn ts_information_descriptor(&self) -> Option<super::descriptor::TsInformationDescriptor<'a>> {
+        super::descriptor::descriptors(self.descriptors)
+            .find(|&(tag, _)| tag == super::descriptor::TsInformationDescriptor::TAG)
+            .and_then(|(_, body)| super::descriptor::TsInformationDescriptor::parse(body))
+    }
+
+    pub fn partial_reception_descriptor(&self) -> Option<super::descriptor::PartialReceptionDescriptor> {
+        super::descriptor::descriptors(self.descriptors)
+            .find(|&(tag, _)| tag == super::descriptor::PartialReceptionDescriptor::TAG)
+            .and_then(|(_, body)| super::descriptor::PartialReceptionDescriptor::parse(body))
+    }
+}
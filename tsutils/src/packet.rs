@@ -28,7 +28,7 @@ pub fn ts_packets<R>(reader: R) -> TsPackets<R> {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct TsPacket<'a> {
     pub sync_byte: u8,
     pub transport_error_indicator: bool,
@@ -60,11 +60,8 @@ impl<'a> TsPacket<'a> {
         let adaptation_field = if adaptation_field_control == 0b10 ||
                                   adaptation_field_control == 0b11 {
             let adaptation_field = AdaptationField::parse(&packet[index..]);
-            if let Some(ref af) = adaptation_field {
-                index += af.adaptation_field_length as usize + 1;
-            } else {
-                index += 1
-            }
+            // A malformed adaptation field is skipped as well, but not beyond the packet
+            index = std::cmp::min(index + packet[index] as usize + 1, packet.len());
             adaptation_field
         } else {
             None
@@ -93,9 +90,96 @@ impl<'a> TsPacket<'a> {
     pub fn check_sync_byte(&self) -> bool {
         self.sync_byte == 0x47
     }
+
+    /// data_bytes without pointer_field when payload_unit_start_indicator is set, i.e. the end
+    /// of the previous section followed by the new sections.  Same as data_bytes otherwise.
+    pub fn payload_after_pointer(&self) -> Option<&'a [u8]> {
+        match self.data_bytes {
+            Some(data_bytes) if self.payload_unit_start_indicator => {
+                if data_bytes.is_empty() {
+                    None
+                } else {
+                    Some(&data_bytes[1..])
+                }
+            }
+            data_bytes => data_bytes,
+        }
+    }
+
+    /// Bytes continuing the section started in the previous packets, i.e. the ones before the
+    /// position pointed by pointer_field, or whole data_bytes without
+    /// payload_unit_start_indicator.  None when pointer_field points beyond data_bytes.
+    pub fn section_continuation(&self) -> Option<&'a [u8]> {
+        if self.payload_unit_start_indicator {
+            self.split_at_pointer().map(|(continuation, _)| continuation)
+        } else {
+            self.data_bytes
+        }
+    }
+
+    /// Bytes from the first section starting in the packet, i.e. the ones at the position
+    /// pointed by pointer_field.  None without payload_unit_start_indicator or when
+    /// pointer_field points beyond data_bytes.
+    pub fn section_start(&self) -> Option<&'a [u8]> {
+        if self.payload_unit_start_indicator {
+            self.split_at_pointer().map(|(_, start)| start)
+        } else {
+            None
+        }
+    }
+
+    // Split data_bytes after pointer_field at the position pointed by it
+    // ISO/IEC 13818-1 2.4.4.2
+    fn split_at_pointer(&self) -> Option<(&'a [u8], &'a [u8])> {
+        match self.data_bytes {
+            Some(data_bytes) if !data_bytes.is_empty() => {
+                let pointer_field = data_bytes[0] as usize;
+                if pointer_field < data_bytes.len() {
+                    Some(data_bytes[1..].split_at(pointer_field))
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Serialize the packet.  adaptation_field_length decides where data_bytes start, and
+    /// data_bytes which do not fit are truncated and missing bytes are filled with 0xff.
+    pub fn to_bytes(&self) -> [u8; 188] {
+        let mut packet = [0xff; 188];
+        packet[0] = self.sync_byte;
+        packet[1] = (self.transport_error_indicator as u8) << 7 |
+                    (self.payload_unit_start_indicator as u8) << 6 |
+                    (self.transport_priority as u8) << 5 |
+                    (self.pid >> 8) as u8 & 0b00011111;
+        packet[2] = self.pid as u8;
+        packet[3] = (self.transport_scrambling_control & 0b11) << 6 |
+                    (self.adaptation_field_control & 0b11) << 4 |
+                    self.continuity_counter & 0b00001111;
+
+        let mut index = 4;
+        if self.adaptation_field_control == 0b10 || self.adaptation_field_control == 0b11 {
+            match self.adaptation_field {
+                Some(ref af) => {
+                    af.write(&mut packet[index..]);
+                    index += af.adaptation_field_length as usize + 1;
+                }
+                None => {
+                    packet[index] = 0;
+                    index += 1;
+                }
+            }
+        }
+        if let Some(data_bytes) = self.data_bytes {
+            let len = std::cmp::min(data_bytes.len(), 188 - index);
+            packet[index..(index + len)].copy_from_slice(&data_bytes[..len]);
+        }
+        packet
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct AdaptationField<'a> {
     pub adaptation_field_length: u8,
     pub discontinuity_indicator: bool,
@@ -110,15 +194,19 @@ pub struct AdaptationField<'a> {
 }
 
 impl<'a> AdaptationField<'a> {
+    // None when it is empty or any field runs past adaptation_field_length, e.g. in damaged
+    // packets
     fn parse(packet: &'a [u8]) -> Option<Self> {
         // ISO/IEC 13818-1 2.4.3.4 Table 2-6
         // ISO/IEC 13818-1 2.4.3.5
         let adaptation_field_length = packet[0];
-        if adaptation_field_length == 0 {
+        let end = adaptation_field_length as usize + 1;
+        if adaptation_field_length == 0 || end > packet.len() {
             None
         } else {
-            let discontinuity_indicator = (packet[1] & 0b100000) != 0;
-            let random_access_indicator = (packet[1] & 0b010000) != 0;
+            let packet = &packet[..end];
+            let discontinuity_indicator = (packet[1] & 0b10000000) != 0;
+            let random_access_indicator = (packet[1] & 0b01000000) != 0;
             let elementary_stream_priority_indicator = (packet[1] & 0b00100000) != 0;
             let pcr_flag = (packet[1] & 0b00010000) != 0;
             let opcr_flag = (packet[1] & 0b00001000) != 0;
@@ -129,7 +217,7 @@ impl<'a> AdaptationField<'a> {
             let mut index = 2;
 
             let pcr = if pcr_flag {
-                let pcr = PCR::new(&packet[index..]);
+                let pcr = PCR::new(packet.get(index..(index + PCR::size()))?);
                 index += PCR::size();
                 Some(pcr)
             } else {
@@ -137,7 +225,7 @@ impl<'a> AdaptationField<'a> {
             };
 
             let opcr = if opcr_flag {
-                let opcr = OPCR::new(&packet[index..]);
+                let opcr = OPCR::new(packet.get(index..(index + OPCR::size()))?);
                 index += OPCR::size();
                 Some(opcr)
             } else {
@@ -145,7 +233,7 @@ impl<'a> AdaptationField<'a> {
             };
 
             let splice_countdown = if splicing_point_flag {
-                let splice_countdown = packet[index] as i8;
+                let splice_countdown = *packet.get(index)? as i8;
                 index += 1;
                 Some(splice_countdown)
             } else {
@@ -153,9 +241,9 @@ impl<'a> AdaptationField<'a> {
             };
 
             let transport_private_data = if transport_private_data_flag {
-                let length = packet[index] as usize;
+                let length = *packet.get(index)? as usize;
                 index += 1;
-                let data = &packet[index..(index + length)];
+                let data = packet.get(index..(index + length))?;
                 index += length;
                 Some(data)
             } else {
@@ -163,15 +251,17 @@ impl<'a> AdaptationField<'a> {
             };
 
             let adaptation_field_extension = if adaptation_field_extension_flag {
-                let extension = AdaptationFieldExtension::new(&packet[index..]);
-                index += extension.adaptation_field_extension_length as usize;
+                let length = *packet.get(index)? as usize;
+                let bytes = packet.get(index..(index + 1 + length))?;
+                let extension = AdaptationFieldExtension::new(bytes)?;
+                index += 1 + length;
                 Some(extension)
             } else {
                 None
             };
 
             // Check stuffing_bytes
-            for &stuffing_byte in &packet[index..(adaptation_field_length as usize + 1)] {
+            for &stuffing_byte in &packet[index..] {
                 if stuffing_byte != 0xff {
                     warn!("Invalid stuffing_byte in adaptation field: {}",
                           stuffing_byte);
@@ -192,9 +282,137 @@ impl<'a> AdaptationField<'a> {
             })
         }
     }
+
+    // Remaining bytes up to adaptation_field_length are left as stuffing_byte
+    fn write(&self, packet: &mut [u8]) {
+        packet[0] = self.adaptation_field_length;
+        packet[1] = (self.discontinuity_indicator as u8) << 7 |
+                    (self.random_access_indicator as u8) << 6 |
+                    (self.elementary_stream_priority_indicator as u8) << 5 |
+                    (self.pcr.is_some() as u8) << 4 |
+                    (self.opcr.is_some() as u8) << 3 |
+                    (self.splice_countdown.is_some() as u8) << 2 |
+                    (self.transport_private_data.is_some() as u8) << 1 |
+                    self.adaptation_field_extension.is_some() as u8;
+
+        let mut index = 2;
+        if let Some(ref pcr) = self.pcr {
+            write_clock_reference(&mut packet[index..],
+                                  pcr.program_clock_reference_base,
+                                  pcr.reserved,
+                                  pcr.program_clock_reference_extension);
+            index += PCR::size();
+        }
+        if let Some(ref opcr) = self.opcr {
+            write_clock_reference(&mut packet[index..],
+                                  opcr.original_program_clock_reference_base,
+                                  opcr.reserved,
+                                  opcr.original_program_clock_reference_extension);
+            index += OPCR::size();
+        }
+        if let Some(splice_countdown) = self.splice_countdown {
+            packet[index] = splice_countdown as u8;
+            index += 1;
+        }
+        if let Some(data) = self.transport_private_data {
+            packet[index] = data.len() as u8;
+            index += 1;
+            packet[index..(index + data.len())].copy_from_slice(data);
+            index += data.len();
+        }
+        if let Some(ref extension) = self.adaptation_field_extension {
+            extension.write(&mut packet[index..]);
+        }
+    }
+}
+
+/// Build an adaptation field filling the packet before data_bytes with stuffing_byte, e.g. to
+/// insert PCR at the required interval after the original PCR PID is removed.
+#[derive(Debug, Default)]
+pub struct AdaptationFieldBuilder {
+    discontinuity_indicator: bool,
+    random_access_indicator: bool,
+    pcr: Option<u64>,
+}
+
+impl AdaptationFieldBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// PCR in 27MHz, which wraps around at 2^33 * 300
+    pub fn pcr(mut self, pcr: u64) -> Self {
+        self.pcr = Some(pcr);
+        self
+    }
+
+    pub fn discontinuity(mut self) -> Self {
+        self.discontinuity_indicator = true;
+        self
+    }
+
+    pub fn random_access(mut self) -> Self {
+        self.random_access_indicator = true;
+        self
+    }
+
+    /// Adaptation field followed by data_len bytes of data_bytes.  None when the fields do not
+    /// fit, or when data_len is 183 and adaptation_field_length has to be 0 without any flag.
+    pub fn build(self, data_len: usize) -> Option<AdaptationField<'static>> {
+        // 4 bytes of the header and 1 byte of adaptation_field_length
+        if data_len > 183 {
+            return None;
+        }
+        let adaptation_field_length = 183 - data_len;
+        let min_length = 1 + self.pcr.map(|_| PCR::size()).unwrap_or(0);
+        if adaptation_field_length < min_length {
+            return None;
+        }
+        Some(AdaptationField {
+            adaptation_field_length: adaptation_field_length as u8,
+            discontinuity_indicator: self.discontinuity_indicator,
+            random_access_indicator: self.random_access_indicator,
+            elementary_stream_priority_indicator: false,
+            transport_private_data_flag: false,
+            pcr: self.pcr.map(PCR::from_27mhz),
+            opcr: None,
+            splice_countdown: None,
+            transport_private_data: None,
+            adaptation_field_extension: None,
+        })
+    }
+
+    /// Packet carrying only the adaptation field (adaptation_field_control is 0b10).  Give the
+    /// continuity_counter of the last packet of the PID since it is not incremented by packets
+    /// without payload.
+    pub fn packet(self, pid: u16, continuity_counter: u8) -> [u8; 188] {
+        let packet = TsPacket {
+            sync_byte: 0x47,
+            transport_error_indicator: false,
+            payload_unit_start_indicator: false,
+            transport_priority: false,
+            pid: pid,
+            transport_scrambling_control: 0,
+            adaptation_field_control: 0b10,
+            continuity_counter: continuity_counter,
+            adaptation_field: self.build(0),
+            data_bytes: None,
+        };
+        packet.to_bytes()
+    }
 }
 
-#[derive(Debug)]
+// ISO/IEC 13818-1 2.4.3.4 Table 2-6: 33-bit base, 6-bit reserved and 9-bit extension
+fn write_clock_reference(packet: &mut [u8], base: u64, reserved: u8, extension: u16) {
+    packet[0] = (base >> 25) as u8;
+    packet[1] = (base >> 17) as u8;
+    packet[2] = (base >> 9) as u8;
+    packet[3] = (base >> 1) as u8;
+    packet[4] = ((base & 1) as u8) << 7 | reserved & 0b01111110 | (extension >> 8) as u8 & 1;
+    packet[5] = extension as u8;
+}
+
+#[derive(Debug, PartialEq)]
 pub struct PCR {
     pub program_clock_reference_base: u64,
     pub reserved: u8,
@@ -204,22 +422,37 @@ pub struct PCR {
 impl PCR {
     fn new(packet: &[u8]) -> Self {
         PCR {
-            program_clock_reference_base: ((packet[0] as u64) << 32) | ((packet[1] as u64) << 24) |
-                                          ((packet[2] as u64) << 16) |
-                                          (packet[3] as u64) << 8 |
-                                          (packet[4] & 0b10000000) as u64,
+            program_clock_reference_base: ((packet[0] as u64) << 25) | ((packet[1] as u64) << 17) |
+                                          ((packet[2] as u64) << 9) |
+                                          (packet[3] as u64) << 1 |
+                                          ((packet[4] & 0b10000000) >> 7) as u64,
             reserved: packet[4] & 0b01111110,
             program_clock_reference_extension: ((packet[4] & 0b00000001) as u16) << 8 |
                                                packet[5] as u16,
         }
     }
 
+    /// PCR of the value in 27MHz with reserved bits set to 1
+    pub fn from_27mhz(pcr: u64) -> Self {
+        PCR {
+            program_clock_reference_base: pcr / 300 % (1 << 33),
+            reserved: 0b01111110,
+            program_clock_reference_extension: (pcr % 300) as u16,
+        }
+    }
+
+    /// Value in 27MHz, i.e. program_clock_reference_base * 300 +
+    /// program_clock_reference_extension
+    pub fn to_27mhz(&self) -> u64 {
+        self.program_clock_reference_base * 300 + self.program_clock_reference_extension as u64
+    }
+
     fn size() -> usize {
         6
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct OPCR {
     pub original_program_clock_reference_base: u64,
     pub reserved: u8,
@@ -229,11 +462,11 @@ pub struct OPCR {
 impl OPCR {
     fn new(packet: &[u8]) -> Self {
         OPCR {
-            original_program_clock_reference_base: ((packet[0] as u64) << 32) |
-                                                   ((packet[1] as u64) << 24) |
-                                                   ((packet[2] as u64) << 16) |
-                                                   (packet[3] as u64) << 8 |
-                                                   (packet[4] & 0b10000000) as u64,
+            original_program_clock_reference_base: ((packet[0] as u64) << 25) |
+                                                   ((packet[1] as u64) << 17) |
+                                                   ((packet[2] as u64) << 9) |
+                                                   (packet[3] as u64) << 1 |
+                                                   ((packet[4] & 0b10000000) >> 7) as u64,
             reserved: packet[4] & 0b01111110,
             original_program_clock_reference_extension: ((packet[4] & 0b00000001) as u16) << 8 |
                                                         packet[5] as u16,
@@ -245,7 +478,7 @@ impl OPCR {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct AdaptationFieldExtension<'a> {
     pub adaptation_field_extension_length: u8,
     pub reserved: u8,
@@ -256,17 +489,19 @@ pub struct AdaptationFieldExtension<'a> {
 }
 
 impl<'a> AdaptationFieldExtension<'a> {
-    fn new(packet: &'a [u8]) -> Self {
+    // packet ends at adaptation_field_extension_length.  None when any field runs past it.
+    fn new(packet: &'a [u8]) -> Option<Self> {
         let adaptation_field_extension_length = packet[0];
-        let ltw_flag = (packet[1] & 0b10000000) != 0;
-        let piecewise_rate_flag = (packet[1] & 0b01000000) != 0;
-        let seamless_splice_flag = (packet[1] & 0b00100000) != 0;
-        let reserved = packet[1] & 0b00011111;
+        let flags = *packet.get(1)?;
+        let ltw_flag = (flags & 0b10000000) != 0;
+        let piecewise_rate_flag = (flags & 0b01000000) != 0;
+        let seamless_splice_flag = (flags & 0b00100000) != 0;
+        let reserved = flags & 0b00011111;
 
         let mut index = 2;
 
         let ltw = if ltw_flag {
-            let ltw = LegalTimeWindow::new(&packet[index..]);
+            let ltw = LegalTimeWindow::new(packet.get(index..(index + LegalTimeWindow::size()))?);
             index += LegalTimeWindow::size();
             Some(ltw)
         } else {
@@ -274,9 +509,9 @@ impl<'a> AdaptationFieldExtension<'a> {
         };
 
         let piecewise_rate = if piecewise_rate_flag {
-            let rate = ((packet[index] & 0b00111111) as u32) << 16 |
-                       ((packet[index + 1] as u32) << 16) |
-                       (packet[index + 1] as u32);
+            let bytes = packet.get(index..(index + 3))?;
+            let rate = ((bytes[0] & 0b00111111) as u32) << 16 | ((bytes[1] as u32) << 8) |
+                       (bytes[2] as u32);
             index += 3;
             Some(rate)
         } else {
@@ -284,7 +519,7 @@ impl<'a> AdaptationFieldExtension<'a> {
         };
 
         let seamless_splice = if seamless_splice_flag {
-            let splice = SeamlessSplice::new(&packet[index..]);
+            let splice = SeamlessSplice::new(packet.get(index..(index + SeamlessSplice::size()))?);
             index += SeamlessSplice::size();
             Some(splice)
         } else {
@@ -293,18 +528,50 @@ impl<'a> AdaptationFieldExtension<'a> {
 
         let trailing_reserved = &packet[index..];
 
-        AdaptationFieldExtension {
+        Some(AdaptationFieldExtension {
             adaptation_field_extension_length: adaptation_field_extension_length,
             reserved: reserved,
             ltw: ltw,
             piecewise_rate: piecewise_rate,
             seamless_splice: seamless_splice,
             trailing_reserved: trailing_reserved,
+        })
+    }
+
+    fn write(&self, packet: &mut [u8]) {
+        packet[0] = self.adaptation_field_extension_length;
+        packet[1] = (self.ltw.is_some() as u8) << 7 | (self.piecewise_rate.is_some() as u8) << 6 |
+                    (self.seamless_splice.is_some() as u8) << 5 |
+                    self.reserved & 0b00011111;
+
+        let mut index = 2;
+        if let Some(ref ltw) = self.ltw {
+            packet[index] = (ltw.ltw_valid_flag as u8) << 7 | (ltw.ltw_offset >> 8) as u8 & 0b01111111;
+            packet[index + 1] = ltw.ltw_offset as u8;
+            index += LegalTimeWindow::size();
+        }
+        if let Some(rate) = self.piecewise_rate {
+            packet[index] = 0b11000000 | (rate >> 16) as u8 & 0b00111111;
+            packet[index + 1] = (rate >> 8) as u8;
+            packet[index + 2] = rate as u8;
+            index += 3;
+        }
+        if let Some(ref splice) = self.seamless_splice {
+            // Each part of DTS_next_AU is followed by marker_bit
+            let dts = splice.dts_next_au;
+            packet[index] = splice.splice_type << 4 | ((dts >> 30) as u8 & 0b111) << 1 | 1;
+            packet[index + 1] = (dts >> 22) as u8;
+            packet[index + 2] = (dts >> 14) as u8 & 0b11111110 | 1;
+            packet[index + 3] = (dts >> 7) as u8;
+            packet[index + 4] = (dts << 1) as u8 | 1;
+            index += SeamlessSplice::size();
         }
+        packet[index..(index + self.trailing_reserved.len())]
+            .copy_from_slice(self.trailing_reserved);
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct LegalTimeWindow {
     pub ltw_valid_flag: bool,
     pub ltw_offset: u16,
@@ -323,7 +590,7 @@ impl LegalTimeWindow {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct SeamlessSplice {
     pub splice_type: u8,
     pub dts_next_au: u64,
@@ -332,10 +599,10 @@ pub struct SeamlessSplice {
 impl SeamlessSplice {
     fn new(packet: &[u8]) -> Self {
         SeamlessSplice {
-            splice_type: packet[0] & 0b11110000,
-            dts_next_au: ((((packet[0] & 0b00001110) >> 1) as u64) << 30 |
-                          ((packet[1] >> 1) as u64) << 15 |
-                          ((packet[2] >> 1) as u64)),
+            splice_type: packet[0] >> 4,
+            dts_next_au: (((packet[0] & 0b00001110) >> 1) as u64) << 30 |
+                         ((packet[1] as u64) << 7 | (packet[2] >> 1) as u64) << 15 |
+                         ((packet[3] as u64) << 7 | (packet[4] >> 1) as u64),
         }
     }
 
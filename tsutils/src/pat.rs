@@ -1,6 +1,6 @@
 extern crate std;
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct ProgramAssociationTable {
     pub table_id: u8,
     pub transport_stream_id: u16,
@@ -16,6 +16,9 @@ impl ProgramAssociationTable {
     pub fn parse(payload: &[u8]) -> Result<Self, super::psi::ParseError> {
         // ISO/IEC 13818-1 2.4.4.1 Table 2-29
         // ISO/IEC 13818-1 2.4.4.2
+        if payload.is_empty() || payload.len() < 1 + payload[0] as usize + 8 {
+            return Err(super::psi::ParseError::InsufficientLength);
+        }
         let pointer_field = payload[0] as usize;
         let payload = &payload[(1 + pointer_field)..];
 
@@ -35,13 +38,18 @@ impl ProgramAssociationTable {
             return Err(super::psi::ParseError::IncorrectSectionSyntaxIndicator);
         }
         let section_length = ((payload[1] & 0b00001111) as usize) << 8 | payload[2] as usize;
+        // 5 bytes of the header after section_length and CRC_32
+        if section_length < 5 + 4 || payload.len() < 3 + section_length {
+            return Err(super::psi::ParseError::InsufficientLength);
+        }
         let transport_stream_id = ((payload[3] as u16) << 8) | payload[4] as u16;
         let version_number = (payload[5] & 0b00111110) >> 1;
         let current_next_indicator = (payload[5] & 0b00000001) != 0;
         let section_number = payload[6];
         let last_section_number = payload[7];
 
-        let n = (section_length - 5) / 4;
+        // Excluding CRC_32
+        let n = (section_length - 5 - 4) / 4;
         let mut program_map = std::collections::HashMap::new();
         for i in 0..n {
             let index = 8 + i * 4;
@@ -69,4 +77,28 @@ impl ProgramAssociationTable {
             crc32: crc32,
         })
     }
+
+    /// Serialize into a payload starting with pointer_field like the one parse() takes.
+    /// CRC_32 is calculated instead of taken from crc32.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut programs: Vec<_> = self.program_map.iter().map(|(&pid, &n)| (n, pid)).collect();
+        programs.sort();
+        let section_length = 5 + programs.len() * 4 + 4;
+        let mut section = vec![self.table_id,
+                               0b10110000 | (section_length >> 8) as u8 & 0b00001111,
+                               section_length as u8,
+                               (self.transport_stream_id >> 8) as u8,
+                               self.transport_stream_id as u8,
+                               0b11000000 | (self.version_number & 0b00011111) << 1 |
+                               self.current_next_indicator as u8,
+                               self.section_number,
+                               self.last_section_number];
+        for (program_number, pid) in programs {
+            section.extend_from_slice(&[(program_number >> 8) as u8,
+                                        program_number as u8,
+                                        0b11100000 | (pid >> 8) as u8 & 0b00011111,
+                                        pid as u8]);
+        }
+        super::psi::finish_section(section)
+    }
 }
@@ -0,0 +1,177 @@
+// 27MHz, base of 33 bits and extension of 9 bits
+const PCR_MODULO: u64 = (1 << 33) * 300;
+// ISO/IEC 13818-1 2.7.2 requires PCR at least every 100ms
+const MAX_INTERVAL: u64 = 27000000 / 10;
+// Larger jumps are treated as discontinuities
+const MAX_JUMP: u64 = 27000000 * 10;
+const ANALYZER: &str = "pcr";
+
+/// Measure PCR intervals and jitter of every PID carrying PCR.  The jitter is the difference
+/// between a PCR and the value extrapolated from the previous PCR by the packet count, assuming
+/// the average rate so far is constant.
+///
+/// Events: interval (data: interval in seconds over 100ms), discontinuity (data: the previous
+/// and current PCR in 27MHz, flagged by discontinuity_indicator or not) and summary per PID
+/// (data: pcrs, mean_interval and max_interval in seconds, max_jitter in nanoseconds).
+#[derive(Debug, Default)]
+pub struct JitterAnalyzer {
+    pids: std::collections::BTreeMap<u16, PidState>,
+}
+
+#[derive(Debug, Default)]
+struct PidState {
+    pcrs: u64,
+    // (packet index, PCR) of the first PCR since the last discontinuity
+    first: Option<(u64, u64)>,
+    last: Option<(u64, u64)>,
+    // Sum of PCR ticks since the first PCR
+    elapsed: u64,
+    intervals: u64,
+    interval_sum: u64,
+    max_interval: u64,
+    max_jitter: f64,
+}
+
+impl JitterAnalyzer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl super::report::Analyzer for JitterAnalyzer {
+    fn push(&mut self,
+            index: u64,
+            _buf: &[u8],
+            packet: Option<&super::TsPacket>,
+            reports: &mut Vec<super::report::Report>) {
+        let packet = match packet {
+            Some(packet) => packet,
+            None => return,
+        };
+        let af = match packet.adaptation_field {
+            Some(ref af) => af,
+            None => return,
+        };
+        let pcr = match af.pcr {
+            Some(ref pcr) => pcr.to_27mhz(),
+            None => return,
+        };
+        let state = self.pids.entry(packet.pid).or_default();
+        state.pcrs += 1;
+        if let Some((last_index, last_pcr)) = state.last {
+            let interval = (pcr + PCR_MODULO - last_pcr) % PCR_MODULO;
+            if af.discontinuity_indicator || interval > MAX_JUMP {
+                reports.push(super::report::Report::new(ANALYZER, "discontinuity")
+                    .packet(index)
+                    .pid(packet.pid)
+                    .field("previous", last_pcr)
+                    .field("current", pcr)
+                    .field("indicated", af.discontinuity_indicator));
+                state.first = Some((index, pcr));
+                state.elapsed = 0;
+            } else {
+                if let Some((first_index, _)) = state.first {
+                    if state.elapsed > 0 && last_index > first_index {
+                        let rate = state.elapsed as f64 / (last_index - first_index) as f64;
+                        let expected = (index - last_index) as f64 * rate;
+                        // 27MHz ticks into nanoseconds
+                        let jitter = (interval as f64 - expected).abs() * 1000.0 / 27.0;
+                        if jitter > state.max_jitter {
+                            state.max_jitter = jitter;
+                        }
+                    }
+                }
+                state.elapsed += interval;
+                state.intervals += 1;
+                state.interval_sum += interval;
+                if interval > state.max_interval {
+                    state.max_interval = interval;
+                }
+                if interval > MAX_INTERVAL {
+                    reports.push(super::report::Report::new(ANALYZER, "interval")
+                        .packet(index)
+                        .pid(packet.pid)
+                        .field("interval", interval as f64 / 27000000.0));
+                }
+            }
+        } else {
+            state.first = Some((index, pcr));
+        }
+        state.last = Some((index, pcr));
+    }
+
+    fn finish(&mut self, reports: &mut Vec<super::report::Report>) {
+        for (&pid, state) in &self.pids {
+            let mean_interval = if state.intervals == 0 {
+                0.0
+            } else {
+                state.interval_sum as f64 / state.intervals as f64 / 27000000.0
+            };
+            reports.push(super::report::Report::new(ANALYZER, "summary")
+                .pid(pid)
+                .field("pcrs", state.pcrs)
+                .field("mean_interval", mean_interval)
+                .field("max_interval", state.max_interval as f64 / 27000000.0)
+                .field("max_jitter", state.max_jitter));
+        }
+    }
+
+    fn checkpoint(&self) -> Option<serde_json::Value> {
+        let pids: Vec<_> = self.pids
+            .iter()
+            .map(|(&pid, state)| {
+                json!({
+                    "pid": pid,
+                    "pcrs": state.pcrs,
+                    "first": state.first.map(|(index, pcr)| [index, pcr]),
+                    "last": state.last.map(|(index, pcr)| [index, pcr]),
+                    "elapsed": state.elapsed,
+                    "intervals": state.intervals,
+                    "interval_sum": state.interval_sum,
+                    "max_interval": state.max_interval,
+                    "max_jitter": state.max_jitter,
+                })
+            })
+            .collect();
+        Some(pids.into())
+    }
+
+    fn restore(&mut self, state: &serde_json::Value) -> bool {
+        let pids = state.as_array().and_then(|pids| {
+            pids.iter()
+                .map(|state| {
+                    Some((state.get("pid")?.as_u64()? as u16, PidState::from_checkpoint(state)?))
+                })
+                .collect::<Option<_>>()
+        });
+        match pids {
+            Some(pids) => {
+                self.pids = pids;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl PidState {
+    // Inverse of JitterAnalyzer::checkpoint() for one PID
+    fn from_checkpoint(state: &serde_json::Value) -> Option<Self> {
+        let count = |name| state.get(name).and_then(|count| count.as_u64());
+        let pair = |name| match state.get(name) {
+            Some(&serde_json::Value::Null) => Some(None),
+            Some(pair) => Some(Some((pair.get(0)?.as_u64()?, pair.get(1)?.as_u64()?))),
+            None => None,
+        };
+        Some(PidState {
+            pcrs: count("pcrs")?,
+            first: pair("first")?,
+            last: pair("last")?,
+            elapsed: count("elapsed")?,
+            intervals: count("intervals")?,
+            interval_sum: count("interval_sum")?,
+            max_interval: count("max_interval")?,
+            max_jitter: state.get("max_jitter")?.as_f64()?,
+        })
+    }
+}
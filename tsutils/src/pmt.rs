@@ -1,4 +1,6 @@
-#[derive(Debug)]
+const STREAM_IDENTIFIER_DESCRIPTOR: u8 = 0x52;
+
+#[derive(Debug, PartialEq)]
 pub struct ProgramMapTable<'a> {
     pub table_id: u8,
     pub program_number: u16,
@@ -16,6 +18,9 @@ impl<'a> ProgramMapTable<'a> {
     pub fn parse(payload: &'a [u8]) -> Result<Self, super::psi::ParseError> {
         // ISO/IEC 13818-1 2.4.4.1 Table 2-29
         // ISO/IEC 13818-1 2.4.4.2
+        if payload.is_empty() || payload.len() < 1 + payload[0] as usize + 12 {
+            return Err(super::psi::ParseError::InsufficientLength);
+        }
         let pointer_field = payload[0] as usize;
         let payload = &payload[(1 + pointer_field)..];
 
@@ -33,6 +38,12 @@ impl<'a> ProgramMapTable<'a> {
             return Err(super::psi::ParseError::IncorrectSectionSyntaxIndicator);
         }
         let section_length = ((payload[1] & 0b00001111) as usize) << 8 | payload[2] as usize;
+        // 9 bytes of the header after section_length and CRC_32
+        if section_length < 9 + 4 || payload.len() < 3 + section_length {
+            return Err(super::psi::ParseError::InsufficientLength);
+        }
+        // Excluding CRC_32
+        let end = 3 + section_length - 4;
         let program_number = (payload[3] as u16) << 8 | payload[4] as u16;
         let version_number = (payload[5] & 0b00111110) >> 1;
         let current_next_indicator = (payload[5] & 0b00000001) != 0;
@@ -40,19 +51,28 @@ impl<'a> ProgramMapTable<'a> {
         let last_section_number = payload[7];
         let pcr_pid = ((payload[8] & 0b00011111) as u16) << 8 | payload[9] as u16;
         let program_info_length = ((payload[10] & 0b00001111) as usize) << 8 | payload[11] as usize;
+        if 12 + program_info_length > end {
+            return Err(super::psi::ParseError::InsufficientLength);
+        }
         let program_info = &payload[12..(12 + program_info_length)];
 
         let mut index = 12 + program_info_length;
         let mut es_info = vec![];
-        while index < 3 + section_length - 4 {
-            let info = EsInfo::new(&payload[index..]);
+        while index < end {
+            if index + 5 > end {
+                return Err(super::psi::ParseError::InsufficientLength);
+            }
+            let es_info_length = ((payload[index + 3] & 0b00001111) as usize) << 8 |
+                                 payload[index + 4] as usize;
+            if index + 5 + es_info_length > end {
+                return Err(super::psi::ParseError::InsufficientLength);
+            }
+            let info = EsInfo::new(&payload[index..end]);
             index += info.size();
             es_info.push(info);
         }
-        let crc32 = (payload[3 + section_length - 4] as u32) << 24 |
-                    (payload[3 + section_length - 3] as u32) << 16 |
-                    (payload[3 + section_length - 2] as u32) << 8 |
-                    (payload[3 + section_length - 1] as u32);
+        let crc32 = (payload[end] as u32) << 24 | (payload[end + 1] as u32) << 16 |
+                    (payload[end + 2] as u32) << 8 | (payload[end + 3] as u32);
 
         Ok(ProgramMapTable {
             table_id: table_id,
@@ -67,9 +87,40 @@ impl<'a> ProgramMapTable<'a> {
             crc32: crc32,
         })
     }
+
+    /// Serialize into a payload starting with pointer_field like the one parse() takes.
+    /// CRC_32 is calculated instead of taken from crc32.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let section_length = 9 + self.program_info.len() +
+                             self.es_info.iter().map(|es| es.size()).sum::<usize>() +
+                             4;
+        let mut section = vec![self.table_id,
+                               0b10110000 | (section_length >> 8) as u8 & 0b00001111,
+                               section_length as u8,
+                               (self.program_number >> 8) as u8,
+                               self.program_number as u8,
+                               0b11000000 | (self.version_number & 0b00011111) << 1 |
+                               self.current_next_indicator as u8,
+                               self.section_number,
+                               self.last_section_number,
+                               0b11100000 | (self.pcr_pid >> 8) as u8 & 0b00011111,
+                               self.pcr_pid as u8,
+                               0b11110000 | (self.program_info.len() >> 8) as u8 & 0b00001111,
+                               self.program_info.len() as u8];
+        section.extend_from_slice(self.program_info);
+        for es in &self.es_info {
+            section.extend_from_slice(&[es.stream_type,
+                                        0b11100000 | (es.elementary_pid >> 8) as u8 & 0b00011111,
+                                        es.elementary_pid as u8,
+                                        0b11110000 | (es.descriptor.len() >> 8) as u8 & 0b00001111,
+                                        es.descriptor.len() as u8]);
+            section.extend_from_slice(es.descriptor);
+        }
+        super::psi::finish_section(section)
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct EsInfo<'a> {
     pub stream_type: u8,
     pub elementary_pid: u16,
@@ -92,4 +143,24 @@ impl<'a> EsInfo<'a> {
     pub fn size(&self) -> usize {
         5 + self.descriptor.len()
     }
+
+    pub fn hierarchical_transmission_descriptor(&self) -> Option<super::descriptor::HierarchicalTransmissionDescriptor> {
+        super::descriptor::descriptors(self.descriptor)
+            .find(|&(tag, _)| tag == super::descriptor::HierarchicalTransmissionDescriptor::TAG)
+            .and_then(|(_, body)| super::descriptor::HierarchicalTransmissionDescriptor::parse(body))
+    }
+
+    pub fn video_decode_control_descriptor(&self) -> Option<super::descriptor::VideoDecodeControlDescriptor> {
+        super::descriptor::descriptors(self.descriptor)
+            .find(|&(tag, _)| tag == super::descriptor::VideoDecodeControlDescriptor::TAG)
+            .and_then(|(_, body)| super::descriptor::VideoDecodeControlDescriptor::parse(body))
+    }
+
+    /// component_tag of the stream_identifier_descriptor, which the descriptors in EIT such
+    /// as data_content_descriptor refer to
+    pub fn component_tag(&self) -> Option<u8> {
+        super::descriptor::descriptors(self.descriptor)
+            .find(|&(tag, _)| tag == STREAM_IDENTIFIER_DESCRIPTOR)
+            .and_then(|(_, body)| body.first().cloned())
+    }
 }
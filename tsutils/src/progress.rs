@@ -0,0 +1,165 @@
+// The callback is called every time this many bytes are read
+const REPORT_INTERVAL: u64 = 16 * 1024 * 1024;
+
+type Callback = Box<dyn FnMut(&Progress) + Send>;
+
+/// Flag shared with another thread, e.g. of a GUI or an admin API, to abort a long-running API
+/// cooperatively.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: std::sync::Arc<std::sync::atomic::AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, std::sync::atomic::Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(std::sync::atomic::Ordering::SeqCst)
+    }
+}
+
+/// Error returned by Watched reads after the cancellation
+#[derive(Debug)]
+pub struct Cancelled;
+
+impl std::fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// Whether the error was caused by the cancellation of Watched
+pub fn is_cancelled(e: &std::io::Error) -> bool {
+    e.get_ref().is_some_and(|inner| inner.is::<Cancelled>())
+}
+
+/// Progress passed to the callback of Watched
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Progress {
+    /// Bytes read so far
+    pub bytes: u64,
+    /// Size of the whole stream if known
+    pub total: Option<u64>,
+    pub elapsed: std::time::Duration,
+}
+
+impl Progress {
+    pub fn percent(&self) -> Option<f64> {
+        match self.total {
+            Some(total) if total > 0 => Some(self.bytes as f64 * 100.0 / total as f64),
+            _ => None,
+        }
+    }
+
+    /// Remaining time estimated from the speed so far
+    pub fn eta(&self) -> Option<std::time::Duration> {
+        let total = self.total?;
+        if self.bytes == 0 {
+            return None;
+        }
+        let remaining = total.saturating_sub(self.bytes) as f64;
+        Some(self.elapsed.mul_f64(remaining / self.bytes as f64))
+    }
+}
+
+/// Reader which reports its progress and fails with Cancelled once the token is cancelled.
+/// Wrap the reader given to any of the APIs reading a stream, e.g. filter::keep_av(),
+/// report::analyze(), demux::demux() or edit::edit(), which then return the error of
+/// Cancelled instead of reading the rest.
+pub struct Watched<R> {
+    inner: R,
+    bytes: u64,
+    total: Option<u64>,
+    started: std::time::Instant,
+    last_report: u64,
+    callback: Option<Callback>,
+    token: Option<CancellationToken>,
+}
+
+impl<R> Watched<R> {
+    pub fn new(inner: R) -> Self {
+        Watched {
+            inner: inner,
+            bytes: 0,
+            total: None,
+            started: std::time::Instant::now(),
+            last_report: 0,
+            callback: None,
+            token: None,
+        }
+    }
+
+    /// Size of the whole stream to calculate percent and ETA, e.g. the length of the file
+    pub fn total(mut self, total: u64) -> Self {
+        self.total = Some(total);
+        self
+    }
+
+    /// Call the callback every 16MiB and at the end of the stream.
+    pub fn on_progress<F>(mut self, callback: F) -> Self
+        where F: FnMut(&Progress) + Send + 'static
+    {
+        self.callback = Some(Box::new(callback));
+        self
+    }
+
+    pub fn cancellation(mut self, token: CancellationToken) -> Self {
+        self.token = Some(token);
+        self
+    }
+
+    pub fn progress(&self) -> Progress {
+        Progress {
+            bytes: self.bytes,
+            total: self.total,
+            elapsed: self.started.elapsed(),
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn report(&mut self) {
+        self.last_report = self.bytes;
+        let progress = self.progress();
+        if let Some(ref mut callback) = self.callback {
+            callback(&progress);
+        }
+    }
+}
+
+impl<R> std::io::Read for Watched<R>
+    where R: std::io::Read
+{
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.token.as_ref().is_some_and(|token| token.is_cancelled()) {
+            return Err(std::io::Error::other(Cancelled));
+        }
+        let n = self.inner.read(buf)?;
+        self.bytes += n as u64;
+        if (n == 0 && self.last_report != self.bytes) ||
+           self.bytes - self.last_report >= REPORT_INTERVAL {
+            self.report();
+        }
+        Ok(n)
+    }
+}
+
+impl<R> std::io::Seek for Watched<R>
+    where R: std::io::Seek
+{
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        self.bytes = self.inner.seek(pos)?;
+        self.last_report = self.bytes;
+        Ok(self.bytes)
+    }
+}
@@ -2,4 +2,136 @@
 pub enum ParseError {
     IncorrectTableId { expected: u8, actual: u8 },
     IncorrectSectionSyntaxIndicator,
+    InsufficientLength,
+}
+
+/// Assemble sections from the packets of one PID.  Unlike the payload accumulated until the
+/// next payload_unit_start_indicator, each section starts with table_id and ends with CRC_32,
+/// and multiple sections in one packet are split.
+#[derive(Default)]
+pub struct SectionAssembler {
+    buf: Vec<u8>,
+    started: bool,
+}
+
+impl SectionAssembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, packet: &super::TsPacket) -> Vec<Vec<u8>> {
+        let mut sections = vec![];
+        if packet.payload_unit_start_indicator {
+            let start = match (packet.section_continuation(), packet.section_start()) {
+                (Some(continuation), Some(start)) => {
+                    if self.started {
+                        self.buf.extend_from_slice(continuation);
+                        self.drain_sections(&mut sections);
+                    }
+                    start
+                }
+                _ => {
+                    self.buf.clear();
+                    self.started = false;
+                    return sections;
+                }
+            };
+            self.buf.clear();
+            self.buf.extend_from_slice(start);
+            self.started = true;
+        } else if self.started {
+            match packet.data_bytes {
+                Some(data_bytes) => self.buf.extend_from_slice(data_bytes),
+                None => return sections,
+            }
+        }
+        self.drain_sections(&mut sections);
+        sections
+    }
+
+    /// The partial section for checkpoint::Checkpoint
+    pub fn checkpoint(&self) -> serde_json::Value {
+        json!({
+            "buf": super::checkpoint::hex(&self.buf),
+            "started": self.started,
+        })
+    }
+
+    pub fn restore(state: &serde_json::Value) -> Option<Self> {
+        Some(SectionAssembler {
+            buf: super::checkpoint::unhex(state.get("buf")?)?,
+            started: state.get("started")?.as_bool()?,
+        })
+    }
+
+    fn drain_sections(&mut self, sections: &mut Vec<Vec<u8>>) {
+        while self.buf.len() >= 3 {
+            if self.buf[0] == 0xff {
+                // stuffing_byte
+                self.buf.clear();
+                self.started = false;
+                break;
+            }
+            let section_length = ((self.buf[1] & 0b00001111) as usize) << 8 |
+                                 self.buf[2] as usize;
+            if self.buf.len() < 3 + section_length {
+                break;
+            }
+            let rest = self.buf.split_off(3 + section_length);
+            sections.push(std::mem::replace(&mut self.buf, rest));
+        }
+    }
+}
+
+/// CRC_32 of sections (ISO/IEC 13818-1 Annex A), which is 0 over a whole section including
+/// its CRC_32 field.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffffffffu32;
+    for &b in data {
+        crc ^= (b as u32) << 24;
+        for _ in 0..8 {
+            crc = if crc & 0x80000000 != 0 {
+                crc << 1 ^ 0x04c11db7
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+// Append CRC_32 to the section and prepend pointer_field
+pub(crate) fn finish_section(mut section: Vec<u8>) -> Vec<u8> {
+    let crc = crc32(&section);
+    section.extend_from_slice(&[(crc >> 24) as u8, (crc >> 16) as u8, (crc >> 8) as u8, crc as u8]);
+    section.insert(0, 0);
+    section
+}
+
+/// Write a payload starting with pointer_field, e.g. the one serialized by to_bytes() of the
+/// tables, into packets of the PID.  The rest of the last packet is filled with stuffing bytes.
+pub fn write_payload<W>(writer: &mut W,
+                        pid: u16,
+                        continuity_counter: &mut u8,
+                        payload: &[u8])
+                        -> std::io::Result<()>
+    where W: std::io::Write
+{
+    for (i, chunk) in payload.chunks(184).enumerate() {
+        let packet = super::TsPacket {
+            sync_byte: 0x47,
+            transport_error_indicator: false,
+            payload_unit_start_indicator: i == 0,
+            transport_priority: false,
+            pid: pid,
+            transport_scrambling_control: 0,
+            adaptation_field_control: 0b01,
+            continuity_counter: *continuity_counter,
+            adaptation_field: None,
+            data_bytes: Some(chunk),
+        };
+        writer.write_all(&packet.to_bytes())?;
+        *continuity_counter = (*continuity_counter + 1) & 0b00001111;
+    }
+    Ok(())
 }
@@ -0,0 +1,136 @@
+/// An event found by an analyzer.  Every analyzer writes the same JSON Lines schema so that
+/// dashboards can ingest any of them:
+///
+/// ```text
+/// {"analyzer":"integrity","event":"drop","packet":1234,"pid":256,"data":{"expected":3,"actual":5}}
+/// ```
+///
+/// packet is the index of the 188-byte packet where the event was found and pid is the PID it
+/// concerns; both are omitted for events about the whole stream such as summaries.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    pub analyzer: &'static str,
+    pub event: &'static str,
+    pub packet: Option<u64>,
+    pub pid: Option<u16>,
+    pub data: serde_json::Map<String, serde_json::Value>,
+}
+
+impl Report {
+    pub fn new(analyzer: &'static str, event: &'static str) -> Self {
+        Report {
+            analyzer: analyzer,
+            event: event,
+            packet: None,
+            pid: None,
+            data: serde_json::Map::new(),
+        }
+    }
+
+    pub fn packet(mut self, index: u64) -> Self {
+        self.packet = Some(index);
+        self
+    }
+
+    pub fn pid(mut self, pid: u16) -> Self {
+        self.pid = Some(pid);
+        self
+    }
+
+    pub fn field<V>(mut self, name: &str, value: V) -> Self
+        where V: Into<serde_json::Value>
+    {
+        self.data.insert(name.to_owned(), value.into());
+        self
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        let mut object = serde_json::Map::new();
+        object.insert("analyzer".to_owned(), self.analyzer.into());
+        object.insert("event".to_owned(), self.event.into());
+        if let Some(packet) = self.packet {
+            object.insert("packet".to_owned(), packet.into());
+        }
+        if let Some(pid) = self.pid {
+            object.insert("pid".to_owned(), pid.into());
+        }
+        object.insert("data".to_owned(), serde_json::Value::Object(self.data.clone()));
+        serde_json::Value::Object(object)
+    }
+
+    /// Write the report as a line of JSON Lines
+    pub fn write_line<W>(&self, mut writer: W) -> Result<(), std::io::Error>
+        where W: std::io::Write
+    {
+        serde_json::to_writer(&mut writer, &self.to_json())?;
+        writer.write_all(b"\n")
+    }
+}
+
+/// Something which inspects packets one by one and reports events.
+pub trait Analyzer {
+    /// packet is None when the packet is broken (wrong sync_byte, transport_error_indicator or
+    /// too long adaptation_field_length) and its fields cannot be trusted.
+    fn push(&mut self,
+            index: u64,
+            buf: &[u8],
+            packet: Option<&super::TsPacket>,
+            reports: &mut Vec<Report>);
+
+    /// Report the summary after the last packet
+    fn finish(&mut self, reports: &mut Vec<Report>);
+
+    /// State after the last pushed packet saved by checkpoint::analyze(), or None when the
+    /// analyzer does not support checkpoints
+    fn checkpoint(&self) -> Option<serde_json::Value> {
+        None
+    }
+
+    /// Restore the state returned by checkpoint().  Return false when the state is not the one
+    /// of this analyzer.
+    fn restore(&mut self, _state: &serde_json::Value) -> bool {
+        false
+    }
+}
+
+/// Run the analyzers over the whole stream and pass each report to the callback as soon as it
+/// is found.
+pub fn analyze<R, F>(reader: R,
+                     analyzers: &mut [&mut dyn Analyzer],
+                     mut callback: F)
+                     -> Result<(), std::io::Error>
+    where R: std::io::Read,
+          F: FnMut(Report) -> Result<(), std::io::Error>
+{
+    let mut reports = vec![];
+    for (index, buf) in super::packet::ts_packets(reader).enumerate() {
+        let buf = buf?;
+        let packet = if is_broken(&buf) {
+            None
+        } else {
+            Some(super::TsPacket::new(&buf))
+        };
+        for analyzer in analyzers.iter_mut() {
+            analyzer.push(index as u64, &buf, packet.as_ref(), &mut reports);
+        }
+        for report in reports.drain(..) {
+            callback(report)?;
+        }
+    }
+    for analyzer in analyzers.iter_mut() {
+        analyzer.finish(&mut reports);
+    }
+    for report in reports {
+        callback(report)?;
+    }
+    Ok(())
+}
+
+/// Whether TsPacket::new cannot be trusted or would panic on the packet
+pub fn is_broken(buf: &[u8]) -> bool {
+    if buf[0] != 0x47 || (buf[1] & 0b10000000) != 0 {
+        return true;
+    }
+    let adaptation_field_control = (buf[3] & 0b00110000) >> 4;
+    (adaptation_field_control == 0b10 || adaptation_field_control == 0b11) && buf[4] > 183
+}
@@ -0,0 +1,52 @@
+/// Running Status Table, which tells when events start or stop earlier than EIT says
+#[derive(Debug)]
+pub struct RunningStatusTable {
+    pub table_id: u8,
+    pub statuses: Vec<RunningStatus>,
+}
+
+impl RunningStatusTable {
+    /// Parse a section assembled by psi::SectionAssembler.
+    pub fn parse(section: &[u8]) -> Result<Self, super::psi::ParseError> {
+        // ARIB STD-B10 Part 2 5.2.10
+        if section.len() < 3 {
+            return Err(super::psi::ParseError::InsufficientLength);
+        }
+        let table_id = section[0];
+        if table_id != 0x71 {
+            return Err(super::psi::ParseError::IncorrectTableId {
+                expected: 0x71,
+                actual: table_id,
+            });
+        }
+        let section_length = ((section[1] & 0b00001111) as usize) << 8 | section[2] as usize;
+        if section.len() < 3 + section_length || !section_length.is_multiple_of(9) {
+            return Err(super::psi::ParseError::InsufficientLength);
+        }
+        let statuses = section[3..(3 + section_length)]
+            .chunks(9)
+            .map(|status| {
+                RunningStatus {
+                    transport_stream_id: (status[0] as u16) << 8 | status[1] as u16,
+                    original_network_id: (status[2] as u16) << 8 | status[3] as u16,
+                    service_id: (status[4] as u16) << 8 | status[5] as u16,
+                    event_id: (status[6] as u16) << 8 | status[7] as u16,
+                    running_status: status[8] & 0b00000111,
+                }
+            })
+            .collect();
+        Ok(RunningStatusTable {
+            table_id: table_id,
+            statuses: statuses,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct RunningStatus {
+    pub transport_stream_id: u16,
+    pub original_network_id: u16,
+    pub service_id: u16,
+    pub event_id: u16,
+    pub running_status: u8,
+}
@@ -0,0 +1,124 @@
+// running_status of EIT and RST (ARIB STD-B10 Part 2 5.2.7 Table 5-6)
+const NOT_RUNNING: u8 = 1;
+const STARTS_IN_A_FEW_SECONDS: u8 = 2;
+const RUNNING: u8 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransitionKind {
+    Started,
+    Ended,
+}
+
+/// Change of the event on air of a service
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transition {
+    /// Index of the packet completing the section which told the change
+    pub packet: u64,
+    pub original_network_id: u16,
+    pub service_id: u16,
+    pub event_id: u16,
+    pub kind: TransitionKind,
+}
+
+/// Follow the present event of EIT[p/f] and RST of each service to find when events actually
+/// start and end, e.g. a drama delayed by an overrunning baseball game.  Nothing is reported
+/// for the event already on air when a service is found for the first time.
+#[derive(Default)]
+pub struct EventTracker {
+    eit_assembler: super::psi::SectionAssembler,
+    rst_assembler: super::psi::SectionAssembler,
+    // The event on air keyed by (original_network_id, service_id)
+    present: std::collections::HashMap<(u16, u16), Option<u16>>,
+}
+
+impl EventTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The event on air of the service
+    pub fn present(&self, original_network_id: u16, service_id: u16) -> Option<u16> {
+        self.present.get(&(original_network_id, service_id)).and_then(|&event_id| event_id)
+    }
+
+    /// Push the packet at the index of the stream and return the transitions it completes.
+    pub fn push(&mut self, index: u64, packet: &super::TsPacket) -> Vec<Transition> {
+        let mut transitions = vec![];
+        match packet.pid {
+            0x0012 => {
+                for section in self.eit_assembler.push(packet) {
+                    let eit = match super::EventInformationTable::parse(&section) {
+                        Ok(eit) => eit,
+                        Err(_) => continue,
+                    };
+                    // Section 0 of EIT[p/f actual] and EIT[p/f other] has the present event
+                    if (eit.table_id != 0x4e && eit.table_id != 0x4f) || eit.section_number != 0 {
+                        continue;
+                    }
+                    let present = eit.events
+                        .first()
+                        .filter(|event| {
+                            event.running_status != NOT_RUNNING &&
+                            event.running_status != STARTS_IN_A_FEW_SECONDS
+                        })
+                        .map(|event| event.event_id);
+                    self.set_present(index,
+                                     (eit.original_network_id, eit.service_id),
+                                     present,
+                                     &mut transitions);
+                }
+            }
+            0x0013 => {
+                for section in self.rst_assembler.push(packet) {
+                    let rst = match super::rst::RunningStatusTable::parse(&section) {
+                        Ok(rst) => rst,
+                        Err(_) => continue,
+                    };
+                    for status in &rst.statuses {
+                        let key = (status.original_network_id, status.service_id);
+                        match status.running_status {
+                            RUNNING => {
+                                self.set_present(index, key, Some(status.event_id), &mut transitions)
+                            }
+                            NOT_RUNNING if self.present(key.0, key.1) == Some(status.event_id) => {
+                                self.set_present(index, key, None, &mut transitions)
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+        transitions
+    }
+
+    fn set_present(&mut self,
+                   index: u64,
+                   key: (u16, u16),
+                   present: Option<u16>,
+                   transitions: &mut Vec<Transition>) {
+        let previous = match self.present.insert(key, present) {
+            Some(previous) => previous,
+            None => return,
+        };
+        if previous == present {
+            return;
+        }
+        let transition = |event_id, kind| {
+            Transition {
+                packet: index,
+                original_network_id: key.0,
+                service_id: key.1,
+                event_id: event_id,
+                kind: kind,
+            }
+        };
+        if let Some(event_id) = previous {
+            transitions.push(transition(event_id, TransitionKind::Ended));
+        }
+        if let Some(event_id) = present {
+            transitions.push(transition(event_id, TransitionKind::Started));
+        }
+    }
+}
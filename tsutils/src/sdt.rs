@@ -0,0 +1,247 @@
+#[derive(Debug)]
+pub struct ServiceDescriptionTable<'a> {
+    pub table_id: u8,
+    pub transport_stream_id: u16,
+    pub version_number: u8,
+    pub current_next_indicator: bool,
+    pub section_number: u8,
+    pub last_section_number: u8,
+    pub original_network_id: u16,
+    pub services: Vec<Service<'a>>,
+    pub crc32: u32,
+}
+
+impl<'a> ServiceDescriptionTable<'a> {
+    /// Parse a section assembled by psi::SectionAssembler.
+    pub fn parse(section: &'a [u8]) -> Result<Self, super::psi::ParseError> {
+        // ARIB STD-B10 Part 2 5.2.6
+        if section.len() < 15 {
+            return Err(super::psi::ParseError::InsufficientLength);
+        }
+        let table_id = section[0];
+        if table_id != 0x42 && table_id != 0x46 {
+            return Err(super::psi::ParseError::IncorrectTableId {
+                expected: 0x42,
+                actual: table_id,
+            });
+        }
+        let section_syntax_indicator = (section[1] & 0b10000000) != 0;
+        if !section_syntax_indicator {
+            return Err(super::psi::ParseError::IncorrectSectionSyntaxIndicator);
+        }
+        let section_length = ((section[1] & 0b00001111) as usize) << 8 | section[2] as usize;
+        if section.len() < 3 + section_length || section_length < 12 {
+            return Err(super::psi::ParseError::InsufficientLength);
+        }
+        let transport_stream_id = (section[3] as u16) << 8 | section[4] as u16;
+        let version_number = (section[5] & 0b00111110) >> 1;
+        let current_next_indicator = (section[5] & 0b00000001) != 0;
+        let section_number = section[6];
+        let last_section_number = section[7];
+        let original_network_id = (section[8] as u16) << 8 | section[9] as u16;
+
+        let end = 3 + section_length - 4;
+        let mut index = 11;
+        let mut services = vec![];
+        while index < end {
+            if index + 5 > end {
+                return Err(super::psi::ParseError::InsufficientLength);
+            }
+            let service = Service::new(&section[index..end])?;
+            index += service.size();
+            services.push(service);
+        }
+        let crc32 = (section[end] as u32) << 24 | (section[end + 1] as u32) << 16 |
+                    (section[end + 2] as u32) << 8 |
+                    (section[end + 3] as u32);
+
+        Ok(ServiceDescriptionTable {
+            table_id: table_id,
+            transport_stream_id: transport_stream_id,
+            version_number: version_number,
+            current_next_indicator: current_next_indicator,
+            section_number: section_number,
+            last_section_number: last_section_number,
+            original_network_id: original_network_id,
+            services: services,
+            crc32: crc32,
+        })
+    }
+
+    /// SDT actual
+    pub fn is_actual(&self) -> bool {
+        self.table_id == 0x42
+    }
+}
+
+#[derive(Debug)]
+pub struct Service<'a> {
+    pub service_id: u16,
+    pub eit_user_defined_flags: u8,
+    pub eit_schedule_flag: bool,
+    pub eit_present_following_flag: bool,
+    pub running_status: u8,
+    pub free_ca_mode: bool,
+    pub descriptors: &'a [u8],
+}
+
+impl<'a> Service<'a> {
+    fn new(payload: &'a [u8]) -> Result<Self, super::psi::ParseError> {
+        let service_id = (payload[0] as u16) << 8 | payload[1] as u16;
+        let eit_user_defined_flags = (payload[2] & 0b00011100) >> 2;
+        let eit_schedule_flag = (payload[2] & 0b00000010) != 0;
+        let eit_present_following_flag = (payload[2] & 0b00000001) != 0;
+        let running_status = (payload[3] & 0b11100000) >> 5;
+        let free_ca_mode = (payload[3] & 0b00010000) != 0;
+        let descriptors_loop_length = ((payload[3] & 0b00001111) as usize) << 8 |
+                                      payload[4] as usize;
+        if payload.len() < 5 + descriptors_loop_length {
+            return Err(super::psi::ParseError::InsufficientLength);
+        }
+        let descriptors = &payload[5..(5 + descriptors_loop_length)];
+        Ok(Service {
+            service_id: service_id,
+            eit_user_defined_flags: eit_user_defined_flags,
+            eit_schedule_flag: eit_schedule_flag,
+            eit_present_following_flag: eit_present_following_flag,
+            running_status: running_status,
+            free_ca_mode: free_ca_mode,
+            descriptors: descriptors,
+        })
+    }
+
+    pub fn size(&self) -> usize {
+        5 + self.descriptors.len()
+    }
+
+    pub fn service_descriptor(&self) -> Option<super::descriptor::ServiceDescriptor<'a>> {
+        super::descriptor::descriptors(self.descriptors)
+            .find(|&(tag, _)| tag == super::descriptor::ServiceDescriptor::TAG)
+            .and_then(|(_, body)| super::descriptor::ServiceDescriptor::parse(body))
+    }
+
+    /// service_type of the service_descriptor
+    pub fn service_type(&self) -> Option<ServiceType> {
+        self.service_descriptor().map(|descriptor| ServiceType::from(descriptor.service_type))
+    }
+}
+
+/// service_type of service_descriptor
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ServiceType {
+    DigitalTelevision,
+    DigitalAudio,
+    /// 臨時映像サービス
+    TemporaryVideo,
+    /// 臨時音声サービス
+    TemporaryAudio,
+    /// 臨時データサービス
+    TemporaryData,
+    Engineering,
+    PromotionVideo,
+    PromotionAudio,
+    PromotionData,
+    /// 事前蓄積用データサービス
+    PreAccumulationData,
+    /// 蓄積専用データサービス
+    AccumulationData,
+    BookmarkListData,
+    ServerTypeSimultaneous,
+    IndependentFile,
+    UltraHighDefinitionTelevision,
+    Data,
+    Other(u8),
+}
+
+impl From<u8> for ServiceType {
+    fn from(service_type: u8) -> Self {
+        // ARIB STD-B10 Part 2 6.2.13 and Part 1 Annex H
+        match service_type {
+            0x01 => ServiceType::DigitalTelevision,
+            0x02 => ServiceType::DigitalAudio,
+            0xa1 => ServiceType::TemporaryVideo,
+            0xa2 => ServiceType::TemporaryAudio,
+            0xa3 => ServiceType::TemporaryData,
+            0xa4 => ServiceType::Engineering,
+            0xa5 => ServiceType::PromotionVideo,
+            0xa6 => ServiceType::PromotionAudio,
+            0xa7 => ServiceType::PromotionData,
+            0xa8 => ServiceType::PreAccumulationData,
+            0xa9 => ServiceType::AccumulationData,
+            0xaa => ServiceType::BookmarkListData,
+            0xab => ServiceType::ServerTypeSimultaneous,
+            0xac => ServiceType::IndependentFile,
+            0xad => ServiceType::UltraHighDefinitionTelevision,
+            0xc0 => ServiceType::Data,
+            _ => ServiceType::Other(service_type),
+        }
+    }
+}
+
+impl From<ServiceType> for u8 {
+    fn from(service_type: ServiceType) -> Self {
+        match service_type {
+            ServiceType::DigitalTelevision => 0x01,
+            ServiceType::DigitalAudio => 0x02,
+            ServiceType::TemporaryVideo => 0xa1,
+            ServiceType::TemporaryAudio => 0xa2,
+            ServiceType::TemporaryData => 0xa3,
+            ServiceType::Engineering => 0xa4,
+            ServiceType::PromotionVideo => 0xa5,
+            ServiceType::PromotionAudio => 0xa6,
+            ServiceType::PromotionData => 0xa7,
+            ServiceType::PreAccumulationData => 0xa8,
+            ServiceType::AccumulationData => 0xa9,
+            ServiceType::BookmarkListData => 0xaa,
+            ServiceType::ServerTypeSimultaneous => 0xab,
+            ServiceType::IndependentFile => 0xac,
+            ServiceType::UltraHighDefinitionTelevision => 0xad,
+            ServiceType::Data => 0xc0,
+            ServiceType::Other(service_type) => service_type,
+        }
+    }
+}
+
+impl ServiceType {
+    /// Services with video, including temporary and promotion ones
+    pub fn is_television(&self) -> bool {
+        matches!(*self,
+                 ServiceType::DigitalTelevision |
+                 ServiceType::TemporaryVideo |
+                 ServiceType::PromotionVideo |
+                 ServiceType::UltraHighDefinitionTelevision)
+    }
+
+    /// Services with audio only, including temporary and promotion ones
+    pub fn is_radio(&self) -> bool {
+        matches!(*self,
+                 ServiceType::DigitalAudio |
+                 ServiceType::TemporaryAudio |
+                 ServiceType::PromotionAudio)
+    }
+
+    /// Data broadcasting services without video nor audio
+    pub fn is_data(&self) -> bool {
+        matches!(*self,
+                 ServiceType::TemporaryData |
+                 ServiceType::PromotionData |
+                 ServiceType::PreAccumulationData |
+                 ServiceType::AccumulationData |
+                 ServiceType::BookmarkListData |
+                 ServiceType::ServerTypeSimultaneous |
+                 ServiceType::IndependentFile |
+                 ServiceType::Data)
+    }
+
+    /// 臨時サービス, broadcast only for a while, e.g. during a sports program overrunning
+    pub fn is_temporary(&self) -> bool {
+        matches!(*self,
+                 ServiceType::TemporaryVideo |
+                 ServiceType::TemporaryAudio |
+                 ServiceType::TemporaryData)
+    }
+
+    pub fn is_engineering(&self) -> bool {
+        *self == ServiceType::Engineering
+    }
+}
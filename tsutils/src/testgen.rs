@@ -0,0 +1,329 @@
+use proptest::prelude::*;
+
+/// Owned fields of a packet generated by packet().  Packets borrow their bytes, so build one
+/// with PacketSpec::packet().
+#[derive(Debug, Clone)]
+pub struct PacketSpec {
+    pub transport_error_indicator: bool,
+    pub payload_unit_start_indicator: bool,
+    pub transport_priority: bool,
+    pub pid: u16,
+    pub transport_scrambling_control: u8,
+    pub adaptation_field_control: u8,
+    pub continuity_counter: u8,
+    pub adaptation_field: Option<AdaptationFieldSpec>,
+    pub data_bytes: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AdaptationFieldSpec {
+    pub adaptation_field_length: u8,
+    pub discontinuity_indicator: bool,
+    pub random_access_indicator: bool,
+    pub elementary_stream_priority_indicator: bool,
+    /// (base, reserved, extension)
+    pub pcr: Option<(u64, u8, u16)>,
+    pub opcr: Option<(u64, u8, u16)>,
+    pub splice_countdown: Option<i8>,
+    pub transport_private_data: Option<Vec<u8>>,
+    pub adaptation_field_extension: Option<ExtensionSpec>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ExtensionSpec {
+    pub reserved: u8,
+    /// (ltw_valid_flag, ltw_offset)
+    pub ltw: Option<(bool, u16)>,
+    pub piecewise_rate: Option<u32>,
+    /// (splice_type, DTS_next_AU)
+    pub seamless_splice: Option<(u8, u64)>,
+    pub trailing_reserved: Vec<u8>,
+}
+
+impl PacketSpec {
+    pub fn packet<'a>(&'a self) -> super::TsPacket<'a> {
+        super::TsPacket {
+            sync_byte: 0x47,
+            transport_error_indicator: self.transport_error_indicator,
+            payload_unit_start_indicator: self.payload_unit_start_indicator,
+            transport_priority: self.transport_priority,
+            pid: self.pid,
+            transport_scrambling_control: self.transport_scrambling_control,
+            adaptation_field_control: self.adaptation_field_control,
+            continuity_counter: self.continuity_counter,
+            adaptation_field: self.adaptation_field.as_ref().map(|af| af.adaptation_field()),
+            data_bytes: self.data_bytes.as_deref(),
+        }
+    }
+}
+
+impl AdaptationFieldSpec {
+    fn adaptation_field<'a>(&'a self) -> super::packet::AdaptationField<'a> {
+        super::packet::AdaptationField {
+            adaptation_field_length: self.adaptation_field_length,
+            discontinuity_indicator: self.discontinuity_indicator,
+            random_access_indicator: self.random_access_indicator,
+            elementary_stream_priority_indicator: self.elementary_stream_priority_indicator,
+            transport_private_data_flag: self.transport_private_data.is_some(),
+            pcr: self.pcr.map(|(base, reserved, extension)| {
+                super::packet::PCR {
+                    program_clock_reference_base: base,
+                    reserved: reserved,
+                    program_clock_reference_extension: extension,
+                }
+            }),
+            opcr: self.opcr.map(|(base, reserved, extension)| {
+                super::packet::OPCR {
+                    original_program_clock_reference_base: base,
+                    reserved: reserved,
+                    original_program_clock_reference_extension: extension,
+                }
+            }),
+            splice_countdown: self.splice_countdown,
+            transport_private_data: self.transport_private_data.as_deref(),
+            adaptation_field_extension: self.adaptation_field_extension
+                .as_ref()
+                .map(|extension| extension.extension()),
+        }
+    }
+
+    // Bytes before stuffing_byte
+    fn min_length(&self) -> usize {
+        1 + self.pcr.map(|_| 6).unwrap_or(0) + self.opcr.map(|_| 6).unwrap_or(0) +
+        self.splice_countdown.map(|_| 1).unwrap_or(0) +
+        self.transport_private_data.as_ref().map(|data| 1 + data.len()).unwrap_or(0) +
+        self.adaptation_field_extension
+            .as_ref()
+            .map(|extension| 1 + extension.length())
+            .unwrap_or(0)
+    }
+}
+
+impl ExtensionSpec {
+    fn extension<'a>(&'a self) -> super::packet::AdaptationFieldExtension<'a> {
+        super::packet::AdaptationFieldExtension {
+            adaptation_field_extension_length: self.length() as u8,
+            reserved: self.reserved,
+            ltw: self.ltw.map(|(valid, offset)| {
+                super::packet::LegalTimeWindow {
+                    ltw_valid_flag: valid,
+                    ltw_offset: offset,
+                }
+            }),
+            piecewise_rate: self.piecewise_rate,
+            seamless_splice: self.seamless_splice.map(|(splice_type, dts)| {
+                super::packet::SeamlessSplice {
+                    splice_type: splice_type,
+                    dts_next_au: dts,
+                }
+            }),
+            trailing_reserved: &self.trailing_reserved,
+        }
+    }
+
+    // adaptation_field_extension_length
+    fn length(&self) -> usize {
+        1 + self.ltw.map(|_| 2).unwrap_or(0) + self.piecewise_rate.map(|_| 3).unwrap_or(0) +
+        self.seamless_splice.map(|_| 5).unwrap_or(0) + self.trailing_reserved.len()
+    }
+}
+
+// 33-bit base, 6-bit reserved kept in place and 9-bit extension
+fn clock_reference() -> impl Strategy<Value = (u64, u8, u16)> {
+    (0..(1u64 << 33), any::<u8>(), 0..(1u16 << 9))
+        .prop_map(|(base, reserved, extension)| (base, reserved & 0b01111110, extension))
+}
+
+fn extension() -> impl Strategy<Value = ExtensionSpec> {
+    (0..(1u8 << 5),
+     proptest::option::of((any::<bool>(), 0..(1u16 << 15))),
+     proptest::option::of(0..(1u32 << 22)),
+     proptest::option::of((0..(1u8 << 4), 0..(1u64 << 33))),
+     proptest::collection::vec(any::<u8>(), 0..32))
+        .prop_map(|(reserved, ltw, piecewise_rate, seamless_splice, trailing_reserved)| {
+            ExtensionSpec {
+                reserved: reserved,
+                ltw: ltw,
+                piecewise_rate: piecewise_rate,
+                seamless_splice: seamless_splice,
+                trailing_reserved: trailing_reserved,
+            }
+        })
+}
+
+/// Adaptation field of the minimum length, which packet() extends with stuffing_byte
+fn adaptation_field() -> impl Strategy<Value = AdaptationFieldSpec> {
+    (any::<(bool, bool, bool)>(),
+     proptest::option::of(clock_reference()),
+     proptest::option::of(clock_reference()),
+     proptest::option::of(any::<i8>()),
+     proptest::option::of(proptest::collection::vec(any::<u8>(), 0..64)),
+     proptest::option::of(extension()))
+        .prop_map(|((discontinuity, random_access, priority),
+                    pcr,
+                    opcr,
+                    splice_countdown,
+                    transport_private_data,
+                    adaptation_field_extension)| {
+            let mut af = AdaptationFieldSpec {
+                adaptation_field_length: 0,
+                discontinuity_indicator: discontinuity,
+                random_access_indicator: random_access,
+                elementary_stream_priority_indicator: priority,
+                pcr: pcr,
+                opcr: opcr,
+                splice_countdown: splice_countdown,
+                transport_private_data: transport_private_data,
+                adaptation_field_extension: adaptation_field_extension,
+            };
+            af.adaptation_field_length = af.min_length() as u8;
+            af
+        })
+}
+
+/// Valid 188-byte packets whose adaptation field and data_bytes fill the packet exactly
+pub fn packet() -> impl Strategy<Value = PacketSpec> {
+    (any::<(bool, bool, bool)>(),
+     0..(1u16 << 13),
+     0..4u8,
+     1..4u8,
+     0..16u8,
+     proptest::option::of(adaptation_field()),
+     0..184usize,
+     proptest::collection::vec(any::<u8>(), 184))
+        .prop_map(|((error, start, priority), pid, scrambling, control, cc, af, stuffing, data)| {
+            let mut spec = PacketSpec {
+                transport_error_indicator: error,
+                payload_unit_start_indicator: start,
+                transport_priority: priority,
+                pid: pid,
+                transport_scrambling_control: scrambling,
+                adaptation_field_control: control,
+                continuity_counter: cc,
+                adaptation_field: None,
+                data_bytes: None,
+            };
+            let mut data = data;
+            match control {
+                0b01 => spec.data_bytes = Some(data),
+                0b10 => {
+                    // Without data_bytes, the adaptation field fills the packet
+                    spec.adaptation_field = af.map(|mut af| {
+                        af.adaptation_field_length = 183;
+                        af
+                    });
+                }
+                _ => {
+                    let length = match af {
+                        Some(mut af) => {
+                            let length = std::cmp::min(af.adaptation_field_length as usize +
+                                                       stuffing,
+                                                       183);
+                            af.adaptation_field_length = length as u8;
+                            spec.adaptation_field = Some(af);
+                            length
+                        }
+                        None => 0,
+                    };
+                    data.truncate(184 - 1 - length);
+                    spec.data_bytes = Some(data);
+                }
+            }
+            spec
+        })
+}
+
+/// PAT with CRC_32 left 0.  Program 0 (network_PID) is not generated since parse() drops it.
+pub fn pat() -> impl Strategy<Value = super::ProgramAssociationTable> {
+    (any::<u16>(),
+     0..32u8,
+     any::<bool>(),
+     any::<(u8, u8)>(),
+     proptest::collection::hash_map(0..(1u16 << 13), 1..=0xffffu16, 0..32))
+        .prop_map(|(transport_stream_id, version_number, current_next_indicator, (n, last), map)| {
+            super::ProgramAssociationTable {
+                table_id: 0x00,
+                transport_stream_id: transport_stream_id,
+                version_number: version_number,
+                current_next_indicator: current_next_indicator,
+                section_number: n,
+                last_section_number: last,
+                program_map: map,
+                crc32: 0,
+            }
+        })
+}
+
+/// Owned fields of a PMT generated by pmt().  Build one with PmtSpec::table().
+#[derive(Debug, Clone)]
+pub struct PmtSpec {
+    pub program_number: u16,
+    pub version_number: u8,
+    pub current_next_indicator: bool,
+    pub section_number: u8,
+    pub last_section_number: u8,
+    pub pcr_pid: u16,
+    pub program_info: Vec<u8>,
+    /// (stream_type, elementary_PID, descriptors)
+    pub es_info: Vec<(u8, u16, Vec<u8>)>,
+}
+
+impl PmtSpec {
+    /// PMT with CRC_32 left 0
+    pub fn table<'a>(&'a self) -> super::ProgramMapTable<'a> {
+        super::ProgramMapTable {
+            table_id: 0x02,
+            program_number: self.program_number,
+            version_number: self.version_number,
+            current_next_indicator: self.current_next_indicator,
+            section_number: self.section_number,
+            last_section_number: self.last_section_number,
+            pcr_pid: self.pcr_pid,
+            program_info: &self.program_info,
+            es_info: self.es_info
+                .iter()
+                .map(|&(stream_type, elementary_pid, ref descriptor)| {
+                    super::pmt::EsInfo {
+                        stream_type: stream_type,
+                        elementary_pid: elementary_pid,
+                        descriptor: descriptor,
+                    }
+                })
+                .collect(),
+            crc32: 0,
+        }
+    }
+}
+
+/// PMT fitting in a section of 1021 bytes.  Descriptor loops are arbitrary bytes since they
+/// are not parsed with the table.
+pub fn pmt() -> impl Strategy<Value = PmtSpec> {
+    let es = (any::<u8>(),
+              0..(1u16 << 13),
+              proptest::collection::vec(any::<u8>(), 0..32));
+    (any::<u16>(),
+     0..32u8,
+     any::<bool>(),
+     any::<(u8, u8)>(),
+     0..(1u16 << 13),
+     proptest::collection::vec(any::<u8>(), 0..64),
+     proptest::collection::vec(es, 0..16))
+        .prop_map(|(program_number,
+                    version_number,
+                    current_next_indicator,
+                    (n, last),
+                    pcr_pid,
+                    program_info,
+                    es_info)| {
+            PmtSpec {
+                program_number: program_number,
+                version_number: version_number,
+                current_next_indicator: current_next_indicator,
+                section_number: n,
+                last_section_number: last,
+                pcr_pid: pcr_pid,
+                program_info: program_info,
+                es_info: es_info,
+            }
+        })
+}
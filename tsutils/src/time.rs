@@ -0,0 +1,26 @@
+// Modified Julian Date of 1970-01-01
+const MJD_UNIX_EPOCH: i64 = 40587;
+const JST_OFFSET: i64 = 9 * 60 * 60;
+
+fn bcd(b: u8) -> i64 {
+    ((b >> 4) * 10 + (b & 0x0f)) as i64
+}
+
+/// Parse 40-bit MJD + BCD JST time used in EIT and TOT into Unix time.  Return None when all
+/// bits are 1 (undefined).
+pub fn parse_jst_time(bytes: &[u8]) -> Option<i64> {
+    if bytes[0..5].iter().all(|&b| b == 0xff) {
+        return None;
+    }
+    let mjd = (bytes[0] as i64) << 8 | bytes[1] as i64;
+    Some((mjd - MJD_UNIX_EPOCH) * 86400 + bcd(bytes[2]) * 3600 + bcd(bytes[3]) * 60 +
+         bcd(bytes[4]) - JST_OFFSET)
+}
+
+/// Parse 24-bit BCD duration into seconds.  Return None when all bits are 1 (undefined).
+pub fn parse_bcd_duration(bytes: &[u8]) -> Option<u32> {
+    if bytes[0..3].iter().all(|&b| b == 0xff) {
+        return None;
+    }
+    Some((bcd(bytes[0]) * 3600 + bcd(bytes[1]) * 60 + bcd(bytes[2])) as u32)
+}
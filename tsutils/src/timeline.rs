@@ -0,0 +1,192 @@
+// 27MHz, base of 33 bits and extension of 9 bits
+const PCR_MODULO: u64 = (1 << 33) * 300;
+// Larger jumps without discontinuity_indicator are treated as PCR resets
+const MAX_PCR_INTERVAL: u64 = 27000000 * 10;
+
+/// Range of PCR without discontinuities.  Times are in 27MHz.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    /// Index of the packet carrying the first PCR
+    pub packet: u64,
+    /// The first PCR
+    pub pcr: u64,
+    /// Media time at the first PCR
+    pub start: u64,
+    /// Media time at the last PCR
+    pub end: u64,
+    /// Whether the segment was started by discontinuity_indicator rather than a PCR reset
+    pub indicated: bool,
+}
+
+/// Map PCR of one PID onto a media time continuing across discontinuity_indicator and PCR
+/// resets, e.g. when the broadcaster restarts its encoder during the recording.  The media time
+/// starts from 0 at the first PCR.  The time between the last PCR before a discontinuity and
+/// the first one after it is estimated from the number of packets in between at the rate of
+/// the previous segment.
+#[derive(Debug, Default)]
+pub struct Timeline {
+    pcr_pid: Option<u16>,
+    segments: Vec<Segment>,
+    // (packet index, PCR) of the last PCR
+    last: Option<(u64, u64)>,
+}
+
+impl Timeline {
+    /// Follow the first PID carrying PCR.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Follow PCR of the PID, e.g. PCR_PID of the program.
+    pub fn with_pcr_pid(pcr_pid: u16) -> Self {
+        Timeline {
+            pcr_pid: Some(pcr_pid),
+            ..Self::default()
+        }
+    }
+
+    pub fn pcr_pid(&self) -> Option<u16> {
+        self.pcr_pid
+    }
+
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    /// The last PCR pushed
+    pub fn last_pcr(&self) -> Option<u64> {
+        self.last.map(|(_, pcr)| pcr)
+    }
+
+    /// Media time at the last PCR in 27MHz
+    pub fn duration(&self) -> u64 {
+        self.segments.last().map(|segment| segment.end).unwrap_or(0)
+    }
+
+    pub fn duration_secs(&self) -> f64 {
+        self.duration() as f64 / 27000000.0
+    }
+
+    /// Push the packet at the index of the stream.  Return the media time in 27MHz when it
+    /// carries PCR of the followed PID.
+    pub fn push(&mut self, index: u64, packet: &super::TsPacket) -> Option<u64> {
+        let af = packet.adaptation_field.as_ref()?;
+        let pcr = af.pcr.as_ref()?;
+        if self.pcr_pid.is_none() {
+            self.pcr_pid = Some(packet.pid);
+        }
+        if self.pcr_pid != Some(packet.pid) {
+            return None;
+        }
+        Some(self.push_pcr(index, pcr.to_27mhz(), af.discontinuity_indicator))
+    }
+
+    /// Push PCR in 27MHz carried by the packet at the index and return its media time.
+    pub fn push_pcr(&mut self, index: u64, pcr: u64, discontinuity_indicator: bool) -> u64 {
+        let (last_index, last_pcr) = match self.last {
+            Some(last) => last,
+            None => {
+                self.segments.push(Segment {
+                    packet: index,
+                    pcr: pcr,
+                    start: 0,
+                    end: 0,
+                    indicated: false,
+                });
+                self.last = Some((index, pcr));
+                return 0;
+            }
+        };
+        self.last = Some((index, pcr));
+
+        let interval = (pcr + PCR_MODULO - last_pcr) % PCR_MODULO;
+        let start = {
+            let segment = self.segments.last_mut().unwrap();
+            if !discontinuity_indicator && interval <= MAX_PCR_INTERVAL {
+                segment.end += interval;
+                return segment.end;
+            }
+            let gap = if segment.end > segment.start && last_index > segment.packet {
+                let rate = (segment.end - segment.start) as f64 /
+                           (last_index - segment.packet) as f64;
+                (rate * (index - last_index) as f64) as u64
+            } else {
+                0
+            };
+            segment.end + gap
+        };
+        debug!("PCR discontinuity at packet {}: {} -> {}{}",
+               index,
+               last_pcr,
+               pcr,
+               if discontinuity_indicator { " (indicated)" } else { "" });
+        self.segments.push(Segment {
+            packet: index,
+            pcr: pcr,
+            start: start,
+            end: start,
+            indicated: discontinuity_indicator,
+        });
+        start
+    }
+
+    /// State for checkpoint::Checkpoint
+    pub fn checkpoint(&self) -> serde_json::Value {
+        json!({
+            "pcr_pid": self.pcr_pid,
+            "segments": self.segments
+                .iter()
+                .map(|segment| {
+                    json!([segment.packet,
+                           segment.pcr,
+                           segment.start,
+                           segment.end,
+                           segment.indicated])
+                })
+                .collect::<Vec<_>>(),
+            "last": self.last.map(|(index, pcr)| [index, pcr]),
+        })
+    }
+
+    pub fn restore(state: &serde_json::Value) -> Option<Self> {
+        let pcr_pid = match *state.get("pcr_pid")? {
+            serde_json::Value::Null => None,
+            ref pid => Some(pid.as_u64()? as u16),
+        };
+        let segments = state.get("segments")?
+            .as_array()?
+            .iter()
+            .map(|segment| {
+                Some(Segment {
+                    packet: segment.get(0)?.as_u64()?,
+                    pcr: segment.get(1)?.as_u64()?,
+                    start: segment.get(2)?.as_u64()?,
+                    end: segment.get(3)?.as_u64()?,
+                    indicated: segment.get(4)?.as_bool()?,
+                })
+            })
+            .collect::<Option<Vec<_>>>()?;
+        let last = match *state.get("last")? {
+            serde_json::Value::Null => None,
+            ref last => Some((last.get(0)?.as_u64()?, last.get(1)?.as_u64()?)),
+        };
+        Some(Timeline {
+            pcr_pid: pcr_pid,
+            segments: segments,
+            last: last,
+        })
+    }
+
+    /// Media time in 27MHz of PCR, or of a clock with the same time base, observed at the
+    /// packet index.  None before the first PCR.
+    pub fn media_time(&self, index: u64, pcr: u64) -> Option<u64> {
+        let segment = self.segments.iter().rev().find(|segment| segment.packet <= index)?;
+        let offset = (pcr + PCR_MODULO - segment.pcr) % PCR_MODULO;
+        // Slightly earlier clocks than the first PCR of the segment wrap around
+        if offset > PCR_MODULO / 2 {
+            Some(segment.start.saturating_sub(PCR_MODULO - offset))
+        } else {
+            Some(segment.start + offset)
+        }
+    }
+}
@@ -0,0 +1,44 @@
+#[derive(Debug)]
+pub struct TimeOffsetTable<'a> {
+    pub table_id: u8,
+    /// Unix time
+    pub jst_time: Option<i64>,
+    /// Empty for TDT
+    pub descriptors: &'a [u8],
+}
+
+impl<'a> TimeOffsetTable<'a> {
+    /// Parse TOT or TDT section assembled by psi::SectionAssembler.
+    pub fn parse(section: &'a [u8]) -> Result<Self, super::psi::ParseError> {
+        // ARIB STD-B10 Part 2 5.2.8, 5.2.9
+        if section.len() < 8 {
+            return Err(super::psi::ParseError::InsufficientLength);
+        }
+        let table_id = section[0];
+        if table_id != 0x70 && table_id != 0x73 {
+            return Err(super::psi::ParseError::IncorrectTableId {
+                expected: 0x73,
+                actual: table_id,
+            });
+        }
+        let jst_time = super::time::parse_jst_time(&section[3..8]);
+        let descriptors = if table_id == 0x73 {
+            if section.len() < 10 {
+                return Err(super::psi::ParseError::InsufficientLength);
+            }
+            let descriptors_loop_length = ((section[8] & 0b00001111) as usize) << 8 |
+                                          section[9] as usize;
+            if section.len() < 10 + descriptors_loop_length {
+                return Err(super::psi::ParseError::InsufficientLength);
+            }
+            &section[10..(10 + descriptors_loop_length)]
+        } else {
+            &section[8..8]
+        };
+        Ok(TimeOffsetTable {
+            table_id: table_id,
+            jst_time: jst_time,
+            descriptors: descriptors,
+        })
+    }
+}
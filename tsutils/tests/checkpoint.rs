@@ -0,0 +1,257 @@
+#[macro_use]
+extern crate serde_json;
+extern crate tsutils;
+
+// The stream of the benchmarks, whose EIT sections span several packets so that checkpoints
+// are taken in the middle of sections
+#[allow(dead_code)]
+#[path = "../benches/synthetic/mod.rs"]
+mod synthetic;
+
+use tsutils::checkpoint::Checkpoint;
+use tsutils::report::{Analyzer, Report};
+
+fn temp_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("tsutils-checkpoint-{}-{}.json", std::process::id(), name))
+}
+
+// The synthetic stream with a video packet dropped, so that the integrity analyzer has
+// something to report besides the summary
+fn stream() -> Vec<u8> {
+    let mut bytes = synthetic::stream(188 * 1500);
+    let dropped = bytes.chunks(188)
+        .enumerate()
+        .filter(|&(_, buf)| tsutils::TsPacket::new(buf).pid == synthetic::VIDEO_PID)
+        .nth(500)
+        .map(|(index, _)| index)
+        .unwrap();
+    bytes.drain((dropped * 188)..((dropped + 1) * 188));
+    bytes
+}
+
+struct Analyzers {
+    integrity: tsutils::integrity::Monitor,
+    pcr: tsutils::pcr::JitterAnalyzer,
+    format: tsutils::format::FormatAnalyzer,
+    epg: tsutils::epg::CoverageAnalyzer,
+}
+
+impl Analyzers {
+    fn new() -> Self {
+        Analyzers {
+            integrity: tsutils::integrity::Monitor::new(),
+            pcr: tsutils::pcr::JitterAnalyzer::new(),
+            format: tsutils::format::FormatAnalyzer::new(),
+            epg: tsutils::epg::CoverageAnalyzer::new(),
+        }
+    }
+
+    fn all(&mut self) -> Vec<&mut dyn Analyzer> {
+        vec![&mut self.integrity, &mut self.pcr, &mut self.format, &mut self.epg]
+    }
+
+    fn push(&mut self, bytes: &[u8], start: u64, reports: &mut Vec<Report>) {
+        let mut analyzers = self.all();
+        for (i, buf) in bytes.chunks(188).enumerate() {
+            let packet = if tsutils::report::is_broken(buf) {
+                None
+            } else {
+                Some(tsutils::TsPacket::new(buf))
+            };
+            for analyzer in analyzers.iter_mut() {
+                analyzer.push(start + i as u64, buf, packet.as_ref(), reports);
+            }
+        }
+    }
+
+    fn finish(&mut self, reports: &mut Vec<Report>) {
+        for analyzer in self.all() {
+            analyzer.finish(reports);
+        }
+    }
+}
+
+fn uninterrupted(bytes: &[u8]) -> Vec<Report> {
+    let mut reports = vec![];
+    let mut analyzers = Analyzers::new();
+    analyzers.push(bytes, 0, &mut reports);
+    analyzers.finish(&mut reports);
+    reports
+}
+
+#[test]
+fn save_and_load() {
+    let path = temp_path("save_and_load");
+    let checkpoint = Checkpoint {
+        packet: 42,
+        states: vec![json!({"buf": "00b0ff", "started": true}), serde_json::Value::Null],
+    };
+    checkpoint.save(&path).unwrap();
+    let loaded = Checkpoint::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded, Some(checkpoint));
+    assert!(!temp_path("save_and_load.tmp").exists());
+
+    assert_eq!(Checkpoint::load(&path).unwrap(), None);
+}
+
+#[test]
+fn load_malformed() {
+    let path = temp_path("load_malformed");
+    for body in &["{\"packet\":1}", "{\"packet\":\"1\",\"states\":[]}", "[]"] {
+        std::fs::write(&path, body).unwrap();
+        let error = Checkpoint::load(&path).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData, "{}", body);
+    }
+    // Truncated JSON is an I/O error of serde_json
+    std::fs::write(&path, "{\"packet\":1,\"sta").unwrap();
+    assert!(Checkpoint::load(&path).is_err());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn restored_analyzers_report_the_same() {
+    let bytes = stream();
+    let expected = uninterrupted(&bytes);
+    let path = temp_path("restored_analyzers_report_the_same");
+    let packets = bytes.len() / 188;
+    for split in (1..packets).step_by(97) {
+        let mut reports = vec![];
+        let mut analyzers = Analyzers::new();
+        analyzers.push(&bytes[..(split * 188)], 0, &mut reports);
+        let states = analyzers.all()
+            .iter()
+            .map(|analyzer| analyzer.checkpoint().unwrap())
+            .collect();
+        Checkpoint {
+                packet: split as u64,
+                states: states,
+            }
+            .save(&path)
+            .unwrap();
+
+        let checkpoint = Checkpoint::load(&path).unwrap().unwrap();
+        assert_eq!(checkpoint.packet, split as u64);
+        let mut analyzers = Analyzers::new();
+        for (analyzer, state) in analyzers.all().iter_mut().zip(&checkpoint.states) {
+            assert!(analyzer.restore(state), "{} at packet {}", state, split);
+        }
+        analyzers.push(&bytes[(split * 188)..], split as u64, &mut reports);
+        analyzers.finish(&mut reports);
+        assert_eq!(reports, expected, "split at packet {}", split);
+    }
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn restore_rejects_other_states() {
+    let mut analyzers = Analyzers::new();
+    analyzers.push(&stream()[..(188 * 100)], 0, &mut vec![]);
+    let states: Vec<_> = analyzers.all()
+        .iter()
+        .map(|analyzer| analyzer.checkpoint().unwrap())
+        .collect();
+    for (i, analyzer) in Analyzers::new().all().iter_mut().enumerate() {
+        assert!(!analyzer.restore(&json!({})));
+        assert!(!analyzer.restore(&states[(i + 1) % states.len()]));
+    }
+}
+
+#[test]
+fn section_assembler_across_checkpoint() {
+    let bytes = stream();
+    let packets: Vec<_> = bytes.chunks(188)
+        .filter(|buf| tsutils::TsPacket::new(buf).pid == synthetic::EIT_PID)
+        .collect();
+    let mut expected = vec![];
+    let mut assembler = tsutils::psi::SectionAssembler::new();
+    for buf in &packets {
+        expected.extend(assembler.push(&tsutils::TsPacket::new(buf)));
+    }
+    assert!(expected.len() > 1);
+
+    for split in 1..packets.len() {
+        let mut sections = vec![];
+        let mut assembler = tsutils::psi::SectionAssembler::new();
+        for buf in &packets[..split] {
+            sections.extend(assembler.push(&tsutils::TsPacket::new(buf)));
+        }
+        // Through the JSON written to the file
+        let state: serde_json::Value =
+            serde_json::from_str(&assembler.checkpoint().to_string()).unwrap();
+        let mut assembler = tsutils::psi::SectionAssembler::restore(&state).unwrap();
+        for buf in &packets[split..] {
+            sections.extend(assembler.push(&tsutils::TsPacket::new(buf)));
+        }
+        assert_eq!(sections, expected, "split at packet {}", split);
+    }
+}
+
+#[test]
+fn section_assembler_rejects_malformed_hex() {
+    for buf in &["0", "0g", "abc", "\u{e9}0"] {
+        let state = json!({"buf": buf, "started": true});
+        assert!(tsutils::psi::SectionAssembler::restore(&state).is_none(), "{}", buf);
+    }
+    let state = json!({"buf": "4E", "started": true});
+    assert!(tsutils::psi::SectionAssembler::restore(&state).is_some());
+}
+
+#[test]
+fn analyze_resumes_from_checkpoint() {
+    // Fails after the bytes, like a process killed in the middle of the file
+    struct Interrupted<'a> {
+        inner: std::io::Cursor<&'a [u8]>,
+        limit: u64,
+    }
+
+    impl<'a> std::io::Read for Interrupted<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.inner.position() >= self.limit {
+                return Err(std::io::Error::other("interrupted"));
+            }
+            let len = std::cmp::min(buf.len() as u64, self.limit - self.inner.position());
+            self.inner.read(&mut buf[..(len as usize)])
+        }
+    }
+
+    impl<'a> std::io::Seek for Interrupted<'a> {
+        fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    let bytes = stream();
+    let expected = uninterrupted(&bytes);
+    let path = temp_path("analyze_resumes_from_checkpoint");
+    let interval = 100;
+
+    let mut analyzers = Analyzers::new();
+    let reader = Interrupted {
+        inner: std::io::Cursor::new(&bytes[..]),
+        limit: 188 * 1234,
+    };
+    let result =
+        tsutils::checkpoint::analyze(reader, &mut analyzers.all(), &path, interval, |_| Ok(()));
+    assert!(result.is_err());
+    let checkpoint = Checkpoint::load(&path).unwrap().unwrap();
+    assert_eq!(checkpoint.packet, 1200);
+
+    let mut reports = vec![];
+    let mut analyzers = Analyzers::new();
+    tsutils::checkpoint::analyze(std::io::Cursor::new(&bytes[..]),
+                                 &mut analyzers.all(),
+                                 &path,
+                                 interval,
+                                 |report| {
+                                     reports.push(report);
+                                     Ok(())
+                                 })
+        .unwrap();
+    assert!(!path.exists());
+    // Reports of the packets before the checkpoint are not found again
+    let resumed: Vec<_> = expected.into_iter()
+        .filter(|report| report.packet.is_none_or(|packet| packet >= checkpoint.packet))
+        .collect();
+    assert_eq!(reports, resumed);
+}
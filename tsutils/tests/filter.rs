@@ -0,0 +1,62 @@
+extern crate tsutils;
+
+use std::io::Write;
+
+// A payload-only packet of the PID, scrambled with the even key when scrambled is set
+fn packet(pid: u16, counter: u8, scrambled: bool) -> Vec<u8> {
+    let mut buf = vec![0xff; 188];
+    buf[..4].copy_from_slice(&[0x47,
+                               (pid >> 8) as u8,
+                               pid as u8,
+                               if scrambled { 0x90 } else { 0x10 } | (counter & 0x0f)]);
+    buf
+}
+
+fn drop_scrambled(packets: &[Vec<u8>],
+                  only_descrambled: bool)
+                  -> (Vec<Vec<u8>>, tsutils::filter::ScrambledReport) {
+    let mut writer = tsutils::filter::DropScrambled::new(vec![], only_descrambled);
+    // In pieces not aligned to packets
+    for chunk in packets.concat().chunks(100) {
+        writer.write_all(chunk).unwrap();
+    }
+    let (written, report) = writer.into_inner();
+    (written.chunks(188).map(|buf| buf.to_vec()).collect(), report)
+}
+
+#[test]
+fn drop_all_scrambled() {
+    let packets = vec![packet(0x0100, 0, true),
+                       packet(0x0100, 1, false),
+                       packet(0x0110, 0, true),
+                       packet(0x0100, 2, true)];
+    let (written, report) = drop_scrambled(&packets, false);
+    assert_eq!(written, vec![packets[1].clone()]);
+    assert_eq!(report.dropped.iter().map(|(&pid, &n)| (pid, n)).collect::<Vec<_>>(),
+               vec![(0x0100, 2), (0x0110, 1)]);
+}
+
+#[test]
+fn drop_only_descrambled() {
+    let packets = vec![// Before descrambling starts for the PID
+                       packet(0x0100, 0, true),
+                       packet(0x0100, 1, true),
+                       packet(0x0100, 2, false),
+                       packet(0x0100, 3, true),
+                       packet(0x0100, 4, false),
+                       // Entirely scrambled
+                       packet(0x0110, 0, true),
+                       packet(0x0110, 1, true)];
+    let (written, report) = drop_scrambled(&packets, true);
+    // Scrambled packets are dropped only after the first clear one of the PID
+    assert_eq!(written,
+               vec![packets[0].clone(),
+                    packets[1].clone(),
+                    packets[2].clone(),
+                    packets[4].clone(),
+                    packets[5].clone(),
+                    packets[6].clone()]);
+    assert_eq!(report.dropped.iter().map(|(&pid, &n)| (pid, n)).collect::<Vec<_>>(),
+               vec![(0x0100, 1)]);
+    assert_eq!(report.total(), 1);
+}
@@ -0,0 +1,81 @@
+extern crate tsutils;
+
+// A packet of adaptation field only with discontinuity_indicator, random_access_indicator,
+// PCR_flag and OPCR_flag set
+fn adaptation_field_packet() -> [u8; 188] {
+    let mut packet = [0xff; 188];
+    packet[..6].copy_from_slice(&[0x47, 0x01, 0x00, 0x20, 183, 0b11011000]);
+    // program_clock_reference_base=0x123456789, program_clock_reference_extension=0x123
+    packet[6..12].copy_from_slice(&[0x91, 0xa2, 0xb3, 0xc4, 0xff, 0x23]);
+    // original_program_clock_reference_base=0x0abcdef01,
+    // original_program_clock_reference_extension=0x0ff
+    packet[12..18].copy_from_slice(&[0x55, 0xe6, 0xf7, 0x80, 0xfe, 0xff]);
+    packet
+}
+
+#[test]
+fn adaptation_field_flags() {
+    let bytes = adaptation_field_packet();
+    let packet = tsutils::TsPacket::new(&bytes);
+    let af = packet.adaptation_field.unwrap();
+    assert!(af.discontinuity_indicator);
+    assert!(af.random_access_indicator);
+    assert!(!af.elementary_stream_priority_indicator);
+    assert!(!af.transport_private_data_flag);
+    assert_eq!(af.splice_countdown, None);
+}
+
+#[test]
+fn pcr_and_opcr() {
+    let bytes = adaptation_field_packet();
+    let packet = tsutils::TsPacket::new(&bytes);
+    let af = packet.adaptation_field.unwrap();
+    let pcr = af.pcr.unwrap();
+    assert_eq!(pcr.program_clock_reference_base, 0x123456789);
+    assert_eq!(pcr.program_clock_reference_extension, 0x123);
+    assert_eq!(pcr.to_27mhz(), 0x123456789 * 300 + 0x123);
+    let opcr = af.opcr.unwrap();
+    assert_eq!(opcr.original_program_clock_reference_base, 0x0abcdef01);
+    assert_eq!(opcr.original_program_clock_reference_extension, 0x0ff);
+}
+
+#[test]
+fn serialized_packet_is_identical() {
+    let bytes = adaptation_field_packet();
+    assert_eq!(&tsutils::TsPacket::new(&bytes).to_bytes()[..], &bytes[..]);
+}
+
+// transport_private_data_length runs past adaptation_field_length of 150
+fn truncated_private_data_packet() -> [u8; 188] {
+    let mut packet = [0xff; 188];
+    packet[..7].copy_from_slice(&[0x47, 0x01, 0x00, 0x30, 150, 0b00000010, 200]);
+    packet
+}
+
+#[test]
+fn truncated_adaptation_field() {
+    let bytes = truncated_private_data_packet();
+    let packet = tsutils::TsPacket::new(&bytes);
+    assert_eq!(packet.adaptation_field, None);
+    assert_eq!(packet.data_bytes, Some(&bytes[155..]));
+
+    // adaptation_field_extension_length runs past adaptation_field_length
+    let mut bytes = [0xff; 188];
+    bytes[..8].copy_from_slice(&[0x47, 0x01, 0x00, 0x30, 3, 0b00000001, 100, 0x1f]);
+    assert_eq!(tsutils::TsPacket::new(&bytes).adaptation_field, None);
+
+    // adaptation_field_length runs past the packet
+    let mut bytes = [0xff; 188];
+    bytes[..5].copy_from_slice(&[0x47, 0x01, 0x00, 0x30, 200]);
+    let packet = tsutils::TsPacket::new(&bytes);
+    assert_eq!(packet.adaptation_field, None);
+    assert_eq!(packet.data_bytes, Some(&[][..]));
+}
+
+#[test]
+fn truncated_adaptation_field_is_transport_error() {
+    let bytes = truncated_private_data_packet();
+    let report = tsutils::integrity::check(&bytes[..]).unwrap();
+    assert_eq!(report.packets, 1);
+    assert_eq!(report.transport_errors, 1);
+}
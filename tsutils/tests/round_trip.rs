@@ -0,0 +1,71 @@
+#[macro_use]
+extern crate proptest;
+extern crate tsutils;
+
+use proptest::prelude::any;
+use proptest::sample::Index;
+use tsutils::testgen;
+
+proptest! {
+    #[test]
+    fn packet(spec in testgen::packet()) {
+        let packet = spec.packet();
+        let bytes = packet.to_bytes();
+        prop_assert_eq!(tsutils::TsPacket::new(&bytes), packet);
+    }
+
+    #[test]
+    fn pat(mut pat in testgen::pat()) {
+        let payload = pat.to_bytes();
+        // CRC_32 over a whole section including CRC_32 is 0
+        prop_assert_eq!(tsutils::psi::crc32(&payload[1..]), 0);
+        let parsed = tsutils::ProgramAssociationTable::parse(&payload).unwrap();
+        pat.crc32 = parsed.crc32;
+        prop_assert_eq!(parsed, pat);
+    }
+
+    #[test]
+    fn pmt(spec in testgen::pmt()) {
+        let mut pmt = spec.table();
+        let payload = pmt.to_bytes();
+        prop_assert_eq!(tsutils::psi::crc32(&payload[1..]), 0);
+        let parsed = tsutils::ProgramMapTable::parse(&payload).unwrap();
+        pmt.crc32 = parsed.crc32;
+        prop_assert_eq!(parsed, pmt);
+    }
+
+    #[test]
+    fn truncated_pat(pat in testgen::pat(), cut in any::<Index>()) {
+        let payload = pat.to_bytes();
+        let payload = &payload[..cut.index(payload.len())];
+        prop_assert!(tsutils::ProgramAssociationTable::parse(payload).is_err());
+    }
+
+    #[test]
+    fn truncated_pmt(spec in testgen::pmt(), cut in any::<Index>()) {
+        let payload = spec.table().to_bytes();
+        let payload = &payload[..cut.index(payload.len())];
+        prop_assert!(tsutils::ProgramMapTable::parse(payload).is_err());
+    }
+}
+
+#[test]
+fn pat_shorter_than_header() {
+    // section_length=5 leaves no room for CRC_32
+    let payload = [0x00, 0x00, 0xb0, 0x05, 0x00, 0x01, 0xc1, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff];
+    match tsutils::ProgramAssociationTable::parse(&payload) {
+        Err(tsutils::psi::ParseError::InsufficientLength) => {}
+        result => panic!("unexpected {:?}", result),
+    }
+}
+
+#[test]
+fn pmt_program_info_past_section() {
+    // program_info_length=0x20 runs past section_length=13
+    let payload = [0x00, 0x02, 0xb0, 0x0d, 0x00, 0x01, 0xc1, 0x00, 0x00, 0xe1, 0x00, 0xf0, 0x20,
+                   0xff, 0xff, 0xff, 0xff];
+    match tsutils::ProgramMapTable::parse(&payload) {
+        Err(tsutils::psi::ParseError::InsufficientLength) => {}
+        result => panic!("unexpected {:?}", result),
+    }
+}
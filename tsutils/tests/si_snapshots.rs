@@ -0,0 +1,171 @@
+#[macro_use]
+extern crate serde_json;
+extern crate tsutils;
+
+mod support;
+
+use tsutils::descriptor;
+
+fn descriptors_json(payload: &[u8]) -> serde_json::Value {
+    descriptor::descriptors(payload)
+        .map(|(tag, body)| {
+            match tag {
+                descriptor::ServiceDescriptor::TAG => {
+                    let d = descriptor::ServiceDescriptor::parse(body).unwrap();
+                    json!({
+                        "tag": "service",
+                        "service_type": d.service_type,
+                        "service_provider_name": tsutils::arib_string::decode(d.service_provider_name),
+                        "service_name": tsutils::arib_string::decode(d.service_name),
+                    })
+                }
+                descriptor::ShortEventDescriptor::TAG => {
+                    let d = descriptor::ShortEventDescriptor::parse(body).unwrap();
+                    json!({
+                        "tag": "short_event",
+                        "iso_639_language_code": String::from_utf8_lossy(d.iso_639_language_code),
+                        "event_name": tsutils::arib_string::decode(d.event_name),
+                        "text": tsutils::arib_string::decode(d.text),
+                    })
+                }
+                descriptor::ContentNibble::TAG => {
+                    let nibbles: Vec<_> = descriptor::ContentNibble::parse_descriptor(body)
+                        .iter()
+                        .map(|n| {
+                            json!({
+                                "content_nibble_level_1": n.content_nibble_level_1,
+                                "content_nibble_level_2": n.content_nibble_level_2,
+                                "genre_name": n.genre_name(),
+                            })
+                        })
+                        .collect();
+                    json!({
+                        "tag": "content",
+                        "nibbles": nibbles,
+                    })
+                }
+                _ => {
+                    json!({
+                        "tag": tag,
+                        "body": body,
+                    })
+                }
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn pat() {
+    let sections = support::sections("pat");
+    let pat = tsutils::ProgramAssociationTable::parse(&support::payload(&sections[0])).unwrap();
+    let mut program_map: Vec<_> = pat.program_map.iter().collect();
+    program_map.sort();
+    support::assert_snapshot("pat",
+                             &json!({
+                                 "table_id": pat.table_id,
+                                 "transport_stream_id": pat.transport_stream_id,
+                                 "version_number": pat.version_number,
+                                 "current_next_indicator": pat.current_next_indicator,
+                                 "section_number": pat.section_number,
+                                 "last_section_number": pat.last_section_number,
+                                 "program_map": program_map,
+                                 "crc32": pat.crc32,
+                             }));
+}
+
+#[test]
+fn pmt() {
+    let sections = support::sections("pmt");
+    let payload = support::payload(&sections[0]);
+    let pmt = tsutils::ProgramMapTable::parse(&payload).unwrap();
+    let es_info: Vec<_> = pmt.es_info
+        .iter()
+        .map(|es| {
+            json!({
+                "stream_type": es.stream_type,
+                "elementary_pid": es.elementary_pid,
+                "descriptors": descriptors_json(es.descriptor),
+            })
+        })
+        .collect();
+    support::assert_snapshot("pmt",
+                             &json!({
+                                 "table_id": pmt.table_id,
+                                 "program_number": pmt.program_number,
+                                 "version_number": pmt.version_number,
+                                 "current_next_indicator": pmt.current_next_indicator,
+                                 "pcr_pid": pmt.pcr_pid,
+                                 "program_info": descriptors_json(pmt.program_info),
+                                 "es_info": es_info,
+                                 "crc32": pmt.crc32,
+                             }));
+}
+
+#[test]
+fn sdt() {
+    let sections = support::sections("sdt");
+    let sdt = tsutils::ServiceDescriptionTable::parse(&sections[0]).unwrap();
+    let services: Vec<_> = sdt.services
+        .iter()
+        .map(|service| {
+            json!({
+                "service_id": service.service_id,
+                "eit_schedule_flag": service.eit_schedule_flag,
+                "eit_present_following_flag": service.eit_present_following_flag,
+                "running_status": service.running_status,
+                "free_ca_mode": service.free_ca_mode,
+                "descriptors": descriptors_json(service.descriptors),
+            })
+        })
+        .collect();
+    support::assert_snapshot("sdt",
+                             &json!({
+                                 "table_id": sdt.table_id,
+                                 "is_actual": sdt.is_actual(),
+                                 "transport_stream_id": sdt.transport_stream_id,
+                                 "version_number": sdt.version_number,
+                                 "original_network_id": sdt.original_network_id,
+                                 "services": services,
+                                 "crc32": sdt.crc32,
+                             }));
+}
+
+#[test]
+fn eit() {
+    let sections = support::sections("eit");
+    let eits: Vec<_> = sections.iter()
+        .map(|section| {
+            let eit = tsutils::EventInformationTable::parse(section).unwrap();
+            let events: Vec<_> = eit.events
+                .iter()
+                .map(|event| {
+                    json!({
+                        "event_id": event.event_id,
+                        "start_time": event.start_time,
+                        "duration": event.duration,
+                        "end_time": event.end_time(),
+                        "running_status": event.running_status,
+                        "free_ca_mode": event.free_ca_mode,
+                        "descriptors": descriptors_json(event.descriptors),
+                    })
+                })
+                .collect();
+            json!({
+                "table_id": eit.table_id,
+                "is_present_following_actual": eit.is_present_following_actual(),
+                "service_id": eit.service_id,
+                "version_number": eit.version_number,
+                "section_number": eit.section_number,
+                "last_section_number": eit.last_section_number,
+                "transport_stream_id": eit.transport_stream_id,
+                "original_network_id": eit.original_network_id,
+                "segment_last_section_number": eit.segment_last_section_number,
+                "last_table_id": eit.last_table_id,
+                "events": events,
+                "crc32": eit.crc32,
+            })
+        })
+        .collect();
+    support::assert_snapshot("eit", &json!(eits));
+}
@@ -0,0 +1,70 @@
+// Golden-file harness shared by the tests of the parsers.
+//
+// A fixture tests/fixtures/NAME.hex holds PSI/SI sections as hex bytes, one section per line,
+// where lines starting with '#' are comments.  Fixtures must not contain real broadcast data;
+// build them from synthetic sections or anonymize the names and IDs of captured ones.
+//
+// A snapshot tests/snapshots/NAME.json is the expected JSON of the parsed structures.  Run the
+// tests with UPDATE_SNAPSHOTS=1 to write the actual JSON instead, and review the diff before
+// committing it.
+
+extern crate serde_json;
+
+use std::io::Write;
+
+fn path(dir: &str, name: &str, extension: &str) -> std::path::PathBuf {
+    std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join(dir)
+        .join(format!("{}.{}", name, extension))
+}
+
+/// Sections of the fixture in the order of the lines
+pub fn sections(name: &str) -> Vec<Vec<u8>> {
+    let path = path("fixtures", name, "hex");
+    let content = std::fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("failed to read {}: {}", path.display(), e));
+    content.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            line.split_whitespace()
+                .map(|byte| {
+                    u8::from_str_radix(byte, 16).unwrap_or_else(|e| {
+                        panic!("invalid byte {:?} in {}: {}", byte, path.display(), e)
+                    })
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// The section as a payload starting with pointer_field, which PAT and PMT parsers expect
+pub fn payload(section: &[u8]) -> Vec<u8> {
+    let mut payload = vec![0];
+    payload.extend_from_slice(section);
+    payload
+}
+
+/// Compare the value with the snapshot, or write it when UPDATE_SNAPSHOTS is set
+pub fn assert_snapshot(name: &str, actual: &serde_json::Value) {
+    let path = path("snapshots", name, "json");
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        let mut file = std::fs::File::create(&path).unwrap();
+        serde_json::to_writer_pretty(&mut file, actual).unwrap();
+        file.write_all(b"\n").unwrap();
+        return;
+    }
+    let file = std::fs::File::open(&path).unwrap_or_else(|e| {
+        panic!("failed to open {} (run with UPDATE_SNAPSHOTS=1 to create it): {}",
+               path.display(),
+               e)
+    });
+    let expected: serde_json::Value = serde_json::from_reader(file).unwrap();
+    if &expected != actual {
+        panic!("{} does not match\nexpected: {}\nactual: {}",
+               path.display(),
+               serde_json::to_string_pretty(&expected).unwrap(),
+               serde_json::to_string_pretty(actual).unwrap());
+    }
+}